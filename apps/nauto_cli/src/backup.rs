@@ -0,0 +1,186 @@
+use crate::gitops;
+use anyhow::{Context, Result};
+use git2::Repository;
+use nauto_model::{BackupStorage, Job, JobResult, TaskSummary};
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes the snapshots collected by a ConfigBackup job to `storage` and records, per device,
+/// what changed since the previous backup. Dry runs report diffs without writing anything.
+pub fn store(storage: &BackupStorage, job: &Job, result: &mut JobResult) -> Result<()> {
+    let (root, git) = match storage {
+        BackupStorage::Directory { path } => (PathBuf::from(path), false),
+        BackupStorage::Git { path } => (PathBuf::from(path), true),
+    };
+    let stamp = result.started_at.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut changed = Vec::new();
+    for summary in &mut result.device_results {
+        if store_device(&root, summary, &stamp, !git && !job.dry_run, job.dry_run)? {
+            changed.push(summary.device_id.clone());
+        }
+    }
+    if git && !job.dry_run && !changed.is_empty() {
+        let repo = open_or_init(&root)?;
+        let pathspecs: Vec<String> = changed.iter().map(|id| device_dir(id)).collect();
+        let pathspecs: Vec<&str> = pathspecs.iter().map(String::as_str).collect();
+        let message = format!(
+            "{}: {} device(s) changed\n\n{}\n",
+            job.name,
+            changed.len(),
+            changed.join("\n")
+        );
+        gitops::commit(&repo, &pathspecs, &message)?;
+    }
+    Ok(())
+}
+
+/// Returns whether any file differed from the stored copy.
+fn store_device(
+    root: &Path,
+    summary: &mut TaskSummary,
+    stamp: &str,
+    keep_history: bool,
+    dry_run: bool,
+) -> Result<bool> {
+    let Some(snapshot) = summary.backup.clone() else {
+        return Ok(false);
+    };
+    let dir = root.join(device_dir(&summary.device_id));
+    let mut files = vec![("running.cfg", snapshot.running)];
+    if let Some(startup) = snapshot.startup {
+        files.push(("startup.cfg", startup));
+    }
+
+    let mut diffs = String::new();
+    let mut changed = false;
+    for (name, content) in files {
+        let path = dir.join(name);
+        let previous = fs::read_to_string(&path).ok();
+        match previous.as_deref() {
+            Some(previous) if previous == content => {
+                summary
+                    .logs
+                    .push(format!("{name} unchanged since last backup"));
+                continue;
+            }
+            Some(previous) => {
+                diffs.push_str(
+                    &TextDiff::from_lines(previous, content.as_str())
+                        .unified_diff()
+                        .header(&format!("a/{name}"), &format!("b/{name}"))
+                        .to_string(),
+                );
+                summary
+                    .logs
+                    .push(format!("{name} changed since last backup"));
+            }
+            None => summary.logs.push(format!("{name}: initial backup")),
+        }
+        changed = true;
+        if dry_run {
+            continue;
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        fs::write(&path, &content)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        if keep_history {
+            let history = dir.join("history");
+            fs::create_dir_all(&history)?;
+            fs::write(history.join(format!("{stamp}-{name}")), &content)?;
+        }
+    }
+    if !diffs.is_empty() {
+        summary.diff = Some(diffs);
+    }
+    Ok(changed)
+}
+
+fn open_or_init(root: &Path) -> Result<Repository> {
+    match Repository::open(root) {
+        Ok(repo) => Ok(repo),
+        Err(_) => {
+            fs::create_dir_all(root)?;
+            Repository::init(root)
+                .with_context(|| format!("failed to init backup repo at {}", root.display()))
+        }
+    }
+}
+
+/// Device ids become directory names; anything outside `[A-Za-z0-9._-]` is replaced.
+fn device_dir(device_id: &str) -> String {
+    device_id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use nauto_model::{ConfigSnapshot, JobKind, TargetSelector, TaskStatus};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn backup_result(running: &str) -> JobResult {
+        JobResult {
+            job_id: Uuid::new_v4(),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            device_results: vec![TaskSummary {
+                device_id: "core/r1".into(),
+                status: TaskStatus::Success,
+                started_at: None,
+                finished_at: None,
+                logs: Vec::new(),
+                diff: None,
+                outputs: Vec::new(),
+                getters: None,
+                backup: Some(ConfigSnapshot {
+                    running: running.into(),
+                    startup: None,
+                }),
+                preflight: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn reports_changes_against_previous_git_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = BackupStorage::Git {
+            path: dir.path().to_string_lossy().into_owned(),
+        };
+        let job = Job {
+            id: Uuid::new_v4(),
+            name: "nightly-backup".into(),
+            kind: JobKind::ConfigBackup {
+                include_startup: false,
+                storage: storage.clone(),
+            },
+            targets: TargetSelector::All,
+            parameters: HashMap::new(),
+            max_parallel: None,
+            dry_run: false,
+            approval_id: None,
+        };
+
+        let mut first = backup_result("hostname r1\n");
+        store(&storage, &job, &mut first).unwrap();
+        assert!(first.device_results[0].diff.is_none());
+        assert!(dir.path().join("core_r1/running.cfg").exists());
+
+        let mut second = backup_result("hostname r1\nntp server 10.0.0.1\n");
+        store(&storage, &job, &mut second).unwrap();
+        let diff = second.device_results[0].diff.as_deref().unwrap();
+        assert!(diff.contains("+ntp server 10.0.0.1"));
+
+        let repo = Repository::open(dir.path()).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_count(), 1);
+    }
+}
@@ -1,11 +1,15 @@
+use crate::job_runner;
 use anyhow::Result;
 use clap::Args;
-use nauto_drivers::drivers::GenericSshDriver;
+use nauto_drivers::drivers::simulated::{SimulatedDriver, SimulationConfig};
 use nauto_drivers::DriverRegistry;
 use nauto_engine::{InMemoryInventory, JobEngine};
-use nauto_model::{CapabilitySet, CredentialRef, Device, DeviceType, Job, JobKind, TargetSelector};
+use nauto_model::{
+    CapabilitySet, CredentialRef, Device, DeviceType, Job, JobKind, TargetSelector, TaskStatus,
+};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 #[derive(Args)]
@@ -14,25 +18,46 @@ pub struct BenchCmd {
     pub devices: usize,
     #[arg(long, default_value_t = 100)]
     pub parallel: usize,
+    #[arg(
+        long,
+        help = "Simulation profile YAML (latency, failure rate, canned outputs); zero latency if omitted"
+    )]
+    pub profile: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Job file to run instead of `show version`; targets are ignored"
+    )]
+    pub job: Option<PathBuf>,
 }
 
 pub async fn run(cmd: BenchCmd) -> Result<()> {
-    let devices = build_devices(cmd.devices);
-    let inventory = InMemoryInventory::new(devices);
-    let registry = DriverRegistry::new(vec![Arc::new(GenericSshDriver::default())]);
+    let config = match &cmd.profile {
+        Some(path) => SimulationConfig::load(path)?,
+        None => SimulationConfig::default(),
+    };
+    let inventory = InMemoryInventory::new(build_devices(cmd.devices));
+    let driver = SimulatedDriver::new(DeviceType::CiscoIos, Arc::new(config));
+    let registry = DriverRegistry::new(vec![Arc::new(driver)]);
     let engine = JobEngine::new(inventory, registry).with_parallel(cmd.parallel);
 
-    let job = Job {
-        id: Uuid::new_v4(),
-        name: format!("bench-{}-{}", cmd.devices, cmd.parallel),
-        kind: JobKind::CommandBatch {
-            commands: vec!["show version".into()],
+    let job = match &cmd.job {
+        Some(path) => Job {
+            targets: TargetSelector::All,
+            max_parallel: None,
+            ..job_runner::load_job(path)?.into()
+        },
+        None => Job {
+            id: Uuid::new_v4(),
+            name: format!("bench-{}-{}", cmd.devices, cmd.parallel),
+            kind: JobKind::CommandBatch {
+                commands: vec!["show version".into()],
+            },
+            targets: TargetSelector::All,
+            parameters: Default::default(),
+            max_parallel: None,
+            dry_run: false,
+            approval_id: None,
         },
-        targets: TargetSelector::All,
-        parameters: Default::default(),
-        max_parallel: None,
-        dry_run: false,
-        approval_id: None,
     };
 
     let start = Instant::now();
@@ -44,10 +69,32 @@ pub async fn run(cmd: BenchCmd) -> Result<()> {
     } else {
         total
     };
+    let failed = result
+        .device_results
+        .iter()
+        .filter(|task| task.status == TaskStatus::Failed)
+        .count();
+    // Per device, from acquiring a parallelism slot to the driver returning.
+    let mut latencies: Vec<Duration> = result
+        .device_results
+        .iter()
+        .filter_map(|task| (task.finished_at? - task.started_at?).to_std().ok())
+        .collect();
+    latencies.sort();
 
-    println!("Devices processed: {}", total as usize);
+    println!("Devices processed: {} (failed: {failed})", total as usize);
     println!("Elapsed: {:.2}s", elapsed);
     println!("Throughput: {:.2} devices/sec", throughput);
+    println!(
+        "Latency p50/p95/p99: {:.1}/{:.1}/{:.1} ms",
+        percentile_ms(&latencies, 50.0),
+        percentile_ms(&latencies, 95.0),
+        percentile_ms(&latencies, 99.0)
+    );
+    match peak_rss_kib() {
+        Some(kib) => println!("Peak memory: {:.1} MiB", kib as f64 / 1024.0),
+        None => println!("Peak memory: unavailable on this platform"),
+    }
     Ok(())
 }
 
@@ -56,8 +103,8 @@ fn build_devices(count: usize) -> Vec<Device> {
         .map(|i| Device {
             id: format!("bench-{i}"),
             name: format!("bench-{i}"),
-            device_type: DeviceType::GenericSsh,
-            mgmt_address: format!("10.0.0.{i}"),
+            device_type: DeviceType::CiscoIos,
+            mgmt_address: format!("sim-{i}"),
             credential: CredentialRef {
                 name: "bench".into(),
             },
@@ -66,3 +113,25 @@ fn build_devices(count: usize) -> Vec<Device> {
         })
         .collect()
 }
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile_ms(sorted: &[Duration], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1].as_secs_f64() * 1000.0
+}
+
+/// High-water mark of the resident set, from `/proc/self/status` (Linux only).
+fn peak_rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
@@ -1,6 +1,6 @@
 use anyhow::{Context, Result};
 use clap::Args;
-use git2::{IndexAddOption, Repository};
+use git2::{IndexAddOption, Repository, Signature};
 use nauto_model::Device;
 use serde::Deserialize;
 use std::fs;
@@ -49,12 +49,20 @@ pub fn run(cmd: GitOpsCmd) -> Result<()> {
 }
 
 fn commit_configs(repo: &Repository, message: &str) -> Result<()> {
+    commit(repo, &["configs"], message)
+}
+
+/// Stages `pathspecs` and commits them on top of HEAD (or as the root commit).
+pub(crate) fn commit(repo: &Repository, pathspecs: &[&str], message: &str) -> Result<()> {
     let mut index = repo.index()?;
-    index.add_all(["configs"], IndexAddOption::DEFAULT, None)?;
+    index.add_all(pathspecs, IndexAddOption::DEFAULT, None)?;
     index.write()?;
     let tree_id = index.write_tree()?;
     let tree = repo.find_tree(tree_id)?;
-    let sig = repo.signature()?;
+    // Scheduled jobs often run where no git identity is configured.
+    let sig = repo
+        .signature()
+        .or_else(|_| Signature::now("netrust", "netrust@localhost"))?;
     let parents: Vec<git2::Commit> = repo
         .head()
         .ok()
@@ -1,13 +1,17 @@
-use crate::{audit, plugins};
-use anyhow::{anyhow, Result};
+use crate::{audit, backup, plugins, settings};
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
 use nauto_drivers::drivers::{
-    AristaEosDriver, CiscoIosDriver, CiscoNxosApiDriver, GenericSshDriver, JuniperJunosDriver,
-    MerakiCloudDriver, MockDriver,
+    AristaEosDriver, CiscoIosDriver, CiscoIosXrDriver, CiscoNxosApiDriver, FortinetFortiosDriver,
+    GenericNetconfDriver, GenericSshDriver, JuniperJunosDriver, LinuxHostDriver, MerakiCloudDriver,
+    MockDriver, PaloAltoPanosDriver,
 };
-use nauto_drivers::{DeviceDriver, DriverRegistry};
+use nauto_drivers::{DeviceDriver, DriverRegistry, Transport, VersionReq};
 use nauto_engine::{InMemoryInventory, JobEngine};
-use nauto_model::{CapabilitySet, Device, DeviceType, Job, JobKind, JobResult, TargetSelector};
+use nauto_model::{
+    CapabilitySet, Device, DeviceType, Job, JobKind, JobResult, TargetSelector, TaskStatus,
+};
+use nauto_security::Redactor;
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
@@ -74,12 +78,74 @@ pub async fn execute_job(
         job.dry_run = true;
     }
     let registry = driver_registry();
-    let engine = JobEngine::new(InMemoryInventory::new(inventory.devices.clone()), registry);
-    let result = engine.execute(job.clone()).await?;
+    let engine = JobEngine::new(InMemoryInventory::new(inventory.devices.clone()), registry)
+        .with_redactor(Redactor::from_env()?)
+        .with_credential_store(settings::credential_store()?);
+    let mut result = engine.execute(job.clone()).await?;
+    if let JobKind::ConfigBackup { storage, .. } = &job.kind {
+        backup::store(storage, &job, &mut result)?;
+    }
     audit::record(audit_path.to_path_buf(), &job, &result)?;
     Ok((job, result))
 }
 
+/// Runs a Preflight job against `job`'s targets and fails unless at least `min_ready`
+/// percent of them are reachable and accept their credential. Returns the ready and
+/// targeted device counts.
+pub async fn preflight_gate(
+    job: &Job,
+    inventory: &InventoryFile,
+    min_ready: f64,
+) -> Result<(usize, usize)> {
+    let preflight = Job {
+        id: Uuid::new_v4(),
+        name: format!("{} (pre-flight)", job.name),
+        kind: JobKind::Preflight,
+        targets: job.targets.clone(),
+        parameters: HashMap::new(),
+        max_parallel: None,
+        dry_run: true,
+        approval_id: None,
+    };
+    let engine = JobEngine::new(
+        InMemoryInventory::new(inventory.devices.clone()),
+        driver_registry(),
+    )
+    .with_redactor(Redactor::from_env()?)
+    .with_credential_store(settings::credential_store()?);
+    let result = engine.execute(preflight).await?;
+    let total = result.device_results.len();
+    let not_ready: Vec<String> = result
+        .device_results
+        .iter()
+        .filter(|task| task.status != TaskStatus::Success)
+        .map(|task| {
+            let reason = task
+                .logs
+                .iter()
+                .find(|line| line.contains("FAILED") || line.starts_with("error:"))
+                .or(task.logs.first())
+                .cloned()
+                .unwrap_or_default();
+            format!("{}: {}", task.device_id, reason)
+        })
+        .collect();
+    let ready = total - not_ready.len();
+    if total > 0 && (ready as f64) * 100.0 < min_ready * total as f64 {
+        bail!(
+            "pre-flight: {}/{} devices ready, below the {}% threshold\n{}",
+            ready,
+            total,
+            min_ready,
+            not_ready.join("\n")
+        );
+    }
+    for line in &not_ready {
+        warn!("pre-flight: {line}");
+    }
+    Ok((ready, total))
+}
+
 pub fn load_inventory(path: &Path) -> Result<InventoryFile> {
     let data = std::fs::read_to_string(path)?;
     let inventory = serde_yaml::from_str(&data)?;
@@ -161,11 +227,18 @@ pub fn driver_registry() -> DriverRegistry {
     }
     let mut drivers: Vec<Arc<dyn DeviceDriver>> = vec![
         Arc::new(CiscoIosDriver::default()),
+        Arc::new(CiscoIosXrDriver::default().with_transport(Transport::Ssh)),
+        Arc::new(CiscoIosXrDriver::default().with_transport(Transport::Netconf)),
         Arc::new(JuniperJunosDriver::default()),
         Arc::new(GenericSshDriver::default()),
-        Arc::new(AristaEosDriver::default()),
+        Arc::new(AristaEosDriver::default().with_transport(Transport::Ssh)),
+        Arc::new(AristaEosDriver::default().with_transport(Transport::Http)),
         Arc::new(CiscoNxosApiDriver::default()),
         Arc::new(MerakiCloudDriver::default()),
+        Arc::new(GenericNetconfDriver::default()),
+        Arc::new(PaloAltoPanosDriver::default()),
+        Arc::new(FortinetFortiosDriver::default()),
+        Arc::new(LinuxHostDriver::default()),
     ];
     extend_with_plugin_drivers(&mut drivers);
     DriverRegistry::new(drivers)
@@ -174,11 +247,16 @@ pub fn driver_registry() -> DriverRegistry {
 fn mock_driver_registry() -> DriverRegistry {
     let drivers = [
         nauto_model::DeviceType::CiscoIos,
+        nauto_model::DeviceType::CiscoIosXr,
         nauto_model::DeviceType::JuniperJunos,
         nauto_model::DeviceType::GenericSsh,
         nauto_model::DeviceType::AristaEos,
         nauto_model::DeviceType::CiscoNxosApi,
         nauto_model::DeviceType::MerakiCloud,
+        nauto_model::DeviceType::Netconf,
+        nauto_model::DeviceType::PaloAltoPanos,
+        nauto_model::DeviceType::FortinetFortios,
+        nauto_model::DeviceType::LinuxHost,
     ]
     .into_iter()
     .map(|device_type| Arc::new(MockDriver::new(device_type)) as Arc<dyn DeviceDriver>)
@@ -186,6 +264,8 @@ fn mock_driver_registry() -> DriverRegistry {
     DriverRegistry::new(drivers)
 }
 
+/// A plugin for a type that already has a built-in driver is only registered when it
+/// declares the versions it covers, and then overrides the built-in for those versions.
 fn extend_with_plugin_drivers(drivers: &mut Vec<Arc<dyn DeviceDriver>>) {
     let enable_plugins = std::env::var("NAUTO_ENABLE_PLUGIN_DRIVERS")
         .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
@@ -194,9 +274,21 @@ fn extend_with_plugin_drivers(drivers: &mut Vec<Arc<dyn DeviceDriver>>) {
     for descriptor in plugins::plugin_drivers() {
         match descriptor.device_type.parse::<DeviceType>() {
             Ok(device_type) => {
-                if drivers
-                    .iter()
-                    .any(|driver| driver.device_type() == device_type)
+                let versions = match descriptor.versions.as_deref().map(str::parse::<VersionReq>) {
+                    Some(Ok(versions)) => Some(versions),
+                    Some(Err(err)) => {
+                        warn!(
+                            "Plugin {} declared invalid versions: {}",
+                            descriptor.vendor, err
+                        );
+                        continue;
+                    }
+                    None => None,
+                };
+                if versions.is_none()
+                    && drivers
+                        .iter()
+                        .any(|driver| driver.device_type() == device_type)
                 {
                     continue;
                 }
@@ -207,7 +299,7 @@ fn extend_with_plugin_drivers(drivers: &mut Vec<Arc<dyn DeviceDriver>>) {
                     );
                     continue;
                 }
-                let placeholder = PluginDriverPlaceholder::new(&descriptor, device_type);
+                let placeholder = PluginDriverPlaceholder::new(&descriptor, device_type, versions);
                 drivers.push(Arc::new(placeholder));
             }
             Err(err) => warn!(
@@ -222,14 +314,20 @@ struct PluginDriverPlaceholder {
     vendor: String,
     device_type: DeviceType,
     capabilities: CapabilitySet,
+    versions: Option<VersionReq>,
 }
 
 impl PluginDriverPlaceholder {
-    fn new(descriptor: &plugins::PluginDriverDescriptor, device_type: DeviceType) -> Self {
+    fn new(
+        descriptor: &plugins::PluginDriverDescriptor,
+        device_type: DeviceType,
+        versions: Option<VersionReq>,
+    ) -> Self {
         Self {
             vendor: descriptor.vendor.clone(),
             device_type,
             capabilities: capability_mask_to_set(descriptor.capabilities),
+            versions,
         }
     }
 }
@@ -248,10 +346,15 @@ impl DeviceDriver for PluginDriverPlaceholder {
         self.capabilities.clone()
     }
 
+    fn versions(&self) -> Option<VersionReq> {
+        self.versions.clone()
+    }
+
     async fn execute(
         &self,
         device: &Device,
         _action: nauto_drivers::DriverAction<'_>,
+        _ctx: &nauto_drivers::JobContext,
     ) -> Result<nauto_drivers::DriverExecutionResult> {
         Err(anyhow!(
             "plugin driver from {} is not yet executable for device {}",
@@ -274,5 +377,6 @@ fn capability_mask_to_set(mask: nauto_plugin_sdk::CapabilityMask) -> CapabilityS
         supports_rollback: mask.contains(nauto_plugin_sdk::CapabilityMask::ROLLBACK),
         supports_diff: mask.contains(nauto_plugin_sdk::CapabilityMask::DIFF),
         supports_dry_run: mask.contains(nauto_plugin_sdk::CapabilityMask::DRY_RUN),
+        supports_replace: mask.contains(nauto_plugin_sdk::CapabilityMask::REPLACE),
     }
 }
@@ -1,5 +1,6 @@
 pub mod approvals;
 pub mod audit;
+pub mod backup;
 pub mod bench;
 pub mod compliance;
 pub mod gitops;
@@ -9,7 +10,9 @@ pub mod marketplace;
 pub mod notifications;
 pub mod observability;
 pub mod plugins;
+pub mod remediation;
 pub mod scheduler;
+pub mod settings;
 pub mod telemetry;
 pub mod transactions;
 pub mod tui;
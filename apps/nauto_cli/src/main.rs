@@ -2,10 +2,10 @@ use anyhow::{bail, Context, Result};
 use clap::{Parser, Subcommand};
 use nauto_cli::{
     approvals, bench, compliance, gitops, integrations, job_runner, marketplace, notifications,
-    observability, plugins, scheduler, telemetry, transactions, tui, worker,
+    observability, plugins, remediation, scheduler, settings, telemetry, transactions, tui, worker,
 };
-use nauto_model::{Credential, CredentialRef, TaskStatus};
-use nauto_security::{CredentialStore, KeyringStore};
+use nauto_model::{Credential, CredentialRef, Job, TaskStatus};
+use nauto_security::CredentialStore;
 use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -46,8 +46,34 @@ enum Commands {
         plan: Option<PathBuf>,
         #[arg(long, default_value_t = false, help = "Disable CLI progress indicator")]
         no_progress: bool,
+        #[arg(
+            long,
+            default_value_t = false,
+            help = "Check reachability and login of the targets before running the job"
+        )]
+        preflight: bool,
+        #[arg(
+            long,
+            default_value_t = 100.0,
+            help = "Minimum percentage of targets that must pass pre-flight"
+        )]
+        min_ready: f64,
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with = "replay",
+            help = "Record each device session into DIR/<device id>.yaml, credentials redacted"
+        )]
+        record: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with = "preflight",
+            help = "Replay device sessions recorded with --record instead of contacting devices"
+        )]
+        replay: Option<PathBuf>,
     },
-    /// Store credentials securely using the OS keychain
+    /// Store credentials in the configured credential store (OS keychain by default)
     Creds {
         #[arg(long)]
         name: String,
@@ -92,8 +118,10 @@ enum Commands {
     Integrations(integrations::IntegrationsCmd),
     /// Interact with plugin marketplace index
     Marketplace(marketplace::MarketplaceCmd),
-    /// Run synthetic benchmark against mock drivers
+    /// Benchmark the job engine against simulated devices
     Bench(bench::BenchCmd),
+    /// Generate a ConfigPush job that converges a device on a desired config
+    Remediate(remediation::RemediateCmd),
     /// Plan staged change transactions
     Transactions(transactions::TransactionsCmd),
     /// Process queued jobs as a worker node
@@ -119,12 +147,16 @@ async fn main() -> Result<()> {
             dry_run,
             plan,
             no_progress,
+            preflight,
+            min_ready,
+            record,
+            replay,
         } => {
-            let mut progress = if no_progress {
-                None
-            } else {
-                Some(ProgressBar::start("Executing job"))
-            };
+            if let Some(dir) = replay {
+                nauto_drivers::replay::set_mode(Some(nauto_drivers::replay::Mode::Replay(dir)));
+            } else if let Some(dir) = record {
+                nauto_drivers::replay::set_mode(Some(nauto_drivers::replay::Mode::Record(dir)));
+            }
             let job_file = job_runner::load_job(&job)?;
             if let Some(approval_id) = job_file.approval_id {
                 if !approvals::is_approved(&approvals, &approval_id)? {
@@ -137,16 +169,22 @@ async fn main() -> Result<()> {
                 }
             }
             let inventory_file = job_runner::load_inventory(&inventory)?;
+            let job_file: Job = job_file.into();
+            if preflight {
+                let (ready, total) =
+                    job_runner::preflight_gate(&job_file, &inventory_file, min_ready).await?;
+                println!("Pre-flight: {ready}/{total} devices ready");
+            }
+            let mut progress = if no_progress {
+                None
+            } else {
+                Some(ProgressBar::start("Executing job"))
+            };
 
             if let Some(plan_path) = plan {
-                let results = job_runner::run_plan(
-                    &plan_path,
-                    job_file.into(),
-                    inventory_file,
-                    &audit_log,
-                    dry_run,
-                )
-                .await?;
+                let results =
+                    job_runner::run_plan(&plan_path, job_file, inventory_file, &audit_log, dry_run)
+                        .await?;
                 if let Some(mut spinner) = progress.take() {
                     spinner.stop();
                     println!();
@@ -161,8 +199,7 @@ async fn main() -> Result<()> {
                 }
             } else {
                 let (_job, result) =
-                    job_runner::execute_job(job_file.into(), inventory_file, &audit_log, dry_run)
-                        .await?;
+                    job_runner::execute_job(job_file, inventory_file, &audit_log, dry_run).await?;
                 if let Some(mut spinner) = progress.take() {
                     spinner.stop();
                     println!();
@@ -204,6 +241,7 @@ async fn main() -> Result<()> {
         Commands::Integrations(cmd) => integrations::run(cmd)?,
         Commands::Marketplace(cmd) => marketplace::run(cmd)?,
         Commands::Bench(cmd) => bench::run(cmd).await?,
+        Commands::Remediate(cmd) => remediation::run(cmd)?,
         Commands::Transactions(cmd) => transactions::run(cmd)?,
         Commands::Worker(cmd) => worker::run(cmd)?,
         Commands::Observability(cmd) => observability::run(cmd)?,
@@ -221,7 +259,7 @@ fn init_tracing() {
 }
 
 async fn store_credentials(name: String, username: String, password: String) -> Result<()> {
-    let store = KeyringStore::new("netrust");
+    let store = settings::credential_store()?;
     let reference = CredentialRef { name };
     let credential = Credential::UserPassword { username, password };
     store.store(&reference, &credential).await?;
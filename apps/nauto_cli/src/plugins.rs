@@ -62,6 +62,9 @@ pub fn plugin_drivers() -> Vec<PluginDriverDescriptor> {
 pub struct PluginDriverDescriptor {
     pub vendor: String,
     pub device_type: String,
+    /// Version constraint such as `>=4.32`; a plugin with one overrides the built-in driver
+    /// for matching devices.
+    pub versions: Option<String>,
     pub capabilities: CapabilityMask,
     pub artifact: PathBuf,
 }
@@ -69,6 +72,7 @@ pub struct PluginDriverDescriptor {
 struct LoadedPlugin {
     vendor: String,
     device_type: String,
+    versions: Option<String>,
     capabilities: CapabilityMask,
     path: PathBuf,
 }
@@ -85,6 +89,7 @@ impl From<LoadedPlugin> for PluginDriverDescriptor {
         PluginDriverDescriptor {
             vendor: plugin.vendor,
             device_type: plugin.device_type,
+            versions: plugin.versions,
             capabilities: plugin.capabilities,
             artifact: plugin.path,
         }
@@ -156,10 +161,24 @@ fn load_single(engine: &Engine, path: &Path) -> Result<LoadedPlugin> {
         device_ptr as usize,
         device_len as usize,
     )?;
+    // Plugins built before version overrides existed do not export these.
+    let versions = match (
+        instance.get_typed_func::<(), i32>(&mut store, "plugin_versions_ptr"),
+        instance.get_typed_func::<(), i32>(&mut store, "plugin_versions_len"),
+    ) {
+        (Ok(ptr), Ok(len)) => {
+            let ptr = ptr.call(&mut store, ())?;
+            let len = len.call(&mut store, ())?;
+            Some(read_utf8(&mut store, &memory, ptr as usize, len as usize)?)
+                .filter(|versions| !versions.trim().is_empty())
+        }
+        _ => None,
+    };
 
     Ok(LoadedPlugin {
         vendor,
         device_type,
+        versions,
         capabilities: CapabilityMask::from_bits_truncate(caps_bits),
         path: path.to_path_buf(),
     })
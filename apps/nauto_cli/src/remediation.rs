@@ -0,0 +1,70 @@
+use crate::job_runner;
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use nauto_drivers::remediation;
+use nauto_model::{ConfigMode, JobKind, TargetSelector};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct RemediateCmd {
+    #[arg(long)]
+    pub inventory: PathBuf,
+    #[arg(long, help = "Device id from the inventory")]
+    pub device: String,
+    #[arg(long, help = "Current running config, e.g. a ConfigBackup running.cfg")]
+    pub running: PathBuf,
+    #[arg(long, help = "Intended configuration")]
+    pub desired: PathBuf,
+    #[arg(long, help = "Write the job file here instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+/// Same layout as the job files `nauto_cli run --job` reads.
+#[derive(Debug, Serialize)]
+struct RemediationJob {
+    name: String,
+    kind: JobKind,
+    targets: TargetSelector,
+    dry_run: bool,
+}
+
+pub fn run(cmd: RemediateCmd) -> Result<()> {
+    let inventory = job_runner::load_inventory(&cmd.inventory)?;
+    let device = inventory
+        .devices
+        .iter()
+        .find(|device| device.id == cmd.device)
+        .ok_or_else(|| anyhow!("device {} not found in inventory", cmd.device))?;
+    let running = fs::read_to_string(&cmd.running)
+        .with_context(|| format!("failed to read {}", cmd.running.display()))?;
+    let desired = fs::read_to_string(&cmd.desired)
+        .with_context(|| format!("failed to read {}", cmd.desired.display()))?;
+
+    let snippet = remediation::remediate(&device.device_type, &running, &desired)?;
+    if snippet.is_empty() {
+        eprintln!("{} already matches the desired config", device.name);
+        return Ok(());
+    }
+    let job = RemediationJob {
+        name: format!("remediate-{}", device.id),
+        kind: JobKind::ConfigPush {
+            snippet,
+            mode: ConfigMode::Merge,
+        },
+        targets: TargetSelector::ByIds {
+            ids: vec![device.id.clone()],
+        },
+        dry_run: false,
+    };
+    let yaml = serde_yaml::to_string(&job)?;
+    match cmd.output {
+        Some(path) => {
+            fs::write(&path, yaml)?;
+            eprintln!("Remediation job written to {}", path.display());
+        }
+        None => print!("{yaml}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! The netrust config file: the path in `NAUTO_CONFIG`, else `netrust.yaml` in the working
+//! directory if present. Its `credentials:` section is the only credential configuration
+//! the CLI uses when present; `NAUTO_CREDENTIAL_STORE` is then ignored. Without one,
+//! credentials come from `nauto_drivers::ssh::default_credential_store` (the keyring, or
+//! Vault via environment).
+
+use anyhow::{Context, Result};
+use nauto_security::{CredentialStore, CredentialsConfig};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tracing::warn;
+
+const DEFAULT_CONFIG_FILE: &str = "netrust.yaml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub credentials: Option<CredentialsConfig>,
+}
+
+impl Settings {
+    pub fn load() -> Result<Self> {
+        match config_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+        serde_yaml::from_str(&text)
+            .with_context(|| format!("parsing config file {}", path.display()))
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("NAUTO_CONFIG").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let local = PathBuf::from(DEFAULT_CONFIG_FILE);
+    local.exists().then_some(local)
+}
+
+static CREDENTIAL_STORE: OnceLock<Arc<dyn CredentialStore>> = OnceLock::new();
+
+/// The credential store every driver in this process uses, built once from the config
+/// file's `credentials:` section so Vault logins and cached secrets are shared between
+/// pre-flight and the job itself. The job runner hands it to every driver in the registry,
+/// so none of them resolves through the drivers' own default store.
+pub fn credential_store() -> Result<Arc<dyn CredentialStore>> {
+    if let Some(store) = CREDENTIAL_STORE.get() {
+        return Ok(store.clone());
+    }
+    let store = store_for(
+        Settings::load()?.credentials,
+        nauto_drivers::config::credential_backend(),
+    )?;
+    Ok(CREDENTIAL_STORE.get_or_init(|| store).clone())
+}
+
+/// The config file's chain when it has a `credentials:` section, whatever
+/// `NAUTO_CREDENTIAL_STORE` (`env_backend`) says; the drivers' default store otherwise.
+fn store_for(
+    credentials: Option<CredentialsConfig>,
+    env_backend: Option<&str>,
+) -> Result<Arc<dyn CredentialStore>> {
+    let Some(config) = credentials else {
+        return Ok(nauto_drivers::ssh::default_credential_store());
+    };
+    if let Some(backend) = env_backend {
+        warn!(
+            "NAUTO_CREDENTIAL_STORE={backend} is ignored: the config file's credentials section selects the credential stores"
+        );
+    }
+    Ok(Arc::new(
+        config.build().context("credentials in config file")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nauto_model::CredentialRef;
+
+    #[tokio::test]
+    async fn config_file_chain_wins_over_the_environment_backend() {
+        let settings: Settings = serde_yaml::from_str(
+            "credentials:\n  chain: [env]\n  env: {prefix: NAUTO_SETTINGS_TEST_}\n",
+        )
+        .unwrap();
+        let store = store_for(settings.credentials, Some("vault")).unwrap();
+        let err = store
+            .resolve(&CredentialRef { name: "lab".into() })
+            .await
+            .unwrap_err();
+        // Only the chain was asked; the Vault backend would have complained about VAULT_ADDR.
+        assert_eq!(
+            err.to_string(),
+            "credential 'lab' not found (env: NAUTO_SETTINGS_TEST_LAB_USERNAME is not set)"
+        );
+    }
+}
@@ -26,6 +26,213 @@ fn run_command_uses_mock_drivers() {
     assert!(audit_path.exists(), "audit log should be written");
 }
 
+#[test]
+fn preflight_threshold_gates_the_run() {
+    let dir = TempDir::new().expect("temp dir");
+    let inventory = dir.path().join("inventory.yaml");
+    std::fs::write(
+        &inventory,
+        r#"
+devices:
+  - id: r1
+    name: r1
+    device_type: cisco_ios
+    mgmt_address: 10.0.0.1
+    credential: { name: default }
+    tags: []
+    capabilities: {}
+  - id: r2
+    name: r2
+    device_type: cisco_ios
+    mgmt_address: 10.0.0.2
+    credential: { name: default }
+    tags: ["mock:unreachable"]
+    capabilities: {}
+"#,
+    )
+    .expect("write inventory");
+    let run = |min_ready: &str| {
+        let mut cmd = Command::cargo_bin("nauto_cli").expect("binary");
+        cmd.env("NAUTO_USE_MOCK_DRIVERS", "1")
+            .arg("run")
+            .arg("--job")
+            .arg(path("examples/jobs/show_version.yaml"))
+            .arg("--inventory")
+            .arg(&inventory)
+            .arg("--audit-log")
+            .arg(dir.path().join("audit.log"))
+            .arg("--no-progress")
+            .arg("--preflight")
+            .arg("--min-ready")
+            .arg(min_ready);
+        cmd
+    };
+
+    run("100")
+        .assert()
+        .failure()
+        .stderr(contains("1/2 devices ready"))
+        .stderr(contains("r2:"));
+    run("50")
+        .assert()
+        .success()
+        .stdout(contains("Pre-flight: 1/2 devices ready"))
+        .stdout(contains("Job complete"));
+}
+
+#[test]
+fn run_replays_recorded_sessions() {
+    let dir = TempDir::new().expect("temp dir");
+    let inventory = dir.path().join("inventory.yaml");
+    std::fs::write(
+        &inventory,
+        r#"
+devices:
+  - id: core-r1
+    name: Core-R1
+    device_type: cisco_ios
+    mgmt_address: 10.0.0.1
+    credential: { name: lab-default }
+    tags: []
+    capabilities: {}
+"#,
+    )
+    .expect("write inventory");
+    let run = |replay: &str| {
+        let mut cmd = Command::cargo_bin("nauto_cli").expect("binary");
+        cmd.arg("run")
+            .arg("--job")
+            .arg(path("examples/jobs/show_version.yaml"))
+            .arg("--inventory")
+            .arg(&inventory)
+            .arg("--audit-log")
+            .arg(dir.path().join("audit.log"))
+            .arg("--no-progress")
+            .arg("--replay")
+            .arg(replay);
+        cmd
+    };
+
+    run(&path("examples/replay"))
+        .assert()
+        .success()
+        .stdout(contains("Job complete: success=1 failed=0"));
+    run(dir.path().to_str().expect("utf-8 path"))
+        .assert()
+        .success()
+        .stdout(contains("Failed devices: core-r1"));
+}
+
+#[test]
+fn run_redacts_secrets_in_output_and_audit_log() {
+    let dir = TempDir::new().expect("temp dir");
+    let job = dir.path().join("job.yaml");
+    std::fs::write(
+        &job,
+        r#"
+name: Show community
+kind:
+  type: command_batch
+  commands:
+    - show community S3cret
+targets:
+  mode: all
+"#,
+    )
+    .expect("write job");
+    let patterns = dir.path().join("redact.txt");
+    std::fs::write(&patterns, "# site secrets\ncommunity (\\S+)\n").expect("write patterns");
+    let audit = dir.path().join("audit.log");
+    let run = |patterns: &Path| {
+        let mut cmd = Command::cargo_bin("nauto_cli").expect("binary");
+        cmd.env("NAUTO_USE_MOCK_DRIVERS", "1")
+            .env("NAUTO_REDACT_PATTERNS", patterns)
+            .arg("run")
+            .arg("--job")
+            .arg(&job)
+            .arg("--inventory")
+            .arg(path("examples/inventory.yaml"))
+            .arg("--audit-log")
+            .arg(&audit)
+            .arg("--no-progress");
+        cmd
+    };
+
+    let output = run(&patterns).assert().success().get_output().clone();
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("S3cret"));
+    let records = std::fs::read_to_string(dir.path().join("audit.devices.jsonl")).unwrap();
+    assert!(records.contains("community <redacted>"), "{records}");
+    assert!(!records.contains("S3cret"), "{records}");
+
+    std::fs::write(&patterns, "community (\\S+\n").expect("write patterns");
+    run(&patterns)
+        .assert()
+        .failure()
+        .stderr(contains("invalid redaction pattern"));
+}
+
+#[test]
+fn bench_reports_latency_percentiles_for_simulated_devices() {
+    Command::cargo_bin("nauto_cli")
+        .expect("binary")
+        .arg("bench")
+        .arg("--devices")
+        .arg("200")
+        .arg("--parallel")
+        .arg("200")
+        .arg("--profile")
+        .arg(path("examples/simulation.yaml"))
+        .assert()
+        .success()
+        .stdout(contains("Devices processed: 200"))
+        .stdout(contains("Latency p50/p95/p99:"))
+        .stdout(contains("Peak memory:"));
+}
+
+#[test]
+fn creds_follow_the_config_file_chain() {
+    let dir = TempDir::new().expect("temp dir");
+    let secrets = dir.path().join("credentials.age");
+    let config = dir.path().join("netrust.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            "credentials:\n  chain: [env, file]\n  file:\n    path: {}\n",
+            secrets.display()
+        ),
+    )
+    .expect("write config");
+    let creds = |name: &str| {
+        let mut cmd = Command::cargo_bin("nauto_cli").expect("binary");
+        cmd.env("NAUTO_CONFIG", &config)
+            .env("NAUTO_ENCRYPTION_KEY", "test-passphrase")
+            .args([
+                "creds",
+                "--name",
+                name,
+                "--username",
+                "admin",
+                "--password-stdin",
+            ])
+            .write_stdin("s3cr3t-value\n");
+        cmd
+    };
+
+    // Environment credentials are read-only, so the chain stores into the encrypted file.
+    creds("lab").assert().success();
+    let stored = std::fs::read(&secrets).expect("credentials file");
+    assert!(!String::from_utf8_lossy(&stored).contains("s3cr3t-value"));
+
+    creds("env:ci")
+        .assert()
+        .failure()
+        .stderr(contains("read-only"));
+    creds("vault:core")
+        .assert()
+        .failure()
+        .stderr(contains("'vault' is not configured"));
+}
+
 fn path(relative: &str) -> String {
     Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("..")
@@ -0,0 +1,100 @@
+//! Normalization for configuration backups. Lines that change on every read (timestamps,
+//! byte counts, NTP clock drift, configuration checksums) are dropped so that two backups of
+//! an unchanged device compare equal.
+
+use nauto_model::{ConfigSnapshot, Device};
+use once_cell::sync::Lazy;
+use regex::RegexSet;
+
+static VOLATILE: Lazy<RegexSet> = Lazy::new(|| {
+    RegexSet::new([
+        // IOS / IOS-XE
+        r"^Building configuration\.\.\.$",
+        r"^Current configuration\s*:\s*\d+ bytes$",
+        r"^Using \d+ out of \d+ bytes",
+        r"^! (Last configuration change|NVRAM config last updated) at ",
+        r"^! No configuration change since last restart",
+        r"^ntp clock-period \d+$",
+        // IOS-XR prints the current time above the configuration.
+        r"^(Mon|Tue|Wed|Thu|Fri|Sat|Sun) [A-Z][a-z]{2} +\d+ \d{2}:\d{2}:\d{2}(\.\d+)? \S+$",
+        r"^!! Last configuration change at ",
+        // EOS and NX-OS headers
+        r"^! ?(Command|Time): ",
+        r"^! Startup-config last modified at ",
+        r"^!(Running configuration last done|Startup config saved) at: ",
+        // Junos
+        r"^## Last (commit|changed): ",
+        // ASA and FortiOS checksums and revision counters
+        r"^Cryptochecksum:",
+        r"^: (Saved|Written by )",
+        r"^#conf_file_ver=",
+    ])
+    .expect("volatile line patterns")
+});
+
+/// Drops volatile lines and trailing whitespace, and turns CRLF into LF.
+pub fn normalize(config: &str) -> String {
+    let mut normalized = String::with_capacity(config.len());
+    for line in config.lines().map(str::trim_end) {
+        if VOLATILE.is_match(line) {
+            continue;
+        }
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+    normalized
+}
+
+pub fn snapshot(running: &str, startup: Option<&str>) -> ConfigSnapshot {
+    ConfigSnapshot {
+        running: normalize(running),
+        startup: startup.map(normalize),
+    }
+}
+
+/// Job log line for a fetched snapshot.
+pub fn describe(device: &Device, snapshot: &ConfigSnapshot) -> String {
+    let mut line = format!(
+        "[{}] fetched running config ({} lines)",
+        device.name,
+        snapshot.running.lines().count()
+    );
+    if let Some(startup) = &snapshot.startup {
+        line.push_str(&format!(
+            " and startup config ({} lines)",
+            startup.lines().count()
+        ));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_timestamps_and_clock_drift() {
+        let ios = "Building configuration...\r\n\r\nCurrent configuration : 1834 bytes\r\n\
+                   !\r\n! Last configuration change at 10:12:01 UTC Mon Mar 4 2024 by admin\r\n\
+                   ! NVRAM config last updated at 10:12:05 UTC Mon Mar 4 2024 by admin\r\n\
+                   version 17.9   \r\nhostname r1\r\nntp clock-period 17179869\r\nend\r\n";
+        assert_eq!(normalize(ios), "\n!\nversion 17.9\nhostname r1\nend\n");
+
+        let junos = "## Last commit: 2024-03-04 10:12:01 UTC by netrust\nversion 23.2R1;\n";
+        assert_eq!(normalize(junos), "version 23.2R1;\n");
+
+        let eos = "! Command: show running-config\n! device: leaf1 (vEOS-lab, EOS-4.31.2F)\n\
+                   ! Time: Mon Mar  4 10:12:01 2024\nhostname leaf1\n";
+        assert_eq!(
+            normalize(eos),
+            "! device: leaf1 (vEOS-lab, EOS-4.31.2F)\nhostname leaf1\n"
+        );
+
+        let xr = "Mon Mar  4 10:12:01.123 UTC\nBuilding configuration...\n\
+                  !! IOS XR Configuration 7.9.2\n!! Last configuration change at Mon Mar  4 by admin\nhostname xr1\n";
+        assert_eq!(
+            normalize(xr),
+            "!! IOS XR Configuration 7.9.2\nhostname xr1\n"
+        );
+    }
+}
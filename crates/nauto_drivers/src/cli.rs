@@ -0,0 +1,222 @@
+//! Interactive CLI sessions for platforms whose configuration state only lives inside a
+//! single shell (IOS-XR candidate config, config modes that exec channels cannot hold).
+
+use crate::ssh;
+use crate::ssh::Client;
+use anyhow::{bail, Context, Result};
+use nauto_model::Device;
+use nauto_security::CredentialStore;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+const READ_CHUNK: usize = 4096;
+/// Markers the Cisco-style CLIs print when a command is rejected.
+const ERROR_MARKERS: &[&str] = &[
+    "% Invalid input",
+    "% Incomplete command",
+    "% Ambiguous command",
+    "% Failed",
+    "% Error",
+    "% Bad",
+];
+
+trait CliIo: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T> CliIo for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
+
+pub struct CliSession {
+    #[allow(dead_code)]
+    client: Option<Client>,
+    stream: Pin<Box<dyn CliIo>>,
+    buffer: String,
+    prompt: String,
+}
+
+impl CliSession {
+    /// Opens a PTY shell over SSH and waits for the first prompt.
+    pub async fn connect(
+        device: &Device,
+        store: &dyn CredentialStore,
+        port: u16,
+    ) -> Result<CliSession> {
+        // Raw TCP shell for the conformance stand-ins; never compiled into production builds.
+        #[cfg(any(test, feature = "conformance"))]
+        if let Some(addr) = device.mgmt_address.strip_prefix("tcp://") {
+            let stream = tokio::net::TcpStream::connect(addr)
+                .await
+                .with_context(|| format!("cli tcp connect {} ({addr})", device.name))?;
+            return CliSession::from_stream(stream).await;
+        }
+        if device.mgmt_address.starts_with("tcp://") {
+            bail!(
+                "{}: tcp:// management addresses are only supported by the conformance stand-ins",
+                device.name
+            );
+        }
+        let client = ssh::connect(device, store, port).await?;
+        let channel = client
+            .get_channel()
+            .await
+            .with_context(|| format!("cli channel {}", device.name))?;
+        channel
+            .request_pty(true, "vt100", 511, 24, 0, 0, &[])
+            .await
+            .context("pty request denied")?;
+        channel
+            .request_shell(true)
+            .await
+            .context("shell request denied")?;
+        let mut session = CliSession::from_stream(channel.into_stream()).await?;
+        session.client = Some(client);
+        debug!(
+            target: "drivers::cli",
+            "{} interactive session at prompt {}",
+            device.name,
+            session.prompt
+        );
+        Ok(session)
+    }
+
+    /// Waits for the initial prompt on an already established transport.
+    pub async fn from_stream<S>(stream: S) -> Result<CliSession>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut session = CliSession {
+            client: None,
+            stream: Box::pin(stream),
+            buffer: String::new(),
+            prompt: String::new(),
+        };
+        tokio::time::timeout(ssh::command_timeout(), session.read_until_prompt())
+            .await
+            .context("cli timeout waiting for the first prompt")??;
+        Ok(session)
+    }
+
+    /// The most recent prompt, e.g. `RP/0/RP0/CPU0:xr1(config)#`.
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    /// Sends one command and returns its output without the echo and trailing prompt.
+    /// Fails if the device answers with one of the CLI error markers.
+    pub async fn run(&mut self, command: &str) -> Result<String> {
+        let output = self.send(command).await?;
+        if let Some(marker) = ERROR_MARKERS.iter().find(|marker| {
+            output
+                .lines()
+                .any(|line| line.trim_start().starts_with(**marker))
+        }) {
+            bail!(
+                "command '{}' rejected ({}): {}",
+                command,
+                marker.trim_start_matches("% "),
+                output.trim()
+            );
+        }
+        Ok(output)
+    }
+
+    /// Like [`CliSession::run`] but returns the output even when it contains errors.
+    pub async fn send(&mut self, command: &str) -> Result<String> {
+        self.stream
+            .as_mut()
+            .write_all(format!("{command}\n").as_bytes())
+            .await?;
+        self.stream.as_mut().flush().await?;
+        let raw = tokio::time::timeout(ssh::command_timeout(), self.read_until_prompt())
+            .await
+            .with_context(|| format!("cli timeout waiting for prompt after '{command}'"))??;
+        Ok(strip_echo(&raw, command))
+    }
+
+    async fn read_until_prompt(&mut self) -> Result<String> {
+        let mut chunk = [0u8; READ_CHUNK];
+        loop {
+            if let Some((output, prompt)) = split_prompt(&self.buffer, prompt_base(&self.prompt)) {
+                self.prompt = prompt;
+                self.buffer.clear();
+                return Ok(output);
+            }
+            let read = self.stream.as_mut().read(&mut chunk).await?;
+            if read == 0 {
+                bail!("cli stream closed");
+            }
+            self.buffer
+                .push_str(&String::from_utf8_lossy(&chunk[..read]).replace('\r', ""));
+        }
+    }
+}
+
+/// Splits a buffer ending in a prompt line (`host#`, `host(config)#`, `host>`) into the
+/// output before it and the prompt itself. Once the hostname is known, only prompts that
+/// start with it count, so output lines ending in `#` or `>` are not mistaken for one.
+fn split_prompt(buffer: &str, base: &str) -> Option<(String, String)> {
+    let (output, last) = match buffer.rfind('\n') {
+        Some(idx) => (&buffer[..idx + 1], &buffer[idx + 1..]),
+        None => ("", buffer),
+    };
+    let candidate = last.trim_end();
+    let is_prompt = !candidate.is_empty()
+        && candidate.len() <= 128
+        && !candidate.contains(char::is_whitespace)
+        && candidate.starts_with(base)
+        && (candidate.ends_with('#') || candidate.ends_with('>'));
+    is_prompt.then(|| (output.to_string(), candidate.to_string()))
+}
+
+fn prompt_base(prompt: &str) -> &str {
+    let end = prompt.find(['(', '#', '>']).unwrap_or(prompt.len());
+    &prompt[..end]
+}
+
+fn strip_echo(raw: &str, command: &str) -> String {
+    match raw.split_once('\n') {
+        Some((first, rest)) if first.trim_end().ends_with(command.trim()) => rest.to_string(),
+        _ => raw.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn runs_commands_between_prompts() {
+        let (client_io, mut server_io) = duplex(4096);
+        let server = tokio::spawn(async move {
+            server_io.write_all(b"\r\nxr1#").await.unwrap();
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            for reply in [
+                "show clock\r\n12:00:00.000 UTC\r\nxr1#",
+                "configure\r\nxr1(config)#",
+                "bogus\r\n      ^\r\n% Invalid input detected at '^' marker.\r\nxr1(config)#",
+            ] {
+                loop {
+                    server_io.read_exact(&mut byte).await.unwrap();
+                    if byte[0] == b'\n' {
+                        break;
+                    }
+                    line.push(byte[0]);
+                }
+                line.clear();
+                server_io.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+
+        let mut session = CliSession::from_stream(client_io).await.unwrap();
+        assert_eq!(session.prompt(), "xr1#");
+        assert_eq!(
+            session.run("show clock").await.unwrap(),
+            "12:00:00.000 UTC\n"
+        );
+        session.run("configure").await.unwrap();
+        assert_eq!(session.prompt(), "xr1(config)#");
+        let err = session.run("bogus").await.unwrap_err();
+        assert!(err.to_string().contains("Invalid input"));
+        server.await.unwrap();
+    }
+}
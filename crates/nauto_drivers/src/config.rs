@@ -1,9 +1,15 @@
 use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 const DEFAULT_SSH_TIMEOUT_SECS: u64 = 30;
 const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 15;
 const DEFAULT_HTTP_RETRIES: usize = 2;
+const DEFAULT_COMMIT_CONFIRM_SECS: u64 = 120;
+const DEFAULT_RELOAD_TIMEOUT_SECS: u64 = 1800;
+const DEFAULT_PREFLIGHT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_DIFF_MAX_LINES: usize = 1000;
+const DEFAULT_MERAKI_API_BASE: &str = "https://api.meraki.com/api/v1";
 
 static SSH_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
     env_duration(
@@ -27,6 +33,77 @@ static HTTP_RETRIES: Lazy<usize> = Lazy::new(|| {
         .unwrap_or(DEFAULT_HTTP_RETRIES)
 });
 
+static COMMIT_CONFIRM: Lazy<Duration> = Lazy::new(|| {
+    env_duration(
+        "NAUTO_COMMIT_CONFIRM_SECS",
+        Duration::from_secs(DEFAULT_COMMIT_CONFIRM_SECS),
+    )
+});
+
+static RELOAD_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+    env_duration(
+        "NAUTO_RELOAD_TIMEOUT_SECS",
+        Duration::from_secs(DEFAULT_RELOAD_TIMEOUT_SECS),
+    )
+});
+
+static PREFLIGHT_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+    env_duration(
+        "NAUTO_PREFLIGHT_TIMEOUT_SECS",
+        Duration::from_secs(DEFAULT_PREFLIGHT_TIMEOUT_SECS),
+    )
+});
+
+static DIFF_MAX_LINES: Lazy<usize> = Lazy::new(|| {
+    std::env::var("NAUTO_DIFF_MAX_LINES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(DEFAULT_DIFF_MAX_LINES)
+});
+
+static MERAKI_API_BASE: Lazy<String> = Lazy::new(|| {
+    std::env::var("NAUTO_MERAKI_API_BASE")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_MERAKI_API_BASE.into())
+});
+
+static TEMPLATE_DIR: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    std::env::var("NAUTO_TEMPLATE_DIR")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+});
+
+static SSH_KNOWN_HOSTS: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    std::env::var("NAUTO_SSH_KNOWN_HOSTS")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+});
+
+static RECORD_DIR: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    std::env::var("NAUTO_RECORD_DIR")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+});
+
+static REPLAY_DIR: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    std::env::var("NAUTO_REPLAY_DIR")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+});
+
+static CREDENTIAL_BACKEND: Lazy<Option<String>> = Lazy::new(|| {
+    std::env::var("NAUTO_CREDENTIAL_STORE")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_ascii_lowercase())
+});
+
 pub fn ssh_command_timeout() -> Duration {
     *SSH_TIMEOUT
 }
@@ -39,6 +116,57 @@ pub fn http_retry_limit() -> usize {
     *HTTP_RETRIES
 }
 
+/// Window for confirmed commits; `NAUTO_COMMIT_CONFIRM_SECS=0` disables them.
+pub fn commit_confirm_timeout() -> Option<Duration> {
+    Some(*COMMIT_CONFIRM).filter(|timeout| !timeout.is_zero())
+}
+
+/// How long an upgrade waits for a reloaded device to accept SSH again.
+pub fn reload_timeout() -> Duration {
+    *RELOAD_TIMEOUT
+}
+
+/// Budget for each pre-flight step (DNS, TCP connect, login) on one device.
+pub fn preflight_timeout() -> Duration {
+    *PREFLIGHT_TIMEOUT
+}
+
+/// Longest diff kept in a job result; longer diffs end with a truncation marker.
+pub fn diff_max_lines() -> usize {
+    *DIFF_MAX_LINES
+}
+
+/// Dashboard API root; override with `NAUTO_MERAKI_API_BASE` to target a stand-in.
+pub fn meraki_api_base() -> &'static str {
+    &MERAKI_API_BASE
+}
+
+/// Extra parser templates (an ntc-templates style `index` plus `.textfsm` files) that
+/// take precedence over the built-in library.
+pub fn template_dir() -> Option<&'static Path> {
+    TEMPLATE_DIR.as_deref()
+}
+
+/// known_hosts file used to verify SSH host keys instead of `~/.ssh/known_hosts`.
+pub fn ssh_known_hosts() -> Option<&'static Path> {
+    SSH_KNOWN_HOSTS.as_deref()
+}
+
+/// Directory device sessions are recorded into (see `replay`).
+pub fn record_dir() -> Option<&'static Path> {
+    RECORD_DIR.as_deref()
+}
+
+/// Directory of recorded sessions to replay instead of contacting devices.
+pub fn replay_dir() -> Option<&'static Path> {
+    REPLAY_DIR.as_deref()
+}
+
+/// Credential backend drivers use by default (`keyring` or `vault`); keyring when unset.
+pub fn credential_backend() -> Option<&'static str> {
+    CREDENTIAL_BACKEND.as_deref()
+}
+
 fn env_duration(var: &str, default: Duration) -> Duration {
     std::env::var(var)
         .ok()
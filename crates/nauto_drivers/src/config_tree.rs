@@ -0,0 +1,463 @@
+//! Configuration text as a tree, and a semantic diff between two trees.
+//!
+//! Indented configs (IOS, EOS, NX-OS, FortiOS) nest by indentation; Junos text nests by
+//! braces; `set`-style configs are flat. Order matters by default: an entry that moved
+//! within an ordered list (ACL entries, route-map and prefix-list sequences, Junos firewall
+//! terms) is reported as removed from its old position and added at its new one. Sections
+//! the device does not evaluate top to bottom (interfaces, lines, routing processes, Junos
+//! `system`), `remark` and `description` lines, and top-level blocks other than numbered
+//! ACLs and prefix lists are compared as a set. The rendered diff keeps the device's own
+//! indentation and prints each changed block's parents as context.
+
+use crate::config;
+use similar::TextDiff;
+use tracing::warn;
+
+/// Lines that only close a block in indented configs.
+const CLOSERS: &[&str] = &["end", "exit", "next", "exit-address-family"];
+
+/// Blocks whose statements take effect regardless of their order; nothing below them is
+/// compared in order.
+const UNORDERED_SECTIONS: &[&str] = &[
+    "interface",
+    "line",
+    "vlan",
+    "router",
+    "interfaces",
+    "system",
+    "protocols",
+    "routing-options",
+    "snmp",
+    "chassis",
+    "vlans",
+];
+
+/// Lines that may move anywhere within their block.
+const UNORDERED_LINES: &[&str] = &["remark", "description"];
+
+/// Top-level lines that form ordered lists, and how many leading words name the list.
+const ORDERED_TOP_LEVEL: &[(&str, usize)] = &[
+    ("access-list", 2),
+    ("ip prefix-list", 3),
+    ("ipv6 prefix-list", 3),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// Leading whitespace as it appeared on the device.
+    pub indent: String,
+    /// The line without its indentation; siblings are matched on this.
+    pub text: String,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigTree {
+    pub roots: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+}
+
+/// A block (a line and everything under it) added or removed below `parents`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub parents: Vec<Node>,
+    pub node: Node,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub changes: Vec<Change>,
+}
+
+/// Diff text cut to a line budget; `truncated` says whether anything was left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedDiff {
+    pub text: String,
+    pub truncated: bool,
+}
+
+impl ConfigTree {
+    /// Parses Junos-style braces when any line opens a block with `{`, indentation otherwise.
+    pub fn parse(config: &str) -> Self {
+        if config.lines().any(|line| line.trim_end().ends_with('{')) {
+            Self::parse_braces(config)
+        } else {
+            Self::parse_indented(config)
+        }
+    }
+
+    pub fn parse_indented(config: &str) -> Self {
+        let mut roots = Vec::new();
+        // Indentation widths of the currently open blocks, outermost first.
+        let mut open: Vec<usize> = Vec::new();
+        for line in config.lines() {
+            let text = line.trim();
+            if text.is_empty()
+                || text.starts_with('!')
+                || text.starts_with('#')
+                || CLOSERS.contains(&text)
+            {
+                continue;
+            }
+            let width = line.len() - line.trim_start().len();
+            while open.last().is_some_and(|&outer| outer >= width) {
+                open.pop();
+            }
+            let siblings = descend(&mut roots, open.len());
+            siblings.push(node(line, text));
+            open.push(width);
+        }
+        Self { roots }
+    }
+
+    pub fn parse_braces(config: &str) -> Self {
+        let mut roots = Vec::new();
+        let mut depth: usize = 0;
+        for line in config.lines() {
+            let text = line.trim();
+            if text.is_empty() || text.starts_with('#') || text.starts_with("/*") {
+                continue;
+            }
+            if text.starts_with('}') {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            descend(&mut roots, depth).push(node(line, text));
+            if text.ends_with('{') {
+                depth += 1;
+            }
+        }
+        Self { roots }
+    }
+
+    /// The tree back as configuration text, each line with its original indentation.
+    /// Closing braces are not restored, so this suits indented configs.
+    pub fn render(&self) -> String {
+        fn push(nodes: &[Node], out: &mut String) {
+            for node in nodes {
+                out.push_str(&node.indent);
+                out.push_str(&node.text);
+                out.push('\n');
+                push(&node.children, out);
+            }
+        }
+        let mut out = String::new();
+        push(&self.roots, &mut out);
+        out
+    }
+
+    pub fn diff(&self, after: &ConfigTree) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        diff_children(
+            &self.roots,
+            &after.roots,
+            &mut Vec::new(),
+            &mut diff.changes,
+        );
+        diff
+    }
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// `-`/`+` lines for each change, preceded by the parent lines not already printed for
+    /// the previous change. Stops after `max_lines` and says how many lines were dropped.
+    pub fn render(&self, max_lines: usize) -> RenderedDiff {
+        let mut lines = Vec::new();
+        let mut printed: &[Node] = &[];
+        for change in &self.changes {
+            let shared = printed
+                .iter()
+                .zip(&change.parents)
+                .take_while(|(a, b)| a.text == b.text)
+                .count();
+            for parent in &change.parents[shared..] {
+                lines.push(format!(" {}{}", parent.indent, parent.text));
+            }
+            let sign = match change.kind {
+                ChangeKind::Added => '+',
+                ChangeKind::Removed => '-',
+            };
+            push_block(&change.node, sign, &mut lines);
+            printed = &change.parents;
+        }
+        cap(lines, max_lines)
+    }
+}
+
+/// Semantic diff of two configurations, capped at `NAUTO_DIFF_MAX_LINES`.
+pub fn render_diff(before: &str, after: &str) -> String {
+    let diff = ConfigTree::parse(before).diff(&ConfigTree::parse(after));
+    finish(diff.render(config::diff_max_lines()))
+}
+
+/// Line-by-line diff for payloads that are not CLI configuration (XML, JSON, plain files),
+/// capped at `NAUTO_DIFF_MAX_LINES`.
+pub fn line_diff(before: &str, after: &str) -> String {
+    let lines = TextDiff::from_lines(before, after)
+        .iter_all_changes()
+        .map(|change| {
+            let sign = match change.tag() {
+                similar::ChangeTag::Delete => "-",
+                similar::ChangeTag::Insert => "+",
+                similar::ChangeTag::Equal => " ",
+            };
+            format!("{sign}{}", change.to_string().trim_end())
+        })
+        .collect();
+    finish(cap(lines, config::diff_max_lines()))
+}
+
+fn finish(rendered: RenderedDiff) -> String {
+    if rendered.truncated {
+        warn!(
+            target: "drivers::diff",
+            "diff exceeded NAUTO_DIFF_MAX_LINES and was truncated"
+        );
+    }
+    rendered.text
+}
+
+fn cap(mut lines: Vec<String>, max_lines: usize) -> RenderedDiff {
+    let truncated = lines.len() > max_lines;
+    if truncated {
+        let dropped = lines.len() - max_lines;
+        lines.truncate(max_lines);
+        lines.push(format!(
+            "... diff truncated, {dropped} more lines not shown"
+        ));
+    }
+    let mut text = lines.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    RenderedDiff { text, truncated }
+}
+
+fn node(line: &str, text: &str) -> Node {
+    Node {
+        indent: line[..line.len() - line.trim_start().len()].to_string(),
+        text: text.to_string(),
+        children: Vec::new(),
+    }
+}
+
+/// The children list `depth` levels down the last branch of `siblings`.
+fn descend(siblings: &mut Vec<Node>, depth: usize) -> &mut Vec<Node> {
+    if depth == 0 || siblings.is_empty() {
+        return siblings;
+    }
+    let last = siblings.len() - 1;
+    descend(&mut siblings[last].children, depth - 1)
+}
+
+fn diff_children(before: &[Node], after: &[Node], parents: &mut Vec<Node>, out: &mut Vec<Change>) {
+    // Pair siblings by text, first unmatched occurrence first, so repeated lines still pair up.
+    let mut matched = vec![false; after.len()];
+    let mut partners = vec![None; before.len()];
+    for (i, old) in before.iter().enumerate() {
+        let partner = after
+            .iter()
+            .enumerate()
+            .position(|(j, new)| !matched[j] && new.text == old.text);
+        if let Some(j) = partner {
+            matched[j] = true;
+            partners[i] = Some(j);
+        }
+    }
+    // A moved entry of an ordered list is reported where it was and where it is now.
+    let moved = out_of_order(before, &partners, parents);
+    for (i, old) in before.iter().enumerate() {
+        if partners[i].is_none() || moved[i] {
+            out.push(change(ChangeKind::Removed, parents, old));
+        }
+    }
+    for (i, old) in before.iter().enumerate() {
+        let Some(j) = partners[i] else { continue };
+        if moved[i] {
+            matched[j] = false;
+        } else if old.children != after[j].children {
+            parents.push(Node {
+                children: Vec::new(),
+                ..after[j].clone()
+            });
+            diff_children(&old.children, &after[j].children, parents, out);
+            parents.pop();
+        }
+    }
+    for (new, _) in after.iter().zip(&matched).filter(|(_, seen)| !**seen) {
+        out.push(change(ChangeKind::Added, parents, new));
+    }
+}
+
+/// Which of the paired `before` siblings changed position within their ordered list: all
+/// but the longest run of pairs that kept their relative order.
+fn out_of_order(before: &[Node], partners: &[Option<usize>], parents: &[Node]) -> Vec<bool> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, old) in before.iter().enumerate() {
+        if partners[i].is_none() {
+            continue;
+        }
+        let Some(key) = order_group(parents, old) else {
+            continue;
+        };
+        match groups.iter_mut().find(|(group, _)| *group == key) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+    let mut moved = vec![false; before.len()];
+    for (_, members) in groups {
+        let positions: Vec<usize> = members.iter().filter_map(|&i| partners[i]).collect();
+        let kept = longest_increasing(&positions);
+        for (&i, kept) in members.iter().zip(kept) {
+            moved[i] = !kept;
+        }
+    }
+    moved
+}
+
+/// The ordered list `node` belongs to under `parents`, or `None` when its position does not
+/// matter. Inside a block every line is ordered unless the block sits in one of
+/// `UNORDERED_SECTIONS`; at the top level only `ORDERED_TOP_LEVEL` lists are, each list
+/// keyed by its leading words.
+pub(crate) fn order_group(parents: &[Node], node: &Node) -> Option<String> {
+    if UNORDERED_LINES.contains(&first_word(&node.text)) {
+        return None;
+    }
+    if parents.is_empty() {
+        return ORDERED_TOP_LEVEL
+            .iter()
+            .find(|(prefix, _)| {
+                node.text
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with(' '))
+            })
+            .map(|(_, words)| {
+                node.text
+                    .split_whitespace()
+                    .take(*words)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+    }
+    if parents
+        .iter()
+        .any(|parent| UNORDERED_SECTIONS.contains(&first_word(&parent.text)))
+    {
+        return None;
+    }
+    Some(String::new())
+}
+
+fn first_word(text: &str) -> &str {
+    text.split_whitespace().next().unwrap_or("")
+}
+
+/// Marks a longest strictly increasing subsequence of `values`.
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // `tails[k]` is the index ending the best run of length k + 1 found so far.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (i, value) in values.iter().enumerate() {
+        let at = tails.partition_point(|&t| values[t] < *value);
+        if at > 0 {
+            previous[i] = Some(tails[at - 1]);
+        }
+        if at == tails.len() {
+            tails.push(i);
+        } else {
+            tails[at] = i;
+        }
+    }
+    let mut kept = vec![false; values.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        kept[i] = true;
+        next = previous[i];
+    }
+    kept
+}
+
+fn change(kind: ChangeKind, parents: &[Node], node: &Node) -> Change {
+    Change {
+        kind,
+        parents: parents.to_vec(),
+        node: node.clone(),
+    }
+}
+
+fn push_block(node: &Node, sign: char, lines: &mut Vec<String>) {
+    lines.push(format!("{sign}{}{}", node.indent, node.text));
+    for child in &node.children {
+        push_block(child, sign, lines);
+    }
+    if node.text.ends_with('{') {
+        lines.push(format!("{sign}{}}}", node.indent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_changed_blocks_with_parents_and_ignores_unordered_moves() {
+        let before = "hostname r1\n!\ninterface Gi1\n description uplink\n ip address 10.0.0.1 255.255.255.0\n\
+                      !\nip access-list extended EDGE\n remark allow web\n permit tcp any any eq 443\n\
+                      !\nrouter bgp 65000\n address-family ipv4\n  neighbor 10.0.0.2 activate\n exit-address-family\nend\n";
+        let after = "hostname r1\n!\nip access-list extended EDGE\n permit tcp any any eq 443\n remark allow web\n\
+                     !\ninterface Gi1\n description core uplink\n ip address 10.0.0.1 255.255.255.0\n\
+                     !\nrouter bgp 65000\n address-family ipv4\n  neighbor 10.0.0.2 activate\n  neighbor 10.0.0.6 activate\n\
+                     exit-address-family\n!\nntp server 10.0.0.50\nend\n";
+        let diff = ConfigTree::parse(before).diff(&ConfigTree::parse(after));
+        assert_eq!(diff.changes.len(), 4);
+        assert_eq!(
+            diff.render(100).text,
+            " interface Gi1\n- description uplink\n+ description core uplink\n\
+             \x20router bgp 65000\n  address-family ipv4\n+  neighbor 10.0.0.6 activate\n\
+             +ntp server 10.0.0.50\n"
+        );
+
+        let rendered = diff.render(2);
+        assert!(rendered.truncated);
+        assert!(rendered
+            .text
+            .ends_with("... diff truncated, 5 more lines not shown\n"));
+
+        let junos_before =
+            "system {\n    host-name r1;\n    ntp {\n        server 10.0.0.1;\n    }\n}\n";
+        let junos_after = "system {\n    host-name r1;\n}\nsnmp {\n    community public;\n}\n";
+        let junos = ConfigTree::parse(junos_before).diff(&ConfigTree::parse(junos_after));
+        assert_eq!(
+            junos.render(100).text,
+            " system {\n-    ntp {\n-        server 10.0.0.1;\n-    }\n+snmp {\n+    community public;\n+}\n"
+        );
+    }
+
+    #[test]
+    fn reports_reordered_acl_and_prefix_list_entries() {
+        let before = "ip access-list extended EDGE\n permit tcp any any eq 443\n deny ip any any\n\
+                      !\nip prefix-list LAN permit 10.0.0.0/8\nip prefix-list LAN deny 0.0.0.0/0 le 32\n\
+                      ip prefix-list WAN permit 0.0.0.0/0\n";
+        let after =
+            "ip prefix-list WAN permit 0.0.0.0/0\nip access-list extended EDGE\n deny ip any any\n\
+                     \x20permit tcp any any eq 443\n!\nip prefix-list LAN deny 0.0.0.0/0 le 32\n\
+                     ip prefix-list LAN permit 10.0.0.0/8\n";
+        let diff = ConfigTree::parse(before).diff(&ConfigTree::parse(after));
+        assert_eq!(
+            diff.render(100).text,
+            "-ip prefix-list LAN permit 10.0.0.0/8\n ip access-list extended EDGE\n\
+             - permit tcp any any eq 443\n+ permit tcp any any eq 443\n+ip prefix-list LAN permit 10.0.0.0/8\n"
+        );
+    }
+}
@@ -0,0 +1,547 @@
+//! An IOS/EOS/NX-OS flavoured CLI held in memory, served over SSH exec, eAPI or NX-API,
+//! and an IOS-XR flavour served as an interactive SSH shell.
+//!
+//! The running config is a flat list of lines (indentation kept) that config lines are
+//! merged into. `configure terminal` applies lines as they arrive; `configure session <name>`
+//! stages them until `commit` or `abort`, and `rollback clean-config` starts the session
+//! from an empty config. NX-OS checkpoints are kept by name. On IOS-XR `configure terminal`
+//! opens a candidate that `commit` applies, and commits are kept with their comments for
+//! `show configuration commit list` and `rollback configuration last 1`. Other `show`
+//! commands answer with a placeholder line.
+
+use super::http::{spawn_http, HttpResponse};
+use super::ssh::{spawn_ssh, spawn_ssh_with_streams, Exec};
+use super::{Fault, Faults, StandIn};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+const INVALID_INPUT: &str = "% Invalid input detected at '^' marker.";
+
+/// The configuration session an IOS-XR `configure terminal` opens.
+const XR_CANDIDATE: &str = "candidate";
+
+/// IDs IOS-XR gives its commits, counting up from here.
+const XR_FIRST_COMMIT_ID: u64 = 1_000_000_001;
+
+#[derive(Clone)]
+pub struct CliStandIn {
+    address: String,
+    cli: Arc<Mutex<Cli>>,
+    faults: Faults,
+}
+
+impl CliStandIn {
+    /// Each exec request is a script of one command per line, run until the first error.
+    pub async fn ssh(running: &[&str]) -> Self {
+        let (cli, faults) = state(running);
+        let (server_cli, server_faults) = (cli.clone(), faults.clone());
+        let addr = spawn_ssh(move |script| {
+            let fault = server_faults.get();
+            if fault == Some(Fault::Hang) {
+                return None;
+            }
+            let mut cli = server_cli.lock().unwrap();
+            cli.reject = fault == Some(Fault::RejectConfig);
+            let mut stdout = String::new();
+            let mut exec = Exec::default();
+            for line in script.lines() {
+                match cli.run(line) {
+                    Ok(output) => stdout.push_str(&output),
+                    Err(err) => {
+                        exec.stderr = err;
+                        exec.status = 1;
+                        break;
+                    }
+                }
+            }
+            cli.mode = Mode::Exec;
+            exec.stdout = stdout;
+            Some(exec)
+        })
+        .await;
+        Self {
+            address: addr.to_string(),
+            cli,
+            faults,
+        }
+    }
+
+    /// `POST /command-api` JSON-RPC `runCmds`; the address is the `http://` base URL.
+    pub async fn eapi(running: &[&str]) -> Self {
+        let (cli, faults) = state(running);
+        let (server_cli, server_faults) = (cli.clone(), faults.clone());
+        let address = spawn_http(move |request| {
+            let fault = server_faults.get();
+            if fault == Some(Fault::Hang) {
+                return HttpResponse::hang();
+            }
+            let request = request.json();
+            let format = request["params"]["format"].as_str().unwrap_or("json");
+            let commands: Vec<String> = request["params"]["cmds"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|cmd| cmd.as_str().or_else(|| cmd["cmd"].as_str()))
+                .map(str::to_string)
+                .collect();
+            let mut cli = server_cli.lock().unwrap();
+            cli.reject = fault == Some(Fault::RejectConfig);
+            let mut results = Vec::new();
+            let mut error = None;
+            for (idx, command) in commands.iter().enumerate() {
+                match cli.run(command) {
+                    Ok(output) if format == "text" => results.push(json!({ "output": output })),
+                    Ok(output) if output.is_empty() => results.push(json!({})),
+                    Ok(output) => results.push(json!({ "stand_in": output })),
+                    Err(err) => {
+                        let mut data = results.clone();
+                        data.push(json!({ "errors": [err] }));
+                        error = Some(json!({
+                            "code": 1002,
+                            "message": format!(
+                                "CLI command {} of {} '{}' failed: invalid command",
+                                idx + 1,
+                                commands.len(),
+                                command
+                            ),
+                            "data": data
+                        }));
+                        break;
+                    }
+                }
+            }
+            cli.mode = Mode::Exec;
+            let id = request["id"].clone();
+            HttpResponse::json(match error {
+                Some(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                None => json!({ "jsonrpc": "2.0", "id": id, "result": results }),
+            })
+        })
+        .await;
+        Self {
+            address,
+            cli,
+            faults,
+        }
+    }
+
+    /// `POST /ins` with `cli_show`, `cli_show_ascii` and `cli_conf` (commands joined by
+    /// ` ;`, run in config mode); the address is the `http://` base URL.
+    pub async fn nxapi(running: &[&str]) -> Self {
+        let (cli, faults) = state(running);
+        let (server_cli, server_faults) = (cli.clone(), faults.clone());
+        let address = spawn_http(move |request| {
+            let fault = server_faults.get();
+            if fault == Some(Fault::Hang) {
+                return HttpResponse::hang();
+            }
+            let request = request.json();
+            let kind = request["ins_api"]["type"].as_str().unwrap_or_default();
+            let input = request["ins_api"]["input"].as_str().unwrap_or_default();
+            let mut cli = server_cli.lock().unwrap();
+            cli.reject = fault == Some(Fault::RejectConfig);
+            let ok = |body: Value| json!({ "code": "200", "msg": "Success", "body": body });
+            let error = |err: String| {
+                json!({ "code": "400", "msg": "CLI execution error", "clierror": err })
+            };
+            let outputs = match kind {
+                "cli_show_ascii" => match cli.run(input) {
+                    Ok(output) => ok(Value::String(output)),
+                    Err(err) => error(err),
+                },
+                "cli_show" => match cli.run(input) {
+                    Ok(output) => ok(json!({ "stand_in": output })),
+                    Err(err) => error(err),
+                },
+                "cli_conf" => {
+                    cli.mode = Mode::Terminal;
+                    let mut outputs = Vec::new();
+                    for command in input.split(" ;") {
+                        match cli.run(command.trim()) {
+                            Ok(_) => outputs.push(ok(Value::String(String::new()))),
+                            Err(err) => {
+                                outputs.push(error(err));
+                                break;
+                            }
+                        }
+                    }
+                    // Single-command replies are a bare object, like the real NX-API.
+                    match outputs.len() {
+                        1 => outputs.remove(0),
+                        _ => Value::Array(outputs),
+                    }
+                }
+                other => error(format!("unsupported request type {other}")),
+            };
+            cli.mode = Mode::Exec;
+            HttpResponse::json(json!({ "ins_api": { "outputs": { "output": outputs } } }))
+        })
+        .await;
+        Self {
+            address,
+            cli,
+            faults,
+        }
+    }
+
+    /// IOS-XR: a PTY shell with `RP/0/RP0/CPU0:<hostname>#` prompts and one command per
+    /// line. While hanging, the shell reads commands and never answers.
+    pub async fn iosxr(running: &[&str]) -> Self {
+        let (cli, faults) = state(running);
+        cli.lock().unwrap().xr = true;
+        let (server_cli, server_faults) = (cli.clone(), faults.clone());
+        let addr = spawn_ssh_with_streams(
+            |_| None,
+            move |name, stream| {
+                if name != "shell" {
+                    return false;
+                }
+                let (cli, faults) = (server_cli.clone(), server_faults.clone());
+                tokio::spawn(async move {
+                    let (read, mut write) = tokio::io::split(stream);
+                    let mut lines = BufReader::new(read).lines();
+                    if faults.get() == Some(Fault::Hang) {
+                        std::future::pending::<()>().await;
+                    }
+                    let prompt = cli.lock().unwrap().xr_prompt();
+                    if write
+                        .write_all(format!("\r\n{prompt}").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let fault = faults.get();
+                        if fault == Some(Fault::Hang) {
+                            std::future::pending::<()>().await;
+                        }
+                        let reply = {
+                            let mut cli = cli.lock().unwrap();
+                            cli.reject = fault == Some(Fault::RejectConfig);
+                            let output = match cli.run(&line) {
+                                Ok(output) => output,
+                                Err(err) => format!("{err}\n"),
+                            };
+                            format!("{line}\r\n{output}{}", cli.xr_prompt())
+                        };
+                        if write.write_all(reply.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+                true
+            },
+        )
+        .await;
+        Self {
+            address: addr.to_string(),
+            cli,
+            faults,
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Every command received so far, in order.
+    pub fn commands(&self) -> Vec<String> {
+        self.cli.lock().unwrap().commands.clone()
+    }
+}
+
+impl StandIn for CliStandIn {
+    fn running(&self) -> String {
+        self.cli.lock().unwrap().show_running()
+    }
+
+    fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+fn state(running: &[&str]) -> (Arc<Mutex<Cli>>, Faults) {
+    let cli = Cli {
+        running: running.iter().map(|line| line.to_string()).collect(),
+        ..Default::default()
+    };
+    (Arc::new(Mutex::new(cli)), Faults::default())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Exec,
+    Terminal,
+    Session(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct ConfigSession {
+    base: Vec<String>,
+    lines: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Cli {
+    running: Vec<String>,
+    mode: Mode,
+    sessions: BTreeMap<String, ConfigSession>,
+    checkpoints: BTreeMap<String, Vec<String>>,
+    commands: Vec<String>,
+    /// Set per request from [`Fault::RejectConfig`].
+    reject: bool,
+    /// IOS-XR candidate and commit semantics.
+    xr: bool,
+    /// IOS-XR commit history, oldest first.
+    commits: Vec<XrCommit>,
+}
+
+#[derive(Debug, Clone)]
+struct XrCommit {
+    id: u64,
+    comment: String,
+    /// The running config before this commit.
+    previous: Vec<String>,
+}
+
+impl Cli {
+    fn run(&mut self, line: &str) -> Result<String, String> {
+        let line = line.trim_end();
+        let command = line.trim();
+        if command.is_empty() {
+            return Ok(String::new());
+        }
+        self.commands.push(command.to_string());
+        match command {
+            "enable"
+            | "terminal length 0"
+            | "terminal width 0"
+            | "write memory"
+            | "copy running-config startup-config"
+            | "verify" => return Ok(String::new()),
+            "configure terminal" | "configure" if self.xr => {
+                return self.run(&format!("configure session {XR_CANDIDATE}"));
+            }
+            "configure terminal" | "configure" => {
+                self.mode = Mode::Terminal;
+                return Ok(String::new());
+            }
+            "show configuration commit list 1 detail" if self.xr => {
+                return Ok(self
+                    .commits
+                    .last()
+                    .map(|commit| {
+                        format!(
+                            "   1) CommitId: {}\n      Comment:  {}\n",
+                            commit.id, commit.comment
+                        )
+                    })
+                    .unwrap_or_default())
+            }
+            "rollback configuration last 1" if self.xr => {
+                let commit = self.commits.pop().ok_or("% No commits to roll back")?;
+                self.running = commit.previous;
+                return Ok(String::new());
+            }
+            "end" | "exit" if self.mode != Mode::Exec => {
+                if self.xr {
+                    self.sessions.remove(XR_CANDIDATE);
+                }
+                self.mode = Mode::Exec;
+                return Ok(String::new());
+            }
+            "show running-config" => return Ok(self.show_running()),
+            "show checkpoint summary" => {
+                return Ok(self
+                    .checkpoints
+                    .keys()
+                    .enumerate()
+                    .map(|(idx, name)| format!("{}) {}:\nCreated by admin\n", idx + 1, name))
+                    .collect())
+            }
+            _ => {}
+        }
+        if let Some(name) = command.strip_prefix("configure session ") {
+            let base = self.running.clone();
+            self.sessions
+                .entry(name.to_string())
+                .or_insert_with(|| ConfigSession {
+                    base,
+                    lines: Vec::new(),
+                });
+            self.mode = Mode::Session(name.to_string());
+            return Ok(String::new());
+        }
+        if let Some(name) = command
+            .strip_prefix("show session-config named ")
+            .and_then(|rest| rest.strip_suffix(" diffs"))
+        {
+            let session = self.sessions.get(name).ok_or_else(|| no_session(name))?;
+            return Ok(session
+                .lines
+                .iter()
+                .map(|line| format!("+{line}\n"))
+                .collect());
+        }
+        if let Some(name) = command.strip_prefix("show configuration session ") {
+            let session = self.sessions.get(name).ok_or_else(|| no_session(name))?;
+            let lines: String = session
+                .lines
+                .iter()
+                .map(|line| format!("{line}\n"))
+                .collect();
+            return Ok(format!("config session {name}\n{lines}"));
+        }
+        if let Some(name) = command.strip_prefix("checkpoint ") {
+            self.checkpoints
+                .insert(name.to_string(), self.running.clone());
+            return Ok(String::new());
+        }
+        if let Some(name) = command.strip_prefix("no checkpoint ") {
+            self.checkpoints.remove(name);
+            return Ok(String::new());
+        }
+        if let Some(name) = command.strip_prefix("rollback running-config checkpoint ") {
+            let saved = self
+                .checkpoints
+                .get(name)
+                .ok_or_else(|| format!("checkpoint {name} not found"))?;
+            self.running = saved.clone();
+            return Ok("Rollback completed successfully.\n".into());
+        }
+        if let (true, Mode::Session(name)) = (self.xr, self.mode.clone()) {
+            if let Some(output) = self.xr_candidate_command(&name, command)? {
+                return Ok(output);
+            }
+        }
+        if command.starts_with("show ") {
+            return Ok(format!("{command}: stand-in output\n"));
+        }
+        match self.mode.clone() {
+            Mode::Exec => Err(INVALID_INPUT.into()),
+            Mode::Terminal => {
+                if self.reject {
+                    return Err(INVALID_INPUT.into());
+                }
+                merge(&mut self.running, line);
+                Ok(String::new())
+            }
+            Mode::Session(name) => self.session_command(&name, command, line),
+        }
+    }
+
+    fn session_command(&mut self, name: &str, command: &str, line: &str) -> Result<String, String> {
+        match command {
+            "abort" => {
+                self.sessions.remove(name);
+                self.mode = Mode::Exec;
+            }
+            "commit" => self.commit(name)?,
+            command if command.starts_with("commit timer ") => self.commit(name)?,
+            "rollback clean-config" => {
+                let session = self
+                    .sessions
+                    .get_mut(name)
+                    .ok_or_else(|| no_session(name))?;
+                session.base.clear();
+            }
+            _ if self.reject => return Err(INVALID_INPUT.into()),
+            _ => {
+                let session = self
+                    .sessions
+                    .get_mut(name)
+                    .ok_or_else(|| no_session(name))?;
+                session.lines.push(line.to_string());
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn commit(&mut self, name: &str) -> Result<(), String> {
+        let session = self.sessions.remove(name).ok_or_else(|| no_session(name))?;
+        let mut running = session.base;
+        for line in &session.lines {
+            merge(&mut running, line);
+        }
+        self.running = running;
+        self.mode = Mode::Exec;
+        Ok(())
+    }
+
+    /// `show` and `commit` in an IOS-XR candidate; `None` for anything else.
+    fn xr_candidate_command(
+        &mut self,
+        name: &str,
+        command: &str,
+    ) -> Result<Option<String>, String> {
+        let staged = |prefix: &str| -> Result<String, String> {
+            let session = self.sessions.get(name).ok_or_else(|| no_session(name))?;
+            Ok(session
+                .lines
+                .iter()
+                .map(|line| format!("{prefix}{}\n", line.trim()))
+                .collect())
+        };
+        if command == "show configuration" {
+            return staged("").map(|lines| Some(format!("{lines}end\n")));
+        }
+        if command == "show commit changes diff" {
+            return staged("+ ").map(Some);
+        }
+        let Some(rest) = command.strip_prefix("commit") else {
+            return Ok(None);
+        };
+        if !(rest.is_empty() || rest.starts_with(' ')) {
+            return Ok(None);
+        }
+        let comment = rest.split(" comment ").nth(1).unwrap_or_default();
+        let previous = self.running.clone();
+        let id = XR_FIRST_COMMIT_ID + self.commits.len() as u64;
+        self.commit(name)?;
+        if self.running != previous {
+            self.commits.push(XrCommit {
+                id,
+                comment: comment.to_string(),
+                previous,
+            });
+        }
+        // The candidate stays open after a commit, as on the device.
+        self.run(&format!("configure session {name}"))?;
+        Ok(Some(String::new()))
+    }
+
+    fn xr_prompt(&self) -> String {
+        let hostname = self
+            .running
+            .iter()
+            .find_map(|line| line.strip_prefix("hostname "))
+            .unwrap_or("xr");
+        let mode = if self.mode == Mode::Exec {
+            ""
+        } else {
+            "(config)"
+        };
+        format!("RP/0/RP0/CPU0:{hostname}{mode}#")
+    }
+
+    fn show_running(&self) -> String {
+        self.running
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+}
+
+fn merge(running: &mut Vec<String>, line: &str) {
+    if !running.iter().any(|existing| existing == line) {
+        running.push(line.to_string());
+    }
+}
+
+fn no_session(name: &str) -> String {
+    format!("% session {name} does not exist")
+}
@@ -0,0 +1,129 @@
+//! FortiOS REST stand-in: a firewall address table behind `/api/v2`, reached with the
+//! `fortios-lab` API token.
+//!
+//! The config backup renders the table in CLI format and CMDB `PUT`/`POST` on
+//! `firewall/address/<name>` set an entry's subnet. A restore parses the uploaded backup
+//! back into the table, so restoring a snapshot puts the running config back. Other `GET`s
+//! answer with an empty result list.
+
+use super::http::{spawn_http, HttpResponse};
+use super::{Fault, Faults, StandIn};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+pub const API_TOKEN: &str = "fortios-test-token";
+const ADDRESS_PATH: &str = "/api/v2/cmdb/firewall/address/";
+
+#[derive(Clone)]
+pub struct FortiosStandIn {
+    address: String,
+    addresses: Arc<Mutex<BTreeMap<String, String>>>,
+    faults: Faults,
+}
+
+impl FortiosStandIn {
+    /// `addresses` are `(name, subnet)` entries of `config firewall address`.
+    pub async fn spawn(addresses: &[(&str, &str)]) -> Self {
+        let addresses: BTreeMap<String, String> = addresses
+            .iter()
+            .map(|(name, subnet)| (name.to_string(), subnet.to_string()))
+            .collect();
+        let addresses = Arc::new(Mutex::new(addresses));
+        let faults = Faults::default();
+        let (server_addresses, server_faults) = (addresses.clone(), faults.clone());
+        let address = spawn_http(move |request| {
+            let fault = server_faults.get();
+            if fault == Some(Fault::Hang) {
+                return HttpResponse::hang();
+            }
+            if request.header("Authorization") != Some(&format!("Bearer {API_TOKEN}")) {
+                return HttpResponse::status(401, json!({ "http_status": 401 }).to_string());
+            }
+            let path = request.path.split('?').next().unwrap_or_default();
+            let mut table = server_addresses.lock().unwrap();
+            match (request.method.as_str(), path) {
+                ("GET", "/api/v2/monitor/system/config/backup") => {
+                    HttpResponse::status(200, backup(&table))
+                }
+                ("PUT" | "POST", path) if path.starts_with(ADDRESS_PATH) => {
+                    if fault == Some(Fault::RejectConfig) {
+                        return error(-651);
+                    }
+                    let name = &path[ADDRESS_PATH.len()..];
+                    let Some(subnet) = request.json()["subnet"].as_str().map(str::to_string) else {
+                        return error(-5);
+                    };
+                    table.insert(name.to_string(), subnet);
+                    HttpResponse::json(json!({ "status": "success", "http_status": 200 }))
+                }
+                ("POST", "/api/v2/monitor/system/config/restore") => {
+                    let file = request.json()["file_content"]
+                        .as_str()
+                        .and_then(|content| STANDARD.decode(content).ok())
+                        .and_then(|bytes| String::from_utf8(bytes).ok());
+                    let Some(file) = file else {
+                        return error(-1);
+                    };
+                    *table = parse_backup(&file);
+                    HttpResponse::json(json!({ "status": "success" }))
+                }
+                ("GET", _) => HttpResponse::json(json!({ "status": "success", "results": [] })),
+                _ => error(-3),
+            }
+        })
+        .await;
+        Self {
+            address,
+            addresses,
+            faults,
+        }
+    }
+
+    /// The `http://` base to use as the device's management address.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl StandIn for FortiosStandIn {
+    fn running(&self) -> String {
+        backup(&self.addresses.lock().unwrap())
+    }
+
+    fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+fn backup(addresses: &BTreeMap<String, String>) -> String {
+    let mut text = String::from("#config-version=FGT60F\nconfig firewall address\n");
+    for (name, subnet) in addresses {
+        text.push_str(&format!(
+            "    edit \"{name}\"\n        set subnet {subnet}\n    next\n"
+        ));
+    }
+    text.push_str("end\n");
+    text
+}
+
+fn parse_backup(text: &str) -> BTreeMap<String, String> {
+    let mut addresses = BTreeMap::new();
+    let mut name = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(edit) = line.strip_prefix("edit ") {
+            name = Some(edit.trim_matches('"').to_string());
+        } else if let (Some(subnet), Some(name)) = (line.strip_prefix("set subnet "), &name) {
+            addresses.insert(name.clone(), subnet.to_string());
+        }
+    }
+    addresses
+}
+
+fn error(code: i64) -> HttpResponse {
+    HttpResponse::status(
+        500,
+        json!({ "status": "error", "http_status": 500, "error": code }).to_string(),
+    )
+}
@@ -0,0 +1,167 @@
+//! HTTP/1.1 stand-in: one request per connection, answered by a plain closure.
+
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+
+    /// Query-string and form-encoded body parameters, in that order.
+    pub fn params(&self) -> Vec<(String, String)> {
+        let query = self.path.split_once('?').map(|(_, q)| q).unwrap_or("");
+        [query, self.body.as_str()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .flat_map(|part| {
+                reqwest::Url::parse(&format!("http://stand-in/?{part}"))
+                    .map(|url| url.query_pairs().into_owned().collect::<Vec<_>>())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    pub fn param(&self, name: &str) -> Option<String> {
+        self.params()
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Keep the connection open without ever answering.
+    pub hang: bool,
+}
+
+impl HttpResponse {
+    pub fn json(value: Value) -> Self {
+        Self::status(200, value.to_string()).with_header("content-type", "application/json")
+    }
+
+    pub fn status(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            hang: false,
+        }
+    }
+
+    /// A request the server accepts and never answers, for client timeout checks.
+    pub fn hang() -> Self {
+        Self {
+            hang: true,
+            ..Self::status(200, "")
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Serves one request per connection on 127.0.0.1 and returns the `http://` base URL.
+pub async fn spawn_http<F>(handler: F) -> String
+where
+    F: Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut stream = stream;
+                if let Some(request) = read_request(&mut stream).await {
+                    let response = handler(request);
+                    if response.hang {
+                        std::future::pending::<()>().await;
+                    }
+                    let _ = write_response(&mut stream, &response).await;
+                }
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<HttpRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return None,
+            Ok(read) => buf.extend_from_slice(&chunk[..read]),
+        }
+        let Some(split) = buf.windows(4).position(|window| window == b"\r\n\r\n") else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&buf[..split]).to_string();
+        let mut lines = head.lines();
+        let mut request_line = lines.next()?.split_whitespace();
+        let method = request_line.next()?.to_string();
+        let path = request_line.next()?.to_string();
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let length: usize = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or(0);
+        let body_start = split + 4;
+        if buf.len() - body_start < length {
+            continue;
+        }
+        let body = String::from_utf8_lossy(&buf[body_start..body_start + length]).to_string();
+        return Some(HttpRequest {
+            method,
+            path,
+            headers,
+            body,
+        });
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: &HttpResponse) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} Stand-in\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "content-length: {}\r\nconnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,254 @@
+//! Linux host stand-in: files held in memory behind the SSH stand-in, written over SFTP.
+//!
+//! Exec requests understand the file scripts the Linux host driver sends (`test -e`, `cat`,
+//! `cp`, `mv`, `rm`, with `chmod`/`chown` accepted and ignored). Any other command succeeds
+//! and echoes itself, except under [`Fault::RejectConfig`], where it fails the way a
+//! validate command rejects a bad file.
+
+use super::ssh::{spawn_ssh_with_streams, Exec};
+use super::{Fault, Faults, StandIn};
+use russh_sftp::protocol::{FileAttributes, Handle, OpenFlags, Status, StatusCode};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Where SFTP uploads are staged; kept out of [`StandIn::running`].
+const STAGING_DIR: &str = "/tmp/";
+
+type Files = Arc<Mutex<BTreeMap<String, String>>>;
+
+#[derive(Clone)]
+pub struct LinuxStandIn {
+    address: String,
+    files: Files,
+    faults: Faults,
+}
+
+impl LinuxStandIn {
+    /// `files` are `(path, content)` pairs present on the host.
+    pub async fn spawn(files: &[(&str, &str)]) -> Self {
+        let files: Files = Arc::new(Mutex::new(
+            files
+                .iter()
+                .map(|(path, content)| (path.to_string(), content.to_string()))
+                .collect(),
+        ));
+        let faults = Faults::default();
+        let (exec_files, exec_faults) = (files.clone(), faults.clone());
+        let sftp_files = files.clone();
+        let addr = spawn_ssh_with_streams(
+            move |script| {
+                let fault = exec_faults.get();
+                if fault == Some(Fault::Hang) {
+                    return None;
+                }
+                let mut files = exec_files.lock().unwrap();
+                Some(run(&mut files, script, fault == Some(Fault::RejectConfig)))
+            },
+            move |name, stream| {
+                if name != "sftp" {
+                    return false;
+                }
+                let sftp = Sftp {
+                    files: sftp_files.clone(),
+                    handles: HashMap::new(),
+                };
+                tokio::spawn(russh_sftp::server::run(stream, sftp));
+                true
+            },
+        )
+        .await;
+        Self {
+            address: addr.to_string(),
+            files,
+            faults,
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl StandIn for LinuxStandIn {
+    fn running(&self) -> String {
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(path, _)| !path.starts_with(STAGING_DIR))
+            .map(|(path, content)| format!("==> {path} <==\n{content}"))
+            .collect()
+    }
+
+    fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+/// Runs one exec request: a file script, or any other command.
+fn run(files: &mut BTreeMap<String, String>, script: &str, reject: bool) -> Exec {
+    let statements = statements(script);
+    let is_file_script = statements
+        .first()
+        .and_then(|words| words.first())
+        .is_some_and(|word| matches!(word.as_str(), "test" | "set" | "rm"));
+    if !is_file_script {
+        if reject {
+            return Exec {
+                stderr: format!("{script}: stand-in rejected the configuration\n"),
+                status: 1,
+                ..Exec::default()
+            };
+        }
+        return Exec {
+            stdout: format!("ran {script}\n"),
+            ..Exec::default()
+        };
+    }
+    let mut exec = Exec::default();
+    for words in &statements {
+        let args: Vec<&str> = words.iter().map(String::as_str).collect();
+        let result = match args.as_slice() {
+            ["test", "-e", path, "||", "exit", status] if !files.contains_key(*path) => {
+                Err(status.parse().unwrap_or(1))
+            }
+            ["cat", "--", path] => match files.get(*path) {
+                Some(content) => {
+                    exec.stdout.push_str(content);
+                    Ok(())
+                }
+                None => Err(1),
+            },
+            ["cp", "--", from, to] => match files.get(*from).cloned() {
+                Some(content) => {
+                    files.insert(to.to_string(), content);
+                    Ok(())
+                }
+                None => Err(1),
+            },
+            ["mv", "-f", "--", from, to] => match files.remove(*from) {
+                Some(content) => {
+                    files.insert(to.to_string(), content);
+                    Ok(())
+                }
+                None => Err(1),
+            },
+            ["rm", "-f", "--", path] => {
+                files.remove(*path);
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(status) = result {
+            exec.status = status;
+            exec.stderr = format!("{}: failed\n", args.join(" "));
+            break;
+        }
+    }
+    exec
+}
+
+/// Splits a script into `;`-separated statements of words, honouring single quotes and
+/// the `'\''` escape the driver's quoting produces.
+fn statements(script: &str) -> Vec<Vec<String>> {
+    let mut statements = Vec::new();
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = script.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if !quoted => word.get_or_insert_with(String::new).extend(chars.next()),
+            '\'' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            _ if quoted => word.get_or_insert_with(String::new).push(c),
+            ';' => {
+                words.extend(word.take());
+                statements.push(std::mem::take(&mut words));
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    statements.push(words);
+    statements.retain(|words| !words.is_empty());
+    statements
+}
+
+/// SFTP server for uploads: writes collect per handle and land in the file on `close`.
+struct Sftp {
+    files: Files,
+    handles: HashMap<String, Upload>,
+}
+
+struct Upload {
+    path: String,
+    data: Vec<u8>,
+}
+
+impl russh_sftp::server::Handler for Sftp {
+    type Error = StatusCode;
+
+    fn unimplemented(&self) -> Self::Error {
+        StatusCode::OpUnsupported
+    }
+
+    async fn open(
+        &mut self,
+        id: u32,
+        filename: String,
+        pflags: OpenFlags,
+        _attrs: FileAttributes,
+    ) -> Result<Handle, Self::Error> {
+        let data = if pflags.contains(OpenFlags::TRUNCATE) {
+            Vec::new()
+        } else {
+            let files = self.files.lock().unwrap();
+            files
+                .get(&filename)
+                .map(|content| content.clone().into_bytes())
+                .unwrap_or_default()
+        };
+        let handle = format!("h{id}");
+        self.handles.insert(
+            handle.clone(),
+            Upload {
+                path: filename,
+                data,
+            },
+        );
+        Ok(Handle { id, handle })
+    }
+
+    async fn write(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Result<Status, Self::Error> {
+        let upload = self.handles.get_mut(&handle).ok_or(StatusCode::Failure)?;
+        upload.data.resize(offset as usize, 0);
+        upload.data.extend_from_slice(&data);
+        Ok(ok(id))
+    }
+
+    async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+        let upload = self.handles.remove(&handle).ok_or(StatusCode::Failure)?;
+        let content = String::from_utf8(upload.data).map_err(|_| StatusCode::Failure)?;
+        self.files.lock().unwrap().insert(upload.path, content);
+        Ok(ok(id))
+    }
+}
+
+fn ok(id: u32) -> Status {
+    Status {
+        id,
+        status_code: StatusCode::Ok,
+        error_message: "Ok".into(),
+        language_tag: "en-US".into(),
+    }
+}
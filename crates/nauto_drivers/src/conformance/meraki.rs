@@ -0,0 +1,94 @@
+//! Meraki Dashboard stand-in: JSON resources kept by path under `/api/v1`. GET returns a
+//! resource, PUT merges the body into it; both require the `meraki-lab` API key.
+
+use super::http::{spawn_http, HttpResponse};
+use super::{Fault, Faults, StandIn};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+pub const API_KEY: &str = "meraki-test-key";
+const API_PREFIX: &str = "/api/v1";
+
+#[derive(Clone)]
+pub struct MerakiStandIn {
+    address: String,
+    resources: Arc<Mutex<BTreeMap<String, Value>>>,
+    faults: Faults,
+}
+
+impl MerakiStandIn {
+    /// `resources` are keyed by API path without the `/api/v1` prefix, e.g.
+    /// `/networks/N_1/wireless/ssids/0`.
+    pub async fn spawn(resources: &[(&str, Value)]) -> Self {
+        let resources: BTreeMap<String, Value> = resources
+            .iter()
+            .map(|(path, value)| (path.to_string(), value.clone()))
+            .collect();
+        let resources = Arc::new(Mutex::new(resources));
+        let faults = Faults::default();
+        let (server_resources, server_faults) = (resources.clone(), faults.clone());
+        let address = spawn_http(move |request| {
+            let fault = server_faults.get();
+            if fault == Some(Fault::Hang) {
+                return HttpResponse::hang();
+            }
+            if request.header("X-Cisco-Meraki-API-Key") != Some(API_KEY) {
+                return HttpResponse::status(
+                    401,
+                    json!({ "errors": ["Invalid API key"] }).to_string(),
+                );
+            }
+            let Some(path) = request.path.strip_prefix(API_PREFIX) else {
+                return HttpResponse::status(404, "{}");
+            };
+            let mut resources = server_resources.lock().unwrap();
+            match request.method.as_str() {
+                "GET" => match resources.get(path) {
+                    Some(resource) => HttpResponse::json(resource.clone()),
+                    None => HttpResponse::status(404, "{}"),
+                },
+                "PUT" if fault == Some(Fault::RejectConfig) => HttpResponse::status(
+                    400,
+                    json!({ "errors": ["Stand-in rejected the update"] }).to_string(),
+                ),
+                "PUT" => {
+                    let Some(Value::Object(resource)) = resources.get_mut(path) else {
+                        return HttpResponse::status(404, "{}");
+                    };
+                    if let Value::Object(update) = request.json() {
+                        resource.extend(update);
+                    }
+                    HttpResponse::json(Value::Object(resource.clone()))
+                }
+                _ => HttpResponse::status(400, "{}"),
+            }
+        })
+        .await;
+        Self {
+            address,
+            resources,
+            faults,
+        }
+    }
+
+    /// The Dashboard API root to hand to `MerakiCloudDriver::with_api_base`.
+    pub fn api_base(&self) -> String {
+        format!("{}{API_PREFIX}", self.address)
+    }
+
+    pub fn resource(&self, path: &str) -> Option<Value> {
+        self.resources.lock().unwrap().get(path).cloned()
+    }
+}
+
+impl StandIn for MerakiStandIn {
+    fn running(&self) -> String {
+        let resources = self.resources.lock().unwrap();
+        serde_json::to_string_pretty(&*resources).unwrap_or_default()
+    }
+
+    fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
@@ -0,0 +1,320 @@
+//! Driver conformance: local protocol stand-ins and a suite every [`DeviceDriver`] runs
+//! against one of them. Used by this crate's tests and, with the `conformance` feature, by
+//! drivers that live elsewhere (plugins).
+//!
+//! A stand-in keeps the device configuration in memory and can be told to reject
+//! configuration or to stop answering. [`run`] then checks the command batch, a config push
+//! with its diff, dry run and rollback when the driver advertises them, that a rejected push
+//! comes back as an error without touching the device, and that a silent device fails the
+//! job instead of hanging it.
+
+pub mod cli;
+pub mod fortios;
+pub mod http;
+pub mod linux;
+pub mod meraki;
+pub mod netconf;
+pub mod panos;
+pub mod ssh;
+
+use crate::{config, DeviceDriver, DriverAction, DriverExecutionResult, JobContext};
+use anyhow::Result;
+use nauto_model::{ConfigMode, Device, JobKind};
+use std::fmt;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+/// Credentials every stand-in accepts, by name.
+const FALLBACK_CREDENTIALS: &str = r#"{
+  "lab": {"UserPassword": {"username": "admin", "password": "admin"}},
+  "nxos-lab": {"UserPassword": {"username": "admin", "password": "admin"}},
+  "meraki-lab": {"Token": {"token": "meraki-test-key"}},
+  "panos-lab": {"UserPassword": {"username": "admin", "password": "paloalto"}},
+  "fortios-lab": {"Token": {"token": "fortios-test-token"}}
+}"#;
+
+/// Transport timeout the suite runs with unless one is already configured.
+const SHORT_TIMEOUT_SECS: &str = "2";
+
+/// The OS keyring is unavailable in tests, so point the keyring store at a plaintext
+/// fallback file. Written once per process because the env var is process-wide.
+pub fn use_fallback_credentials() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let path =
+            std::env::temp_dir().join(format!("nauto-driver-tests-{}.json", std::process::id()));
+        std::fs::write(&path, FALLBACK_CREDENTIALS).expect("write fallback credentials");
+        std::env::set_var("NAUTO_KEYRING_FILE", &path);
+    });
+}
+
+/// Sets `NAUTO_SSH_TIMEOUT_SECS` and `NAUTO_HTTP_TIMEOUT_SECS` to two seconds when they are
+/// unset, so the timeout check finishes quickly. Drivers read these once, so call this
+/// before the first driver is built.
+pub fn use_short_timeouts() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        for var in ["NAUTO_SSH_TIMEOUT_SECS", "NAUTO_HTTP_TIMEOUT_SECS"] {
+            if std::env::var_os(var).is_none() {
+                std::env::set_var(var, SHORT_TIMEOUT_SECS);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Configuration changes fail the way the platform reports a bad line.
+    RejectConfig,
+    /// Requests are accepted and never answered.
+    Hang,
+}
+
+/// Fault switch shared between a stand-in and its server tasks.
+#[derive(Debug, Clone, Default)]
+pub struct Faults(Arc<Mutex<Option<Fault>>>);
+
+impl Faults {
+    pub fn set(&self, fault: Option<Fault>) {
+        *self.0.lock().unwrap() = fault;
+    }
+
+    pub fn get(&self) -> Option<Fault> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// A local server that plays the device for one driver.
+pub trait StandIn: Send + Sync {
+    /// The configuration the stand-in currently holds, in its own rendering.
+    fn running(&self) -> String;
+
+    fn faults(&self) -> &Faults;
+}
+
+/// What the suite sends: a device pointing at the stand-in, a command batch and a config
+/// snippet whose push leaves `expect` somewhere in [`StandIn::running`].
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub device: Device,
+    pub commands: Vec<String>,
+    pub snippet: String,
+    pub expect: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Passed,
+    Skipped(&'static str),
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub check: &'static str,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.verdict {
+            Verdict::Passed => write!(f, "{}: passed", self.check),
+            Verdict::Skipped(reason) => write!(f, "{}: skipped ({reason})", self.check),
+            Verdict::Failed(reason) => write!(f, "{}: FAILED ({reason})", self.check),
+        }
+    }
+}
+
+/// Runs every check against `standin`. Rollback runs before the plain push so it starts
+/// from the stand-in's initial configuration.
+pub async fn run(
+    driver: &dyn DeviceDriver,
+    standin: &dyn StandIn,
+    scenario: &Scenario,
+) -> Vec<Outcome> {
+    let suite = Suite {
+        driver,
+        standin,
+        scenario,
+    };
+    standin.faults().set(None);
+    let outcomes = vec![
+        suite.check("command_batch", suite.command_batch()).await,
+        suite.check("dry_run", suite.dry_run()).await,
+        suite.check("rollback", suite.rollback()).await,
+        suite.check("config_push", suite.config_push()).await,
+        suite
+            .check("error_propagation", suite.error_propagation())
+            .await,
+        suite.check("timeout", suite.timeout()).await,
+    ];
+    standin.faults().set(None);
+    outcomes
+}
+
+/// [`run`], panicking with the full report when any check failed.
+pub async fn assert_conformant(
+    driver: &dyn DeviceDriver,
+    standin: &dyn StandIn,
+    scenario: &Scenario,
+) {
+    let outcomes = run(driver, standin, scenario).await;
+    if outcomes
+        .iter()
+        .any(|outcome| matches!(outcome.verdict, Verdict::Failed(_)))
+    {
+        let report: Vec<String> = outcomes.iter().map(ToString::to_string).collect();
+        panic!(
+            "{} is not conformant:\n  {}",
+            driver.name(),
+            report.join("\n  ")
+        );
+    }
+}
+
+struct Suite<'a> {
+    driver: &'a dyn DeviceDriver,
+    standin: &'a dyn StandIn,
+    scenario: &'a Scenario,
+}
+
+type CheckResult = std::result::Result<Verdict, String>;
+
+impl Suite<'_> {
+    async fn check(
+        &self,
+        name: &'static str,
+        result: impl std::future::Future<Output = CheckResult>,
+    ) -> Outcome {
+        let verdict = result.await.unwrap_or_else(Verdict::Failed);
+        self.standin.faults().set(None);
+        Outcome {
+            check: name,
+            verdict,
+        }
+    }
+
+    async fn execute(&self, job: &JobKind, dry_run: bool) -> Result<DriverExecutionResult> {
+        let ctx = JobContext {
+            dry_run,
+            ..Default::default()
+        };
+        self.driver
+            .execute(&self.scenario.device, DriverAction::Job(job), &ctx)
+            .await
+    }
+
+    fn push(&self) -> JobKind {
+        JobKind::ConfigPush {
+            snippet: self.scenario.snippet.clone(),
+            mode: ConfigMode::Merge,
+        }
+    }
+
+    async fn command_batch(&self) -> CheckResult {
+        let job = JobKind::CommandBatch {
+            commands: self.scenario.commands.clone(),
+        };
+        let res = self.execute(&job, false).await.map_err(describe)?;
+        if res.outputs.len() != self.scenario.commands.len() {
+            return Err(format!(
+                "{} outputs for {} commands",
+                res.outputs.len(),
+                self.scenario.commands.len()
+            ));
+        }
+        Ok(Verdict::Passed)
+    }
+
+    async fn dry_run(&self) -> CheckResult {
+        if !self.driver.capabilities().supports_dry_run {
+            return Ok(Verdict::Skipped("driver does not advertise dry runs"));
+        }
+        let before = self.standin.running();
+        let res = self.execute(&self.push(), true).await.map_err(describe)?;
+        if self.standin.running() != before {
+            return Err("dry run changed the device configuration".into());
+        }
+        require_diff(&res)?;
+        Ok(Verdict::Passed)
+    }
+
+    async fn rollback(&self) -> CheckResult {
+        if !self.driver.capabilities().supports_rollback {
+            return Ok(Verdict::Skipped("driver does not advertise rollback"));
+        }
+        let before = self.standin.running();
+        let res = self.execute(&self.push(), false).await.map_err(describe)?;
+        if self.standin.running() == before {
+            return Err("push did not change the device configuration".into());
+        }
+        self.driver
+            .rollback(&self.scenario.device, res.pre_snapshot)
+            .await
+            .map_err(describe)?;
+        if self.standin.running() != before {
+            return Err("rollback did not restore the configuration".into());
+        }
+        Ok(Verdict::Passed)
+    }
+
+    async fn config_push(&self) -> CheckResult {
+        let res = self.execute(&self.push(), false).await.map_err(describe)?;
+        if !self.standin.running().contains(&self.scenario.expect) {
+            return Err(format!(
+                "'{}' missing from the configuration after the push",
+                self.scenario.expect
+            ));
+        }
+        if self.driver.capabilities().supports_diff {
+            require_diff(&res)?;
+        }
+        Ok(Verdict::Passed)
+    }
+
+    async fn error_propagation(&self) -> CheckResult {
+        let before = self.standin.running();
+        self.standin.faults().set(Some(Fault::RejectConfig));
+        if self.execute(&self.push(), false).await.is_ok() {
+            return Err("rejected configuration reported success".into());
+        }
+        if self.standin.running() != before {
+            return Err("rejected push left the configuration changed".into());
+        }
+        Ok(Verdict::Passed)
+    }
+
+    async fn timeout(&self) -> CheckResult {
+        self.standin.faults().set(Some(Fault::Hang));
+        let job = JobKind::CommandBatch {
+            commands: self.scenario.commands.clone(),
+        };
+        match tokio::time::timeout(hang_budget(), self.execute(&job, false)).await {
+            Ok(Err(_)) => Ok(Verdict::Passed),
+            Ok(Ok(_)) => Err("silent device reported success".into()),
+            Err(_) => Err(format!(
+                "driver still waiting after {}s",
+                hang_budget().as_secs()
+            )),
+        }
+    }
+}
+
+/// How long a driver may take to give up on a silent device: every retry of the longest
+/// transport timeout, with room for backoff.
+fn hang_budget() -> Duration {
+    let timeout = config::ssh_command_timeout().max(config::http_timeout());
+    let attempts = config::http_retry_limit() as u32 + 1;
+    timeout * attempts * 2 + Duration::from_secs(5)
+}
+
+fn require_diff(res: &DriverExecutionResult) -> std::result::Result<(), String> {
+    match res.diff.as_deref() {
+        Some(diff) if !diff.trim().is_empty() => Ok(()),
+        _ => Err("no diff reported".into()),
+    }
+}
+
+fn describe(err: anyhow::Error) -> String {
+    format!("{err:#}")
+}
@@ -0,0 +1,289 @@
+//! NETCONF stand-in over plain TCP (`tcp://` management addresses), or over SSH for Junos:
+//! base:1.1 framing after the hello, with candidate and running datastores held as strings.
+//! `edit-config` appends (or replaces, for `default-operation replace`), `commit` copies
+//! candidate to running and `discard-changes` copies it back. A confirmed commit keeps the
+//! previous running config until a plain `commit` confirms it or `cancel-commit` puts it
+//! back. A `<invalid/>` element is answered with an `rpc-error`, and `<discarded>` elements
+//! are accepted and dropped, the way servers prune nodes they do not implement. The Junos RPCs work on configuration text: `config-text`
+//! edits and `load-configuration` overrides, committed text and `compare` output from
+//! `get-configuration`, and `commit-configuration`.
+
+use super::ssh::{spawn_ssh_with_streams, Exec};
+use super::{Fault, Faults, StandIn};
+use crate::config_tree;
+use crate::netconf::{self, decode_chunked, decode_eom};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const INVALID_VALUE: &str = "<rpc-error><error-type>application</error-type><error-tag>invalid-value</error-tag><error-severity>error</error-severity><error-message>bad element</error-message></rpc-error>";
+
+#[derive(Debug, Clone, Default)]
+pub struct Datastores {
+    pub running: String,
+    pub candidate: String,
+    /// Every operation received, without the `<rpc>` wrapper.
+    pub rpcs: Vec<String>,
+    /// Running config from before a confirmed commit that is not confirmed yet.
+    pub unconfirmed: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct NetconfStandIn {
+    address: String,
+    datastores: Arc<Mutex<Datastores>>,
+    faults: Faults,
+}
+
+impl NetconfStandIn {
+    /// Advertises base:1.0, base:1.1 and `capabilities`.
+    pub async fn spawn(capabilities: &[&str], running: &str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let datastores = Arc::new(Mutex::new(Datastores {
+            running: running.into(),
+            candidate: running.into(),
+            ..Default::default()
+        }));
+        let faults = Faults::default();
+        let hello = hello(capabilities);
+        let (server_state, server_faults) = (datastores.clone(), faults.clone());
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                tokio::spawn(serve(
+                    stream,
+                    hello.clone(),
+                    server_state.clone(),
+                    server_faults.clone(),
+                ));
+            }
+        });
+        Self {
+            address: format!("tcp://{addr}"),
+            datastores,
+            faults,
+        }
+    }
+
+    /// Junos: the `netconf` subsystem over SSH, with configuration text as `running`, and
+    /// exec requests for operational commands. The address is `127.0.0.1:<port>`.
+    pub async fn junos(running: &str) -> Self {
+        let datastores = Arc::new(Mutex::new(Datastores {
+            running: running.into(),
+            candidate: running.into(),
+            ..Default::default()
+        }));
+        let faults = Faults::default();
+        let hello = hello(&[netconf::CAP_CANDIDATE, netconf::CAP_VALIDATE]);
+        let exec_faults = faults.clone();
+        let (server_state, server_faults) = (datastores.clone(), faults.clone());
+        let addr = spawn_ssh_with_streams(
+            move |command| {
+                (exec_faults.get() != Some(Fault::Hang)).then(|| Exec {
+                    stdout: format!("{command}: stand-in output\n"),
+                    ..Default::default()
+                })
+            },
+            move |name, stream| {
+                if name != "netconf" {
+                    return false;
+                }
+                tokio::spawn(serve(
+                    stream,
+                    hello.clone(),
+                    server_state.clone(),
+                    server_faults.clone(),
+                ));
+                true
+            },
+        )
+        .await;
+        Self {
+            address: addr.to_string(),
+            datastores,
+            faults,
+        }
+    }
+
+    /// The `tcp://` management address, or `127.0.0.1:<port>` for [`NetconfStandIn::junos`].
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn datastores(&self) -> Datastores {
+        self.datastores.lock().unwrap().clone()
+    }
+}
+
+impl StandIn for NetconfStandIn {
+    fn running(&self) -> String {
+        self.datastores.lock().unwrap().running.clone()
+    }
+
+    fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+/// Advertises base:1.0, base:1.1 and `capabilities`.
+fn hello(capabilities: &[&str]) -> String {
+    let caps: String = [netconf::BASE_1_0, netconf::BASE_1_1]
+        .iter()
+        .chain(capabilities)
+        .map(|cap| format!("<capability>{cap}</capability>"))
+        .collect();
+    format!(
+        "<hello xmlns=\"urn:ietf:params:xml:ns:netconf:base:1.0\"><capabilities>{caps}</capabilities><session-id>7</session-id></hello>]]>]]>"
+    )
+}
+
+async fn serve<S>(mut stream: S, hello: String, state: Arc<Mutex<Datastores>>, faults: Faults)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if stream.write_all(hello.as_bytes()).await.is_err() {
+        return;
+    }
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let mut hello_done = false;
+    loop {
+        let decoded = if hello_done {
+            decode_chunked(&buf).ok().flatten()
+        } else {
+            decode_eom(&buf)
+        };
+        let Some((message, consumed)) = decoded else {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(read) => buf.extend_from_slice(&chunk[..read]),
+            }
+            continue;
+        };
+        buf.drain(..consumed);
+        if !hello_done {
+            hello_done = true;
+            continue;
+        }
+        let fault = faults.get();
+        if fault == Some(Fault::Hang) {
+            std::future::pending::<()>().await;
+        }
+        let request = String::from_utf8_lossy(&message).to_string();
+        let body = handle(
+            &request,
+            &mut state.lock().unwrap(),
+            fault == Some(Fault::RejectConfig),
+        );
+        let id = between(&request, "message-id=\"", "\"").unwrap_or("0");
+        let reply = format!(
+            "<rpc-reply message-id=\"{id}\" xmlns=\"urn:ietf:params:xml:ns:netconf:base:1.0\">{body}</rpc-reply>"
+        );
+        let framed = format!("\n#{}\n{reply}\n##\n", reply.len());
+        if stream.write_all(framed.as_bytes()).await.is_err()
+            || request.contains("<close-session/>")
+        {
+            return;
+        }
+    }
+}
+
+fn handle(request: &str, state: &mut Datastores, reject: bool) -> String {
+    let op = between(request, "\">", "</rpc>").unwrap_or_default();
+    state.rpcs.push(op.to_string());
+    if op.starts_with("<get-config>") {
+        let data = if op.contains("<candidate/>") {
+            &state.candidate
+        } else {
+            &state.running
+        };
+        return format!("<data>{data}</data>");
+    }
+    if op.starts_with("<get-configuration") {
+        // Junos: committed text, or the candidate compared with it (`show | compare`).
+        if op.contains("compare=") {
+            let diff = config_tree::line_diff(&state.running, &state.candidate);
+            return format!(
+                "<configuration-output>{}</configuration-output>",
+                netconf::escape_text(&diff)
+            );
+        }
+        return format!(
+            "<configuration-text>{}</configuration-text>",
+            netconf::escape_text(&state.running)
+        );
+    }
+    if op.starts_with("<load-configuration") {
+        if let Some(text) = netconf::element_text(op, "configuration-text") {
+            state.candidate = text;
+        }
+        return "<ok/>".into();
+    }
+    if op.starts_with("<edit-config>") {
+        if reject {
+            return INVALID_VALUE.into();
+        }
+        if let Some(text) = netconf::element_text(op, "configuration-text") {
+            let target = if op.contains("<target><candidate/>") {
+                &mut state.candidate
+            } else {
+                &mut state.running
+            };
+            target.push_str(&text);
+            return "<ok/>".into();
+        }
+        let config = between(op, "<config>", "</config>").unwrap_or_default();
+        if config.contains("<invalid/>") {
+            return INVALID_VALUE.into();
+        }
+        let target = if op.contains("<target><candidate/>") {
+            &mut state.candidate
+        } else {
+            &mut state.running
+        };
+        let config = drop_discarded(config);
+        if op.contains("<default-operation>replace") {
+            *target = config;
+        } else {
+            target.push_str(&config);
+        }
+    } else if op.starts_with("<commit") {
+        if op.contains("<confirmed/>") {
+            state
+                .unconfirmed
+                .get_or_insert_with(|| state.running.clone());
+        } else {
+            state.unconfirmed = None;
+        }
+        state.running = state.candidate.clone();
+    } else if op.starts_with("<cancel-commit") {
+        let Some(previous) = state.unconfirmed.take() else {
+            return INVALID_VALUE.into();
+        };
+        state.running = previous.clone();
+        state.candidate = previous;
+    } else if op.starts_with("<discard-changes/>") {
+        state.candidate = state.running.clone();
+    }
+    "<ok/>".into()
+}
+
+fn drop_discarded(config: &str) -> String {
+    let mut kept = config.to_string();
+    while let Some(start) = kept.find("<discarded>") {
+        let Some(len) = kept[start..].find("</discarded>") else {
+            break;
+        };
+        kept.replace_range(start..start + len + "</discarded>".len(), "");
+    }
+    kept
+}
+
+fn between<'a>(haystack: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = haystack.find(start)? + start.len();
+    let to = haystack[from..].find(end)? + from;
+    Some(&haystack[from..to])
+}
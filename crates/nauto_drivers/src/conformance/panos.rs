@@ -0,0 +1,152 @@
+//! PAN-OS XML API stand-in: a running and a candidate config as XML text behind `/api/`.
+//!
+//! `keygen` trades the `panos-lab` password for [`API_KEY`], which every other request must
+//! carry. `set` appends its element inside the last element named in the xpath, `edit` on
+//! `/config` replaces the candidate root, and `commit` copies the candidate to running and
+//! returns a job that has already finished.
+
+use super::http::{spawn_http, HttpResponse};
+use super::{Fault, Faults, StandIn};
+use crate::netconf;
+use std::sync::{Arc, Mutex};
+
+pub const API_KEY: &str = "LAB-KEY";
+const PASSWORD: &str = "paloalto";
+const COMMIT_JOB: &str = "7";
+
+#[derive(Clone)]
+pub struct PanosStandIn {
+    address: String,
+    firewall: Arc<Mutex<Firewall>>,
+    faults: Faults,
+}
+
+struct Firewall {
+    running: String,
+    candidate: String,
+}
+
+impl PanosStandIn {
+    /// `running` is the XML of the initial `/config`, e.g. `<config><address/></config>`.
+    pub async fn spawn(running: &str) -> Self {
+        let firewall = Arc::new(Mutex::new(Firewall {
+            running: running.into(),
+            candidate: running.into(),
+        }));
+        let faults = Faults::default();
+        let (server_firewall, server_faults) = (firewall.clone(), faults.clone());
+        let address = spawn_http(move |request| {
+            let fault = server_faults.get();
+            if fault == Some(Fault::Hang) {
+                return HttpResponse::hang();
+            }
+            let param = |name: &str| request.param(name).unwrap_or_default();
+            if param("type") == "keygen" {
+                if param("password") != PASSWORD {
+                    return error("Invalid credentials.");
+                }
+                return ok(&format!("<key>{API_KEY}</key>"));
+            }
+            if request.header("X-PAN-KEY") != Some(API_KEY) {
+                return HttpResponse::status(403, error_body("Invalid credentials."));
+            }
+            let mut fw = server_firewall.lock().unwrap();
+            match (param("type").as_str(), param("action").as_str()) {
+                ("config", "show") => ok(&fw.running),
+                ("config", "set" | "edit") if fault == Some(Fault::RejectConfig) => {
+                    error(&format!("{} is unexpected", param("xpath")))
+                }
+                ("config", "set") => {
+                    let Some(tag) = param("xpath").rsplit('/').next().map(str::to_string) else {
+                        return error("xpath is missing");
+                    };
+                    let element = param("element");
+                    let empty = format!("<{tag}/>");
+                    let close = format!("</{tag}>");
+                    fw.candidate = if fw.candidate.contains(&empty) {
+                        fw.candidate
+                            .replacen(&empty, &format!("<{tag}>{element}{close}"), 1)
+                    } else if fw.candidate.contains(&close) {
+                        fw.candidate.replacen(&close, &format!("{element}{close}"), 1)
+                    } else {
+                        return error(&format!("{} does not exist", param("xpath")));
+                    };
+                    ok("")
+                }
+                ("config", "edit") if param("xpath") == "/config" => {
+                    fw.candidate = param("element");
+                    ok("")
+                }
+                ("commit", _) => {
+                    fw.running = fw.candidate.clone();
+                    ok(&format!(
+                        "<msg><line>Commit job enqueued with jobid {COMMIT_JOB}</line></msg><job>{COMMIT_JOB}</job>"
+                    ))
+                }
+                ("op", _) => {
+                    let cmd = param("cmd");
+                    if cmd.contains("<pending-changes>") {
+                        ok(if fw.candidate == fw.running { "no" } else { "yes" })
+                    } else if cmd.contains("<diff>") {
+                        ok(&format!(
+                            "<![CDATA[{}]]>",
+                            crate::config_tree::line_diff(
+                                &netconf::pretty_xml(&fw.running),
+                                &netconf::pretty_xml(&fw.candidate)
+                            )
+                        ))
+                    } else if cmd.contains("<revert>") {
+                        fw.candidate = fw.running.clone();
+                        ok("")
+                    } else if cmd.contains("<jobs>") {
+                        ok(&format!(
+                            "<job><id>{COMMIT_JOB}</id><status>FIN</status><result>OK</result></job>"
+                        ))
+                    } else {
+                        ok(&format!("{}: stand-in output", netconf::escape_text(&cmd)))
+                    }
+                }
+                _ => HttpResponse::status(400, error_body("unexpected request")),
+            }
+        })
+        .await;
+        Self {
+            address,
+            firewall,
+            faults,
+        }
+    }
+
+    /// The `http://` base to use as the device's management address.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl StandIn for PanosStandIn {
+    fn running(&self) -> String {
+        netconf::pretty_xml(&self.firewall.lock().unwrap().running)
+    }
+
+    fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
+
+fn ok(result: &str) -> HttpResponse {
+    HttpResponse::status(
+        200,
+        format!("<response status=\"success\"><result>{result}</result></response>"),
+    )
+}
+
+fn error(message: &str) -> HttpResponse {
+    HttpResponse::status(200, error_body(message))
+}
+
+fn error_body(message: &str) -> String {
+    format!(
+        "<response status=\"error\" code=\"12\"><msg><line>{}</line></msg></response>",
+        netconf::escape_text(message)
+    )
+}
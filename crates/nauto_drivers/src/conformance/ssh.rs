@@ -0,0 +1,213 @@
+//! SSH stand-in: password login as `admin`/`admin` (the `lab` fallback credential) and one
+//! exec request per channel, answered by a closure. Subsystem (`netconf`, `sftp`) and
+//! interactive shell channels are handed to a second closure as a byte stream. The host key
+//! is fixed and registered in a process-wide known_hosts file, which `NAUTO_SSH_KNOWN_HOSTS`
+//! points the drivers at.
+
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::PrivateKey;
+use russh::server::{Auth, Config, Msg, Session};
+use russh::{Channel, ChannelId, ChannelStream, CryptoVec};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+pub const USERNAME: &str = "admin";
+pub const PASSWORD: &str = "admin";
+
+const HOST_KEY_SEED: [u8; 32] = [7; 32];
+
+/// What an exec request returns.
+#[derive(Debug, Clone, Default)]
+pub struct Exec {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: u32,
+}
+
+type ExecHandler = dyn Fn(&str) -> Option<Exec> + Send + Sync;
+type StreamHandler = dyn Fn(&str, ChannelStream<Msg>) -> bool + Send + Sync;
+
+/// Serves SSH on 127.0.0.1 and returns the bound address. The handler gets each exec
+/// command; `None` leaves the request unanswered.
+pub async fn spawn_ssh<F>(handler: F) -> SocketAddr
+where
+    F: Fn(&str) -> Option<Exec> + Send + Sync + 'static,
+{
+    spawn_ssh_with_streams(handler, |_, _| false).await
+}
+
+/// [`spawn_ssh`], and channels that request a subsystem or a shell go to `streams` with the
+/// subsystem name (`shell` for a shell). It returns `false` to refuse the request and must
+/// serve the stream from a task of its own.
+pub async fn spawn_ssh_with_streams<F, S>(handler: F, streams: S) -> SocketAddr
+where
+    F: Fn(&str) -> Option<Exec> + Send + Sync + 'static,
+    S: Fn(&str, ChannelStream<Msg>) -> bool + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let key = host_key();
+    trust(&addr, &key);
+    let config = Arc::new(Config {
+        keys: vec![key],
+        auth_rejection_time: Duration::from_millis(10),
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        ..Default::default()
+    });
+    let handler: Arc<ExecHandler> = Arc::new(handler);
+    let streams: Arc<StreamHandler> = Arc::new(streams);
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+            let server = Server {
+                handler: handler.clone(),
+                streams: streams.clone(),
+                channels: HashMap::new(),
+            };
+            let config = config.clone();
+            tokio::spawn(async move {
+                if let Ok(session) = russh::server::run_stream(config, stream, server).await {
+                    let _ = session.await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+fn host_key() -> PrivateKey {
+    PrivateKey::from(Ed25519Keypair::from_seed(&HOST_KEY_SEED))
+}
+
+/// Adds the stand-in's host key for `addr` to the shared known_hosts file.
+fn trust(addr: &SocketAddr, key: &PrivateKey) {
+    let path = known_hosts();
+    let public = key.public_key().to_openssh().expect("encode host key");
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .expect("open known_hosts");
+    writeln!(file, "[{}]:{} {}", addr.ip(), addr.port(), public).expect("write known_hosts");
+}
+
+fn known_hosts() -> PathBuf {
+    static INIT: Once = Once::new();
+    let path = std::env::temp_dir().join(format!(
+        "nauto-driver-tests-{}.known_hosts",
+        std::process::id()
+    ));
+    INIT.call_once(|| {
+        std::fs::write(&path, "").expect("create known_hosts");
+        std::env::set_var("NAUTO_SSH_KNOWN_HOSTS", &path);
+    });
+    path
+}
+
+struct Server {
+    handler: Arc<ExecHandler>,
+    streams: Arc<StreamHandler>,
+    /// Open session channels not yet claimed by an exec, subsystem or shell request.
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+impl Server {
+    /// Hands the channel to the stream handler. Its writes reach the client only after this
+    /// callback returns, so they follow the success reply.
+    fn stream(
+        &mut self,
+        channel: ChannelId,
+        name: &str,
+        session: &mut Session,
+    ) -> Result<(), russh::Error> {
+        let served = self
+            .channels
+            .remove(&channel)
+            .is_some_and(|open| (self.streams)(name, open.into_stream()));
+        if served {
+            session.channel_success(channel)
+        } else {
+            session.channel_failure(channel)
+        }
+    }
+}
+
+impl russh::server::Handler for Server {
+    type Error = russh::Error;
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        Ok(if user == USERNAME && password == PASSWORD {
+            Auth::Accept
+        } else {
+            Auth::reject()
+        })
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        Ok(true)
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        _col_width: u32,
+        _row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(russh::Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel)
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.stream(channel, "shell", session)
+    }
+
+    async fn subsystem_request(
+        &mut self,
+        channel: ChannelId,
+        name: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.stream(channel, name, session)
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.channels.remove(&channel);
+        session.channel_success(channel)?;
+        let command = String::from_utf8_lossy(data);
+        let Some(exec) = (self.handler)(&command) else {
+            return Ok(());
+        };
+        session.data(channel, CryptoVec::from(exec.stdout.into_bytes()))?;
+        if !exec.stderr.is_empty() {
+            session.extended_data(channel, 1, CryptoVec::from(exec.stderr.into_bytes()))?;
+        }
+        session.exit_status_request(channel, exec.status)?;
+        session.eof(channel)?;
+        session.close(channel)
+    }
+}
@@ -1,25 +1,30 @@
 use crate::{
-    config,
-    ssh::{self, default_credential_store, DEFAULT_SSH_PORT},
-    DeviceDriver, DriverAction, DriverExecutionResult,
+    backup, config,
+    getters::{self, CommandRunner, SshRunner},
+    parsing, preflight, replay,
+    ssh::{self, default_credential_store, Client, DEFAULT_SSH_PORT},
+    transfer::{self, Destination, LocalFile, UpgradePlan},
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext,
 };
 use anyhow::{bail, Context, Result};
-use async_ssh2_tokio::Client;
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Credential, Device, DeviceType, JobKind};
-use nauto_security::{CredentialStore, KeyringStore};
+use nauto_model::{
+    CapabilitySet, ConfigMode, Credential, Device, DeviceType, JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
 use reqwest::Client as HttpClient;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use similar::TextDiff;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct AristaEosDriver {
-    credential_store: KeyringStore,
+    credential_store: Arc<dyn CredentialStore>,
     port: u16,
     http: HttpClient,
+    pinned: Option<crate::Transport>,
 }
 
 impl Default for AristaEosDriver {
@@ -32,6 +37,7 @@ impl Default for AristaEosDriver {
             credential_store: default_credential_store(),
             port: DEFAULT_SSH_PORT,
             http,
+            pinned: None,
         }
     }
 }
@@ -43,22 +49,38 @@ impl DeviceDriver for AristaEosDriver {
     }
 
     fn name(&self) -> &'static str {
-        "Arista EOS CLI"
+        match self.pinned {
+            Some(crate::Transport::Http) => "Arista EOS eAPI",
+            _ => "Arista EOS CLI",
+        }
     }
 
     fn capabilities(&self) -> CapabilitySet {
         CapabilitySet {
-            supports_commit: false,
-            supports_rollback: false,
-            supports_diff: false,
-            supports_dry_run: false,
+            supports_commit: true,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: true,
+            supports_replace: true,
+        }
+    }
+
+    fn transports(&self) -> Vec<crate::Transport> {
+        match self.pinned {
+            Some(transport) => vec![transport],
+            None => vec![crate::Transport::Ssh, crate::Transport::Http],
         }
     }
 
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         let transport = self.transport(device);
         let mut res = DriverExecutionResult::default();
@@ -74,16 +96,99 @@ impl DeviceDriver for AristaEosDriver {
                         .await?;
                 }
             },
-            DriverAction::Job(JobKind::ConfigPush { snippet }) => match transport {
-                Transport::Ssh => {
-                    let client = ssh::connect(device, &self.credential_store, self.port).await?;
-                    self.apply_config_ssh(&client, device, snippet, &mut res)
+            DriverAction::Job(JobKind::ConfigPush { snippet, mode }) => {
+                let channel = self.channel(device, transport).await?;
+                self.apply_config(&channel, device, snippet, *mode, ctx, &mut res)
+                    .await?;
+            }
+            DriverAction::Job(JobKind::Getters { which }) => {
+                let results = match transport {
+                    Transport::Ssh => {
+                        let client =
+                            ssh::connect(device, &self.credential_store, self.port).await?;
+                        let runner = SshRunner::new(&client, device).with_json_suffix("| json");
+                        getters::eos::collect(&runner, which).await?
+                    }
+                    Transport::Eapi => {
+                        let runner = EapiRunner {
+                            driver: self,
+                            device,
+                            creds: self.resolve_http_credentials(device).await?,
+                        };
+                        getters::eos::collect(&runner, which).await?
+                    }
+                };
+                res.getters = Some(results);
+                res.logs.push(format!(
+                    "[{}] collected {}",
+                    device.name,
+                    getters::describe(which)
+                ));
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => {
+                let channel = self.channel(device, transport).await?;
+                let running = show_run(self, &channel, device).await?;
+                let startup = if *include_startup {
+                    Some(
+                        channel
+                            .run(self, device, &["show startup-config".into()])
+                            .await?,
+                    )
+                } else {
+                    None
+                };
+                let snapshot = backup::snapshot(&running, startup.as_deref());
+                res.logs.push(backup::describe(device, &snapshot));
+                res.backup = Some(snapshot);
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer {
+                source,
+                destination,
+                protocol,
+                checksum,
+            }) => {
+                // Copies always need SSH, whichever transport carries the CLI commands.
+                let client = ssh::connect(device, &self.credential_store, self.port).await?;
+                let file = LocalFile::inspect(source, checksum.as_deref()).await?;
+                let destination = Destination::new(destination, remote_path(destination));
+                let runner = SshRunner::new(&client, device);
+                if ctx.dry_run {
+                    transfer::check_free_space(&runner, device, &file, &destination, &mut res.logs)
                         .await?;
+                    res.logs.push(format!(
+                        "[{}] dry run: {} not copied",
+                        device.name, file.name
+                    ));
+                } else {
+                    transfer::stage(
+                        &runner,
+                        &client,
+                        device,
+                        &file,
+                        &destination,
+                        *protocol,
+                        &mut res.logs,
+                    )
+                    .await?;
                 }
-                Transport::Eapi => {
-                    self.apply_config_eapi(device, snippet, &mut res).await?;
-                }
-            },
+            }
+            DriverAction::Job(JobKind::Upgrade {
+                image,
+                target_version,
+                protocol,
+                checksum,
+            }) => {
+                let plan =
+                    UpgradePlan::prepare(image, target_version, *protocol, checksum.as_deref())
+                        .await?;
+                let client = ssh::connect(device, &self.credential_store, self.port).await?;
+                self.upgrade(&client, device, &plan, ctx, &mut res).await?;
+            }
             DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
                 res.logs.push(format!(
                     "[{}] evaluated {} compliance rules",
@@ -95,24 +200,66 @@ impl DeviceDriver for AristaEosDriver {
         Ok(res)
     }
 
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        if let Transport::Ssh = self.transport(device) {
+            return Ok(preflight::ssh(device, &self.credential_store, self.port).await);
+        }
+        let login = async {
+            let creds = self.resolve_http_credentials(device).await?;
+            self.eapi_post(device, vec!["show hostname".into()], "json", &creds)
+                .await
+                .map(drop)
+        };
+        Ok(preflight::http(&self.eapi_endpoint(device), login).await)
+    }
+
     async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
         info!(
             target: "drivers::arista",
-            "Rollback requested on {} snapshot {:?}",
+            "Rollback requested on {} ({} byte snapshot)",
             device.name,
-            snapshot
+            snapshot.as_ref().map(String::len).unwrap_or(0)
         );
-        if let Some(snapshot) = snapshot {
-            let client = ssh::connect(device, &self.credential_store, self.port).await?;
-            let payload = format!("configure replace terminal force\n{snapshot}\n");
-            exec_checked(&client, device, &payload).await?;
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        let channel = self.channel(device, self.transport(device)).await?;
+        let session = format!("netrust-rollback-{}", unix_millis());
+        let staged = channel
+            .run(self, device, &replace_commands(&session, &snapshot))
+            .await;
+        if let Err(err) = staged {
+            abort_session(self, &channel, device, &session).await;
+            return Err(err).context("stage rollback session");
         }
+        channel
+            .run(self, device, &["copy running-config startup-config".into()])
+            .await?;
         Ok(())
     }
 }
 
 impl AristaEosDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    /// Always uses eAPI (`Transport::Http`) or always SSH (anything else), whatever the
+    /// device's tags say, so the registry can hold one driver per transport.
+    pub fn with_transport(mut self, transport: crate::Transport) -> Self {
+        self.pinned = Some(transport);
+        self
+    }
+
     fn transport(&self, device: &Device) -> Transport {
+        if let Some(pinned) = self.pinned {
+            return match pinned {
+                crate::Transport::Http => Transport::Eapi,
+                _ => Transport::Ssh,
+            };
+        }
         if device
             .tags
             .iter()
@@ -141,6 +288,8 @@ impl AristaEosDriver {
                 cmd,
                 summarize(&output)
             ));
+            res.outputs
+                .push(parsing::command_output(&device.device_type, cmd, output));
         }
         Ok(())
     }
@@ -154,67 +303,210 @@ impl AristaEosDriver {
         let creds = self.resolve_http_credentials(device).await?;
         let mut payload = vec!["enable".into()];
         payload.extend(commands.iter().cloned());
-        let response = self.eapi_post(device, payload, &creds).await?;
+        let response = self.eapi_post(device, payload, "json", &creds).await?;
         res.logs
             .extend(response.command_summaries(device.name.as_str()));
+        // The first result belongs to `enable`; the rest line up with `commands`.
+        let results = response.parsed.result.iter().flatten().skip(1);
+        res.outputs.extend(
+            commands
+                .iter()
+                .zip(results)
+                .map(|(cmd, value)| parsing::json_output(cmd, value.clone())),
+        );
         Ok(())
     }
 
-    async fn apply_config_ssh(
+    async fn channel(&self, device: &Device, transport: Transport) -> Result<Channel> {
+        Ok(match transport {
+            Transport::Ssh => {
+                Channel::Ssh(ssh::connect(device, &self.credential_store, self.port).await?)
+            }
+            Transport::Eapi => Channel::Eapi(self.resolve_http_credentials(device).await?),
+        })
+    }
+
+    /// Stages the snippet in a named configuration session, reads the session diff and then
+    /// either aborts (dry run) or commits, using `commit timer` when confirmed commits are on.
+    /// In replace mode the session starts from `rollback clean-config`, so the snippet becomes
+    /// the whole configuration.
+    async fn apply_config(
         &self,
-        client: &Client,
+        channel: &Channel,
         device: &Device,
         snippet: &str,
+        mode: ConfigMode,
+        ctx: &JobContext,
         res: &mut DriverExecutionResult,
     ) -> Result<()> {
-        res.pre_snapshot = Some(show_run(client, device).await?);
-        apply_config(client, device, snippet).await?;
-        res.post_snapshot = Some(show_run(client, device).await?);
-        if let (Some(pre), Some(post)) = (res.pre_snapshot.as_ref(), res.post_snapshot.as_ref()) {
-            res.diff = Some(render_diff(pre, post));
+        res.pre_snapshot = Some(show_run(self, channel, device).await?);
+        let session = session_name(ctx);
+        let staged = async {
+            channel
+                .run(self, device, &session_commands(&session, snippet, mode))
+                .await?;
+            channel
+                .run(
+                    self,
+                    device,
+                    &[format!("show session-config named {session} diffs")],
+                )
+                .await
         }
+        .await;
+        let diff = match staged {
+            Ok(diff) => diff,
+            Err(err) => {
+                abort_session(self, channel, device, &session).await;
+                return Err(err).context("stage EOS configuration session");
+            }
+        };
+        res.diff = Some(diff);
         res.logs.push(format!(
-            "[{}] committed EOS snippet ({} lines)",
+            "[{}] staged {} lines in session {}",
             device.name,
-            snippet.lines().count()
+            snippet.lines().count(),
+            session
         ));
+
+        if ctx.dry_run {
+            abort_session(self, channel, device, &session).await;
+            res.logs.push(format!(
+                "[{}] dry run: session {} aborted",
+                device.name, session
+            ));
+            return Ok(());
+        }
+
+        if let Err(err) = self.commit_session(channel, device, &session, res).await {
+            abort_session(self, channel, device, &session).await;
+            return Err(err);
+        }
+        channel
+            .run(self, device, &["copy running-config startup-config".into()])
+            .await?;
+        res.post_snapshot = Some(show_run(self, channel, device).await?);
         Ok(())
     }
 
-    async fn apply_config_eapi(
+    async fn commit_session(
         &self,
+        channel: &Channel,
         device: &Device,
-        snippet: &str,
+        session: &str,
         res: &mut DriverExecutionResult,
     ) -> Result<()> {
-        let creds = self.resolve_http_credentials(device).await?;
-        let before = self.show_run_eapi(device, &creds).await?;
-        res.pre_snapshot = Some(before.clone());
+        let enter = format!("configure session {session}");
+        if let Some(timeout) = config::commit_confirm_timeout() {
+            channel
+                .run(
+                    self,
+                    device,
+                    &[
+                        enter.clone(),
+                        format!("commit timer {}", commit_timer(timeout)),
+                    ],
+                )
+                .await?;
+            res.logs.push(format!(
+                "[{}] session {} committed with {}s timer",
+                device.name,
+                session,
+                timeout.as_secs()
+            ));
+            // Post-check on a fresh request; if the device stopped answering the timer reverts.
+            show_run(self, channel, device)
+                .await
+                .context("post-commit check before confirming session")?;
+        }
+        channel.run(self, device, &[enter, "commit".into()]).await?;
+        res.logs
+            .push(format!("[{}] session {} committed", device.name, session));
+        Ok(())
+    }
 
-        let mut commands = vec!["enable".into(), "configure terminal".into()];
-        commands.extend(
-            snippet
-                .lines()
-                .map(str::trim)
-                .filter(|line| !line.is_empty())
-                .map(|line| line.to_string()),
-        );
-        commands.push("write memory".into());
+    /// Checks the running version, stages and verifies the SWI, points `boot system` at it,
+    /// reloads and waits for the switch to come back on the target version.
+    async fn upgrade(
+        &self,
+        client: &Client,
+        device: &Device,
+        plan: &UpgradePlan,
+        ctx: &JobContext,
+        res: &mut DriverExecutionResult,
+    ) -> Result<()> {
+        let running = running_version(client, device).await?;
+        res.logs.push(format!(
+            "[{}] pre-check: running {}, target {}",
+            device.name, running, plan.target_version
+        ));
+        if transfer::version_matches(&running, &plan.target_version) {
+            res.logs.push(format!(
+                "[{}] already running {}; upgrade skipped",
+                device.name, plan.target_version
+            ));
+            return Ok(());
+        }
+        let boot_path = plan.boot_path();
+        let destination = Destination::new(&boot_path, remote_path(&boot_path));
+        let runner = SshRunner::new(client, device);
+        if ctx.dry_run {
+            transfer::check_free_space(&runner, device, &plan.image, &destination, &mut res.logs)
+                .await?;
+            res.logs.push(format!(
+                "[{}] dry run: {} not staged, boot variable and reload skipped",
+                device.name, plan.image.name
+            ));
+            return Ok(());
+        }
+        transfer::stage(
+            &runner,
+            client,
+            device,
+            &plan.image,
+            &destination,
+            plan.protocol,
+            &mut res.logs,
+        )
+        .await?;
+        let boot = [
+            "configure".to_string(),
+            format!("boot system {boot_path}"),
+            "end".into(),
+            "copy running-config startup-config".into(),
+        ];
+        exec_checked(client, device, &boot.join("\n")).await?;
+        res.logs.push(format!(
+            "[{}] boot system set to {} and saved",
+            device.name, boot_path
+        ));
 
-        let response = self.eapi_post(device, commands, &creds).await?;
+        // The session drops as the switch goes down, so the outcome of `reload` says nothing.
+        let _ = tokio::time::timeout(ssh::command_timeout(), client.execute("reload now")).await;
+        let timeout = config::reload_timeout();
+        res.logs.push(format!(
+            "[{}] reloading, waiting up to {}s",
+            device.name,
+            timeout.as_secs()
+        ));
+        let client =
+            ssh::wait_until_reachable(device, &self.credential_store, self.port, timeout).await?;
+        let running = running_version(&client, device).await?;
+        if !transfer::version_matches(&running, &plan.target_version) {
+            bail!(
+                "{} came back running {}, expected {}",
+                device.name,
+                running,
+                plan.target_version
+            );
+        }
         res.logs
-            .extend(response.command_summaries(device.name.as_str()));
-
-        let after = self.show_run_eapi(device, &creds).await?;
-        res.post_snapshot = Some(after.clone());
-        res.diff = Some(render_diff(&before, &after));
+            .push(format!("[{}] post-check: running {}", device.name, running));
         Ok(())
     }
 
     async fn resolve_http_credentials(&self, device: &Device) -> Result<(String, String)> {
-        let credential = self
-            .credential_store
-            .resolve(&device.credential)
+        let credential = replay::resolve_credential(&self.credential_store, device)
             .await
             .with_context(|| format!("loading credential {}", device.credential.name))?;
         match credential {
@@ -231,6 +523,7 @@ impl AristaEosDriver {
         &self,
         device: &Device,
         commands: Vec<String>,
+        format: &str,
         creds: &(String, String),
     ) -> Result<EapiResponse> {
         let endpoint = self.eapi_endpoint(device);
@@ -240,21 +533,19 @@ impl AristaEosDriver {
             "params": {
                 "version": 1,
                 "cmds": commands,
-                "format": "json"
+                "format": format
             },
             "id": "netrust"
         });
 
         let retry_limit = config::http_retry_limit();
         for attempt in 0..=retry_limit {
-            match self
+            let request = self
                 .http
                 .post(&endpoint)
                 .basic_auth(&creds.0, Some(&creds.1))
-                .json(&payload)
-                .send()
-                .await
-            {
+                .json(&payload);
+            match replay::send(device, request).await {
                 Ok(resp) => {
                     let status = resp.status();
                     let body = resp.text().await.context("arista eAPI payload")?;
@@ -295,13 +586,6 @@ impl AristaEosDriver {
         unreachable!("eAPI retry loop should have returned")
     }
 
-    async fn show_run_eapi(&self, device: &Device, creds: &(String, String)) -> Result<String> {
-        let payload = vec!["enable".into(), "show running-config".into()];
-        let resp = self.eapi_post(device, payload, creds).await?;
-        resp.first_output()
-            .ok_or_else(|| anyhow::anyhow!("no running-config output from {}", device.name))
-    }
-
     fn eapi_endpoint(&self, device: &Device) -> String {
         if device.mgmt_address.starts_with("http://") || device.mgmt_address.starts_with("https://")
         {
@@ -318,6 +602,60 @@ enum Transport {
     Eapi,
 }
 
+/// An open transport that runs CLI command lists and returns their text output.
+enum Channel {
+    Ssh(Client),
+    Eapi((String, String)),
+}
+
+impl Channel {
+    async fn run(
+        &self,
+        driver: &AristaEosDriver,
+        device: &Device,
+        commands: &[String],
+    ) -> Result<String> {
+        match self {
+            Channel::Ssh(client) => exec_checked(client, device, &commands.join("\n")).await,
+            Channel::Eapi(creds) => {
+                let mut payload = vec!["enable".to_string()];
+                payload.extend(commands.iter().cloned());
+                let response = driver.eapi_post(device, payload, "text", creds).await?;
+                Ok(response.text_output())
+            }
+        }
+    }
+}
+
+/// Getter commands over eAPI, each sent after `enable` in its own request.
+struct EapiRunner<'a> {
+    driver: &'a AristaEosDriver,
+    device: &'a Device,
+    creds: (String, String),
+}
+
+#[async_trait]
+impl CommandRunner for EapiRunner<'_> {
+    async fn text(&self, command: &str) -> Result<String> {
+        Channel::Eapi(self.creds.clone())
+            .run(self.driver, self.device, &[command.to_string()])
+            .await
+    }
+
+    async fn json(&self, command: &str) -> Result<Value> {
+        let payload = vec!["enable".to_string(), command.to_string()];
+        let response = self
+            .driver
+            .eapi_post(self.device, payload, "json", &self.creds)
+            .await?;
+        response
+            .parsed
+            .result
+            .and_then(|mut results| (results.len() > 1).then(|| results.swap_remove(1)))
+            .with_context(|| format!("eAPI returned no output for '{}'", command))
+    }
+}
+
 async fn exec_checked(client: &Client, device: &Device, command: &str) -> Result<String> {
     let exec = tokio::time::timeout(ssh::command_timeout(), client.execute(command))
         .await
@@ -335,42 +673,110 @@ async fn exec_checked(client: &Client, device: &Device, command: &str) -> Result
     Ok(exec.stdout)
 }
 
-async fn show_run(client: &Client, device: &Device) -> Result<String> {
-    exec_checked(client, device, "show running-config").await
+async fn running_version(client: &Client, device: &Device) -> Result<String> {
+    let version = SshRunner::new(client, device)
+        .with_json_suffix("| json")
+        .json("show version")
+        .await?;
+    Ok(getters::as_text(&version["version"]))
 }
 
-async fn apply_config(client: &Client, device: &Device, snippet: &str) -> Result<()> {
-    let payload = format!(
-        "configure terminal\n{}\nend\ncopy running-config startup-config",
-        snippet.trim()
-    );
-    exec_checked(client, device, &payload).await?;
-    Ok(())
+/// SCP and SFTP land in the switch's Linux filesystem, where `flash:` is `/mnt/flash`.
+fn remote_path(cli_path: &str) -> String {
+    match cli_path.strip_prefix("flash:") {
+        Some(rest) => format!("/mnt/flash/{}", rest.trim_start_matches('/')),
+        None => cli_path.to_string(),
+    }
 }
 
-fn summarize(output: &str) -> String {
-    let trimmed = output.trim();
-    if trimmed.len() > 200 {
-        format!("{}…", &trimmed[..200])
-    } else {
-        trimmed.to_string()
+async fn show_run(driver: &AristaEosDriver, channel: &Channel, device: &Device) -> Result<String> {
+    channel
+        .run(driver, device, &["show running-config".into()])
+        .await
+}
+
+async fn abort_session(
+    driver: &AristaEosDriver,
+    channel: &Channel,
+    device: &Device,
+    session: &str,
+) {
+    let commands = [format!("configure session {session}"), "abort".into()];
+    if let Err(err) = channel.run(driver, device, &commands).await {
+        warn!(
+            target: "drivers::arista",
+            "aborting session {} on {} failed: {}",
+            session,
+            device.name,
+            err
+        );
     }
 }
 
-fn render_diff(before: &str, after: &str) -> String {
-    let diff = TextDiff::from_lines(before, after);
-    let mut buf = String::new();
-    for change in diff.iter_all_changes().take(200) {
-        let sign = match change.tag() {
-            similar::ChangeTag::Delete => "-",
-            similar::ChangeTag::Insert => "+",
-            similar::ChangeTag::Equal => " ",
-        };
-        buf.push_str(sign);
-        buf.push_str(change.to_string().trim_end());
-        buf.push('\n');
+fn session_name(ctx: &JobContext) -> String {
+    match ctx.job_id {
+        Some(id) => format!("netrust-{}", &id.simple().to_string()[..12]),
+        None => format!("netrust-{}", unix_millis()),
     }
-    buf
+}
+
+fn unix_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default()
+}
+
+fn session_commands(session: &str, snippet: &str, mode: ConfigMode) -> Vec<String> {
+    let mut commands = vec![format!("configure session {session}")];
+    match mode {
+        ConfigMode::Merge => commands.extend(
+            snippet
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string),
+        ),
+        ConfigMode::Replace => {
+            commands.push("rollback clean-config".into());
+            commands.extend(full_config_lines(snippet));
+        }
+    }
+    commands.push("end".into());
+    commands
+}
+
+/// Session that replaces the whole running config with `snapshot` and commits it.
+fn replace_commands(session: &str, snapshot: &str) -> Vec<String> {
+    let mut commands = vec![
+        format!("configure session {session}"),
+        "rollback clean-config".into(),
+    ];
+    commands.extend(full_config_lines(snapshot));
+    commands.push("commit".into());
+    commands
+}
+
+/// Lines of a `show running-config` style file without comments, blanks or `end`.
+fn full_config_lines(config: &str) -> Vec<String> {
+    config
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| {
+            let trimmed = line.trim();
+            !trimmed.is_empty() && !trimmed.starts_with('!') && trimmed != "end"
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+fn commit_timer(timeout: Duration) -> String {
+    let secs = timeout.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
 }
 
 struct EapiResponse {
@@ -379,16 +785,14 @@ struct EapiResponse {
 }
 
 impl EapiResponse {
-    fn first_output(&self) -> Option<String> {
-        self.parsed.result.as_ref().and_then(|entries| {
-            entries.iter().rev().find_map(|value| {
-                value
-                    .as_object()?
-                    .get("output")?
-                    .as_str()
-                    .map(|s| s.to_string())
-            })
-        })
+    /// Concatenated `output` of every command in a text-format reply.
+    fn text_output(&self) -> String {
+        self.parsed
+            .result
+            .iter()
+            .flatten()
+            .filter_map(|value| value.get("output").and_then(Value::as_str))
+            .collect()
     }
 
     fn command_summaries(&self, device: &str) -> Vec<String> {
@@ -429,3 +833,53 @@ struct EapiError {
     code: i64,
     message: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_commands_wrap_snippet_and_replace_strips_comments() {
+        let staged = session_commands(
+            "netrust-1",
+            "interface Ethernet1\n   description uplink\n\n",
+            ConfigMode::Merge,
+        );
+        assert_eq!(
+            staged,
+            vec![
+                "configure session netrust-1",
+                "interface Ethernet1",
+                "   description uplink",
+                "end"
+            ]
+        );
+
+        let snapshot = "! Command: show running-config\nhostname leaf1\n!\ninterface Ethernet1\n   shutdown\nend\n";
+        assert_eq!(
+            replace_commands("netrust-rollback-1", snapshot),
+            vec![
+                "configure session netrust-rollback-1",
+                "rollback clean-config",
+                "hostname leaf1",
+                "interface Ethernet1",
+                "   shutdown",
+                "commit"
+            ]
+        );
+        assert_eq!(
+            session_commands("netrust-2", snapshot, ConfigMode::Replace),
+            vec![
+                "configure session netrust-2",
+                "rollback clean-config",
+                "hostname leaf1",
+                "interface Ethernet1",
+                "   shutdown",
+                "end"
+            ]
+        );
+        assert_eq!(commit_timer(Duration::from_secs(3725)), "01:02:05");
+        assert_eq!(remote_path("flash:/EOS.swi"), "/mnt/flash/EOS.swi");
+        assert_eq!(remote_path("/mnt/drive/EOS.swi"), "/mnt/drive/EOS.swi");
+    }
+}
@@ -1,18 +1,22 @@
+use crate::ssh::Client;
 use crate::{
+    backup, config, config_tree,
+    getters::{self, SshRunner},
+    parsing, preflight, preview,
     ssh::{self, default_credential_store, DEFAULT_SSH_PORT},
-    DeviceDriver, DriverAction, DriverExecutionResult,
+    transfer::{self, Destination, LocalFile, UpgradePlan},
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext,
 };
 use anyhow::{bail, Context, Result};
-use async_ssh2_tokio::Client;
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Device, DeviceType, JobKind};
-use nauto_security::KeyringStore;
-use similar::TextDiff;
-use tracing::info;
+use nauto_model::{CapabilitySet, ConfigMode, Device, DeviceType, JobKind, PreflightReport};
+use nauto_security::CredentialStore;
+use std::sync::Arc;
+use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct CiscoIosDriver {
-    credential_store: KeyringStore,
+    credential_store: Arc<dyn CredentialStore>,
     port: u16,
 }
 
@@ -40,14 +44,20 @@ impl DeviceDriver for CiscoIosDriver {
             supports_commit: false,
             supports_rollback: false,
             supports_diff: false,
-            supports_dry_run: false,
+            supports_dry_run: true,
+            supports_replace: true,
         }
     }
 
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         let client = ssh::connect(device, &self.credential_store, self.port).await?;
         let mut result = DriverExecutionResult::default();
@@ -61,9 +71,23 @@ impl DeviceDriver for CiscoIosDriver {
                         cmd,
                         summarize(&output)
                     ));
+                    result
+                        .outputs
+                        .push(parsing::command_output(&device.device_type, cmd, output));
                 }
             }
-            DriverAction::Job(JobKind::ConfigPush { snippet }) => {
+            DriverAction::Job(JobKind::ConfigPush {
+                snippet,
+                mode: ConfigMode::Replace,
+            }) => {
+                replace_config(&client, device, snippet, ctx, &mut result).await?;
+            }
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) if ctx.dry_run => {
+                // IOS cannot stage a merge, so the diff is predicted from running-config.
+                let running = show_run(&client, device).await?;
+                preview::predict(device, running, snippet, &mut result);
+            }
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
                 result.pre_snapshot = Some(show_run(&client, device).await?);
                 apply_config(&client, device, snippet).await?;
                 result.logs.push(format!(
@@ -75,9 +99,82 @@ impl DeviceDriver for CiscoIosDriver {
                 if let (Some(pre), Some(post)) =
                     (result.pre_snapshot.as_ref(), result.post_snapshot.as_ref())
                 {
-                    result.diff = Some(render_diff(pre, post));
+                    result.diff = Some(config_tree::render_diff(pre, post));
                 }
             }
+            DriverAction::Job(JobKind::Getters { which }) => {
+                let runner = SshRunner::new(&client, device);
+                result.getters = Some(getters::ios::collect(&runner, which).await?);
+                result.logs.push(format!(
+                    "[{}] collected {}",
+                    device.name,
+                    getters::describe(which)
+                ));
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => {
+                let running = show_run(&client, device).await?;
+                let startup = if *include_startup {
+                    Some(exec_checked(&client, device, "show startup-config").await?)
+                } else {
+                    None
+                };
+                let snapshot = backup::snapshot(&running, startup.as_deref());
+                result.logs.push(backup::describe(device, &snapshot));
+                result.backup = Some(snapshot);
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer {
+                source,
+                destination,
+                protocol,
+                checksum,
+            }) => {
+                let file = LocalFile::inspect(source, checksum.as_deref()).await?;
+                // The IOS SCP server takes CLI paths such as `flash:image.bin` as-is.
+                let destination = Destination::new(destination, destination);
+                let runner = SshRunner::new(&client, device);
+                if ctx.dry_run {
+                    transfer::check_free_space(
+                        &runner,
+                        device,
+                        &file,
+                        &destination,
+                        &mut result.logs,
+                    )
+                    .await?;
+                    result.logs.push(format!(
+                        "[{}] dry run: {} not copied",
+                        device.name, file.name
+                    ));
+                } else {
+                    transfer::stage(
+                        &runner,
+                        &client,
+                        device,
+                        &file,
+                        &destination,
+                        *protocol,
+                        &mut result.logs,
+                    )
+                    .await?;
+                }
+            }
+            DriverAction::Job(JobKind::Upgrade {
+                image,
+                target_version,
+                protocol,
+                checksum,
+            }) => {
+                let plan =
+                    UpgradePlan::prepare(image, target_version, *protocol, checksum.as_deref())
+                        .await?;
+                self.upgrade(&client, device, &plan, ctx, &mut result)
+                    .await?;
+            }
             DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
                 result.logs.push(format!(
                     "[{}] evaluated {} compliance rules",
@@ -89,11 +186,16 @@ impl DeviceDriver for CiscoIosDriver {
         Ok(result)
     }
 
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        Ok(preflight::ssh(device, &self.credential_store, self.port).await)
+    }
+
     async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
         info!(
             target: "drivers::cisco_ios",
-            "Rolling back {} using snapshot {:?}",
-            device.name, snapshot
+            "Rolling back {} ({} byte snapshot)",
+            device.name,
+            snapshot.as_ref().map(String::len).unwrap_or(0)
         );
         if let Some(snapshot) = snapshot {
             let client = ssh::connect(device, &self.credential_store, self.port).await?;
@@ -104,6 +206,106 @@ impl DeviceDriver for CiscoIosDriver {
     }
 }
 
+impl CiscoIosDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    /// Checks the running version, stages and verifies the image, points `boot system` at
+    /// it, reloads and waits for the device to come back on the target version.
+    async fn upgrade(
+        &self,
+        client: &Client,
+        device: &Device,
+        plan: &UpgradePlan,
+        ctx: &JobContext,
+        result: &mut DriverExecutionResult,
+    ) -> Result<()> {
+        let running = running_version(client, device).await?;
+        result.logs.push(format!(
+            "[{}] pre-check: running {}, target {}",
+            device.name, running, plan.target_version
+        ));
+        if transfer::version_matches(&running, &plan.target_version) {
+            result.logs.push(format!(
+                "[{}] already running {}; upgrade skipped",
+                device.name, plan.target_version
+            ));
+            return Ok(());
+        }
+        let boot_path = plan.boot_path();
+        let destination = Destination::new(&boot_path, &boot_path);
+        let runner = SshRunner::new(client, device);
+        if ctx.dry_run {
+            transfer::check_free_space(
+                &runner,
+                device,
+                &plan.image,
+                &destination,
+                &mut result.logs,
+            )
+            .await?;
+            result.logs.push(format!(
+                "[{}] dry run: {} not staged, boot variable and reload skipped",
+                device.name, plan.image.name
+            ));
+            return Ok(());
+        }
+        transfer::stage(
+            &runner,
+            client,
+            device,
+            &plan.image,
+            &destination,
+            plan.protocol,
+            &mut result.logs,
+        )
+        .await?;
+        apply_config(
+            client,
+            device,
+            &format!("no boot system\nboot system {boot_path}"),
+        )
+        .await?;
+        result.logs.push(format!(
+            "[{}] boot system set to {} and saved",
+            device.name, boot_path
+        ));
+
+        // The extra newline answers `[confirm]`. The session drops as the device goes down,
+        // so the outcome of `reload` says nothing.
+        let _ = tokio::time::timeout(ssh::command_timeout(), client.execute("reload\n\n")).await;
+        let timeout = config::reload_timeout();
+        result.logs.push(format!(
+            "[{}] reloading, waiting up to {}s",
+            device.name,
+            timeout.as_secs()
+        ));
+        let client =
+            ssh::wait_until_reachable(device, &self.credential_store, self.port, timeout).await?;
+        let running = running_version(&client, device).await?;
+        if !transfer::version_matches(&running, &plan.target_version) {
+            bail!(
+                "{} came back running {}, expected {}",
+                device.name,
+                running,
+                plan.target_version
+            );
+        }
+        result
+            .logs
+            .push(format!("[{}] post-check: running {}", device.name, running));
+        Ok(())
+    }
+}
+
+async fn running_version(client: &Client, device: &Device) -> Result<String> {
+    let output = exec_checked(client, device, "show version").await?;
+    Ok(getters::ios::facts(&output)?.os_version)
+}
+
 async fn exec_checked(client: &Client, device: &Device, command: &str) -> Result<String> {
     let exec = tokio::time::timeout(ssh::command_timeout(), client.execute(command))
         .await
@@ -137,27 +339,95 @@ async fn apply_config(client: &Client, device: &Device, snippet: &str) -> Result
     Ok(())
 }
 
-fn summarize(output: &str) -> String {
-    let trimmed = output.trim();
-    if trimmed.len() > 200 {
-        format!("{}…", &trimmed[..200])
-    } else {
-        trimmed.to_string()
+/// Full replace: the intended config is written to flash, diffed against running-config
+/// with `show archive config differences` and, unless this is a dry run, applied with
+/// `configure replace`. The staged file is deleted either way.
+async fn replace_config(
+    client: &Client,
+    device: &Device,
+    config: &str,
+    ctx: &JobContext,
+    result: &mut DriverExecutionResult,
+) -> Result<()> {
+    result.pre_snapshot = Some(show_run(client, device).await?);
+    exec_checked(client, device, &stage_script(REPLACE_FILE, config))
+        .await
+        .context("stage replacement config")?;
+    let outcome = async {
+        let diff = exec_checked(
+            client,
+            device,
+            &format!("show archive config differences system:running-config {REPLACE_FILE}"),
+        )
+        .await?;
+        result.diff = Some(diff);
+        if ctx.dry_run {
+            result.logs.push(format!(
+                "[{}] dry run: replacement diff captured, running-config untouched",
+                device.name
+            ));
+            return Ok(());
+        }
+        let output = exec_checked(
+            client,
+            device,
+            &format!("configure replace {REPLACE_FILE} force"),
+        )
+        .await?;
+        if !output.contains("Rollback Done") {
+            bail!(
+                "configure replace on {} did not complete: {}",
+                device.name,
+                summarize(&output)
+            );
+        }
+        exec_checked(client, device, "write memory").await?;
+        result.logs.push(format!(
+            "[{}] replaced running-config ({} lines)",
+            device.name,
+            config.lines().count()
+        ));
+        result.post_snapshot = Some(show_run(client, device).await?);
+        Ok(())
+    }
+    .await;
+    if let Err(err) = exec_checked(client, device, &format!("delete /force {REPLACE_FILE}")).await {
+        warn!(target: "drivers::cisco_ios", "{} removing {} failed: {}", device.name, REPLACE_FILE, err);
+    }
+    outcome
+}
+
+const REPLACE_FILE: &str = "flash:netrust-replace.cfg";
+
+/// Tcl script that writes `content` to `path`; IOS has no other way to create a file from
+/// an exec session.
+fn stage_script(path: &str, content: &str) -> String {
+    let mut quoted = String::with_capacity(content.len() + 2);
+    for ch in content.chars() {
+        if matches!(ch, '\\' | '"' | '$' | '[' | ']' | '{' | '}') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
     }
+    format!("tclsh\nset f [open \"{path}\" w]\nputs -nonewline $f \"{quoted}\"\nclose $f\ntclquit")
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
 }
 
-fn render_diff(before: &str, after: &str) -> String {
-    let diff = TextDiff::from_lines(before, after);
-    let mut buf = String::new();
-    for change in diff.iter_all_changes().take(200) {
-        let sign = match change.tag() {
-            similar::ChangeTag::Delete => "-",
-            similar::ChangeTag::Insert => "+",
-            similar::ChangeTag::Equal => " ",
-        };
-        buf.push_str(sign);
-        buf.push_str(change.to_string().trim_end());
-        buf.push('\n');
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_script_escapes_tcl_specials() {
+        let script = stage_script(REPLACE_FILE, "banner motd ^C $[x] {y}\\ \"q\"^C\n");
+        assert_eq!(
+            script,
+            "tclsh\nset f [open \"flash:netrust-replace.cfg\" w]\n\
+             puts -nonewline $f \"banner motd ^C \\$\\[x\\] \\{y\\}\\\\ \\\"q\\\"^C\n\"\n\
+             close $f\ntclquit"
+        );
     }
-    buf
 }
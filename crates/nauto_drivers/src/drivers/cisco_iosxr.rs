@@ -0,0 +1,693 @@
+use crate::{
+    backup,
+    cli::CliSession,
+    config,
+    config_tree::{ChangeKind, ConfigTree},
+    drivers::GenericNetconfDriver,
+    parsing, preflight, registry,
+    ssh::{default_credential_store, DEFAULT_SSH_PORT},
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext, Transport,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{CapabilitySet, ConfigMode, Device, DeviceType, JobKind, PreflightReport};
+use nauto_security::CredentialStore;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Marker every netrust commit comment starts with.
+const COMMIT_MARKER: &str = "netrust";
+/// First line of a pre-change snapshot whose push committed, followed by the commit id.
+/// Rollback only undoes that commit, and only while it is still the latest one.
+const COMMIT_RECORD: &str = "!! netrust commit ";
+
+/// IOS-XR over an interactive CLI session (candidate config + commit), or NETCONF when
+/// the device's `transport:` tags prefer it.
+#[derive(Clone)]
+pub struct CiscoIosXrDriver {
+    credential_store: Arc<dyn CredentialStore>,
+    port: u16,
+    netconf: GenericNetconfDriver,
+    pinned: Option<Transport>,
+}
+
+impl Default for CiscoIosXrDriver {
+    fn default() -> Self {
+        Self {
+            credential_store: default_credential_store(),
+            port: DEFAULT_SSH_PORT,
+            netconf: GenericNetconfDriver::default(),
+            pinned: None,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for CiscoIosXrDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::CiscoIosXr
+    }
+
+    fn name(&self) -> &'static str {
+        match self.pinned {
+            Some(Transport::Netconf) => "Cisco IOS-XR NETCONF",
+            _ => "Cisco IOS-XR",
+        }
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        CapabilitySet {
+            supports_commit: true,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: true,
+            supports_replace: false,
+        }
+    }
+
+    fn transports(&self) -> Vec<Transport> {
+        match self.pinned {
+            Some(transport) => vec![transport],
+            None => vec![Transport::Ssh, Transport::Netconf],
+        }
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        if self.uses_netconf(device) {
+            return self.netconf.execute(device, action, ctx).await;
+        }
+        let mut session = self.open(device).await?;
+        let mut res = DriverExecutionResult::default();
+        match action {
+            DriverAction::Job(JobKind::CommandBatch { commands }) => {
+                for cmd in commands {
+                    let output = session.run(cmd).await?;
+                    res.logs.push(format!(
+                        "[{}] {} => {}",
+                        device.name,
+                        cmd,
+                        summarize(&output)
+                    ));
+                    res.outputs
+                        .push(parsing::command_output(&device.device_type, cmd, output));
+                }
+            }
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                res.pre_snapshot = Some(show_run(&mut session).await?);
+                session.run("configure terminal").await?;
+                let outcome = stage_and_commit(&mut session, device, snippet, ctx, &mut res).await;
+                if outcome.is_err() || ctx.dry_run {
+                    if let Err(err) = session.run("abort").await {
+                        warn!(target: "drivers::iosxr", "{} abort failed: {}", device.name, err);
+                    }
+                } else {
+                    session.run("end").await?;
+                }
+                outcome?;
+                if !ctx.dry_run {
+                    if let Some(id) = our_commit(&mut session, &commit_comment(ctx)).await? {
+                        res.pre_snapshot = res
+                            .pre_snapshot
+                            .take()
+                            .map(|running| format!("{COMMIT_RECORD}{id}\n{running}"));
+                    }
+                    res.post_snapshot = Some(show_run(&mut session).await?);
+                }
+            }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => {
+                // Committed configuration is what XR boots from; there is no separate startup file.
+                let snapshot = backup::snapshot(&show_run(&mut session).await?, None);
+                res.logs.push(backup::describe(device, &snapshot));
+                if *include_startup {
+                    res.logs.push(format!(
+                        "[{}] no separate startup config on IOS-XR",
+                        device.name
+                    ));
+                }
+                res.backup = Some(snapshot);
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
+            DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
+                res.logs.push(format!(
+                    "[{}] evaluated {} compliance rules",
+                    device.name,
+                    rules.len()
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        if self.uses_netconf(device) {
+            return self.netconf.preflight(device).await;
+        }
+        Ok(preflight::ssh(device, &self.credential_store, self.port).await)
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        if self.uses_netconf(device) {
+            return self.netconf.rollback(device, snapshot).await;
+        }
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        let Some(ours) = snapshot
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix(COMMIT_RECORD))
+        else {
+            info!(
+                target: "drivers::iosxr",
+                "{}: the push committed nothing, so there is nothing to roll back",
+                device.name
+            );
+            return Ok(());
+        };
+        info!(
+            target: "drivers::iosxr",
+            "Rolling back commit {} on {} ({} byte snapshot)",
+            ours,
+            device.name,
+            snapshot.len()
+        );
+        let mut session = self.open(device).await?;
+        let detail = session
+            .run("show configuration commit list 1 detail")
+            .await?;
+        let latest = latest_commit(&detail).map(|(id, _)| id);
+        if latest.as_deref() != Some(ours) {
+            bail!(
+                "latest commit on {} is {}, not netrust commit {}; refusing rollback configuration last 1",
+                device.name,
+                latest.as_deref().unwrap_or("unknown"),
+                ours
+            );
+        }
+        session.run("rollback configuration last 1").await?;
+        Ok(())
+    }
+}
+
+impl CiscoIosXrDriver {
+    /// Resolves credentials, over the CLI and NETCONF alike, from `store` instead of
+    /// [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.netconf = self.netconf.with_credential_store(store.clone());
+        self.credential_store = store;
+        self
+    }
+
+    /// Always uses NETCONF (`Transport::Netconf`) or always the CLI (anything else), whatever
+    /// the device's tags say, so the registry can hold one driver per transport and fall back
+    /// from one to the other.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.pinned = Some(transport);
+        self
+    }
+
+    /// NETCONF when pinned to it or, unpinned, when the device's `transport:` tags rank it
+    /// ahead of SSH.
+    fn uses_netconf(&self, device: &Device) -> bool {
+        if let Some(pinned) = self.pinned {
+            return pinned == Transport::Netconf;
+        }
+        registry::preferred_transports(device)
+            .into_iter()
+            .find(|transport| matches!(transport, Transport::Ssh | Transport::Netconf))
+            == Some(Transport::Netconf)
+    }
+
+    async fn open(&self, device: &Device) -> Result<CliSession> {
+        let mut session = CliSession::connect(device, &self.credential_store, self.port).await?;
+        session.run("terminal length 0").await?;
+        session.run("terminal width 0").await?;
+        Ok(session)
+    }
+}
+
+/// Loads the snippet into the candidate and, unless this is a dry run, commits it —
+/// with `commit confirmed` and a post-check when confirmed commits are enabled.
+async fn stage_and_commit(
+    session: &mut CliSession,
+    device: &Device,
+    snippet: &str,
+    ctx: &JobContext,
+    res: &mut DriverExecutionResult,
+) -> Result<()> {
+    for line in snippet
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+    {
+        session.run(line).await?;
+    }
+    res.logs.push(format!(
+        "[{}] loaded {} lines into candidate",
+        device.name,
+        snippet.lines().count()
+    ));
+
+    if ctx.dry_run {
+        res.diff = Some(strip_banner(&session.run("show configuration").await?));
+        res.logs.push(format!(
+            "[{}] dry run: candidate shown and aborted",
+            device.name
+        ));
+        return Ok(());
+    }
+
+    res.diff = Some(strip_banner(
+        &session.run("show commit changes diff").await?,
+    ));
+    let comment = commit_comment(ctx);
+    match config::commit_confirm_timeout() {
+        Some(timeout) => {
+            // XR accepts 30..=65535 seconds for a confirmed commit.
+            let secs = timeout.as_secs().clamp(30, 65_535);
+            session
+                .run(&format!("commit confirmed {secs} comment {comment}"))
+                .await?;
+            res.logs.push(format!(
+                "[{}] commit confirmed pending ({}s window)",
+                device.name, secs
+            ));
+            // On failure the caller's `abort` throws the trial commit away before it is
+            // confirmed.
+            let running = show_run(session)
+                .await
+                .context("post-commit check before confirming")?;
+            let missing = missing_lines(&running, snippet);
+            if !missing.is_empty() {
+                bail!(
+                    "post-commit check failed, aborting the confirmed commit: running config is missing pushed lines: {}",
+                    missing.join(", ")
+                );
+            }
+            session.run("commit").await?;
+            res.logs.push(format!("[{}] commit confirmed", device.name));
+        }
+        None => {
+            session.run(&format!("commit comment {comment}")).await?;
+            res.logs.push(format!("[{}] commit complete", device.name));
+        }
+    }
+    Ok(())
+}
+
+fn commit_comment(ctx: &JobContext) -> String {
+    match ctx.job_id {
+        Some(id) => format!("{COMMIT_MARKER} job {id}"),
+        None => COMMIT_MARKER.into(),
+    }
+}
+
+/// The id of the newest commit when its comment is `comment`, i.e. the commit this job
+/// just made. `None` when nothing was committed.
+async fn our_commit(session: &mut CliSession, comment: &str) -> Result<Option<String>> {
+    let detail = session
+        .run("show configuration commit list 1 detail")
+        .await?;
+    Ok(latest_commit(&detail)
+        .filter(|(_, latest)| latest == comment)
+        .map(|(id, _)| id))
+}
+
+/// `show configuration commit list 1 detail` prints `CommitId:` (followed by `Label:` on
+/// the same line) and `Comment:` fields for the newest commit.
+fn latest_commit(detail: &str) -> Option<(String, String)> {
+    let field = |name: &str| {
+        detail.lines().find_map(|line| {
+            let (_, value) = line.split_once(name)?;
+            Some(value.trim().to_string())
+        })
+    };
+    let id = field("CommitId:")?.split_whitespace().next()?.to_string();
+    Some((id, field("Comment:").unwrap_or_default()))
+}
+
+/// Drops the `Building configuration...` / `!! IOS XR Configuration` preamble and timestamp.
+/// Lines of `snippet` that `running` does not have, each with its parent lines (`a > b`).
+/// `no ...` lines remove configuration, so they are not looked for.
+fn missing_lines(running: &str, snippet: &str) -> Vec<String> {
+    ConfigTree::parse_indented(running)
+        .diff(&ConfigTree::parse_indented(snippet))
+        .changes
+        .into_iter()
+        .filter(|change| change.kind == ChangeKind::Added && !change.node.text.starts_with("no "))
+        .map(|change| {
+            change
+                .parents
+                .iter()
+                .chain([&change.node])
+                .map(|node| node.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" > ")
+        })
+        .collect()
+}
+
+fn strip_banner(output: &str) -> String {
+    output
+        .lines()
+        .filter(|line| {
+            let trimmed = line.trim();
+            !(trimmed.starts_with("Building configuration")
+                || trimmed.starts_with("!! ")
+                || is_timestamp(trimmed))
+        })
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+fn is_timestamp(line: &str) -> bool {
+    const DAYS: [&str; 7] = ["Mon ", "Tue ", "Wed ", "Thu ", "Fri ", "Sat ", "Sun "];
+    DAYS.iter().any(|day| line.starts_with(day)) && line.ends_with("UTC")
+}
+
+async fn show_run(session: &mut CliSession) -> Result<String> {
+    let output = session.run("show running-config").await?;
+    Ok(strip_banner(&output))
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nauto_model::CredentialRef;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[derive(Default)]
+    struct Router {
+        running: Vec<String>,
+        commits: Vec<(String, Vec<String>)>,
+        commands: Vec<String>,
+        /// A `commit confirmed` waiting for its confirming `commit`.
+        trial: bool,
+    }
+
+    /// Raw-TCP IOS-XR shell: exec/config prompts, a candidate buffer, commit history with
+    /// comments and `rollback configuration last 1`. `abort` throws away a trial commit. Lines
+    /// containing `invalid` are rejected; lines containing `unsupported` are accepted but
+    /// never reach the running config.
+    async fn spawn_router(running: &[&str]) -> (String, Arc<Mutex<Router>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(Router {
+            running: running.iter().map(|line| line.to_string()).collect(),
+            ..Default::default()
+        }));
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    let mut candidate: Option<Vec<String>> = None;
+                    write.write_all(b"\r\nRP/0/RP0/CPU0:xr1#").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let output =
+                            respond(&mut state.lock().unwrap(), &mut candidate, line.trim_end());
+                        let prompt = if candidate.is_some() {
+                            "(config)#"
+                        } else {
+                            "#"
+                        };
+                        let reply = format!("{line}\r\n{output}RP/0/RP0/CPU0:xr1{prompt}");
+                        write.write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        (format!("tcp://{addr}"), state)
+    }
+
+    fn respond(router: &mut Router, candidate: &mut Option<Vec<String>>, line: &str) -> String {
+        router.commands.push(line.to_string());
+        let banner = "Building configuration...\n!! IOS XR Configuration 7.5.2\n";
+        let listing = |lines: &[String], prefix: &str| -> String {
+            lines.iter().map(|l| format!("{prefix}{l}\n")).collect()
+        };
+        match (candidate.as_mut(), line) {
+            (_, "terminal length 0" | "terminal width 0") => String::new(),
+            (_, "show running-config") => {
+                format!("{banner}{}end\n", listing(&router.running, ""))
+            }
+            (None, "configure terminal") => {
+                *candidate = Some(Vec::new());
+                String::new()
+            }
+            (None, "show configuration commit list 1 detail") => match router.commits.last() {
+                Some((comment, _)) => format!(
+                    "   1) CommitId: {}    Label: NONE\n      UserId:   admin\n      Comment:  {comment}\n",
+                    1_000_000_000 + router.commits.len()
+                ),
+                None => String::new(),
+            },
+            (None, "rollback configuration last 1") => {
+                let (_, previous) = router.commits.pop().unwrap();
+                router.running = previous;
+                String::new()
+            }
+            (Some(_), "abort") => {
+                if std::mem::take(&mut router.trial) {
+                    let (_, previous) = router.commits.pop().unwrap();
+                    router.running = previous;
+                }
+                *candidate = None;
+                String::new()
+            }
+            (Some(staged), "end") => {
+                assert!(staged.is_empty(), "uncommitted changes on end");
+                *candidate = None;
+                String::new()
+            }
+            (Some(staged), "show configuration") => {
+                format!("{banner}{}end\n", listing(staged, ""))
+            }
+            (Some(staged), "show commit changes diff") => {
+                format!("{banner}{}", listing(staged, "+ "))
+            }
+            (Some(_), "commit") => {
+                router.trial = false;
+                String::new()
+            }
+            (Some(staged), command) if command.starts_with("commit ") => {
+                let comment = command.split(" comment ").nth(1).unwrap_or_default();
+                let previous = router.running.clone();
+                router.running.extend(
+                    staged
+                        .drain(..)
+                        .filter(|line| !line.contains("unsupported")),
+                );
+                router.commits.push((comment.to_string(), previous));
+                router.trial = command.starts_with("commit confirmed ");
+                String::new()
+            }
+            (Some(_), command) if command.contains("invalid") => {
+                "        ^\n% Invalid input detected at '^' marker.\n".into()
+            }
+            (Some(staged), command) => {
+                staged.push(command.to_string());
+                String::new()
+            }
+            (None, _) => "% Invalid input detected at '^' marker.\n".into(),
+        }
+    }
+
+    fn device(address: &str) -> Device {
+        Device {
+            id: "xr1".into(),
+            name: "xr1".into(),
+            device_type: DeviceType::CiscoIosXr,
+            mgmt_address: address.into(),
+            credential: CredentialRef { name: "lab".into() },
+            tags: vec![],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    #[test]
+    fn transport_follows_tag_order_and_each_instance_is_a_candidate() {
+        let mut device = device("192.0.2.1");
+        device.tags = vec!["transport:ssh".into(), "transport:netconf".into()];
+        assert!(!CiscoIosXrDriver::default().uses_netconf(&device));
+        device.tags.reverse();
+        assert!(CiscoIosXrDriver::default().uses_netconf(&device));
+
+        let registry = crate::DriverRegistry::new(vec![
+            Arc::new(CiscoIosXrDriver::default().with_transport(Transport::Ssh)),
+            Arc::new(CiscoIosXrDriver::default().with_transport(Transport::Netconf)),
+        ]);
+        let names = |device: &Device| -> Vec<&str> {
+            registry
+                .candidates(device)
+                .iter()
+                .map(|driver| driver.name())
+                .collect()
+        };
+        assert_eq!(names(&device), ["Cisco IOS-XR NETCONF", "Cisco IOS-XR"]);
+        device.tags.reverse();
+        assert_eq!(names(&device), ["Cisco IOS-XR", "Cisco IOS-XR NETCONF"]);
+    }
+
+    #[tokio::test]
+    async fn commits_with_comment_and_rolls_back_last_commit() {
+        let (address, state) = spawn_router(&["hostname xr1"]).await;
+        let device = device(&address);
+        let driver = CiscoIosXrDriver::default();
+        let job = JobKind::ConfigPush {
+            snippet: "interface Loopback10\n ipv4 address 10.0.0.10 255.255.255.255".into(),
+            mode: ConfigMode::Merge,
+        };
+        let ctx = JobContext {
+            job_id: Some(uuid::Uuid::nil()),
+            ..Default::default()
+        };
+
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &ctx)
+            .await
+            .unwrap();
+        assert_eq!(
+            res.diff.as_deref(),
+            Some("+ interface Loopback10\n+  ipv4 address 10.0.0.10 255.255.255.255\n")
+        );
+        assert_eq!(
+            res.post_snapshot.as_deref(),
+            Some(
+                "hostname xr1\ninterface Loopback10\n ipv4 address 10.0.0.10 255.255.255.255\nend\n"
+            )
+        );
+        assert!(state.lock().unwrap().commits[0]
+            .0
+            .starts_with("netrust job 0000"));
+        let pre = res.pre_snapshot.unwrap();
+        assert!(pre.starts_with("!! netrust commit 1000000001\nhostname xr1\n"));
+
+        // Another job commits on top: rolling back the first job would undo the second.
+        let second = JobContext {
+            job_id: Some(uuid::Uuid::from_u128(2)),
+            ..Default::default()
+        };
+        let later = JobKind::ConfigPush {
+            snippet: "interface Loopback11".into(),
+            mode: ConfigMode::Merge,
+        };
+        let res2 = driver
+            .execute(&device, DriverAction::Job(&later), &second)
+            .await
+            .unwrap();
+        let err = driver
+            .rollback(&device, Some(pre.clone()))
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("is 1000000002, not netrust commit 1000000001"));
+
+        driver.rollback(&device, res2.pre_snapshot).await.unwrap();
+        driver.rollback(&device, Some(pre.clone())).await.unwrap();
+        assert_eq!(state.lock().unwrap().running, ["hostname xr1"]);
+        // Rolling back again would undo whatever is now the latest commit, so it is refused.
+        assert!(driver.rollback(&device, Some(pre)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn dry_run_and_rejected_lines_abort_the_candidate() {
+        let (address, state) = spawn_router(&["hostname xr1"]).await;
+        let device = device(&address);
+        let driver = CiscoIosXrDriver::default();
+        let job = JobKind::ConfigPush {
+            snippet: "router static\n address-family ipv4 unicast".into(),
+            mode: ConfigMode::Merge,
+        };
+        let dry = JobContext {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &dry)
+            .await
+            .unwrap();
+        assert_eq!(
+            res.diff.as_deref(),
+            Some("router static\n address-family ipv4 unicast\nend\n")
+        );
+
+        let bad = JobKind::ConfigPush {
+            snippet: "interface invalid0".into(),
+            mode: ConfigMode::Merge,
+        };
+        assert!(driver
+            .execute(&device, DriverAction::Job(&bad), &JobContext::default())
+            .await
+            .is_err());
+        let router = state.lock().unwrap();
+        assert_eq!(router.running, ["hostname xr1"]);
+        assert!(router.commits.is_empty());
+        assert_eq!(router.commands.iter().filter(|c| *c == "abort").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn confirmed_commit_is_aborted_when_pushed_lines_are_missing() {
+        let (address, state) = spawn_router(&["hostname xr1"]).await;
+        let device = device(&address);
+        let job = JobKind::ConfigPush {
+            snippet: "interface Loopback10\n ipv4 address 10.0.0.10 255.255.255.255\n ipv6 unsupported-feature".into(),
+            mode: ConfigMode::Merge,
+        };
+
+        let err = CiscoIosXrDriver::default()
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}")
+                .contains("missing pushed lines: interface Loopback10 > ipv6 unsupported-feature"),
+            "{err:#}"
+        );
+        let router = state.lock().unwrap();
+        assert!(router
+            .commands
+            .iter()
+            .any(|c| c.starts_with("commit confirmed ")));
+        assert!(!router.commands.iter().any(|c| c == "commit"));
+        assert_eq!(router.running, ["hostname xr1"]);
+        assert!(router.commits.is_empty());
+    }
+}
@@ -1,19 +1,28 @@
-use crate::{config, DeviceDriver, DriverAction, DriverExecutionResult};
+use crate::{
+    backup, config, config_tree,
+    getters::{self, CommandRunner},
+    parsing, preflight, replay,
+    ssh::default_credential_store,
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext, Transport,
+};
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Credential, Device, DeviceType, JobKind};
-use nauto_security::{CredentialStore, KeyringStore};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use nauto_model::{
+    CapabilitySet, ConfigMode, Credential, Device, DeviceType, JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use similar::TextDiff;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct CiscoNxosApiDriver {
     client: Client,
-    credential_store: KeyringStore,
+    credential_store: Arc<dyn CredentialStore>,
 }
 
 impl Default for CiscoNxosApiDriver {
@@ -23,7 +32,7 @@ impl Default for CiscoNxosApiDriver {
                 .timeout(config::http_timeout())
                 .build()
                 .expect("nxapi client"),
-            credential_store: KeyringStore::new("netrust"),
+            credential_store: default_credential_store(),
         }
     }
 }
@@ -44,13 +53,23 @@ impl DeviceDriver for CiscoNxosApiDriver {
             supports_rollback: true,
             supports_diff: true,
             supports_dry_run: true,
+            supports_replace: true,
         }
     }
 
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Http]
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         let credentials = self.resolve_credentials(device).await?;
         let mut res = DriverExecutionResult::default();
@@ -72,37 +91,70 @@ impl DeviceDriver for CiscoNxosApiDriver {
                         .push(format!("NX-OS API {} -> {}", device.name, reply.summary()));
                     res.logs
                         .extend(reply.command_summaries(device.name.as_str()));
+                    res.outputs.push(match reply.body() {
+                        Some(Value::String(text)) => {
+                            parsing::command_output(&device.device_type, cmd, text)
+                        }
+                        Some(body) => parsing::json_output(cmd, body),
+                        None => parsing::command_output(&device.device_type, cmd, String::new()),
+                    });
                 }
             }
-            DriverAction::Job(JobKind::ConfigPush { snippet }) => {
-                let before = self
-                    .run_show(device, "show running-config", &credentials)
+            DriverAction::Job(JobKind::ConfigPush {
+                snippet,
+                mode: ConfigMode::Replace,
+            }) => {
+                self.replace_config(device, snippet, ctx.dry_run, &credentials, &mut res)
                     .await?;
-                res.pre_snapshot = Some(before.clone());
-                let payload = json!({
-                    "ins_api": {
-                        "version": "1.2",
-                        "type": "cli_conf",
-                        "chunk": "0",
-                        "sid": "1",
-                        "input": snippet,
-                        "output_format": "json"
-                    }
-                });
-                let reply = self.post(device, payload, &credentials).await?;
-                res.logs.push(reply.summary());
-                res.logs
-                    .extend(reply.command_summaries(device.name.as_str()));
+            }
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                if ctx.dry_run {
+                    self.verify_config(device, snippet, &credentials, &mut res)
+                        .await?;
+                } else {
+                    self.apply_config(device, snippet, ConfigMode::Merge, &credentials, &mut res)
+                        .await?;
+                }
+            }
+            DriverAction::Job(JobKind::Getters { which }) => {
+                let runner = NxapiRunner {
+                    driver: self,
+                    device,
+                    creds: &credentials,
+                };
+                res.getters = Some(getters::nxos::collect(&runner, which).await?);
                 res.logs.push(format!(
-                    "[{}] applied NX-OS config via REST ({} lines)",
+                    "[{}] collected {}",
                     device.name,
-                    snippet.lines().count()
+                    getters::describe(which)
                 ));
-                let after = self
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => {
+                let running = self
                     .run_show(device, "show running-config", &credentials)
                     .await?;
-                res.post_snapshot = Some(after.clone());
-                res.diff = Some(render_diff(&before, &after));
+                let startup = if *include_startup {
+                    Some(
+                        self.run_show(device, "show startup-config", &credentials)
+                            .await?,
+                    )
+                } else {
+                    None
+                };
+                let snapshot = backup::snapshot(&running, startup.as_deref());
+                res.logs.push(backup::describe(device, &snapshot));
+                res.backup = Some(snapshot);
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
             }
             DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
                 res.logs.push(format!(
@@ -115,51 +167,61 @@ impl DeviceDriver for CiscoNxosApiDriver {
         Ok(res)
     }
 
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let login = async {
+            let creds = self.resolve_credentials(device).await?;
+            self.run_show(device, "show hostname", &creds)
+                .await
+                .map(drop)
+        };
+        Ok(preflight::http(&device.mgmt_address, login).await)
+    }
+
     async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
-        match snapshot {
-            Some(snapshot) => {
-                let credentials = self.resolve_credentials(device).await?;
-                info!(
-                    target: "drivers::nxos",
-                    "Rollback {} using snapshot ({} bytes)",
-                    device.name,
-                    snapshot.len()
-                );
-                let payload = json!({
-                    "ins_api": {
-                        "version": "1.2",
-                        "type": "cli_conf",
-                        "chunk": "0",
-                        "sid": "rollback",
-                        "input": snapshot,
-                        "output_format": "json"
-                    }
-                });
-                let reply = self.post(device, payload, &credentials).await?;
-                info!(
-                    target: "drivers::nxos",
-                    "Rollback result {} -> {}",
-                    device.name,
-                    reply.summary()
-                );
-            }
-            None => {
-                info!(
-                    target: "drivers::nxos",
-                    "Rollback requested for {} but no snapshot was provided",
-                    device.name
-                );
-            }
+        let Some(snapshot) = snapshot else {
+            info!(
+                target: "drivers::nxos",
+                "Rollback requested for {} but no snapshot was provided",
+                device.name
+            );
+            return Ok(());
+        };
+        let credentials = self.resolve_credentials(device).await?;
+        let checkpoint = checkpoint_name(&snapshot);
+        info!(
+            target: "drivers::nxos",
+            "Rollback {} to checkpoint {}",
+            device.name,
+            checkpoint
+        );
+        let existing = self.list_checkpoints(device, &credentials).await?;
+        if !existing.contains(&checkpoint) {
+            bail!(
+                "checkpoint {} for this snapshot no longer exists on {}",
+                checkpoint,
+                device.name
+            );
         }
+        self.exec(
+            device,
+            &format!("rollback running-config checkpoint {checkpoint}"),
+            &credentials,
+        )
+        .await
+        .with_context(|| format!("rollback {} to {}", device.name, checkpoint))?;
         Ok(())
     }
 }
 
 impl CiscoNxosApiDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
     async fn resolve_credentials(&self, device: &Device) -> Result<(String, String)> {
-        let credential = self
-            .credential_store
-            .resolve(&device.credential)
+        let credential = replay::resolve_credential(&self.credential_store, device)
             .await
             .with_context(|| format!("loading credential {}", device.credential.name))?;
         match credential {
@@ -172,23 +234,241 @@ impl CiscoNxosApiDriver {
         }
     }
 
+    /// Checkpoints running-config, applies the snippet and rolls back to the checkpoint on
+    /// failure. On success older netrust checkpoints are pruned; the one matching this
+    /// pre-snapshot is kept so `rollback` can return to it. Replace mode applies the file
+    /// staged by `replace_config` with `rollback running-config file`.
+    async fn apply_config(
+        &self,
+        device: &Device,
+        snippet: &str,
+        mode: ConfigMode,
+        creds: &(String, String),
+        res: &mut DriverExecutionResult,
+    ) -> Result<()> {
+        let before = self.run_show(device, "show running-config", creds).await?;
+        let checkpoint = checkpoint_name(&before);
+        res.pre_snapshot = Some(before.clone());
+        self.exec(device, &format!("checkpoint {checkpoint}"), creds)
+            .await
+            .context("create checkpoint")?;
+        res.logs.push(format!(
+            "[{}] created checkpoint {}",
+            device.name, checkpoint
+        ));
+
+        let applied = match mode {
+            ConfigMode::Merge => {
+                self.conf(device, &config_lines(snippet), creds)
+                    .await
+                    .map(|reply| {
+                        let mut lines = vec![reply.summary()];
+                        lines.extend(reply.command_summaries(device.name.as_str()));
+                        lines
+                    })
+            }
+            ConfigMode::Replace => self
+                .exec(
+                    device,
+                    &format!("rollback running-config file {REPLACE_FILE}"),
+                    creds,
+                )
+                .await
+                .map(|output| vec![format!("[{}] {}", device.name, output.trim())]),
+        };
+        let applied_logs = match applied {
+            Ok(lines) => lines,
+            Err(err) => {
+                warn!(
+                    target: "drivers::nxos",
+                    "{} config failed, rolling back to {}: {}",
+                    device.name,
+                    checkpoint,
+                    err
+                );
+                let restore = format!("rollback running-config checkpoint {checkpoint}");
+                if let Err(rollback_err) = self.exec(device, &restore, creds).await {
+                    warn!(target: "drivers::nxos", "{} rollback failed: {}", device.name, rollback_err);
+                }
+                self.delete_checkpoint(device, &checkpoint, creds).await;
+                return Err(err);
+            }
+        };
+        res.logs.extend(applied_logs);
+        res.logs.push(format!(
+            "[{}] {} NX-OS config via REST ({} lines)",
+            device.name,
+            match mode {
+                ConfigMode::Merge => "applied",
+                ConfigMode::Replace => "replaced",
+            },
+            snippet.lines().count()
+        ));
+
+        let after = self.run_show(device, "show running-config", creds).await?;
+        res.post_snapshot = Some(after.clone());
+        res.diff = Some(config_tree::render_diff(&before, &after));
+
+        for stale in self.list_checkpoints(device, creds).await? {
+            if stale.starts_with(CHECKPOINT_PREFIX) && stale != checkpoint {
+                self.delete_checkpoint(device, &stale, creds).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Full replace: writes the intended config to bootflash through the NX-API bash shell
+    /// (needs `feature bash-shell`), previews it with `show diff rollback-patch` on dry runs
+    /// and otherwise applies it behind a checkpoint. The staged file is always deleted.
+    async fn replace_config(
+        &self,
+        device: &Device,
+        config: &str,
+        dry_run: bool,
+        creds: &(String, String),
+        res: &mut DriverExecutionResult,
+    ) -> Result<()> {
+        let staging = format!(
+            "echo {} | base64 -d > /bootflash/{}",
+            STANDARD.encode(config),
+            REPLACE_FILE.trim_start_matches("bootflash:")
+        );
+        self.post(device, ins_api("bash", &staging), creds)
+            .await
+            .context("stage replacement config")?;
+        let outcome = if dry_run {
+            self.run_show(
+                device,
+                &format!("show diff rollback-patch running-config file {REPLACE_FILE}"),
+                creds,
+            )
+            .await
+            .map(|patch| {
+                res.diff = Some(patch);
+                res.logs.push(format!(
+                    "[{}] dry run: rollback patch for {} captured",
+                    device.name, REPLACE_FILE
+                ));
+            })
+        } else {
+            self.apply_config(device, config, ConfigMode::Replace, creds, res)
+                .await
+        };
+        if let Err(err) = self
+            .exec(device, &format!("delete {REPLACE_FILE} no-prompt"), creds)
+            .await
+        {
+            warn!(target: "drivers::nxos", "{} removing {} failed: {}", device.name, REPLACE_FILE, err);
+        }
+        outcome
+    }
+
+    /// Dry run: stages the snippet in a config session, runs `verify`, reports the session
+    /// contents and aborts it.
+    async fn verify_config(
+        &self,
+        device: &Device,
+        snippet: &str,
+        creds: &(String, String),
+        res: &mut DriverExecutionResult,
+    ) -> Result<()> {
+        let session = format!("{CHECKPOINT_PREFIX}{:x}", fnv1a(snippet.as_bytes()));
+        let mut staged = vec![format!("configure session {session}")];
+        staged.extend(config_lines(snippet));
+        staged.push("verify".into());
+        let verified = self.conf(device, &staged, creds).await;
+        let contents = match verified {
+            Ok(_) => {
+                self.run_show(
+                    device,
+                    &format!("show configuration session {session}"),
+                    creds,
+                )
+                .await
+            }
+            Err(err) => Err(err),
+        };
+        let abort = [format!("configure session {session}"), "abort".into()];
+        if let Err(err) = self.conf(device, &abort, creds).await {
+            warn!(target: "drivers::nxos", "{} aborting session {} failed: {}", device.name, session, err);
+        }
+        let contents = contents.context("verify config session")?;
+        res.diff = Some(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with("config session"))
+                .map(|line| format!("+{line}\n"))
+                .collect(),
+        );
+        res.logs.push(format!(
+            "[{}] dry run: session {} verified and aborted",
+            device.name, session
+        ));
+        Ok(())
+    }
+
+    async fn list_checkpoints(
+        &self,
+        device: &Device,
+        creds: &(String, String),
+    ) -> Result<Vec<String>> {
+        let summary = self
+            .run_show(device, "show checkpoint summary", creds)
+            .await?;
+        Ok(parse_checkpoint_summary(&summary))
+    }
+
+    async fn delete_checkpoint(&self, device: &Device, name: &str, creds: &(String, String)) {
+        if let Err(err) = self
+            .conf(device, &[format!("no checkpoint {name}")], creds)
+            .await
+        {
+            warn!(target: "drivers::nxos", "{} deleting checkpoint {} failed: {}", device.name, name, err);
+        }
+    }
+
+    async fn conf(
+        &self,
+        device: &Device,
+        commands: &[String],
+        creds: &(String, String),
+    ) -> Result<NxapiResponse> {
+        self.post(device, ins_api("cli_conf", &commands.join(" ;")), creds)
+            .await
+    }
+
+    /// Exec-mode commands (`checkpoint`, `rollback`) share the `cli_show_ascii` request type.
+    async fn exec(
+        &self,
+        device: &Device,
+        command: &str,
+        creds: &(String, String),
+    ) -> Result<String> {
+        self.run_show(device, command, creds).await
+    }
+
     async fn post(
         &self,
         device: &Device,
         payload: Value,
         creds: &(String, String),
     ) -> Result<NxapiResponse> {
-        let url = format!("https://{}/ins", device.mgmt_address);
+        let url = if device.mgmt_address.starts_with("http://")
+            || device.mgmt_address.starts_with("https://")
+        {
+            format!("{}/ins", device.mgmt_address.trim_end_matches('/'))
+        } else {
+            format!("https://{}/ins", device.mgmt_address)
+        };
         let retry_limit = config::http_retry_limit();
         for attempt in 0..=retry_limit {
-            match self
+            let request = self
                 .client
                 .post(&url)
                 .basic_auth(&creds.0, Some(&creds.1))
-                .json(&payload)
-                .send()
-                .await
-            {
+                .json(&payload);
+            match replay::send(device, request).await {
                 Ok(resp) => {
                     let status = resp.status();
                     let body = resp
@@ -234,21 +514,86 @@ impl CiscoNxosApiDriver {
         command: &str,
         creds: &(String, String),
     ) -> Result<String> {
-        let payload = json!({
-            "ins_api": {
-                "version": "1.2",
-                "type": "cli_show",
-                "chunk": "0",
-                "sid": "1",
-                "input": command,
-                "output_format": "json"
-            }
-        });
-        let reply = self.post(device, payload, creds).await?;
-        Ok(reply.raw)
+        let reply = self
+            .post(device, ins_api("cli_show_ascii", command), creds)
+            .await?;
+        Ok(reply.body_text().unwrap_or(reply.raw))
+    }
+}
+
+const CHECKPOINT_PREFIX: &str = "netrust-";
+const REPLACE_FILE: &str = "bootflash:netrust-replace.cfg";
+
+/// Getter commands as `cli_show` (JSON) or `cli_show_ascii` (text) requests.
+struct NxapiRunner<'a> {
+    driver: &'a CiscoNxosApiDriver,
+    device: &'a Device,
+    creds: &'a (String, String),
+}
+
+#[async_trait]
+impl CommandRunner for NxapiRunner<'_> {
+    async fn text(&self, command: &str) -> Result<String> {
+        self.driver.run_show(self.device, command, self.creds).await
+    }
+
+    async fn json(&self, command: &str) -> Result<Value> {
+        let reply = self
+            .driver
+            .post(self.device, ins_api("cli_show", command), self.creds)
+            .await?;
+        reply
+            .body()
+            .with_context(|| format!("NX-API returned no body for '{}'", command))
     }
 }
 
+fn ins_api(kind: &str, input: &str) -> Value {
+    json!({
+        "ins_api": {
+            "version": "1.2",
+            "type": kind,
+            "chunk": "0",
+            "sid": "1",
+            "input": input,
+            "output_format": "json"
+        }
+    })
+}
+
+/// Checkpoint named after the pre-change running-config, so `rollback` can find it again
+/// from the snapshot alone.
+fn checkpoint_name(snapshot: &str) -> String {
+    format!("{CHECKPOINT_PREFIX}{:016x}", fnv1a(snapshot.as_bytes()))
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn config_lines(snippet: &str) -> Vec<String> {
+    snippet
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('!'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Names from `show checkpoint summary`, whose entries start with `N) <name>:`.
+fn parse_checkpoint_summary(summary: &str) -> Vec<String> {
+    summary
+        .lines()
+        .filter_map(|line| {
+            let (index, rest) = line.trim().split_once(") ")?;
+            index.parse::<u32>().ok()?;
+            Some(rest.trim_end_matches(':').trim().to_string())
+        })
+        .collect()
+}
+
 struct NxapiResponse {
     raw: String,
     parsed: NxapiEnvelope,
@@ -266,6 +611,28 @@ impl NxapiResponse {
     fn command_summaries(&self, device: &str) -> Vec<String> {
         self.parsed.ins_api.outputs.command_messages(device)
     }
+
+    /// Body of the first output: a JSON object for `cli_show`, text for `cli_show_ascii`.
+    fn body(&self) -> Option<Value> {
+        self.parsed
+            .ins_api
+            .outputs
+            .output
+            .first()
+            .and_then(|o| o.body.clone())
+    }
+
+    fn body_text(&self) -> Option<String> {
+        let text: String = self
+            .parsed
+            .ins_api
+            .outputs
+            .output
+            .iter()
+            .filter_map(|o| o.body.as_ref().and_then(Value::as_str))
+            .collect();
+        (!text.is_empty()).then_some(text)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -287,10 +654,27 @@ struct NxapiInner {
 
 #[derive(Debug, Deserialize)]
 struct NxapiOutputs {
-    #[serde(default)]
+    #[serde(default, deserialize_with = "one_or_many")]
     output: Vec<NxapiOutput>,
 }
 
+/// NX-API returns a bare object for single-command requests and an array otherwise.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<NxapiOutput>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(NxapiOutput),
+        Many(Vec<NxapiOutput>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(output) => vec![output],
+        OneOrMany::Many(outputs) => outputs,
+    })
+}
+
 impl NxapiOutputs {
     fn is_success(&self) -> bool {
         self.output.iter().all(|o| o.is_success())
@@ -320,6 +704,7 @@ impl NxapiOutputs {
 struct NxapiOutput {
     code: Option<String>,
     msg: Option<String>,
+    body: Option<Value>,
 }
 
 impl NxapiOutput {
@@ -331,18 +716,267 @@ impl NxapiOutput {
     }
 }
 
-fn render_diff(before: &str, after: &str) -> String {
-    let diff = TextDiff::from_lines(before, after);
-    let mut buf = String::new();
-    for change in diff.iter_all_changes().take(200) {
-        let sign = match change.tag() {
-            similar::ChangeTag::Delete => "-",
-            similar::ChangeTag::Insert => "+",
-            similar::ChangeTag::Equal => " ",
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{
+        http::{spawn_http, HttpResponse},
+        use_fallback_credentials,
+    };
+    use nauto_model::CredentialRef;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Switch {
+        running: Vec<String>,
+        checkpoints: BTreeMap<String, Vec<String>>,
+        session: Option<Vec<String>>,
+        files: BTreeMap<String, String>,
+        requests: Vec<String>,
+    }
+
+    /// Minimal NX-API endpoint: `/ins` with running-config, checkpoints and one config
+    /// session held in memory. Commands containing `invalid` are rejected.
+    async fn spawn_nxapi(running: &[&str]) -> (String, Arc<Mutex<Switch>>) {
+        let state = Arc::new(Mutex::new(Switch {
+            running: running.iter().map(|line| line.to_string()).collect(),
+            ..Default::default()
+        }));
+        let server_state = state.clone();
+        let address = spawn_http(move |request| {
+            let request = request.json();
+            let kind = request["ins_api"]["type"].as_str().unwrap_or_default();
+            let input = request["ins_api"]["input"].as_str().unwrap_or_default();
+            let outputs = handle(&mut server_state.lock().unwrap(), kind, input);
+            HttpResponse::json(json!({ "ins_api": { "outputs": { "output": outputs } } }))
+        })
+        .await;
+        (address, state)
+    }
+
+    fn handle(switch: &mut Switch, kind: &str, input: &str) -> Value {
+        switch.requests.push(format!("{kind}: {input}"));
+        let ok = |body: String| json!({ "code": "200", "msg": "Success", "body": body });
+        let error = json!({ "code": "400", "msg": "CLI execution error" });
+        if kind == "bash" {
+            let (encoded, path) = input
+                .strip_prefix("echo ")
+                .and_then(|rest| rest.split_once(" | base64 -d > /bootflash/"))
+                .unwrap();
+            let content = String::from_utf8(STANDARD.decode(encoded).unwrap()).unwrap();
+            switch.files.insert(format!("bootflash:{path}"), content);
+            return ok(String::new());
+        }
+        if kind == "cli_show_ascii" {
+            return if input == "show running-config" {
+                ok(switch.running.join("\n"))
+            } else if input == "show checkpoint summary" {
+                let summary = switch
+                    .checkpoints
+                    .keys()
+                    .enumerate()
+                    .map(|(idx, name)| format!("{}) {}:\nCreated by admin\n", idx + 1, name))
+                    .collect();
+                ok(summary)
+            } else if let Some(name) = input.strip_prefix("checkpoint ") {
+                let saved = switch.running.clone();
+                switch.checkpoints.insert(name.to_string(), saved);
+                ok(String::new())
+            } else if let Some(name) = input.strip_prefix("rollback running-config checkpoint ") {
+                match switch.checkpoints.get(name) {
+                    Some(saved) => {
+                        switch.running = saved.clone();
+                        ok(String::new())
+                    }
+                    None => error,
+                }
+            } else if let Some(file) =
+                input.strip_prefix("show diff rollback-patch running-config file ")
+            {
+                let target: Vec<&str> = switch.files[file].lines().collect();
+                let mut patch = String::new();
+                for line in &switch.running {
+                    if !target.contains(&line.as_str()) {
+                        patch.push_str(&format!("no {line}\n"));
+                    }
+                }
+                for line in &target {
+                    if !switch.running.iter().any(|existing| existing == line) {
+                        patch.push_str(&format!("{line}\n"));
+                    }
+                }
+                ok(patch)
+            } else if let Some(file) = input.strip_prefix("rollback running-config file ") {
+                switch.running = switch.files[file].lines().map(str::to_string).collect();
+                ok("Rollback completed successfully.".into())
+            } else if let Some(file) = input
+                .strip_prefix("delete ")
+                .and_then(|rest| rest.strip_suffix(" no-prompt"))
+            {
+                switch.files.remove(file);
+                ok(String::new())
+            } else if input.starts_with("show configuration session ") {
+                let staged = switch.session.clone().unwrap_or_default();
+                ok(format!("config session netrust\n{}", staged.join("\n")))
+            } else {
+                error
+            };
+        }
+
+        // Single-command replies are a bare object, like the real NX-API.
+        let commands: Vec<&str> = input.split(" ;").map(str::trim).collect();
+        let mut outputs = Vec::new();
+        for command in &commands {
+            if command.starts_with("configure session ") {
+                switch.session.get_or_insert_with(Vec::new);
+            } else if *command == "abort" {
+                switch.session = None;
+            } else if *command == "verify" {
+            } else if let Some(name) = command.strip_prefix("no checkpoint ") {
+                switch.checkpoints.remove(name);
+            } else if command.contains("invalid") {
+                outputs.push(error.clone());
+                break;
+            } else if let Some(session) = switch.session.as_mut() {
+                session.push(command.to_string());
+            } else {
+                switch.running.push(command.to_string());
+            }
+            outputs.push(ok(String::new()));
+        }
+        match outputs.len() {
+            1 => outputs.remove(0),
+            _ => Value::Array(outputs),
+        }
+    }
+
+    fn device(address: &str) -> Device {
+        use_fallback_credentials();
+        Device {
+            id: "n9k1".into(),
+            name: "n9k1".into(),
+            device_type: DeviceType::CiscoNxosApi,
+            mgmt_address: address.into(),
+            credential: CredentialRef {
+                name: "nxos-lab".into(),
+            },
+            tags: vec![],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn push_checkpoints_prunes_and_rolls_back() {
+        let (address, state) = spawn_nxapi(&["hostname n9k1"]).await;
+        let device = device(&address);
+        state
+            .lock()
+            .unwrap()
+            .checkpoints
+            .insert("netrust-stale".into(), vec![]);
+        let driver = CiscoNxosApiDriver::default();
+        let job = JobKind::ConfigPush {
+            snippet: "vlan 20\n  name users".into(),
+            mode: ConfigMode::Merge,
+        };
+
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        let pre = res.pre_snapshot.clone().unwrap();
+        assert!(res.diff.unwrap().contains("+vlan 20"));
+        {
+            let switch = state.lock().unwrap();
+            assert_eq!(switch.running, ["hostname n9k1", "vlan 20", "name users"]);
+            let names: Vec<_> = switch.checkpoints.keys().cloned().collect();
+            assert_eq!(names, [checkpoint_name(&pre)]);
+        }
+
+        driver.rollback(&device, Some(pre)).await.unwrap();
+        assert_eq!(state.lock().unwrap().running, ["hostname n9k1"]);
+
+        let bad = JobKind::ConfigPush {
+            snippet: "vlan 30\ninvalid command".into(),
+            mode: ConfigMode::Merge,
+        };
+        let err = driver
+            .execute(&device, DriverAction::Job(&bad), &JobContext::default())
+            .await;
+        assert!(err.is_err());
+        let switch = state.lock().unwrap();
+        assert_eq!(switch.running, ["hostname n9k1"]);
+        assert!(switch
+            .requests
+            .iter()
+            .any(|req| req.starts_with("cli_show_ascii: rollback running-config checkpoint")));
+    }
+
+    #[tokio::test]
+    async fn dry_run_verifies_session_without_touching_running() {
+        let (address, state) = spawn_nxapi(&["hostname n9k1"]).await;
+        let device = device(&address);
+        let job = JobKind::ConfigPush {
+            snippet: "interface Ethernet1/1\n  description uplink".into(),
+            mode: ConfigMode::Merge,
         };
-        buf.push_str(sign);
-        buf.push_str(change.to_string().trim_end());
-        buf.push('\n');
+        let ctx = JobContext {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        let res = CiscoNxosApiDriver::default()
+            .execute(&device, DriverAction::Job(&job), &ctx)
+            .await
+            .unwrap();
+        assert_eq!(
+            res.diff.as_deref(),
+            Some("+interface Ethernet1/1\n+description uplink\n")
+        );
+        let switch = state.lock().unwrap();
+        assert_eq!(switch.running, ["hostname n9k1"]);
+        assert!(switch.session.is_none());
+        assert!(switch.checkpoints.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replace_previews_patch_then_swaps_running_config() {
+        let (address, state) = spawn_nxapi(&["hostname n9k1", "vlan 10"]).await;
+        let device = device(&address);
+        let driver = CiscoNxosApiDriver::default();
+        let job = JobKind::ConfigPush {
+            snippet: "hostname n9k1\nvlan 20".into(),
+            mode: ConfigMode::Replace,
+        };
+
+        let preview = driver
+            .execute(
+                &device,
+                DriverAction::Job(&job),
+                &JobContext {
+                    dry_run: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(preview.diff.as_deref(), Some("no vlan 10\nvlan 20\n"));
+        {
+            let switch = state.lock().unwrap();
+            assert_eq!(switch.running, ["hostname n9k1", "vlan 10"]);
+            assert!(switch.files.is_empty());
+            assert!(switch.checkpoints.is_empty());
+        }
+
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        assert!(res.diff.unwrap().contains("-vlan 10"));
+        let switch = state.lock().unwrap();
+        assert_eq!(switch.running, ["hostname n9k1", "vlan 20"]);
+        assert!(switch.files.is_empty());
+        assert_eq!(switch.checkpoints.len(), 1);
     }
-    buf
 }
@@ -0,0 +1,568 @@
+use crate::{
+    backup, config, config_tree, parsing, preflight, replay, ssh::default_credential_store,
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext, Transport,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use nauto_model::{
+    CapabilitySet, ConfigMode, Credential, Device, DeviceType, JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
+use reqwest::{Client as HttpClient, Method};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// FortiGate over the FortiOS REST API with an API token. Snapshots are full-config
+/// backups; rollback restores one, which makes the FortiGate reboot.
+#[derive(Clone)]
+pub struct FortinetFortiosDriver {
+    credential_store: Arc<dyn CredentialStore>,
+    http: HttpClient,
+}
+
+impl Default for FortinetFortiosDriver {
+    fn default() -> Self {
+        let http = HttpClient::builder()
+            .timeout(config::http_timeout())
+            .build()
+            .expect("FortiOS reqwest client");
+        Self {
+            credential_store: default_credential_store(),
+            http,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for FortinetFortiosDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::FortinetFortios
+    }
+
+    fn name(&self) -> &'static str {
+        "Fortinet FortiOS"
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        CapabilitySet {
+            supports_commit: false,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: false,
+            supports_replace: false,
+        }
+    }
+
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Http]
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        _ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        let api = self.session(device).await?;
+        let mut res = DriverExecutionResult::default();
+        match action {
+            DriverAction::Job(JobKind::CommandBatch { commands }) => {
+                for cmd in commands {
+                    let path = format!("/api/v2/{}", cmd.trim().trim_start_matches('/'));
+                    let reply = api.json(Method::GET, &path, None).await?;
+                    let summary = match reply.get("results") {
+                        Some(Value::Array(items)) => format!("{} results", items.len()),
+                        Some(other) => summarize(&other.to_string()),
+                        None => summarize(&reply.to_string()),
+                    };
+                    res.logs
+                        .push(format!("[{}] GET {} => {}", device.name, path, summary));
+                    res.outputs.push(parsing::json_output(cmd, reply));
+                }
+            }
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                let changes = parse_changes(snippet)?;
+                let before = api.backup().await?;
+                res.pre_snapshot = Some(before.clone());
+                let mut applied = Vec::new();
+                for change in &changes {
+                    let path = format!("/api/v2/cmdb/{}", change.path.trim_matches('/'));
+                    let call = format!("{} {}", change.method, path);
+                    if let Err(err) = api
+                        .json(change.method.clone(), &path, change.body.as_ref())
+                        .await
+                    {
+                        let err = err.context(call);
+                        if applied.is_empty() {
+                            return Err(err);
+                        }
+                        return Err(self.undo_partial_push(device, before, &applied, err).await);
+                    }
+                    res.logs.push(format!("[{}] {}", device.name, call));
+                    applied.push(call);
+                }
+                let after = api.backup().await?;
+                res.diff = Some(config_tree::render_diff(&before, &after));
+                res.post_snapshot = Some(after);
+            }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => {
+                let snapshot = backup::snapshot(&api.backup().await?, None);
+                res.logs.push(backup::describe(device, &snapshot));
+                if *include_startup {
+                    res.logs.push(format!(
+                        "[{}] no separate startup config on FortiOS",
+                        device.name
+                    ));
+                }
+                res.backup = Some(snapshot);
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
+            DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
+                res.logs.push(format!(
+                    "[{}] evaluated {} compliance rules",
+                    device.name,
+                    rules.len()
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let login = async {
+            let api = self.session(device).await?;
+            api.json(Method::GET, "/api/v2/monitor/system/status", None)
+                .await
+                .map(drop)
+        };
+        Ok(preflight::http(&base_url(device), login).await)
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        warn!(
+            target: "drivers::fortios",
+            "Restoring {} byte backup on {}; the FortiGate reboots to apply it",
+            snapshot.len(),
+            device.name
+        );
+        let api = self.session(device).await?;
+        let payload = json!({
+            "source": "upload",
+            "scope": "global",
+            "file_content": STANDARD.encode(snapshot.as_bytes()),
+        });
+        api.json(
+            Method::POST,
+            "/api/v2/monitor/system/config/restore",
+            Some(&payload),
+        )
+        .await?;
+        info!(target: "drivers::fortios", "{} restore accepted", device.name);
+        Ok(())
+    }
+}
+
+impl FortinetFortiosDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    /// A CMDB change failed after `applied` ones went through: restores the pre-push backup
+    /// so the push does not stay half applied. The FortiGate reboots, as for a rollback; if
+    /// the restore fails too, the error lists the changes left on the device.
+    async fn undo_partial_push(
+        &self,
+        device: &Device,
+        before: String,
+        applied: &[String],
+        err: anyhow::Error,
+    ) -> anyhow::Error {
+        let applied = applied.join(", ");
+        match self.rollback(device, Some(before)).await {
+            Ok(()) => err.context(format!(
+                "push to {} failed; restored the pre-push backup to undo {applied}",
+                device.name
+            )),
+            Err(restore) => {
+                warn!(
+                    target: "drivers::fortios",
+                    "{} restore after failed push failed: {restore:#}",
+                    device.name
+                );
+                err.context(format!(
+                    "push to {} failed and restoring the pre-push backup failed ({restore:#}); \
+                     still applied: {applied}",
+                    device.name
+                ))
+            }
+        }
+    }
+
+    async fn session<'a>(&'a self, device: &'a Device) -> Result<FortiosApi<'a>> {
+        let credential = replay::resolve_credential(&self.credential_store, device)
+            .await
+            .with_context(|| format!("loading credential {}", device.credential.name))?;
+        // A password is not an API token: sending it as a bearer token fails every request
+        // and puts the password in an Authorization header.
+        let Credential::Token { token } = credential else {
+            bail!(
+                "FortiOS device {} needs an API token credential; {} is not a Token \
+                 (create a REST API admin on the FortiGate and store its token)",
+                device.name,
+                device.credential.name
+            );
+        };
+        let vdom = device
+            .tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix("vdom:").map(str::to_string));
+        Ok(FortiosApi {
+            http: &self.http,
+            device,
+            base: base_url(device),
+            token,
+            vdom,
+        })
+    }
+}
+
+/// One CMDB request in a ConfigPush snippet (YAML or JSON list), e.g.
+/// `{method: PUT, path: firewall/address/web01, body: {subnet: 10.0.0.10/32}}`.
+#[derive(Debug, Clone, Deserialize)]
+struct CmdbChange {
+    #[serde(deserialize_with = "method_from_str")]
+    method: Method,
+    path: String,
+    #[serde(default)]
+    body: Option<Value>,
+}
+
+fn method_from_str<'de, D>(deserializer: D) -> Result<Method, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    match raw.to_ascii_uppercase().as_str() {
+        "POST" => Ok(Method::POST),
+        "PUT" => Ok(Method::PUT),
+        "DELETE" => Ok(Method::DELETE),
+        other => Err(serde::de::Error::custom(format!(
+            "unsupported CMDB method {other}"
+        ))),
+    }
+}
+
+fn parse_changes(snippet: &str) -> Result<Vec<CmdbChange>> {
+    serde_yaml::from_str(snippet).context("parse FortiOS CMDB change list")
+}
+
+struct FortiosApi<'a> {
+    http: &'a HttpClient,
+    device: &'a Device,
+    base: String,
+    token: String,
+    vdom: Option<String>,
+}
+
+impl FortiosApi<'_> {
+    /// Full configuration as the CLI-format backup file.
+    async fn backup(&self) -> Result<String> {
+        self.send(
+            Method::GET,
+            "/api/v2/monitor/system/config/backup?scope=global",
+            None,
+        )
+        .await
+    }
+
+    async fn json(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Value> {
+        let text = self.send(method, path, body).await?;
+        let reply: Value = serde_json::from_str(&text)
+            .with_context(|| format!("parse FortiOS response {} {}", self.device.name, path))?;
+        if reply.get("status").and_then(Value::as_str) == Some("error") {
+            bail!("FortiOS {} {} error: {}", self.device.name, path, reply);
+        }
+        Ok(reply)
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<&Value>) -> Result<String> {
+        let url = format!("{}{}", self.base, path);
+        let device = &self.device.name;
+        let retry_limit = config::http_retry_limit();
+        for attempt in 0..=retry_limit {
+            let mut request = self
+                .http
+                .request(method.clone(), &url)
+                .bearer_auth(&self.token);
+            if let Some(vdom) = &self.vdom {
+                request = request.query(&[("vdom", vdom)]);
+            }
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            match replay::send(self.device, request).await {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let text = resp.text().await.context("FortiOS API payload")?;
+                    if !status.is_success() {
+                        bail!("FortiOS {} {} returned {}: {}", device, path, status, text);
+                    }
+                    return Ok(text);
+                }
+                Err(err) if attempt < retry_limit => {
+                    warn!(
+                        target: "drivers::fortios",
+                        "retrying FortiOS {} attempt {} due to {}",
+                        device,
+                        attempt + 1,
+                        err
+                    );
+                    tokio::time::sleep(Duration::from_millis(200 * (attempt as u64 + 1))).await;
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("FortiOS {} {} request", device, path))
+                }
+            }
+        }
+        unreachable!("FortiOS retry loop should have returned")
+    }
+}
+
+fn base_url(device: &Device) -> String {
+    if device.mgmt_address.starts_with("http://") || device.mgmt_address.starts_with("https://") {
+        device.mgmt_address.trim_end_matches('/').to_string()
+    } else {
+        format!("https://{}", device.mgmt_address)
+    }
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{
+        http::{spawn_http, HttpResponse},
+        use_fallback_credentials,
+    };
+    use nauto_model::CredentialRef;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct FortiGate {
+        addresses: BTreeMap<String, String>,
+        restored: Option<String>,
+        requests: Vec<String>,
+    }
+
+    impl FortiGate {
+        fn backup(&self) -> String {
+            let mut text = String::from("#config-version=FGT60F\nconfig firewall address\n");
+            for (name, subnet) in &self.addresses {
+                text.push_str(&format!(
+                    "    edit \"{name}\"\n        set subnet {subnet}\n    next\n"
+                ));
+            }
+            text.push_str("end\n");
+            text
+        }
+    }
+
+    /// REST stand-in: backup renders the address table, CMDB PUT/POST edit it (except for
+    /// `broken`, which fails with a 500) and a restore records the uploaded file.
+    async fn spawn_fortigate(state: Arc<Mutex<FortiGate>>) -> String {
+        spawn_http(move |request| {
+            let mut fgt = state.lock().unwrap();
+            assert_eq!(
+                request.header("Authorization"),
+                Some("Bearer fortios-test-token")
+            );
+            assert_eq!(request.param("vdom").as_deref(), Some("root"));
+            fgt.requests
+                .push(format!("{} {}", request.method, request.path));
+            let path = request
+                .path
+                .split('?')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            match (request.method.as_str(), path.as_str()) {
+                ("GET", "/api/v2/monitor/system/config/backup") => {
+                    HttpResponse::status(200, fgt.backup())
+                }
+                ("PUT", "/api/v2/cmdb/firewall/address/broken") => HttpResponse::status(
+                    500,
+                    json!({ "status": "error", "http_status": 500, "error": -651 }).to_string(),
+                ),
+                ("PUT" | "POST", path) if path.starts_with("/api/v2/cmdb/firewall/address/") => {
+                    let name = path.rsplit('/').next().unwrap().to_string();
+                    let subnet = request.json()["subnet"].as_str().unwrap_or("").to_string();
+                    fgt.addresses.insert(name, subnet);
+                    HttpResponse::json(json!({ "status": "success", "http_status": 200 }))
+                }
+                ("POST", "/api/v2/monitor/system/config/restore") => {
+                    let body = request.json();
+                    assert_eq!(body["source"], "upload");
+                    let file = STANDARD
+                        .decode(body["file_content"].as_str().unwrap())
+                        .unwrap();
+                    fgt.restored = Some(String::from_utf8(file).unwrap());
+                    HttpResponse::json(json!({ "status": "success" }))
+                }
+                ("GET", "/api/v2/cmdb/firewall/address") => HttpResponse::json(json!({
+                    "status": "success",
+                    "results": fgt.addresses.keys().collect::<Vec<_>>(),
+                })),
+                _ => HttpResponse::json(json!({ "status": "error", "error": -3 })),
+            }
+        })
+        .await
+    }
+
+    fn device(base: String) -> Device {
+        use_fallback_credentials();
+        Device {
+            id: "fgt1".into(),
+            name: "fgt-branch".into(),
+            device_type: DeviceType::FortinetFortios,
+            mgmt_address: base,
+            credential: CredentialRef {
+                name: "fortios-lab".into(),
+            },
+            tags: vec!["vdom:root".into()],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_credentials_other_than_api_tokens() {
+        let mut device = device("http://127.0.0.1:9".into());
+        device.credential.name = "lab".into();
+        let err = FortinetFortiosDriver::default()
+            .execute(
+                &device,
+                DriverAction::Job(&JobKind::CommandBatch {
+                    commands: vec!["monitor/system/status".into()],
+                }),
+                &JobContext::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("needs an API token credential"));
+    }
+
+    #[tokio::test]
+    async fn push_diffs_backups_and_rollback_restores_snapshot() {
+        let state = Arc::new(Mutex::new(FortiGate::default()));
+        state
+            .lock()
+            .unwrap()
+            .addresses
+            .insert("web01".into(), "10.0.0.10 255.255.255.255".into());
+        let device = device(spawn_fortigate(state.clone()).await);
+        let driver = FortinetFortiosDriver::default();
+
+        let job = JobKind::ConfigPush {
+            snippet: "- method: PUT\n  path: firewall/address/web01\n  body: {subnet: 10.0.0.11 255.255.255.255}\n".into(),
+            mode: ConfigMode::Merge,
+        };
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        let diff = res.diff.unwrap();
+        assert!(diff.contains("-        set subnet 10.0.0.10 255.255.255.255"));
+        assert!(diff.contains("+        set subnet 10.0.0.11 255.255.255.255"));
+
+        let pre = res.pre_snapshot.unwrap();
+        driver.rollback(&device, Some(pre.clone())).await.unwrap();
+        assert_eq!(
+            state.lock().unwrap().restored.as_deref(),
+            Some(pre.as_str())
+        );
+
+        let listing = JobKind::CommandBatch {
+            commands: vec!["cmdb/firewall/address".into(), "cmdb/bogus".into()],
+        };
+        let err = driver
+            .execute(&device, DriverAction::Job(&listing), &JobContext::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cmdb/bogus"));
+        assert!(state
+            .lock()
+            .unwrap()
+            .requests
+            .contains(&"GET /api/v2/cmdb/firewall/address?vdom=root".to_string()));
+    }
+
+    #[tokio::test]
+    async fn failed_change_restores_the_pre_push_backup() {
+        let state = Arc::new(Mutex::new(FortiGate::default()));
+        state
+            .lock()
+            .unwrap()
+            .addresses
+            .insert("web01".into(), "10.0.0.10 255.255.255.255".into());
+        let before = state.lock().unwrap().backup();
+        let device = device(spawn_fortigate(state.clone()).await);
+
+        let job = JobKind::ConfigPush {
+            snippet: "- method: PUT\n  path: firewall/address/web01\n  body: {subnet: 10.0.0.11 255.255.255.255}\n\
+                      - method: PUT\n  path: firewall/address/broken\n  body: {subnet: 10.0.0.12 255.255.255.255}\n"
+                .into(),
+            mode: ConfigMode::Merge,
+        };
+        let err = FortinetFortiosDriver::default()
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap_err();
+        let message = format!("{err:#}");
+        assert!(
+            message.contains("to undo PUT /api/v2/cmdb/firewall/address/web01"),
+            "{message}"
+        );
+        assert!(
+            message.contains("PUT /api/v2/cmdb/firewall/address/broken"),
+            "{message}"
+        );
+
+        // The first change went through, and the restore puts the backup taken before it back.
+        let fgt = state.lock().unwrap();
+        assert_eq!(fgt.addresses["web01"], "10.0.0.11 255.255.255.255");
+        assert_eq!(fgt.restored.as_deref(), Some(before.as_str()));
+    }
+}
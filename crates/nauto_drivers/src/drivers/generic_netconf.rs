@@ -0,0 +1,530 @@
+use crate::{
+    backup, config, config_tree,
+    netconf::{
+        self, Datastore, DefaultOperation, EditContent, NetconfSession, CAP_CANDIDATE,
+        CAP_CONFIRMED_COMMIT, CAP_STARTUP, CAP_VALIDATE, CAP_WRITABLE_RUNNING,
+    },
+    preflight,
+    ssh::{default_credential_store, DEFAULT_NETCONF_PORT},
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext, Transport,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{
+    CapabilitySet, CommandOutput, ConfigMode, Device, DeviceType, JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Clone)]
+pub struct GenericNetconfDriver {
+    credential_store: Arc<dyn CredentialStore>,
+    port: u16,
+}
+
+impl Default for GenericNetconfDriver {
+    fn default() -> Self {
+        Self {
+            credential_store: default_credential_store(),
+            port: DEFAULT_NETCONF_PORT,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for GenericNetconfDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Netconf
+    }
+
+    fn name(&self) -> &'static str {
+        "Generic NETCONF"
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        CapabilitySet {
+            supports_commit: true,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: false,
+            supports_replace: false,
+        }
+    }
+
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Netconf]
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        _ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        let mut session = NetconfSession::connect(device, &self.credential_store, self.port)
+            .await
+            .with_context(|| format!("open netconf session {}", device.name))?;
+        let result = match action {
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                self.apply_config(&mut session, device, snippet).await
+            }
+            DriverAction::Job(JobKind::CommandBatch { commands }) => {
+                run_rpcs(&mut session, device, commands).await
+            }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => backup_config(&mut session, device, *include_startup).await,
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
+            DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
+                let mut res = DriverExecutionResult::default();
+                res.logs.push(format!(
+                    "[{}] NETCONF compliance check {} rules",
+                    device.name,
+                    rules.len()
+                ));
+                Ok(res)
+            }
+        };
+        let _ = session.close_session().await;
+        result
+    }
+
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        Ok(preflight::ssh(device, &self.credential_store, self.port).await)
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        let Some(snapshot) = snapshot else {
+            info!(
+                target: "drivers::netconf",
+                "Rollback requested for {} but no snapshot was provided",
+                device.name
+            );
+            return Ok(());
+        };
+        info!(
+            target: "drivers::netconf",
+            "Rollback {} to pre-change snapshot ({} bytes)",
+            device.name,
+            snapshot.len()
+        );
+        let mut session = NetconfSession::connect(device, &self.credential_store, self.port)
+            .await
+            .context("open netconf for rollback")?;
+        let content = EditContent::Xml(snapshot);
+        if session.supports(CAP_CANDIDATE) {
+            session.lock(Datastore::Candidate).await?;
+            let outcome = async {
+                session
+                    .edit_config(
+                        Datastore::Candidate,
+                        &content,
+                        Some(DefaultOperation::Replace),
+                    )
+                    .await?;
+                session.commit().await
+            }
+            .await;
+            release_candidate(&mut session, device, outcome.is_err()).await;
+            outcome?;
+        } else {
+            session
+                .edit_config(
+                    Datastore::Running,
+                    &content,
+                    Some(DefaultOperation::Replace),
+                )
+                .await?;
+        }
+        let _ = session.close_session().await;
+        Ok(())
+    }
+}
+
+impl GenericNetconfDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    async fn apply_config(
+        &self,
+        session: &mut NetconfSession,
+        device: &Device,
+        snippet: &str,
+    ) -> Result<DriverExecutionResult> {
+        let content = EditContent::Xml(payload_to_xml(snippet)?);
+        let mut res = DriverExecutionResult {
+            pre_snapshot: Some(session.get_config(Datastore::Running, None).await?),
+            ..Default::default()
+        };
+
+        if session.supports(CAP_CANDIDATE) {
+            session.lock(Datastore::Candidate).await?;
+            let outcome = commit_candidate(session, device, &content, &mut res).await;
+            release_candidate(session, device, outcome.is_err()).await;
+            outcome?;
+        } else if session.supports(CAP_WRITABLE_RUNNING) {
+            session.lock(Datastore::Running).await?;
+            let outcome = session
+                .edit_config(Datastore::Running, &content, Some(DefaultOperation::Merge))
+                .await;
+            if let Err(err) = session.unlock(Datastore::Running).await {
+                warn!(target: "drivers::netconf", "{} unlock running failed: {err}", device.name);
+            }
+            outcome?;
+            res.logs.push(format!(
+                "[{}] edit-config applied to running datastore",
+                device.name
+            ));
+        } else {
+            bail!(
+                "{} advertises neither :candidate nor :writable-running",
+                device.name
+            );
+        }
+
+        res.post_snapshot = Some(session.get_config(Datastore::Running, None).await?);
+        if let (Some(pre), Some(post)) = (res.pre_snapshot.as_ref(), res.post_snapshot.as_ref()) {
+            res.diff = Some(config_tree::line_diff(
+                &netconf::pretty_xml(pre),
+                &netconf::pretty_xml(post),
+            ));
+        }
+        Ok(res)
+    }
+}
+
+async fn commit_candidate(
+    session: &mut NetconfSession,
+    device: &Device,
+    content: &EditContent,
+    res: &mut DriverExecutionResult,
+) -> Result<()> {
+    let reply = session
+        .edit_config(Datastore::Candidate, content, Some(DefaultOperation::Merge))
+        .await?;
+    for warning in reply.warnings() {
+        warn!(target: "drivers::netconf", "{} edit-config warning: {}", device.name, warning);
+    }
+    res.logs.push(format!(
+        "[{}] edit-config loaded into candidate",
+        device.name
+    ));
+
+    if session.supports(CAP_VALIDATE) {
+        session.validate(Datastore::Candidate).await?;
+        res.logs
+            .push(format!("[{}] candidate validated", device.name));
+    }
+
+    match config::commit_confirm_timeout().filter(|_| session.supports(CAP_CONFIRMED_COMMIT)) {
+        Some(timeout) => {
+            session.commit_confirmed(timeout).await?;
+            res.logs.push(format!(
+                "[{}] commit confirmed pending ({}s window)",
+                device.name,
+                timeout.as_secs()
+            ));
+            if let Err(err) = post_check(session, content).await {
+                if let Err(cancel_err) = session.cancel_commit().await {
+                    warn!(
+                        target: "drivers::netconf",
+                        "{} cancel-commit failed; the server reverts when the confirm timeout expires: {cancel_err}",
+                        device.name
+                    );
+                }
+                return Err(err.context("post-commit check failed; confirmed commit cancelled"));
+            }
+            session.commit().await?;
+            res.logs.push(format!("[{}] commit confirmed", device.name));
+        }
+        None => {
+            session.commit().await?;
+            res.logs.push(format!("[{}] commit complete", device.name));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every leaf value of the pushed payload shows up at the same element path in
+/// the new running config, before the confirmed commit is confirmed.
+async fn post_check(session: &mut NetconfSession, content: &EditContent) -> Result<()> {
+    let running = session.get_config(Datastore::Running, None).await?;
+    let EditContent::Xml(payload) = content else {
+        return Ok(());
+    };
+    let present = netconf::leaf_values(&running);
+    let missing: Vec<String> = netconf::leaf_values(payload)
+        .into_iter()
+        .filter(|(path, text)| {
+            !present.iter().any(|(running_path, running_text)| {
+                running_text == text
+                    && (running_path == path || running_path.ends_with(&format!("/{path}")))
+            })
+        })
+        .map(|(path, text)| format!("{path}={text}"))
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "running config is missing pushed values: {}",
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// `<running/>`, plus `<startup/>` when asked for and the server has a startup datastore.
+async fn backup_config(
+    session: &mut NetconfSession,
+    device: &Device,
+    include_startup: bool,
+) -> Result<DriverExecutionResult> {
+    let running = session.get_config(Datastore::Running, None).await?;
+    let mut res = DriverExecutionResult::default();
+    let startup = if include_startup && session.supports(CAP_STARTUP) {
+        Some(session.get_config(Datastore::Startup, None).await?)
+    } else {
+        if include_startup {
+            res.logs
+                .push(format!("[{}] server has no startup datastore", device.name));
+        }
+        None
+    };
+    let snapshot = backup::snapshot(&running, startup.as_deref());
+    res.logs.insert(0, backup::describe(device, &snapshot));
+    res.backup = Some(snapshot);
+    Ok(res)
+}
+
+async fn release_candidate(session: &mut NetconfSession, device: &Device, discard: bool) {
+    let released = if discard {
+        session.discard_and_unlock().await
+    } else {
+        session.unlock(Datastore::Candidate).await.map(|_| ())
+    };
+    if let Err(err) = released {
+        warn!(target: "drivers::netconf", "{} releasing candidate failed: {err}", device.name);
+    }
+}
+
+/// `CommandBatch` entries are raw RPC bodies (e.g. `<get-schema>...`), sent verbatim.
+async fn run_rpcs(
+    session: &mut NetconfSession,
+    device: &Device,
+    commands: &[String],
+) -> Result<DriverExecutionResult> {
+    let mut res = DriverExecutionResult::default();
+    for cmd in commands {
+        let reply = session
+            .rpc(cmd.trim())
+            .await
+            .with_context(|| format!("netconf rpc {} {}", device.name, cmd))?;
+        let output = reply.data.unwrap_or_else(|| {
+            if reply.ok {
+                "ok".into()
+            } else {
+                reply.raw.clone()
+            }
+        });
+        res.logs.push(format!(
+            "[{}] {} => {}",
+            device.name,
+            cmd,
+            summarize(&output)
+        ));
+        res.outputs.push(CommandOutput {
+            command: cmd.clone(),
+            raw: output,
+            parsed: None,
+        });
+    }
+    Ok(res)
+}
+
+/// Accepts either an XML config body or a JSON document mapped through `netconf::json_to_xml`.
+fn payload_to_xml(snippet: &str) -> Result<String> {
+    let trimmed = snippet.trim();
+    if trimmed.starts_with('<') {
+        return Ok(trimmed.to_string());
+    }
+    if trimmed.starts_with('{') {
+        let value: serde_json::Value =
+            serde_json::from_str(trimmed).context("parse JSON config payload")?;
+        return netconf::json_to_xml(&value);
+    }
+    bail!("NETCONF config payload must be XML or a JSON object")
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::netconf::NetconfStandIn;
+    use nauto_model::CredentialRef;
+
+    fn device(address: String) -> Device {
+        Device {
+            id: "xe1".into(),
+            name: "edge-xe1".into(),
+            device_type: DeviceType::Netconf,
+            mgmt_address: address,
+            credential: CredentialRef { name: "lab".into() },
+            tags: vec![],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    fn push(snippet: &str) -> JobKind {
+        JobKind::ConfigPush {
+            snippet: snippet.into(),
+            mode: ConfigMode::Merge,
+        }
+    }
+
+    #[tokio::test]
+    async fn candidate_push_commits_and_rolls_back() {
+        let standin = NetconfStandIn::spawn(
+            &[CAP_CANDIDATE, CAP_VALIDATE, CAP_CONFIRMED_COMMIT],
+            "<hostname>xe1</hostname>",
+        )
+        .await;
+        let device = device(standin.address().into());
+        let driver = GenericNetconfDriver::default();
+
+        let job = push("<ntp><server>10.0.0.1</server></ntp>");
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            standin.datastores().running,
+            "<hostname>xe1</hostname><ntp><server>10.0.0.1</server></ntp>"
+        );
+        assert!(res.diff.unwrap().contains("+<ntp>"));
+        assert!(res
+            .logs
+            .iter()
+            .any(|line| line.ends_with("commit confirmed")));
+        assert!(standin
+            .datastores()
+            .rpcs
+            .iter()
+            .any(|rpc| rpc.contains("<confirmed/>")));
+
+        driver.rollback(&device, res.pre_snapshot).await.unwrap();
+        assert_eq!(standin.datastores().running, "<hostname>xe1</hostname>");
+    }
+
+    #[tokio::test]
+    async fn confirmed_commit_is_cancelled_when_pushed_values_are_missing() {
+        let standin = NetconfStandIn::spawn(
+            &[CAP_CANDIDATE, CAP_CONFIRMED_COMMIT],
+            "<hostname>xe1</hostname>",
+        )
+        .await;
+        let driver = GenericNetconfDriver::default();
+
+        let job = push("<ntp><server>10.0.0.1</server><discarded><key>7</key></discarded></ntp>");
+        let err = driver
+            .execute(
+                &device(standin.address().into()),
+                DriverAction::Job(&job),
+                &JobContext::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("missing pushed values: ntp/discarded/key=7"));
+        let datastores = standin.datastores();
+        assert_eq!(datastores.running, "<hostname>xe1</hostname>");
+        assert!(datastores.rpcs.iter().any(|rpc| rpc == "<cancel-commit/>"));
+        assert!(!datastores.rpcs.iter().any(|rpc| rpc == "<commit/>"));
+    }
+
+    #[tokio::test]
+    async fn rpc_error_discards_and_unlocks_candidate() {
+        let standin = NetconfStandIn::spawn(&[CAP_CANDIDATE], "<hostname>xe1</hostname>").await;
+        let driver = GenericNetconfDriver::default();
+
+        let job = push("<invalid/>");
+        let err = driver
+            .execute(
+                &device(standin.address().into()),
+                DriverAction::Job(&job),
+                &JobContext::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid-value"));
+
+        let state = standin.datastores();
+        assert_eq!(state.running, "<hostname>xe1</hostname>");
+        let tail: Vec<&str> = state
+            .rpcs
+            .iter()
+            .rev()
+            .take(3)
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            tail,
+            [
+                "<close-session/>",
+                "<unlock><target><candidate/></target></unlock>",
+                "<discard-changes/>"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn json_payload_written_to_running_without_candidate() {
+        let standin = NetconfStandIn::spawn(&[CAP_WRITABLE_RUNNING], "").await;
+        let driver = GenericNetconfDriver::default();
+
+        let job = push(r#"{"system": {"@xmlns": "urn:example:system", "hostname": "xr1"}}"#);
+        driver
+            .execute(
+                &device(standin.address().into()),
+                DriverAction::Job(&job),
+                &JobContext::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            standin.datastores().running,
+            r#"<system xmlns="urn:example:system"><hostname>xr1</hostname></system>"#
+        );
+    }
+
+    #[test]
+    fn summaries_cut_multibyte_replies_on_char_boundaries() {
+        let reply = "é".repeat(150);
+        let summary = summarize(&reply);
+        assert!(summary.ends_with('…'));
+        assert_eq!(summary.len(), 200 + '…'.len_utf8());
+    }
+}
@@ -1,18 +1,20 @@
+use crate::ssh::Client;
 use crate::{
+    parsing, preflight, preview,
     ssh::{self, default_credential_store, DEFAULT_SSH_PORT},
-    DeviceDriver, DriverAction, DriverExecutionResult,
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext,
 };
 use anyhow::{bail, Context, Result};
-use async_ssh2_tokio::Client;
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Device, DeviceType, JobKind};
-use nauto_security::KeyringStore;
+use nauto_model::{CapabilitySet, ConfigMode, Device, DeviceType, JobKind, PreflightReport};
+use nauto_security::CredentialStore;
+use std::sync::Arc;
 
 const MAX_LOG_BYTES: usize = 512;
 
 #[derive(Clone)]
 pub struct GenericSshDriver {
-    credential_store: KeyringStore,
+    credential_store: Arc<dyn CredentialStore>,
     port: u16,
 }
 
@@ -40,14 +42,20 @@ impl DeviceDriver for GenericSshDriver {
             supports_commit: false,
             supports_rollback: false,
             supports_diff: false,
-            supports_dry_run: false,
+            supports_dry_run: true,
+            supports_replace: false,
         }
     }
 
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         let client = ssh::connect(device, &self.credential_store, self.port).await?;
 
@@ -55,9 +63,36 @@ impl DeviceDriver for GenericSshDriver {
             DriverAction::Job(JobKind::CommandBatch { commands }) => {
                 self.run_command_batch(&client, device, commands).await
             }
-            DriverAction::Job(JobKind::ConfigPush { snippet }) => {
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) if ctx.dry_run => {
+                let running = exec_and_check(&client, device, "show running-config")
+                    .await
+                    .context("dry run reads show running-config to predict the diff")?;
+                let mut res = DriverExecutionResult::default();
+                preview::predict(device, running, snippet, &mut res);
+                Ok(res)
+            }
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
                 self.push_snippet(&client, device, snippet).await
             }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup { .. }) => {
+                bail!("{} does not support configuration backups", self.name())
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
             DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
                 let mut res = DriverExecutionResult::default();
                 res.logs.push(format!(
@@ -70,12 +105,22 @@ impl DeviceDriver for GenericSshDriver {
         }
     }
 
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        Ok(preflight::ssh(device, &self.credential_store, self.port).await)
+    }
+
     async fn rollback(&self, _device: &Device, _snapshot: Option<String>) -> Result<()> {
         Ok(())
     }
 }
 
 impl GenericSshDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
     async fn run_command_batch(
         &self,
         client: &Client,
@@ -91,6 +136,8 @@ impl GenericSshDriver {
                 cmd,
                 summarize(&stdout)
             ));
+            res.outputs
+                .push(parsing::command_output(&device.device_type, cmd, stdout));
         }
         Ok(res)
     }
@@ -137,13 +184,8 @@ async fn exec_and_check(client: &Client, device: &Device, command: &str) -> Resu
 }
 
 fn summarize(output: &str) -> String {
-    let trimmed = output.trim();
-    if trimmed.is_empty() {
+    if output.trim().is_empty() {
         return "ok".into();
     }
-    if trimmed.len() > MAX_LOG_BYTES {
-        format!("{}…", &trimmed[..MAX_LOG_BYTES])
-    } else {
-        trimmed.to_string()
-    }
+    super::clip(output, MAX_LOG_BYTES)
 }
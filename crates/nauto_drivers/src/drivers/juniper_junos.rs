@@ -1,25 +1,23 @@
 use crate::{
+    backup, config, config_tree,
+    getters::{self, SshRunner},
+    netconf::{self, Datastore, DefaultOperation, EditContent, NetconfSession, RpcReply},
+    parsing, preflight,
     ssh::{self, default_credential_store, DEFAULT_NETCONF_PORT, DEFAULT_SSH_PORT},
-    DeviceDriver, DriverAction, DriverExecutionResult,
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext, Transport,
 };
 use anyhow::{bail, Context, Result};
-use async_ssh2_tokio::Client;
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Device, DeviceType, JobKind};
-use nauto_security::KeyringStore;
-use similar::TextDiff;
-use std::pin::Pin;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use tracing::info;
-
-const NETCONF_EOM: &str = "]]>]]>";
-
-trait NetconfIo: AsyncRead + AsyncWrite + Send + Unpin {}
-impl<T> NetconfIo for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
+use nauto_model::{
+    CapabilitySet, ConfigMode, Device, DeviceType, Getter, JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
+use std::sync::Arc;
+use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct JuniperJunosDriver {
-    credential_store: KeyringStore,
+    credential_store: Arc<dyn CredentialStore>,
     port: u16,
 }
 
@@ -48,21 +46,46 @@ impl DeviceDriver for JuniperJunosDriver {
             supports_rollback: true,
             supports_diff: true,
             supports_dry_run: true,
+            supports_replace: true,
         }
     }
 
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Netconf, Transport::Ssh]
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         match action {
-            DriverAction::Job(JobKind::ConfigPush { snippet }) => {
-                self.apply_config(device, snippet).await
+            DriverAction::Job(JobKind::ConfigPush { snippet, mode }) => {
+                self.apply_config(device, snippet, *mode, ctx).await
             }
             DriverAction::Job(JobKind::CommandBatch { commands }) => {
                 self.run_operational_commands(device, commands).await
             }
+            DriverAction::Job(JobKind::Getters { which }) => {
+                self.collect_getters(device, which).await
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => self.backup_config(device, *include_startup).await,
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
             DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
                 let mut res = DriverExecutionResult::default();
                 res.logs.push(format!(
@@ -75,78 +98,108 @@ impl DeviceDriver for JuniperJunosDriver {
         }
     }
 
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        Ok(preflight::ssh(device, &self.credential_store, self.port).await)
+    }
+
     async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
         info!(
             target: "drivers::juniper",
-            "rollback on {} to snapshot {:?}",
+            "rollback on {} ({} byte snapshot)",
             device.name,
-            snapshot
+            snapshot.as_ref().map(String::len).unwrap_or(0)
         );
-        if let Some(snapshot) = snapshot {
-            let mut session = NetconfSession::connect(device, &self.credential_store, self.port)
-                .await
-                .context("open netconf for rollback")?;
-            session
-                .rpc(&format!(
-                    "<load-configuration action=\"override\"><configuration-text><![CDATA[{snapshot}]]></configuration-text></load-configuration>"
-                ))
-                .await?;
-            session.rpc("<commit/>").await?;
-        }
-        Ok(())
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        let mut session = NetconfSession::connect(device, &self.credential_store, self.port)
+            .await
+            .context("open netconf for rollback")?;
+        session.lock(Datastore::Candidate).await?;
+        let outcome = load_rollback(&mut session, device, &snapshot).await;
+        release_candidate(&mut session, device, outcome.is_err()).await;
+        let _ = session.close_session().await;
+        outcome
     }
 }
 
 impl JuniperJunosDriver {
-    async fn apply_config(&self, device: &Device, snippet: &str) -> Result<DriverExecutionResult> {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    async fn apply_config(
+        &self,
+        device: &Device,
+        snippet: &str,
+        mode: ConfigMode,
+        ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
         let mut session =
             NetconfSession::connect(device, &self.credential_store, self.port).await?;
+        let mut res = DriverExecutionResult {
+            pre_snapshot: Some(committed_config(&mut session).await?),
+            ..Default::default()
+        };
+        session.lock(Datastore::Candidate).await?;
+        let outcome = load_and_commit(&mut session, device, snippet, mode, ctx, &mut res).await;
+        release_candidate(&mut session, device, outcome.is_err() || ctx.dry_run).await;
+        outcome?;
+
+        if !ctx.dry_run {
+            res.post_snapshot = Some(committed_config(&mut session).await?);
+            if let (Some(pre), Some(post)) = (res.pre_snapshot.as_ref(), res.post_snapshot.as_ref())
+            {
+                res.diff = Some(config_tree::render_diff(pre, post));
+            }
+        }
+        let _ = session.close_session().await;
+        Ok(res)
+    }
+
+    /// Committed configuration over NETCONF; Junos boots from it, so there is no separate
+    /// startup configuration to fetch.
+    async fn backup_config(
+        &self,
+        device: &Device,
+        include_startup: bool,
+    ) -> Result<DriverExecutionResult> {
+        let mut session =
+            NetconfSession::connect(device, &self.credential_store, self.port).await?;
+        let running = committed_config(&mut session).await;
+        let _ = session.close_session().await;
+        let snapshot = backup::snapshot(&running?, None);
         let mut res = DriverExecutionResult::default();
-        res.pre_snapshot = Some(
-            session
-                .rpc("<get-config><source><running/></source></get-config>")
-                .await?,
-        );
-        session
-            .rpc("<lock><target><candidate/></target></lock>")
-            .await?;
-        let payload = format!(
-            "<edit-config>\
-                <target><candidate/></target>\
-                <default-operation>merge</default-operation>\
-                <config>\
-                    <configuration-text>\
-                        <![CDATA[{snippet}]]>\
-                    </configuration-text>\
-                </config>\
-            </edit-config>"
-        );
-        session.rpc(&payload).await?;
+        res.logs.push(backup::describe(device, &snapshot));
+        if include_startup {
+            res.logs.push(format!(
+                "[{}] no separate startup config on Junos",
+                device.name
+            ));
+        }
+        res.backup = Some(snapshot);
+        Ok(res)
+    }
+
+    /// Getters run over the CLI with `| display json` rather than NETCONF RPCs.
+    async fn collect_getters(
+        &self,
+        device: &Device,
+        which: &[Getter],
+    ) -> Result<DriverExecutionResult> {
+        let client = ssh::connect(device, &self.credential_store, DEFAULT_SSH_PORT).await?;
+        let runner = SshRunner::new(&client, device).with_json_suffix("| display json");
+        let mut res = DriverExecutionResult {
+            getters: Some(getters::junos::collect(&runner, which).await?),
+            ..Default::default()
+        };
         res.logs.push(format!(
-            "[{}] loaded snippet ({} lines)",
+            "[{}] collected {}",
             device.name,
-            snippet.lines().count()
+            getters::describe(which)
         ));
-
-        session
-            .rpc("<validate><source><candidate/></source></validate>")
-            .await?;
-        res.logs
-            .push(format!("[{}] commit check passed", device.name));
-        session.rpc("<commit/>").await?;
-        res.logs.push(format!("[{}] commit complete", device.name));
-        session
-            .rpc("<unlock><target><candidate/></target></unlock>")
-            .await?;
-
-        res.post_snapshot = Some(
-            session
-                .rpc("<get-config><source><running/></source></get-config>")
-                .await?,
-        );
-        if let (Some(pre), Some(post)) = (res.pre_snapshot.as_ref(), res.post_snapshot.as_ref()) {
-            res.diff = Some(render_diff(pre, post));
-        }
         Ok(res)
     }
 
@@ -176,119 +229,265 @@ impl JuniperJunosDriver {
                 cmd,
                 truncate(result.stdout.trim())
             ));
+            res.outputs.push(parsing::command_output(
+                &device.device_type,
+                cmd,
+                result.stdout,
+            ));
         }
         Ok(res)
     }
 }
 
-struct NetconfSession {
-    #[allow(dead_code)]
-    client: Client,
-    stream: Pin<Box<dyn NetconfIo>>,
-    next_id: u32,
-}
-
-impl NetconfSession {
-    async fn connect(device: &Device, store: &KeyringStore, port: u16) -> Result<NetconfSession> {
-        let client = ssh::connect(device, store, port).await?;
-        let channel = client
-            .get_channel()
-            .await
-            .with_context(|| format!("netconf channel {}", device.name))?;
-        channel
-            .request_subsystem(true, "netconf")
-            .await
-            .context("netconf subsystem denied")?;
-        let stream = channel.into_stream();
-        let mut session = NetconfSession {
-            client,
-            stream: Box::pin(stream),
-            next_id: 1,
-        };
-        session.send_hello().await?;
-        Ok(session)
+async fn load_and_commit(
+    session: &mut NetconfSession,
+    device: &Device,
+    snippet: &str,
+    mode: ConfigMode,
+    ctx: &JobContext,
+    res: &mut DriverExecutionResult,
+) -> Result<()> {
+    let reply = match mode {
+        ConfigMode::Merge => {
+            session
+                .edit_config(
+                    Datastore::Candidate,
+                    &EditContent::Text(snippet.to_string()),
+                    Some(DefaultOperation::Merge),
+                )
+                .await?
+        }
+        ConfigMode::Replace => load_override(session, snippet).await?,
+    };
+    for warning in reply.warnings() {
+        warn!(target: "drivers::juniper", "{} load warning: {}", device.name, warning);
     }
+    res.logs.push(format!(
+        "[{}] loaded {} ({} lines)",
+        device.name,
+        match mode {
+            ConfigMode::Merge => "snippet",
+            ConfigMode::Replace => "full configuration with load override",
+        },
+        snippet.lines().count()
+    ));
+
+    session.validate(Datastore::Candidate).await?;
+    res.logs
+        .push(format!("[{}] commit check passed", device.name));
 
-    async fn send_hello(&mut self) -> Result<()> {
-        let hello = r#"<?xml version="1.0" encoding="UTF-8"?>
-<hello xmlns="urn:ietf:params:xml:ns:netconf:base:1.0">
-  <capabilities>
-    <capability>urn:ietf:params:netconf:base:1.0</capability>
-  </capabilities>
-</hello>]]>]]>"#;
-        self.stream.as_mut().write_all(hello.as_bytes()).await?;
-        self.stream.as_mut().flush().await?;
-        let _server = self.read_reply().await?;
-        Ok(())
+    if ctx.dry_run {
+        // Equivalent of `show | compare` against the active configuration.
+        let reply = session
+            .rpc(r#"<get-configuration compare="rollback" rollback="0" format="text"/>"#)
+            .await?;
+        res.diff =
+            Some(netconf::element_text(&reply.raw, "configuration-output").unwrap_or_default());
+        res.logs.push(format!(
+            "[{}] dry run: candidate diff captured and discarded",
+            device.name
+        ));
+        return Ok(());
     }
 
-    async fn rpc(&mut self, inner: &str) -> Result<String> {
-        let message_id = self.next_id;
-        self.next_id += 1;
-        let payload = format!(
-            r#"<?xml version="1.0" encoding="UTF-8"?><rpc message-id="{message_id}" xmlns="urn:ietf:params:xml:ns:netconf:base:1.0">{inner}</rpc>{NETCONF_EOM}"#
-        );
-        self.stream
-            .as_mut()
-            .write_all(payload.as_bytes())
-            .await
-            .context("write netconf rpc")?;
-        self.stream.as_mut().flush().await?;
-        let reply = self.read_reply().await?;
-        if !reply.contains("<ok/>") && reply.contains("<rpc-error>") {
-            bail!("netconf error: {}", reply);
+    let comment = commit_comment(ctx);
+    match config::commit_confirm_timeout() {
+        Some(timeout) => {
+            let minutes = timeout.as_secs().div_ceil(60).max(1);
+            commit(session, &comment, Some(minutes)).await?;
+            res.logs.push(format!(
+                "[{}] commit confirmed pending ({} min window)",
+                device.name, minutes
+            ));
+            // Post-check: the device must still answer on the same session after the commit.
+            committed_config(session).await?;
+            commit(session, &comment, None).await?;
+            res.logs.push(format!("[{}] commit confirmed", device.name));
+        }
+        None => {
+            commit(session, &comment, None).await?;
+            res.logs.push(format!("[{}] commit complete", device.name));
         }
-        Ok(reply)
     }
+    Ok(())
+}
 
-    async fn read_reply(&mut self) -> Result<String> {
-        let mut buf = Vec::new();
-        loop {
-            let mut chunk = vec![0u8; 4096];
-            let read = self
-                .stream
-                .as_mut()
-                .read(&mut chunk)
-                .await
-                .context("read netconf frame")?;
-            if read == 0 {
-                bail!("netconf stream closed");
-            }
-            buf.extend_from_slice(&chunk[..read]);
-            if buf.len() >= NETCONF_EOM.len()
-                && buf[buf.len() - NETCONF_EOM.len()..] == NETCONF_EOM.as_bytes()[..]
-            {
-                break;
-            }
+async fn commit(
+    session: &mut NetconfSession,
+    comment: &str,
+    confirm_minutes: Option<u64>,
+) -> Result<()> {
+    let confirmed = confirm_minutes
+        .map(|minutes| format!("<confirmed/><confirm-timeout>{minutes}</confirm-timeout>"))
+        .unwrap_or_default();
+    session
+        .rpc(&format!(
+            "<commit-configuration>{confirmed}<log>{}</log></commit-configuration>",
+            netconf::escape_text(comment)
+        ))
+        .await?;
+    Ok(())
+}
+
+fn commit_comment(ctx: &JobContext) -> String {
+    match (ctx.job_id, ctx.job_name.is_empty()) {
+        (Some(id), false) => format!("netrust job {id} ({})", ctx.job_name),
+        (Some(id), true) => format!("netrust job {id}"),
+        (None, _) => "netrust".into(),
+    }
+}
+
+async fn load_rollback(
+    session: &mut NetconfSession,
+    device: &Device,
+    snapshot: &str,
+) -> Result<()> {
+    let history = session.rpc("<get-commit-information/>").await;
+    let rollback_id = match history {
+        Ok(reply) => {
+            last_commit_stamp(snapshot).and_then(|stamp| rollback_id_for(&reply.raw, &stamp))
+        }
+        Err(err) => {
+            warn!(target: "drivers::juniper", "{} commit history unavailable: {err}", device.name);
+            None
+        }
+    };
+    match rollback_id {
+        Some(id) => {
+            info!(target: "drivers::juniper", "{} loading rollback {}", device.name, id);
+            session
+                .rpc(&format!(r#"<load-configuration rollback="{id}"/>"#))
+                .await?;
+        }
+        None => {
+            info!(
+                target: "drivers::juniper",
+                "{} pre-change commit not in history, overriding with snapshot text",
+                device.name
+            );
+            load_override(session, snapshot).await?;
         }
-        buf.truncate(buf.len() - NETCONF_EOM.len());
-        let reply = String::from_utf8(buf).context("netconf not utf8")?;
-        Ok(reply)
     }
+    commit(session, "netrust rollback", None).await
 }
 
-fn truncate(s: &str) -> String {
-    if s.len() > 200 {
-        format!("{}…", &s[..200])
-    } else if s.is_empty() {
-        "ok".into()
+/// `load override`: the candidate becomes exactly `config_text`.
+async fn load_override(session: &mut NetconfSession, config_text: &str) -> Result<RpcReply> {
+    session
+        .rpc(&format!(
+            r#"<load-configuration action="override" format="text"><configuration-text>{}</configuration-text></load-configuration>"#,
+            netconf::escape_text(config_text)
+        ))
+        .await
+}
+
+/// Reads the committed configuration as text, which starts with its `## Last commit:` header.
+async fn committed_config(session: &mut NetconfSession) -> Result<String> {
+    let reply = session
+        .rpc(r#"<get-configuration database="committed" format="text"/>"#)
+        .await?;
+    netconf::element_text(&reply.raw, "configuration-text")
+        .context("get-configuration reply without configuration-text")
+}
+
+async fn release_candidate(session: &mut NetconfSession, device: &Device, discard: bool) {
+    let released = if discard {
+        session.discard_and_unlock().await
     } else {
-        s.to_string()
+        session.unlock(Datastore::Candidate).await.map(|_| ())
+    };
+    if let Err(err) = released {
+        warn!(target: "drivers::juniper", "{} releasing candidate failed: {err}", device.name);
+    }
+}
+
+fn last_commit_stamp(config_text: &str) -> Option<String> {
+    config_text.lines().take(5).find_map(|line| {
+        let rest = line.trim().strip_prefix("## Last commit:")?;
+        let stamp = rest.split(" by ").next()?.trim();
+        (!stamp.is_empty()).then(|| stamp.to_string())
+    })
+}
+
+/// Finds the `<rollback>` id whose commit timestamp matches the snapshot's header.
+fn rollback_id_for(commit_information: &str, stamp: &str) -> Option<u32> {
+    netconf::elements(commit_information, "commit-history")
+        .into_iter()
+        .find_map(|entry| {
+            let when = netconf::element_text(&entry, "date-time")?;
+            if when.trim() != stamp {
+                return None;
+            }
+            netconf::element_text(&entry, "sequence-number")?
+                .trim()
+                .parse()
+                .ok()
+        })
+}
+
+fn truncate(s: &str) -> String {
+    if s.is_empty() {
+        return "ok".into();
     }
+    super::clip(s, 200)
 }
 
-fn render_diff(before: &str, after: &str) -> String {
-    let diff = TextDiff::from_lines(before, after);
-    let mut buf = String::new();
-    for change in diff.iter_all_changes().take(200) {
-        let sign = match change.tag() {
-            similar::ChangeTag::Delete => "-",
-            similar::ChangeTag::Insert => "+",
-            similar::ChangeTag::Equal => " ",
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMIT_INFORMATION: &str = r#"<rpc-reply xmlns:junos="http://xml.juniper.net/junos/22.4R1/junos">
+<commit-information>
+  <commit-history>
+    <sequence-number>0</sequence-number>
+    <user>netrust</user>
+    <client>netconf</client>
+    <date-time junos:seconds="1767225600">2026-01-01 00:00:00 UTC</date-time>
+    <log>netrust job 1f0e</log>
+  </commit-history>
+  <commit-history>
+    <sequence-number>1</sequence-number>
+    <user>admin</user>
+    <client>cli</client>
+    <date-time junos:seconds="1767139200">2025-12-31 00:00:00 UTC</date-time>
+  </commit-history>
+</commit-information>
+</rpc-reply>"#;
+
+    #[test]
+    fn truncates_multibyte_replies_on_a_char_boundary() {
+        // Byte 200 falls inside the 100th `é`.
+        let reply = format!("a{}", "é".repeat(150));
+        let line = truncate(&reply);
+        assert_eq!(line, format!("a{}…", "é".repeat(99)));
+        assert_eq!(truncate(""), "ok");
+    }
+
+    #[test]
+    fn resolves_rollback_id_from_snapshot_header() {
+        let snapshot = "## Last commit: 2025-12-31 00:00:00 UTC by admin\nversion 22.4R1;\nsystem {\n    host-name r1;\n}\n";
+        let stamp = last_commit_stamp(snapshot).expect("header");
+        assert_eq!(stamp, "2025-12-31 00:00:00 UTC");
+        assert_eq!(rollback_id_for(COMMIT_INFORMATION, &stamp), Some(1));
+        assert_eq!(
+            rollback_id_for(COMMIT_INFORMATION, "2020-01-01 00:00:00 UTC"),
+            None
+        );
+        assert_eq!(last_commit_stamp("system {\n}\n"), None);
+    }
+
+    #[test]
+    fn commit_comment_carries_job_id() {
+        let id = uuid::Uuid::nil();
+        let ctx = JobContext {
+            job_id: Some(id),
+            job_name: "ntp rollout".into(),
+            dry_run: false,
         };
-        buf.push_str(sign);
-        buf.push_str(change.to_string().trim_end());
-        buf.push('\n');
+        assert_eq!(
+            commit_comment(&ctx),
+            format!("netrust job {id} (ntp rollout)")
+        );
+        assert_eq!(commit_comment(&JobContext::default()), "netrust");
     }
-    buf
 }
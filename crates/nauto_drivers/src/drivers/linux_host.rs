@@ -0,0 +1,675 @@
+use crate::ssh::Client;
+use crate::{
+    config_tree, parsing, preflight,
+    ssh::{self, default_credential_store, DEFAULT_SSH_PORT},
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{CapabilitySet, ConfigMode, Device, DeviceType, JobKind, PreflightReport};
+use nauto_security::CredentialStore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Exit status the read helper uses for "file does not exist".
+const MISSING_FILE_STATUS: u32 = 44;
+
+/// Linux hosts and Linux-based network OSes (SONiC, Cumulus): commands with exit-code
+/// checks, and config pushes as whole-file replacement plus validate/reload commands.
+#[derive(Clone)]
+pub struct LinuxHostDriver {
+    credential_store: Arc<dyn CredentialStore>,
+    port: u16,
+}
+
+impl Default for LinuxHostDriver {
+    fn default() -> Self {
+        Self {
+            credential_store: default_credential_store(),
+            port: DEFAULT_SSH_PORT,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for LinuxHostDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::LinuxHost
+    }
+
+    fn name(&self) -> &'static str {
+        "Linux Host"
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        CapabilitySet {
+            supports_commit: false,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: true,
+            supports_replace: false,
+        }
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        let host = self.connect(device).await?;
+        match action {
+            DriverAction::Job(JobKind::CommandBatch { commands }) => {
+                run_commands(&host, device, commands).await
+            }
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                let push = HostPush::parse(snippet)?;
+                push_files(&host, device, &push, ctx).await
+            }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup { .. }) => {
+                bail!("{} does not support configuration backups", self.name())
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
+            DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
+                let mut res = DriverExecutionResult::default();
+                res.logs.push(format!(
+                    "[{}] evaluated {} compliance rules",
+                    device.name,
+                    rules.len()
+                ));
+                Ok(res)
+            }
+        }
+    }
+
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        Ok(preflight::ssh(device, &self.credential_store, self.port).await)
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        let snapshot: HostSnapshot =
+            serde_json::from_str(&snapshot).context("parse Linux host snapshot")?;
+        let host = self.connect(device).await?;
+        restore(&host, device, &snapshot, true).await
+    }
+}
+
+impl LinuxHostDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    async fn connect(&self, device: &Device) -> Result<SshHost> {
+        let client = ssh::connect(device, &self.credential_store, self.port).await?;
+        Ok(SshHost {
+            client,
+            device: device.name.clone(),
+            sudo: device.tags.iter().any(|tag| tag == "sudo"),
+        })
+    }
+}
+
+/// ConfigPush snippet (YAML or JSON): files to replace, then commands that must succeed
+/// before (`validate`) and after which (`reload`) the new files count as applied.
+#[derive(Debug, Clone, Deserialize)]
+struct HostPush {
+    #[serde(default)]
+    files: Vec<FileSpec>,
+    #[serde(default)]
+    validate: Vec<String>,
+    #[serde(default)]
+    reload: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FileSpec {
+    path: String,
+    content: String,
+    /// Octal mode for the new file, e.g. "0644". Existing files keep their mode otherwise.
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+impl HostPush {
+    fn parse(snippet: &str) -> Result<Self> {
+        let push: HostPush = serde_yaml::from_str(snippet).context("parse Linux host push")?;
+        if push.files.is_empty() {
+            bail!("Linux host push needs at least one entry under `files`");
+        }
+        for file in &push.files {
+            if !file.path.starts_with('/') {
+                bail!("file path {} must be absolute", file.path);
+            }
+            if let Some(mode) = &file.mode {
+                if mode.is_empty() || !mode.chars().all(|c| ('0'..='7').contains(&c)) {
+                    bail!("file mode {} for {} is not octal", mode, file.path);
+                }
+            }
+        }
+        Ok(push)
+    }
+}
+
+/// Contents of every touched file before (or after) a push. `content: None` means the
+/// file did not exist, so rollback removes it. Reload commands are kept so rollback can
+/// make the restored files take effect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HostSnapshot {
+    files: Vec<FileState>,
+    #[serde(default)]
+    reload: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileState {
+    path: String,
+    content: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ExecOutput {
+    stdout: String,
+    stderr: String,
+    exit_status: u32,
+}
+
+/// What the driver needs from a host; implemented over SSH/SFTP and by test fakes.
+#[async_trait]
+trait HostSession: Send + Sync {
+    async fn exec(&self, command: &str) -> Result<ExecOutput>;
+    async fn read_file(&self, path: &str) -> Result<Option<String>>;
+    /// Writes to a temporary file next to `path` and renames it into place.
+    async fn replace_file(&self, path: &str, content: &str, mode: Option<&str>) -> Result<()>;
+    async fn remove_file(&self, path: &str) -> Result<()>;
+}
+
+struct SshHost {
+    client: Client,
+    device: String,
+    /// `sudo` tag: file operations run through `sudo -n`.
+    sudo: bool,
+}
+
+impl SshHost {
+    fn privileged(&self, script: &str) -> String {
+        if self.sudo {
+            format!("sudo -n sh -c {}", shell_quote(script))
+        } else {
+            script.to_string()
+        }
+    }
+}
+
+#[async_trait]
+impl HostSession for SshHost {
+    async fn exec(&self, command: &str) -> Result<ExecOutput> {
+        let result = tokio::time::timeout(ssh::command_timeout(), self.client.execute(command))
+            .await
+            .with_context(|| format!("ssh exec timeout {} {}", self.device, command))?
+            .with_context(|| format!("ssh exec {} {}", self.device, command))?;
+        Ok(ExecOutput {
+            stdout: result.stdout,
+            stderr: result.stderr,
+            exit_status: result.exit_status,
+        })
+    }
+
+    async fn read_file(&self, path: &str) -> Result<Option<String>> {
+        let path = shell_quote(path);
+        let script = format!("test -e {path} || exit {MISSING_FILE_STATUS}; cat -- {path}");
+        let out = self.exec(&self.privileged(&script)).await?;
+        match out.exit_status {
+            0 => Ok(Some(out.stdout)),
+            MISSING_FILE_STATUS => Ok(None),
+            status => bail!(
+                "reading {} on {} failed (status {}): {}",
+                path,
+                self.device,
+                status,
+                out.stderr.trim()
+            ),
+        }
+    }
+
+    async fn replace_file(&self, path: &str, content: &str, mode: Option<&str>) -> Result<()> {
+        // SFTP lands in /tmp first; the rename happens in the target directory so the
+        // swap is atomic on the destination filesystem.
+        let name = format!(".netrust-{}", Uuid::new_v4().simple());
+        let local = std::env::temp_dir().join(&name);
+        tokio::fs::write(&local, content)
+            .await
+            .with_context(|| format!("staging {} locally", path))?;
+        let staged = format!("/tmp/{name}");
+        let upload = self.client.upload_file(&local, staged.clone()).await;
+        let _ = tokio::fs::remove_file(&local).await;
+        upload.with_context(|| format!("sftp upload {} to {}", path, self.device))?;
+
+        let dest = shell_quote(path);
+        let next = shell_quote(&format!("{path}.netrust-new"));
+        let staged = shell_quote(&staged);
+        let mut script = format!(
+            "set -e; cp -- {staged} {next}; rm -f -- {staged}; \
+             if [ -e {dest} ]; then chmod --reference={dest} {next}; chown --reference={dest} {next} 2>/dev/null || true; fi; "
+        );
+        if let Some(mode) = mode {
+            script.push_str(&format!("chmod {mode} {next}; "));
+        }
+        script.push_str(&format!("mv -f -- {next} {dest}"));
+        let out = self.exec(&self.privileged(&script)).await?;
+        if out.exit_status != 0 {
+            bail!(
+                "replacing {} on {} failed (status {}): {}",
+                path,
+                self.device,
+                out.exit_status,
+                out.stderr.trim()
+            );
+        }
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        let script = format!("rm -f -- {}", shell_quote(path));
+        let out = self.exec(&self.privileged(&script)).await?;
+        if out.exit_status != 0 {
+            bail!(
+                "removing {} on {} failed (status {}): {}",
+                path,
+                self.device,
+                out.exit_status,
+                out.stderr.trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn run_commands(
+    host: &dyn HostSession,
+    device: &Device,
+    commands: &[String],
+) -> Result<DriverExecutionResult> {
+    let mut res = DriverExecutionResult::default();
+    for cmd in commands {
+        let stdout = exec_and_check(host, device, cmd).await?;
+        res.logs.push(format!(
+            "[{}] {} => {}",
+            device.name,
+            cmd,
+            summarize(&stdout)
+        ));
+        res.outputs
+            .push(parsing::command_output(&device.device_type, cmd, stdout));
+    }
+    Ok(res)
+}
+
+async fn push_files(
+    host: &dyn HostSession,
+    device: &Device,
+    push: &HostPush,
+    ctx: &JobContext,
+) -> Result<DriverExecutionResult> {
+    let mut res = DriverExecutionResult::default();
+    let mut before = HostSnapshot {
+        files: Vec::new(),
+        reload: push.reload.clone(),
+    };
+    for file in &push.files {
+        before.files.push(FileState {
+            path: file.path.clone(),
+            content: host.read_file(&file.path).await?,
+        });
+    }
+    let after = HostSnapshot {
+        files: push
+            .files
+            .iter()
+            .map(|file| FileState {
+                path: file.path.clone(),
+                content: Some(file.content.clone()),
+            })
+            .collect(),
+        reload: push.reload.clone(),
+    };
+    res.diff = Some(snapshot_diff(&before, &after));
+    res.pre_snapshot = Some(serde_json::to_string_pretty(&before)?);
+    if ctx.dry_run {
+        res.logs.push(format!(
+            "[{}] dry run: {} files compared, nothing written",
+            device.name,
+            push.files.len()
+        ));
+        return Ok(res);
+    }
+
+    let mut reloading = false;
+    let applied = async {
+        for file in &push.files {
+            host.replace_file(&file.path, &file.content, file.mode.as_deref())
+                .await?;
+            res.logs
+                .push(format!("[{}] replaced {}", device.name, file.path));
+        }
+        for cmd in &push.validate {
+            let stdout = exec_and_check(host, device, cmd)
+                .await
+                .context("validation failed")?;
+            res.logs.push(format!(
+                "[{}] validate {} => {}",
+                device.name,
+                cmd,
+                summarize(&stdout)
+            ));
+        }
+        reloading = true;
+        for cmd in &push.reload {
+            let stdout = exec_and_check(host, device, cmd)
+                .await
+                .context("reload failed")?;
+            res.logs.push(format!(
+                "[{}] reload {} => {}",
+                device.name,
+                cmd,
+                summarize(&stdout)
+            ));
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    if let Err(err) = applied {
+        warn!(
+            target: "drivers::linux",
+            "{} push failed, restoring previous files: {:#}",
+            device.name,
+            err
+        );
+        // Services only need reloading again if they may have picked up the new files.
+        if let Err(restore_err) = restore(host, device, &before, reloading).await {
+            warn!(
+                target: "drivers::linux",
+                "{} restore after failed push also failed: {:#}",
+                device.name,
+                restore_err
+            );
+        }
+        return Err(err);
+    }
+
+    res.post_snapshot = Some(serde_json::to_string_pretty(&after)?);
+    Ok(res)
+}
+
+/// Puts every file in the snapshot back (removing ones that did not exist) and, when
+/// `reload` is set, reruns the reload commands.
+async fn restore(
+    host: &dyn HostSession,
+    device: &Device,
+    snapshot: &HostSnapshot,
+    reload: bool,
+) -> Result<()> {
+    for file in &snapshot.files {
+        match &file.content {
+            Some(content) => host.replace_file(&file.path, content, None).await?,
+            None => host.remove_file(&file.path).await?,
+        }
+    }
+    for cmd in snapshot.reload.iter().filter(|_| reload) {
+        exec_and_check(host, device, cmd).await?;
+    }
+    info!(
+        target: "drivers::linux",
+        "{} restored {} files",
+        device.name,
+        snapshot.files.len()
+    );
+    Ok(())
+}
+
+async fn exec_and_check(host: &dyn HostSession, device: &Device, command: &str) -> Result<String> {
+    let out = host.exec(command).await?;
+    if out.exit_status != 0 {
+        bail!(
+            "command '{}' failed on {} (status {}) stderr: {}",
+            command,
+            device.name,
+            out.exit_status,
+            out.stderr.trim()
+        );
+    }
+    Ok(out.stdout)
+}
+
+fn snapshot_diff(before: &HostSnapshot, after: &HostSnapshot) -> String {
+    let mut buf = String::new();
+    for (old, new) in before.files.iter().zip(&after.files) {
+        let old_text = old.content.as_deref().unwrap_or_default();
+        let new_text = new.content.as_deref().unwrap_or_default();
+        if old.content.is_some() && old_text == new_text {
+            continue;
+        }
+        let label = if old.content.is_none() {
+            "/dev/null"
+        } else {
+            &old.path
+        };
+        buf.push_str(&format!("--- {label}\n+++ {}\n", new.path));
+        buf.push_str(&config_tree::line_diff(old_text, new_text));
+    }
+    buf
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn summarize(output: &str) -> String {
+    if output.trim().is_empty() {
+        return "ok".into();
+    }
+    super::clip(output, 200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nauto_model::CredentialRef;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    /// In-memory host: files in a map, commands answered from a table (unknown ones exit 0).
+    #[derive(Default)]
+    struct FakeHost {
+        files: Mutex<BTreeMap<String, String>>,
+        exits: BTreeMap<String, u32>,
+        read_only: Vec<String>,
+        ran: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HostSession for FakeHost {
+        async fn exec(&self, command: &str) -> Result<ExecOutput> {
+            self.ran.lock().unwrap().push(command.to_string());
+            let exit_status = self.exits.get(command).copied().unwrap_or(0);
+            Ok(ExecOutput {
+                stdout: format!("ran {command}"),
+                stderr: if exit_status == 0 { "" } else { "boom" }.into(),
+                exit_status,
+            })
+        }
+
+        async fn read_file(&self, path: &str) -> Result<Option<String>> {
+            Ok(self.files.lock().unwrap().get(path).cloned())
+        }
+
+        async fn replace_file(&self, path: &str, content: &str, _mode: Option<&str>) -> Result<()> {
+            if self.read_only.iter().any(|read_only| read_only == path) {
+                bail!("{path}: read-only file system");
+            }
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_string(), content.to_string());
+            Ok(())
+        }
+
+        async fn remove_file(&self, path: &str) -> Result<()> {
+            self.files.lock().unwrap().remove(path);
+            Ok(())
+        }
+    }
+
+    fn device() -> Device {
+        Device {
+            id: "leaf1".into(),
+            name: "sonic-leaf1".into(),
+            device_type: DeviceType::LinuxHost,
+            mgmt_address: "192.0.2.10".into(),
+            credential: CredentialRef {
+                name: "sonic-lab".into(),
+            },
+            tags: vec![],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    const PUSH: &str = r#"
+files:
+  - path: /etc/sonic/config_db.json
+    content: "{\"MTU\": 9100}\n"
+  - path: /etc/frr/extra.conf
+    content: "router bgp 65001\n"
+    mode: "0640"
+validate:
+  - sonic-cfggen -j /etc/sonic/config_db.json --print-data
+reload:
+  - config reload -y
+"#;
+
+    fn host_with_config() -> FakeHost {
+        let host = FakeHost::default();
+        host.files.lock().unwrap().insert(
+            "/etc/sonic/config_db.json".into(),
+            "{\"MTU\": 1500}\n".into(),
+        );
+        host
+    }
+
+    #[tokio::test]
+    async fn push_replaces_files_and_rollback_restores_them() {
+        let host = host_with_config();
+        let device = device();
+        let push = HostPush::parse(PUSH).unwrap();
+
+        let res = push_files(&host, &device, &push, &JobContext::default())
+            .await
+            .unwrap();
+        let diff = res.diff.unwrap();
+        assert!(diff.contains("-{\"MTU\": 1500}"));
+        assert!(diff.contains("--- /dev/null\n+++ /etc/frr/extra.conf\n+router bgp 65001"));
+        assert_eq!(
+            host.files.lock().unwrap()["/etc/sonic/config_db.json"],
+            "{\"MTU\": 9100}\n"
+        );
+        assert_eq!(
+            host.ran.lock().unwrap().as_slice(),
+            [
+                "sonic-cfggen -j /etc/sonic/config_db.json --print-data",
+                "config reload -y"
+            ]
+        );
+
+        let snapshot: HostSnapshot = serde_json::from_str(&res.pre_snapshot.unwrap()).unwrap();
+        restore(&host, &device, &snapshot, true).await.unwrap();
+        let files = host.files.lock().unwrap();
+        assert_eq!(files["/etc/sonic/config_db.json"], "{\"MTU\": 1500}\n");
+        assert!(!files.contains_key("/etc/frr/extra.conf"));
+        assert_eq!(host.ran.lock().unwrap().last().unwrap(), "config reload -y");
+    }
+
+    #[tokio::test]
+    async fn failed_validation_restores_files_without_reloading() {
+        let mut host = host_with_config();
+        host.exits.insert(
+            "sonic-cfggen -j /etc/sonic/config_db.json --print-data".into(),
+            2,
+        );
+        let device = device();
+        let push = HostPush::parse(PUSH).unwrap();
+
+        let dry = push_files(
+            &host,
+            &device,
+            &push,
+            &JobContext {
+                dry_run: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(dry.diff.unwrap().contains("+{\"MTU\": 9100}"));
+        assert!(host.ran.lock().unwrap().is_empty());
+
+        let err = push_files(&host, &device, &push, &JobContext::default())
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("status 2"));
+        let files = host.files.lock().unwrap();
+        assert_eq!(files["/etc/sonic/config_db.json"], "{\"MTU\": 1500}\n");
+        assert!(!files.contains_key("/etc/frr/extra.conf"));
+        assert_eq!(
+            host.ran.lock().unwrap().as_slice(),
+            ["sonic-cfggen -j /etc/sonic/config_db.json --print-data"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_write_restores_files_already_replaced() {
+        let mut host = host_with_config();
+        host.read_only.push("/etc/frr/extra.conf".into());
+        let device = device();
+        let push = HostPush::parse(PUSH).unwrap();
+
+        let err = push_files(&host, &device, &push, &JobContext::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        let files = host.files.lock().unwrap();
+        assert_eq!(files["/etc/sonic/config_db.json"], "{\"MTU\": 1500}\n");
+        assert!(!files.contains_key("/etc/frr/extra.conf"));
+        assert!(host.ran.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn quotes_paths_for_the_shell() {
+        assert_eq!(shell_quote("/etc/it's"), r"'/etc/it'\''s'");
+        assert!(HostPush::parse("files: [{path: etc/x, content: y}]").is_err());
+    }
+}
@@ -1,19 +1,32 @@
-use crate::{config, DeviceDriver, DriverAction, DriverExecutionResult};
-use anyhow::{bail, Context, Result};
+use crate::{
+    config, config_tree, parsing, preflight, replay, ssh::default_credential_store, DeviceDriver,
+    DriverAction, DriverExecutionResult, JobContext, Transport,
+};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Credential, Device, DeviceType, JobKind};
-use nauto_security::{CredentialStore, KeyringStore};
-use reqwest::Client;
-use serde_json::{json, Value};
+use nauto_model::{
+    CapabilitySet, ConfigMode, Credential, Device, DeviceType, JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
+use reqwest::{header::HeaderMap, Client, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
 
-const MERAKI_API_BASE: &str = "https://api.meraki.com/api/v1";
-const KEYRING_SERVICE: &str = "netrust";
+/// 429 responses are retried separately from transport errors; the Dashboard rate limit
+/// is per organization, so several back-to-back retries are normal under load.
+const RATE_LIMIT_RETRIES: usize = 5;
+const ACTION_BATCH_POLLS: usize = 30;
+
+/// Drives the Meraki Dashboard API v1. `mgmt_address` holds the network ID; a
+/// `serial:<serial>` tag names the default device for device-scoped operations.
 #[derive(Clone)]
 pub struct MerakiCloudDriver {
     client: Client,
-    credential_store: KeyringStore,
+    credential_store: Arc<dyn CredentialStore>,
+    api_base: String,
 }
 
 impl Default for MerakiCloudDriver {
@@ -24,7 +37,8 @@ impl Default for MerakiCloudDriver {
             .expect("meraki reqwest client");
         Self {
             client,
-            credential_store: KeyringStore::new(KEYRING_SERVICE),
+            credential_store: default_credential_store(),
+            api_base: config::meraki_api_base().to_string(),
         }
     }
 }
@@ -42,57 +56,87 @@ impl DeviceDriver for MerakiCloudDriver {
     fn capabilities(&self) -> CapabilitySet {
         CapabilitySet {
             supports_commit: false,
-            supports_rollback: false,
-            supports_diff: false,
-            supports_dry_run: false,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: true,
+            supports_replace: false,
         }
     }
 
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Http]
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         let mut res = DriverExecutionResult::default();
-        let api_key = self.resolve_api_key(device).await?;
+        let dashboard = self.dashboard(device).await?;
         match action {
             DriverAction::Job(JobKind::CommandBatch { commands }) => {
-                let payload = json!({
-                    "device_id": device.id,
-                    "commands": commands,
-                });
-                submit_meraki_request(
-                    &self.client,
-                    device,
-                    MerakiOperation::CommandBatch,
-                    payload,
-                    &api_key,
-                )
-                .await?;
-                res.logs.push(format!(
-                    "[{}] queued Meraki batch with {} commands",
-                    device.name,
-                    commands.len()
-                ));
+                for command in commands {
+                    let path = read_path(device, command)?;
+                    let body = dashboard.get(&path).await?;
+                    let summary = match &body {
+                        Value::Array(items) => format!("{} items", items.len()),
+                        other => summarize(&other.to_string()),
+                    };
+                    res.logs
+                        .push(format!("[{}] GET {} => {}", device.name, path, summary));
+                    res.outputs.push(parsing::json_output(command, body));
+                }
             }
-            DriverAction::Job(JobKind::ConfigPush { snippet }) => {
-                submit_meraki_request(
-                    &self.client,
-                    device,
-                    MerakiOperation::ConfigPush,
-                    json!({
-                        "device_id": device.id,
-                        "template_snippet": snippet,
-                    }),
-                    &api_key,
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                let changes = parse_changes(snippet)?;
+                let touched = touched_resources(device, &changes)?;
+                let before = dashboard.snapshot(&touched).await?;
+                let pre = snapshot_text(&before)?;
+                res.pre_snapshot = Some(pre.clone());
+
+                if ctx.dry_run {
+                    let predicted = snapshot_text(&predict(&before, &touched))?;
+                    res.diff = Some(config_tree::line_diff(&pre, &predicted));
+                    res.logs.push(format!(
+                        "[{}] dry run: {} Meraki changes not applied",
+                        device.name,
+                        changes.len()
+                    ));
+                    return Ok(res);
+                }
+
+                for change in &changes {
+                    let log = dashboard.apply(change).await?;
+                    res.logs.push(format!("[{}] {}", device.name, log));
+                }
+                let post = snapshot_text(&dashboard.snapshot(&touched).await?)?;
+                res.diff = Some(config_tree::line_diff(&pre, &post));
+                res.post_snapshot = Some(post);
+            }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup { .. }) => {
+                bail!("{} does not support configuration backups", self.name())
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
                 )
-                .await?;
-                res.logs.push(format!(
-                    "[{}] applied Meraki template ({} chars)",
-                    device.name,
-                    snippet.len()
-                ));
-                res.diff = Some("Meraki change tracked via dashboard templates".into());
             }
             DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
                 res.logs.push(format!(
@@ -105,125 +149,69 @@ impl DeviceDriver for MerakiCloudDriver {
         Ok(res)
     }
 
-    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
-        warn!(
-            target: "drivers::meraki",
-            "Rollback requested for {} but Meraki driver currently does not capture snapshots (requested {:?})",
-            device.name,
-            snapshot
-        );
-        Ok(())
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let login = async {
+            let dashboard = self.dashboard(device).await?;
+            dashboard.get("/organizations").await.map(drop)
+        };
+        Ok(preflight::http(&self.api_base, login).await)
     }
-}
-
-async fn submit_meraki_request(
-    client: &Client,
-    device: &Device,
-    operation: MerakiOperation,
-    payload: Value,
-    api_key: &str,
-) -> Result<()> {
-    let url = operation.endpoint(&device.mgmt_address);
-    info!(
-        target: "drivers::meraki",
-        "POST {} ({}) payload {}",
-        url,
-        operation.as_str(),
-        payload
-    );
-    let retry_limit = config::http_retry_limit();
-    for attempt in 0..=retry_limit {
-        match client
-            .post(&url)
-            .header("X-Cisco-Meraki-API-Key", api_key)
-            .json(&payload)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                let status = response.status();
-                let text = response.text().await.with_context(|| {
-                    format!(
-                        "reading meraki response {} {}",
-                        device.name,
-                        operation.as_str()
-                    )
-                })?;
-
-                if !status.is_success() {
-                    bail!(
-                        "Meraki API returned {} for {} {}: {}",
-                        status,
-                        device.name,
-                        operation.as_str(),
-                        text
-                    );
-                }
 
-                info!(
-                    target: "drivers::meraki",
-                    "Meraki {} {} -> {}",
-                    device.name,
-                    operation.as_str(),
-                    status
-                );
-                return Ok(());
-            }
-            Err(err) => {
-                if attempt < retry_limit {
-                    warn!(
-                        target: "drivers::meraki",
-                        "retrying {} {} attempt {} due to {}",
-                        device.name,
-                        operation.as_str(),
-                        attempt + 1,
-                        err
-                    );
-                    tokio::time::sleep(Duration::from_millis(200 * (attempt as u64 + 1))).await;
-                    continue;
-                } else {
-                    return Err(err).with_context(|| {
-                        format!("meraki request {} {}", device.name, operation.as_str())
-                    });
-                }
-            }
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        let Some(snapshot) = snapshot else {
+            warn!(
+                target: "drivers::meraki",
+                "Rollback requested for {} without a snapshot",
+                device.name
+            );
+            return Ok(());
+        };
+        let resources: Vec<ResourceSnapshot> =
+            serde_json::from_str(&snapshot).context("parse Meraki snapshot")?;
+        let dashboard = self.dashboard(device).await?;
+        for resource in &resources {
+            info!(
+                target: "drivers::meraki",
+                "Rollback {} restoring {}",
+                device.name,
+                resource.path
+            );
+            dashboard
+                .send(
+                    Method::PUT,
+                    &resource.path,
+                    Some(&Value::Object(resource.values.clone())),
+                )
+                .await?;
         }
+        Ok(())
     }
-
-    unreachable!("meraki retry loop should return")
 }
 
-#[derive(Copy, Clone)]
-enum MerakiOperation {
-    CommandBatch,
-    ConfigPush,
-}
+impl MerakiCloudDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
 
-impl MerakiOperation {
-    fn as_str(&self) -> &'static str {
-        match self {
-            MerakiOperation::CommandBatch => "command_batch",
-            MerakiOperation::ConfigPush => "config_push",
-        }
+    /// Points the driver at another Dashboard API root, e.g. a local stand-in.
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
     }
 
-    fn endpoint(&self, network_identifier: &str) -> String {
-        match self {
-            MerakiOperation::CommandBatch => {
-                format!("{MERAKI_API_BASE}/networks/{network_identifier}/commands/batch")
-            }
-            MerakiOperation::ConfigPush => {
-                format!("{MERAKI_API_BASE}/networks/{network_identifier}/config_templates/apply")
-            }
-        }
+    async fn dashboard<'a>(&'a self, device: &'a Device) -> Result<Dashboard<'a>> {
+        Ok(Dashboard {
+            client: &self.client,
+            api_base: self.api_base.trim_end_matches('/'),
+            api_key: self.resolve_api_key(device).await?,
+            device,
+        })
     }
-}
 
-impl MerakiCloudDriver {
     async fn resolve_api_key(&self, device: &Device) -> Result<String> {
-        let credential = self
-            .credential_store
-            .resolve(&device.credential)
+        let credential = replay::resolve_credential(&self.credential_store, device)
             .await
             .with_context(|| format!("loading credential {}", device.credential.name))?;
         match credential {
@@ -245,3 +233,606 @@ impl MerakiCloudDriver {
         }
     }
 }
+
+/// A ConfigPush snippet is a YAML/JSON list of Dashboard changes (or `{changes: [...]}`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Change {
+    Network {
+        settings: Map<String, Value>,
+    },
+    Device {
+        serial: Option<String>,
+        settings: Map<String, Value>,
+    },
+    SwitchPort {
+        serial: Option<String>,
+        port_id: String,
+        settings: Map<String, Value>,
+    },
+    Ssid {
+        number: u8,
+        settings: Map<String, Value>,
+    },
+    Vlan {
+        id: u16,
+        settings: Map<String, Value>,
+    },
+    ActionBatch {
+        actions: Vec<BatchAction>,
+        #[serde(default = "default_true")]
+        synchronous: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BatchAction {
+    resource: String,
+    operation: String,
+    #[serde(default)]
+    body: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChangeDocument {
+    List(Vec<Change>),
+    Wrapped { changes: Vec<Change> },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn parse_changes(snippet: &str) -> Result<Vec<Change>> {
+    let document: ChangeDocument =
+        serde_yaml::from_str(snippet).context("parse Meraki change list")?;
+    Ok(match document {
+        ChangeDocument::List(changes) | ChangeDocument::Wrapped { changes } => changes,
+    })
+}
+
+/// A resource the push touches: its API path and the settings it will write.
+#[derive(Debug, Clone)]
+struct Touched {
+    path: String,
+    settings: Map<String, Value>,
+}
+
+/// Only the touched keys are captured, so the snapshot doubles as a rollback PUT body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResourceSnapshot {
+    path: String,
+    values: Map<String, Value>,
+}
+
+fn touched_resources(device: &Device, changes: &[Change]) -> Result<Vec<Touched>> {
+    let network = &device.mgmt_address;
+    let mut touched = Vec::new();
+    for change in changes {
+        let (path, settings) = match change {
+            Change::Network { settings } => (format!("/networks/{network}"), settings),
+            Change::Device { serial, settings } => {
+                let serial = device_serial(device, serial.as_deref())?;
+                (format!("/devices/{serial}"), settings)
+            }
+            Change::SwitchPort {
+                serial,
+                port_id,
+                settings,
+            } => {
+                let serial = device_serial(device, serial.as_deref())?;
+                (
+                    format!("/devices/{serial}/switch/ports/{port_id}"),
+                    settings,
+                )
+            }
+            Change::Ssid { number, settings } => (
+                format!("/networks/{network}/wireless/ssids/{number}"),
+                settings,
+            ),
+            Change::Vlan { id, settings } => (
+                format!("/networks/{network}/appliance/vlans/{id}"),
+                settings,
+            ),
+            Change::ActionBatch { actions, .. } => {
+                for action in actions {
+                    if action.operation == "update" {
+                        touched.push(Touched {
+                            path: action.resource.clone(),
+                            settings: action.body.clone(),
+                        });
+                    } else {
+                        warn!(
+                            target: "drivers::meraki",
+                            "{} action {} on {} is not captured for rollback",
+                            device.name,
+                            action.operation,
+                            action.resource
+                        );
+                    }
+                }
+                continue;
+            }
+        };
+        touched.push(Touched {
+            path,
+            settings: settings.clone(),
+        });
+    }
+    Ok(touched)
+}
+
+fn device_serial(device: &Device, explicit: Option<&str>) -> Result<String> {
+    explicit
+        .map(str::to_string)
+        .or_else(|| {
+            device
+                .tags
+                .iter()
+                .find_map(|tag| tag.strip_prefix("serial:").map(str::to_string))
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "no serial given and {} has no serial:<serial> tag",
+                device.name
+            )
+        })
+}
+
+fn predict(before: &[ResourceSnapshot], touched: &[Touched]) -> Vec<ResourceSnapshot> {
+    before
+        .iter()
+        .zip(touched)
+        .map(|(snapshot, change)| {
+            let mut values = snapshot.values.clone();
+            values.extend(change.settings.clone());
+            ResourceSnapshot {
+                path: snapshot.path.clone(),
+                values,
+            }
+        })
+        .collect()
+}
+
+fn snapshot_text(resources: &[ResourceSnapshot]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(resources)?)
+}
+
+/// Maps a CommandBatch entry to a Dashboard GET path. Accepts raw paths (optionally
+/// prefixed with `GET`, with `{network}`/`{serial}` placeholders) or a few named reads.
+fn read_path(device: &Device, command: &str) -> Result<String> {
+    let command = command.trim();
+    let command = command.strip_prefix("GET ").unwrap_or(command).trim();
+    let network = &device.mgmt_address;
+    let path = match command {
+        "network" => format!("/networks/{network}"),
+        "devices" => format!("/networks/{network}/devices"),
+        "clients" => format!("/networks/{network}/clients"),
+        "ssids" => format!("/networks/{network}/wireless/ssids"),
+        "vlans" => format!("/networks/{network}/appliance/vlans"),
+        "device" => format!("/devices/{}", device_serial(device, None)?),
+        "switch_ports" => format!("/devices/{}/switch/ports", device_serial(device, None)?),
+        path if path.starts_with('/') => {
+            let mut path = path.replace("{network}", network);
+            if path.contains("{serial}") {
+                path = path.replace("{serial}", &device_serial(device, None)?);
+            }
+            path
+        }
+        other => bail!("unsupported Meraki read '{}' on {}", other, device.name),
+    };
+    Ok(path)
+}
+
+struct Dashboard<'a> {
+    client: &'a Client,
+    api_base: &'a str,
+    api_key: String,
+    device: &'a Device,
+}
+
+impl Dashboard<'_> {
+    async fn snapshot(&self, touched: &[Touched]) -> Result<Vec<ResourceSnapshot>> {
+        let mut resources = Vec::new();
+        for resource in touched {
+            let current = self.get(&resource.path).await?;
+            let values = resource
+                .settings
+                .keys()
+                .filter_map(|key| Some((key.clone(), current.get(key)?.clone())))
+                .collect();
+            resources.push(ResourceSnapshot {
+                path: resource.path.clone(),
+                values,
+            });
+        }
+        Ok(resources)
+    }
+
+    async fn apply(&self, change: &Change) -> Result<String> {
+        if let Change::ActionBatch {
+            actions,
+            synchronous,
+        } = change
+        {
+            return self.run_action_batch(actions, *synchronous).await;
+        }
+        let touched = touched_resources(self.device, std::slice::from_ref(change))?;
+        let resource = touched
+            .first()
+            .ok_or_else(|| anyhow!("change touches no resource"))?;
+        self.send(
+            Method::PUT,
+            &resource.path,
+            Some(&Value::Object(resource.settings.clone())),
+        )
+        .await?;
+        Ok(format!(
+            "PUT {} ({} settings)",
+            resource.path,
+            resource.settings.len()
+        ))
+    }
+
+    async fn run_action_batch(&self, actions: &[BatchAction], synchronous: bool) -> Result<String> {
+        let network = self
+            .get(&format!("/networks/{}", self.device.mgmt_address))
+            .await?;
+        let org = network
+            .get("organizationId")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("network {} has no organizationId", self.device.mgmt_address))?
+            .to_string();
+        let payload = json!({
+            "confirmed": true,
+            "synchronous": synchronous,
+            "actions": actions,
+        });
+        let mut batch = self
+            .send(
+                Method::POST,
+                &format!("/organizations/{org}/actionBatches"),
+                Some(&payload),
+            )
+            .await?
+            .0;
+        let id = batch
+            .get("id")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        for _ in 0..ACTION_BATCH_POLLS {
+            let status = &batch["status"];
+            if status["failed"].as_bool().unwrap_or(false) {
+                bail!("Meraki action batch {} failed: {}", id, status["errors"]);
+            }
+            if status["completed"].as_bool().unwrap_or(false) {
+                return Ok(format!(
+                    "action batch {} completed ({} actions)",
+                    id,
+                    actions.len()
+                ));
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            batch = self
+                .get(&format!("/organizations/{org}/actionBatches/{id}"))
+                .await?;
+        }
+        bail!("Meraki action batch {} did not complete", id)
+    }
+
+    /// GET that follows `Link: <...>; rel=next` pagination and concatenates array pages.
+    async fn get(&self, path: &str) -> Result<Value> {
+        let (mut body, mut next) = self.send(Method::GET, path, None).await?;
+        while let Some(url) = next {
+            let (page, following) = self.send(Method::GET, &url, None).await?;
+            match (&mut body, page) {
+                (Value::Array(items), Value::Array(more)) => items.extend(more),
+                (_, page) => body = page,
+            }
+            next = following;
+        }
+        Ok(body)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&Value>,
+    ) -> Result<(Value, Option<String>)> {
+        let url = if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.api_base, path)
+        };
+        let device = &self.device.name;
+        let retry_limit = config::http_retry_limit();
+        let mut errors = 0;
+        let mut throttled = 0;
+        loop {
+            let mut request = self
+                .client
+                .request(method.clone(), &url)
+                .header("X-Cisco-Meraki-API-Key", &self.api_key);
+            if let Some(payload) = payload {
+                request = request.json(payload);
+            }
+            let response = match replay::send(self.device, request).await {
+                Ok(response) => response,
+                Err(err) if errors < retry_limit => {
+                    errors += 1;
+                    warn!(
+                        target: "drivers::meraki",
+                        "retrying {} {} {} attempt {} due to {}",
+                        device,
+                        method,
+                        url,
+                        errors,
+                        err
+                    );
+                    tokio::time::sleep(Duration::from_millis(200 * errors as u64)).await;
+                    continue;
+                }
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("meraki {} {} {}", device, method, url))
+                }
+            };
+
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS && throttled < RATE_LIMIT_RETRIES {
+                throttled += 1;
+                let wait = retry_after(response.headers());
+                warn!(
+                    target: "drivers::meraki",
+                    "{} rate limited on {} {}, waiting {:?}",
+                    device,
+                    method,
+                    url,
+                    wait
+                );
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+            // Joined against the request URL: replayed responses carry no URL of their own.
+            let next = next_link(response.headers())
+                .and_then(|link| reqwest::Url::parse(&url).ok()?.join(&link).ok())
+                .map(String::from);
+            let text = response
+                .text()
+                .await
+                .with_context(|| format!("reading meraki response {} {}", device, url))?;
+            if !status.is_success() {
+                bail!(
+                    "Meraki API returned {} for {} {} {}: {}",
+                    status,
+                    device,
+                    method,
+                    url,
+                    text
+                );
+            }
+            info!(
+                target: "drivers::meraki",
+                "Meraki {} {} {} -> {}",
+                device,
+                method,
+                url,
+                status
+            );
+            let body = if text.trim().is_empty() {
+                Value::Null
+            } else {
+                serde_json::from_str(&text)
+                    .with_context(|| format!("parse meraki response {} {}", device, url))?
+            };
+            return Ok((body, next));
+        }
+    }
+}
+
+fn retry_after(headers: &HeaderMap) -> Duration {
+    headers
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+        .unwrap_or(Duration::from_secs(1))
+}
+
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all("link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.split_once(';')?;
+            params
+                .split(';')
+                .any(|param| param.trim().replace('"', "") == "rel=next")
+                .then(|| {
+                    target
+                        .trim()
+                        .trim_start_matches('<')
+                        .trim_end_matches('>')
+                        .to_string()
+                })
+        })
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{
+        http::{spawn_http, HttpResponse},
+        use_fallback_credentials,
+    };
+    use nauto_model::CredentialRef;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Dashboard {
+        resources: BTreeMap<String, Value>,
+        requests: Vec<String>,
+        throttle_next_put: bool,
+    }
+
+    /// Dashboard stand-in: GET/PUT on stored resources, a paginated device list and a
+    /// one-off 429 on the first PUT when `throttle_next_put` is set.
+    async fn spawn_dashboard(state: Arc<Mutex<Dashboard>>) -> String {
+        spawn_http(move |request| {
+            let mut dashboard = state.lock().unwrap();
+            assert_eq!(
+                request.header("X-Cisco-Meraki-API-Key"),
+                Some("meraki-test-key")
+            );
+            dashboard
+                .requests
+                .push(format!("{} {}", request.method, request.path));
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/api/v1/networks/N_1/devices") => HttpResponse::json(json!([
+                    { "serial": "Q2AA-0001" },
+                    { "serial": "Q2AA-0002" }
+                ]))
+                .with_header(
+                    "Link",
+                    "</api/v1/networks/N_1/devices?startingAfter=Q2AA-0002>; rel=next",
+                ),
+                ("GET", "/api/v1/networks/N_1/devices?startingAfter=Q2AA-0002") => {
+                    HttpResponse::json(json!([{ "serial": "Q2AA-0003" }]))
+                }
+                ("GET", path) => match dashboard.resources.get(path) {
+                    Some(resource) => HttpResponse::json(resource.clone()),
+                    None => HttpResponse::status(404, "{}"),
+                },
+                ("PUT", _) if dashboard.throttle_next_put => {
+                    dashboard.throttle_next_put = false;
+                    HttpResponse::status(429, "{}").with_header("Retry-After", "0")
+                }
+                ("PUT", path) => {
+                    let Some(Value::Object(resource)) = dashboard.resources.get_mut(path) else {
+                        return HttpResponse::status(404, "{}");
+                    };
+                    if let Value::Object(update) = request.json() {
+                        resource.extend(update);
+                    }
+                    HttpResponse::json(Value::Object(resource.clone()))
+                }
+                _ => HttpResponse::status(400, "{}"),
+            }
+        })
+        .await
+    }
+
+    fn device() -> Device {
+        use_fallback_credentials();
+        Device {
+            id: "mx1".into(),
+            name: "branch-1".into(),
+            device_type: DeviceType::MerakiCloud,
+            mgmt_address: "N_1".into(),
+            credential: CredentialRef {
+                name: "meraki-lab".into(),
+            },
+            tags: vec!["serial:Q2AA-0001".into()],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn push_snapshots_touched_resources_and_rolls_back() {
+        let state = Arc::new(Mutex::new(Dashboard {
+            throttle_next_put: true,
+            ..Default::default()
+        }));
+        {
+            let mut dashboard = state.lock().unwrap();
+            dashboard.resources.insert(
+                "/api/v1/devices/Q2AA-0001/switch/ports/3".into(),
+                json!({ "portId": "3", "vlan": 10, "enabled": true, "name": "desk" }),
+            );
+            dashboard.resources.insert(
+                "/api/v1/networks/N_1/wireless/ssids/0".into(),
+                json!({ "number": 0, "name": "corp", "enabled": true }),
+            );
+        }
+        let base = spawn_dashboard(state.clone()).await;
+        let driver = MerakiCloudDriver::default().with_api_base(format!("{base}/api/v1"));
+        let device = device();
+        let job = JobKind::ConfigPush {
+            snippet: "- type: switch_port\n  port_id: \"3\"\n  settings: { vlan: 20 }\n- type: ssid\n  number: 0\n  settings: { name: guest }\n".into(),
+            mode: ConfigMode::Merge,
+        };
+
+        let dry = driver
+            .execute(
+                &device,
+                DriverAction::Job(&job),
+                &JobContext {
+                    dry_run: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(dry.diff.unwrap().contains("+      \"vlan\": 20"));
+        assert!(state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .all(|req| req.starts_with("GET")));
+
+        let res = driver
+            .execute(&device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        let diff = res.diff.unwrap();
+        assert!(diff.contains("-      \"vlan\": 10"));
+        assert!(diff.contains("+      \"name\": \"guest\""));
+        {
+            let dashboard = state.lock().unwrap();
+            let port = &dashboard.resources["/api/v1/devices/Q2AA-0001/switch/ports/3"];
+            assert_eq!(port["vlan"], 20);
+            assert_eq!(port["name"], "desk");
+        }
+
+        driver.rollback(&device, res.pre_snapshot).await.unwrap();
+        let dashboard = state.lock().unwrap();
+        assert_eq!(
+            dashboard.resources["/api/v1/devices/Q2AA-0001/switch/ports/3"]["vlan"],
+            10
+        );
+        assert_eq!(
+            dashboard.resources["/api/v1/networks/N_1/wireless/ssids/0"]["name"],
+            "corp"
+        );
+    }
+
+    #[tokio::test]
+    async fn command_batch_follows_pagination() {
+        let state = Arc::new(Mutex::new(Dashboard::default()));
+        let base = spawn_dashboard(state.clone()).await;
+        let driver = MerakiCloudDriver::default().with_api_base(format!("{base}/api/v1"));
+        let job = JobKind::CommandBatch {
+            commands: vec!["devices".into()],
+        };
+
+        let res = driver
+            .execute(&device(), DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            res.logs,
+            ["[branch-1] GET /networks/N_1/devices => 3 items"]
+        );
+    }
+}
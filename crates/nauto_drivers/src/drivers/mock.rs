@@ -1,7 +1,10 @@
-use crate::{DeviceDriver, DriverAction, DriverExecutionResult};
+use crate::{DeviceDriver, DriverAction, DriverExecutionResult, JobContext};
 use anyhow::Result;
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Device, DeviceType};
+use nauto_model::{
+    CapabilitySet, CheckStatus, CommandOutput, ConfigSnapshot, Device, DeviceFacts, DeviceType,
+    Getter, GetterResults, PreflightCheck, PreflightReport, PreflightStep,
+};
 
 #[derive(Clone)]
 pub struct MockDriver {
@@ -19,6 +22,7 @@ impl MockDriver {
                 supports_rollback: true,
                 supports_diff: true,
                 supports_dry_run: true,
+                supports_replace: true,
             },
             label: "Mock Driver",
         }
@@ -43,11 +47,12 @@ impl DeviceDriver for MockDriver {
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        _ctx: &JobContext,
     ) -> Result<DriverExecutionResult> {
         if device.tags.iter().any(|t| t == "mock:fail") {
             anyhow::bail!("simulated failure for {}", device.name);
         }
-        
+
         if let nauto_model::JobKind::CommandBatch { commands } = action.job_kind() {
             if commands.iter().any(|c| c == "fail") {
                 anyhow::bail!("simulated command failure");
@@ -61,6 +66,37 @@ impl DeviceDriver for MockDriver {
         result
             .logs
             .push(format!("[mock] device={} action={:?}", device.name, action));
+        if let nauto_model::JobKind::CommandBatch { commands } = action.job_kind() {
+            result.outputs = commands
+                .iter()
+                .map(|cmd| CommandOutput {
+                    command: cmd.clone(),
+                    raw: format!("[mock] {} output\n", cmd),
+                    parsed: None,
+                })
+                .collect();
+        }
+        if let nauto_model::JobKind::Getters { which } = action.job_kind() {
+            let mut getters = GetterResults::default();
+            if which.contains(&Getter::Facts) {
+                getters.facts = Some(DeviceFacts {
+                    hostname: device.name.clone(),
+                    vendor: "mock".into(),
+                    ..Default::default()
+                });
+            }
+            result.getters = Some(getters);
+        }
+        if let nauto_model::JobKind::ConfigBackup {
+            include_startup, ..
+        } = action.job_kind()
+        {
+            let running = format!("hostname {}\n", device.name);
+            result.backup = Some(ConfigSnapshot {
+                startup: include_startup.then(|| running.clone()),
+                running,
+            });
+        }
         result.diff = Some("mock diff".into());
         Ok(result)
     }
@@ -68,4 +104,27 @@ impl DeviceDriver for MockDriver {
     async fn rollback(&self, _device: &Device, _snapshot: Option<String>) -> Result<()> {
         Ok(())
     }
+
+    /// Every check passes, except TCP for devices tagged `mock:unreachable`.
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let (tcp, login) = if device.tags.iter().any(|t| t == "mock:unreachable") {
+            (CheckStatus::Failed, CheckStatus::Skipped)
+        } else {
+            (CheckStatus::Passed, CheckStatus::Passed)
+        };
+        let check = |step, status| PreflightCheck {
+            step,
+            status,
+            detail: String::new(),
+        };
+        Ok(PreflightReport {
+            endpoint: device.mgmt_address.clone(),
+            checks: vec![
+                check(PreflightStep::Dns, CheckStatus::Passed),
+                check(PreflightStep::Tcp, tcp),
+                check(PreflightStep::HostKey, login),
+                check(PreflightStep::Auth, login),
+            ],
+        })
+    }
 }
@@ -1,15 +1,41 @@
 pub mod arista_eos;
 pub mod cisco_ios;
+pub mod cisco_iosxr;
 pub mod cisco_nxos_api;
+pub mod fortinet_fortios;
+pub mod generic_netconf;
 pub mod generic_ssh;
 pub mod juniper_junos;
+pub mod linux_host;
 pub mod meraki_cloud;
 pub mod mock;
+pub mod paloalto_panos;
+pub mod simulated;
 
 pub use arista_eos::AristaEosDriver;
 pub use cisco_ios::CiscoIosDriver;
+pub use cisco_iosxr::CiscoIosXrDriver;
 pub use cisco_nxos_api::CiscoNxosApiDriver;
+pub use fortinet_fortios::FortinetFortiosDriver;
+pub use generic_netconf::GenericNetconfDriver;
 pub use generic_ssh::GenericSshDriver;
 pub use juniper_junos::JuniperJunosDriver;
+pub use linux_host::LinuxHostDriver;
 pub use meraki_cloud::MerakiCloudDriver;
 pub use mock::MockDriver;
+pub use paloalto_panos::PaloAltoPanosDriver;
+pub use simulated::SimulatedDriver;
+
+/// `output` trimmed and, past `max_bytes`, cut at the last char boundary with `…` appended;
+/// device output is not always ASCII.
+pub(crate) fn clip(output: &str, max_bytes: usize) -> String {
+    let trimmed = output.trim();
+    if trimmed.len() <= max_bytes {
+        return trimmed.to_string();
+    }
+    let cut = (0..=max_bytes)
+        .rev()
+        .find(|idx| trimmed.is_char_boundary(*idx))
+        .unwrap_or(0);
+    format!("{}…", &trimmed[..cut])
+}
@@ -0,0 +1,670 @@
+use crate::{
+    backup, config, config_tree, netconf, preflight, replay, ssh::default_credential_store,
+    DeviceDriver, DriverAction, DriverExecutionResult, JobContext, Transport,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{
+    CapabilitySet, CommandOutput, ConfigMode, Credential, Device, DeviceType, JobKind,
+    PreflightReport,
+};
+use nauto_security::CredentialStore;
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const DEFAULT_JOB_POLL: Duration = Duration::from_secs(2);
+const COMMIT_TIMEOUT: Duration = Duration::from_secs(900);
+
+/// PAN-OS firewalls and Panorama over the XML API (`/api/`).
+#[derive(Clone)]
+pub struct PaloAltoPanosDriver {
+    credential_store: Arc<dyn CredentialStore>,
+    http: HttpClient,
+    job_poll: Duration,
+}
+
+impl Default for PaloAltoPanosDriver {
+    fn default() -> Self {
+        let http = HttpClient::builder()
+            .timeout(config::http_timeout())
+            .build()
+            .expect("PAN-OS reqwest client");
+        Self {
+            credential_store: default_credential_store(),
+            http,
+            job_poll: DEFAULT_JOB_POLL,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for PaloAltoPanosDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::PaloAltoPanos
+    }
+
+    fn name(&self) -> &'static str {
+        "Palo Alto PAN-OS"
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        CapabilitySet {
+            supports_commit: true,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: true,
+            supports_replace: false,
+        }
+    }
+
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Http]
+    }
+
+    fn with_credentials(&self, store: Arc<dyn CredentialStore>) -> Option<crate::DynDeviceDriver> {
+        Some(Arc::new(self.clone().with_credential_store(store)))
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        let api = self.session(device).await?;
+        let mut res = DriverExecutionResult::default();
+        match action {
+            DriverAction::Job(JobKind::CommandBatch { commands }) => {
+                for cmd in commands {
+                    let reply = api.op(&op_xml(cmd)).await?;
+                    res.logs.push(format!(
+                        "[{}] {} => {}",
+                        device.name,
+                        cmd,
+                        summarize(&reply.result)
+                    ));
+                    res.outputs.push(CommandOutput {
+                        command: cmd.clone(),
+                        raw: reply.result,
+                        parsed: None,
+                    });
+                }
+            }
+            DriverAction::Job(JobKind::ConfigPush {
+                mode: ConfigMode::Replace,
+                ..
+            }) => bail!("{} does not support replace mode", self.name()),
+            DriverAction::Job(JobKind::ConfigPush { snippet, .. }) => {
+                let edits = parse_edits(snippet)?;
+                let pending = api
+                    .op("<check><pending-changes></pending-changes></check>")
+                    .await?;
+                if pending.result.trim() == "yes" {
+                    bail!(
+                        "{} has uncommitted candidate changes; refusing to commit them",
+                        device.name
+                    );
+                }
+                let before = api.running_config().await?;
+                res.pre_snapshot = Some(before.clone());
+
+                let outcome = self.load_and_commit(&api, &edits, ctx, &mut res).await;
+                if outcome.is_err() || ctx.dry_run {
+                    if let Err(err) = api.op("<revert><config></config></revert>").await {
+                        warn!(target: "drivers::panos", "{} revert failed: {}", device.name, err);
+                    }
+                }
+                outcome?;
+                if !ctx.dry_run {
+                    let after = api.running_config().await?;
+                    if res
+                        .diff
+                        .as_deref()
+                        .map(str::trim)
+                        .unwrap_or_default()
+                        .is_empty()
+                    {
+                        res.diff = Some(config_tree::line_diff(&before, &after));
+                    }
+                    res.post_snapshot = Some(after);
+                }
+            }
+            DriverAction::Job(JobKind::Getters { .. }) => {
+                bail!("{} does not support getters", self.name())
+            }
+            DriverAction::Job(JobKind::ConfigBackup {
+                include_startup, ..
+            }) => {
+                let snapshot = backup::snapshot(&api.running_config().await?, None);
+                res.logs.push(backup::describe(device, &snapshot));
+                if *include_startup {
+                    res.logs.push(format!(
+                        "[{}] no separate startup config on PAN-OS",
+                        device.name
+                    ));
+                }
+                res.backup = Some(snapshot);
+            }
+            DriverAction::Job(JobKind::Preflight) => {
+                bail!("pre-flight checks run through DeviceDriver::preflight")
+            }
+            DriverAction::Job(JobKind::FileTransfer { .. } | JobKind::Upgrade { .. }) => {
+                bail!(
+                    "{} does not support file transfers or upgrades",
+                    self.name()
+                )
+            }
+            DriverAction::Job(JobKind::ComplianceCheck { rules }) => {
+                res.logs.push(format!(
+                    "[{}] evaluated {} compliance rules",
+                    device.name,
+                    rules.len()
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let login = async {
+            let api = self.session(device).await?;
+            api.op("<show><system><info></info></system></show>")
+                .await
+                .map(drop)
+        };
+        Ok(preflight::http(&api_endpoint(device), login).await)
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        info!(
+            target: "drivers::panos",
+            "Rollback {} to {} byte snapshot",
+            device.name,
+            snapshot.len()
+        );
+        let api = self.session(device).await?;
+        // Replacing the candidate root with the snapshot and committing restores it wholesale.
+        let restored = async {
+            api.config("edit", "/config", Some(&snapshot)).await?;
+            self.commit(&api, "netrust rollback").await
+        }
+        .await;
+        if restored.is_err() {
+            let _ = api.op("<revert><config></config></revert>").await;
+        }
+        restored.map(|_| ())
+    }
+}
+
+impl PaloAltoPanosDriver {
+    /// Resolves credentials from `store` instead of [`default_credential_store`].
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
+
+    async fn session<'a>(&'a self, device: &'a Device) -> Result<PanosApi<'a>> {
+        let mut api = PanosApi {
+            http: &self.http,
+            device,
+            endpoint: api_endpoint(device),
+            key: String::new(),
+        };
+        let credential = replay::resolve_credential(&self.credential_store, device)
+            .await
+            .with_context(|| format!("loading credential {}", device.credential.name))?;
+        api.key = match credential {
+            Credential::Token { token } => token,
+            Credential::UserPassword { username, password } => {
+                let reply = api
+                    .call(&[
+                        ("type", "keygen"),
+                        ("user", &username),
+                        ("password", &password),
+                    ])
+                    .await
+                    .context("PAN-OS keygen")?;
+                let key = netconf::element_text(&reply.raw, "key")
+                    .ok_or_else(|| anyhow!("keygen reply without key from {}", device.name))?;
+                replay::redact(device, &key)?;
+                key
+            }
+            other => bail!(
+                "credential {:?} unsupported for PAN-OS device {}",
+                other,
+                device.name
+            ),
+        };
+        Ok(api)
+    }
+
+    async fn load_and_commit(
+        &self,
+        api: &PanosApi<'_>,
+        edits: &[ConfigEdit],
+        ctx: &JobContext,
+        res: &mut DriverExecutionResult,
+    ) -> Result<()> {
+        let device = api.device;
+        for edit in edits {
+            let action = match edit.action {
+                EditAction::Set => "set",
+                EditAction::Edit => "edit",
+                EditAction::Delete => "delete",
+            };
+            api.config(action, &edit.xpath, edit.element.as_deref())
+                .await
+                .with_context(|| format!("{} {}", action, edit.xpath))?;
+        }
+        res.logs.push(format!(
+            "[{}] staged {} candidate edits",
+            device.name,
+            edits.len()
+        ));
+
+        let diff = api
+            .op("<show><config><diff></diff></config></show>")
+            .await?;
+        let diff = netconf::element_text(&diff.raw, "result").unwrap_or_default();
+        res.diff = Some(format!("{}\n", diff.trim()));
+        if ctx.dry_run {
+            res.logs.push(format!(
+                "[{}] dry run: candidate diff captured and reverted",
+                device.name
+            ));
+            return Ok(());
+        }
+
+        let comment = match ctx.job_id {
+            Some(id) => format!("netrust job {id}"),
+            None => "netrust".into(),
+        };
+        let job = self.commit(api, &comment).await?;
+        res.logs.push(match job {
+            Some(id) => format!("[{}] commit job {} succeeded", device.name, id),
+            None => format!("[{}] nothing to commit", device.name),
+        });
+        Ok(())
+    }
+
+    /// Commits the candidate and polls the resulting job until it finishes.
+    async fn commit(&self, api: &PanosApi<'_>, description: &str) -> Result<Option<String>> {
+        let cmd = format!(
+            "<commit><description>{}</description></commit>",
+            netconf::escape_text(description)
+        );
+        let reply = api.call(&[("type", "commit"), ("cmd", &cmd)]).await?;
+        let Some(job) = netconf::element_text(&reply.raw, "job") else {
+            // "There are no changes to commit."
+            return Ok(None);
+        };
+        let polls = (COMMIT_TIMEOUT.as_millis() / self.job_poll.as_millis().max(1)) as usize;
+        for _ in 0..polls.max(1) {
+            let status = api
+                .op(&format!("<show><jobs><id>{job}</id></jobs></show>"))
+                .await?;
+            // The job carries its own <status>/<result>, nested in the reply's <result>.
+            let entry = netconf::elements(&status.result, "job")
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            let state = netconf::element_text(&entry, "status").unwrap_or_default();
+            if state == "FIN" {
+                let result = netconf::element_text(&entry, "result").unwrap_or_default();
+                if result.trim() != "OK" {
+                    let details = netconf::element_text(&entry, "details").unwrap_or_default();
+                    bail!(
+                        "PAN-OS commit job {} on {} failed: {}",
+                        job,
+                        api.device.name,
+                        details.trim()
+                    );
+                }
+                return Ok(Some(job));
+            }
+            tokio::time::sleep(self.job_poll).await;
+        }
+        bail!("PAN-OS commit job {} on {} timed out", job, api.device.name)
+    }
+}
+
+struct PanosApi<'a> {
+    http: &'a HttpClient,
+    device: &'a Device,
+    endpoint: String,
+    key: String,
+}
+
+struct PanosReply {
+    raw: String,
+    /// Inner XML (or text) of `<result>`.
+    result: String,
+}
+
+impl PanosApi<'_> {
+    async fn op(&self, cmd: &str) -> Result<PanosReply> {
+        self.call(&[("type", "op"), ("cmd", cmd)]).await
+    }
+
+    async fn config(&self, action: &str, xpath: &str, element: Option<&str>) -> Result<PanosReply> {
+        let mut params = vec![("type", "config"), ("action", action), ("xpath", xpath)];
+        if let Some(element) = element {
+            params.push(("element", element));
+        }
+        self.call(&params).await
+    }
+
+    /// Active configuration, pretty-printed so snapshots diff line by line.
+    async fn running_config(&self) -> Result<String> {
+        let reply = self.config("show", "/config", None).await?;
+        Ok(netconf::pretty_xml(&reply.result))
+    }
+
+    async fn call(&self, params: &[(&str, &str)]) -> Result<PanosReply> {
+        let device = &self.device.name;
+        let retry_limit = config::http_retry_limit();
+        for attempt in 0..=retry_limit {
+            let mut request = self.http.post(&self.endpoint).form(params);
+            if !self.key.is_empty() {
+                request = request.header("X-PAN-KEY", &self.key);
+            }
+            match replay::send(self.device, request).await {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let body = resp.text().await.context("PAN-OS API payload")?;
+                    if !status.is_success() {
+                        bail!("PAN-OS API {} returned {}: {}", device, status, body);
+                    }
+                    return parse_reply(device, body);
+                }
+                Err(err) if attempt < retry_limit => {
+                    warn!(
+                        target: "drivers::panos",
+                        "retrying PAN-OS API {} attempt {} due to {}",
+                        device,
+                        attempt + 1,
+                        err
+                    );
+                    tokio::time::sleep(Duration::from_millis(200 * (attempt as u64 + 1))).await;
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("PAN-OS API {} request", device))
+                }
+            }
+        }
+        unreachable!("PAN-OS retry loop should have returned")
+    }
+}
+
+fn parse_reply(device: &str, raw: String) -> Result<PanosReply> {
+    let status = raw
+        .split_once("status=\"")
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .map(|(status, _)| status.to_string())
+        .unwrap_or_default();
+    if status != "success" {
+        let message = netconf::element_text(&raw, "msg").unwrap_or_else(|| raw.clone());
+        bail!("PAN-OS API {} error: {}", device, message.trim());
+    }
+    let result = netconf::elements(&raw, "result")
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    Ok(PanosReply { raw, result })
+}
+
+fn api_endpoint(device: &Device) -> String {
+    if device.mgmt_address.starts_with("http://") || device.mgmt_address.starts_with("https://") {
+        format!("{}/api/", device.mgmt_address.trim_end_matches('/'))
+    } else {
+        format!("https://{}/api/", device.mgmt_address)
+    }
+}
+
+/// CommandBatch entries are operational commands, either raw XML (`<show><system>...`) or
+/// CLI-style words that map onto nested elements (`show system info`).
+fn op_xml(command: &str) -> String {
+    let command = command.trim();
+    if command.starts_with('<') {
+        return command.to_string();
+    }
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let open: String = words.iter().map(|word| format!("<{word}>")).collect();
+    let close: String = words
+        .iter()
+        .rev()
+        .map(|word| format!("</{word}>"))
+        .collect();
+    format!("{open}{close}")
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EditAction {
+    Set,
+    Edit,
+    Delete,
+}
+
+/// One candidate change in a ConfigPush snippet (YAML or JSON list).
+#[derive(Debug, Clone, Deserialize)]
+struct ConfigEdit {
+    action: EditAction,
+    xpath: String,
+    #[serde(default)]
+    element: Option<String>,
+}
+
+fn parse_edits(snippet: &str) -> Result<Vec<ConfigEdit>> {
+    let edits: Vec<ConfigEdit> = serde_yaml::from_str(snippet).context("parse PAN-OS edit list")?;
+    for edit in &edits {
+        if !matches!(edit.action, EditAction::Delete) && edit.element.is_none() {
+            bail!(
+                "PAN-OS {:?} on {} needs an element",
+                edit.action,
+                edit.xpath
+            );
+        }
+    }
+    Ok(edits)
+}
+
+fn summarize(output: &str) -> String {
+    super::clip(output, 200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{
+        http::{spawn_http, HttpResponse},
+        use_fallback_credentials,
+    };
+    use nauto_model::CredentialRef;
+    use std::sync::{Arc, Mutex};
+    use uuid::Uuid;
+
+    const INITIAL: &str = "<config><address/></config>";
+
+    struct Firewall {
+        running: String,
+        candidate: String,
+        job_polls: usize,
+        descriptions: Vec<String>,
+    }
+
+    fn ok(result: &str) -> HttpResponse {
+        HttpResponse::status(
+            200,
+            format!("<response status=\"success\"><result>{result}</result></response>"),
+        )
+    }
+
+    /// XML API stand-in with a running and a candidate config. `set` appends the element
+    /// under `<address>`, `edit` on `/config` replaces the candidate root.
+    async fn spawn_firewall(state: Arc<Mutex<Firewall>>) -> String {
+        spawn_http(move |request| {
+            let mut fw = state.lock().unwrap();
+            let param = |name: &str| request.param(name).unwrap_or_default();
+            if param("type") == "keygen" {
+                assert_eq!(param("password"), "paloalto");
+                return ok("<key>LAB-KEY</key>");
+            }
+            assert_eq!(request.header("X-PAN-KEY"), Some("LAB-KEY"));
+            match (param("type").as_str(), param("action").as_str()) {
+                ("config", "show") => ok(&fw.running.clone()),
+                ("config", "set") if param("element").contains("bogus") => HttpResponse::status(
+                    200,
+                    "<response status=\"error\" code=\"12\"><msg><line>bogus is unexpected</line></msg></response>",
+                ),
+                ("config", "set") => {
+                    fw.candidate = fw
+                        .candidate
+                        .replace("<address/>", "<address></address>")
+                        .replace("</address>", &format!("{}</address>", param("element")));
+                    ok("")
+                }
+                ("config", "edit") => {
+                    fw.candidate = param("element");
+                    ok("")
+                }
+                ("commit", _) => {
+                    let cmd = param("cmd");
+                    let description = netconf::element_text(&cmd, "description").unwrap();
+                    fw.descriptions.push(description);
+                    fw.running = fw.candidate.clone();
+                    fw.job_polls = 0;
+                    ok("<msg><line>Commit job enqueued with jobid 7</line></msg><job>7</job>")
+                }
+                ("op", _) => {
+                    let cmd = param("cmd");
+                    if cmd.contains("<pending-changes>") {
+                        ok(if fw.candidate == fw.running { "no" } else { "yes" })
+                    } else if cmd.contains("<diff>") {
+                        ok(&format!("<![CDATA[- {}\n+ {}]]>", fw.running, fw.candidate))
+                    } else if cmd.contains("<revert>") {
+                        fw.candidate = fw.running.clone();
+                        ok("")
+                    } else if cmd.contains("<jobs>") {
+                        fw.job_polls += 1;
+                        if fw.job_polls < 2 {
+                            ok("<job><id>7</id><status>ACT</status><result>PEND</result></job>")
+                        } else {
+                            ok("<job><id>7</id><status>FIN</status><result>OK</result></job>")
+                        }
+                    } else {
+                        ok("<system><hostname>pa-lab</hostname></system>")
+                    }
+                }
+                _ => HttpResponse::status(400, "unexpected request"),
+            }
+        })
+        .await
+    }
+
+    fn setup() -> (Arc<Mutex<Firewall>>, PaloAltoPanosDriver) {
+        let state = Arc::new(Mutex::new(Firewall {
+            running: INITIAL.into(),
+            candidate: INITIAL.into(),
+            job_polls: 0,
+            descriptions: Vec::new(),
+        }));
+        let driver = PaloAltoPanosDriver {
+            job_poll: Duration::from_millis(5),
+            ..Default::default()
+        };
+        (state, driver)
+    }
+
+    fn device(base: String) -> Device {
+        use_fallback_credentials();
+        Device {
+            id: "pa1".into(),
+            name: "pa-edge".into(),
+            device_type: DeviceType::PaloAltoPanos,
+            mgmt_address: base,
+            credential: CredentialRef {
+                name: "panos-lab".into(),
+            },
+            tags: vec![],
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    fn address_push() -> JobKind {
+        JobKind::ConfigPush {
+            snippet: "- action: set\n  xpath: /config/address\n  element: <entry name=\"web01\"><ip-netmask>10.0.0.10/32</ip-netmask></entry>\n".into(),
+            mode: ConfigMode::Merge,
+        }
+    }
+
+    #[tokio::test]
+    async fn commits_candidate_and_rolls_back_to_snapshot() {
+        let (state, driver) = setup();
+        let device = device(spawn_firewall(state.clone()).await);
+        let job_id = Uuid::new_v4();
+        let ctx = JobContext {
+            job_id: Some(job_id),
+            ..Default::default()
+        };
+
+        let res = driver
+            .execute(&device, DriverAction::Job(&address_push()), &ctx)
+            .await
+            .unwrap();
+        assert!(res.diff.unwrap().contains("web01"));
+        assert!(res.logs.iter().any(|line| line.contains("commit job 7")));
+        assert!(res.post_snapshot.unwrap().contains("10.0.0.10/32"));
+        {
+            let fw = state.lock().unwrap();
+            assert!(fw.running.contains("web01"));
+            assert_eq!(fw.descriptions, vec![format!("netrust job {job_id}")]);
+        }
+
+        driver.rollback(&device, res.pre_snapshot).await.unwrap();
+        let fw = state.lock().unwrap();
+        assert!(!fw.running.contains("web01"));
+        assert_eq!(fw.descriptions.last().unwrap(), "netrust rollback");
+    }
+
+    #[tokio::test]
+    async fn dry_run_and_failed_edits_revert_the_candidate() {
+        let (state, driver) = setup();
+        let device = device(spawn_firewall(state.clone()).await);
+
+        let dry = driver
+            .execute(
+                &device,
+                DriverAction::Job(&address_push()),
+                &JobContext {
+                    dry_run: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(dry.diff.unwrap().contains("+ <config><address><entry"));
+        assert!(dry.post_snapshot.is_none());
+
+        let bad = JobKind::ConfigPush {
+            snippet: "- action: set\n  xpath: /config/address\n  element: <entry name=\"a\"/>\n- action: set\n  xpath: /config/address\n  element: <bogus/>\n".into(),
+            mode: ConfigMode::Merge,
+        };
+        let err = driver
+            .execute(&device, DriverAction::Job(&bad), &JobContext::default())
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("bogus is unexpected"));
+
+        let fw = state.lock().unwrap();
+        assert_eq!(fw.running, INITIAL);
+        assert_eq!(fw.candidate, INITIAL);
+        assert!(fw.descriptions.is_empty());
+    }
+}
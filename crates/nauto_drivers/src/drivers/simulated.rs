@@ -0,0 +1,475 @@
+//! A driver that plays devices in-process, for benchmarks and demos at a scale no lab has.
+//!
+//! Each device gets a [`SimProfile`]: latency distributions for connecting and for each
+//! command, the share of sessions that fail, canned command outputs and an initial running
+//! config. ConfigPush merges into (or replaces) the device's running config, so later
+//! `show running-config`, backups and rollbacks see the change. Random draws come from a
+//! per-device generator seeded from [`SimulationConfig::seed`] and the device id, so a run
+//! is repeatable device by device.
+
+use crate::config_tree::{self, ConfigTree};
+use crate::preview;
+use crate::{DeviceDriver, DriverAction, DriverExecutionResult, JobContext};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{
+    CapabilitySet, CheckStatus, CommandOutput, ConfigMode, ConfigSnapshot, Device, DeviceFacts,
+    DeviceType, Getter, GetterResults, JobKind, PreflightCheck, PreflightReport, PreflightStep,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Delay distribution, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum Latency {
+    Fixed {
+        ms: f64,
+    },
+    Uniform {
+        min_ms: f64,
+        max_ms: f64,
+    },
+    Normal {
+        mean_ms: f64,
+        std_dev_ms: f64,
+    },
+    /// Long-tailed, like real device response times: half the draws fall below `median_ms`.
+    LogNormal {
+        median_ms: f64,
+        sigma: f64,
+    },
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::Fixed { ms: 0.0 }
+    }
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut Rng) -> Duration {
+        let ms = match *self {
+            Latency::Fixed { ms } => ms,
+            Latency::Uniform { min_ms, max_ms } => min_ms + (max_ms - min_ms) * rng.next_f64(),
+            Latency::Normal {
+                mean_ms,
+                std_dev_ms,
+            } => mean_ms + std_dev_ms * rng.standard_normal(),
+            Latency::LogNormal { median_ms, sigma } => {
+                median_ms * (sigma * rng.standard_normal()).exp()
+            }
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimProfile {
+    /// Paid once per job on the device, like an SSH login.
+    pub connect: Latency,
+    /// Paid per command, config push and rollback.
+    pub command: Latency,
+    /// Share of sessions (0.0-1.0) that fail after connecting.
+    pub failure_rate: f64,
+    /// Output per command. `show running-config` answers with the current config unless
+    /// listed here; other commands get a one-line placeholder.
+    pub outputs: BTreeMap<String, String>,
+    /// Starting config, indented IOS style. `{name}` is replaced with the device name;
+    /// empty means `hostname {name}`.
+    pub running_config: String,
+    pub os_version: String,
+}
+
+/// Profiles for a simulated fleet. A device uses the entry under `devices` for its id, else
+/// the profile named by its `sim:<profile>` tag, else `default`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub default: SimProfile,
+    pub profiles: BTreeMap<String, SimProfile>,
+    pub devices: BTreeMap<String, SimProfile>,
+}
+
+impl SimulationConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading simulation profile {}", path.display()))?;
+        serde_yaml::from_str(&text)
+            .with_context(|| format!("parsing simulation profile {}", path.display()))
+    }
+
+    pub fn profile(&self, device: &Device) -> &SimProfile {
+        if let Some(profile) = self.devices.get(&device.id) {
+            return profile;
+        }
+        device
+            .tags
+            .iter()
+            .filter_map(|tag| tag.strip_prefix("sim:"))
+            .find_map(|name| self.profiles.get(name))
+            .unwrap_or(&self.default)
+    }
+}
+
+#[derive(Clone)]
+pub struct SimulatedDriver {
+    device_type: DeviceType,
+    config: Arc<SimulationConfig>,
+    devices: Arc<Mutex<HashMap<String, SimDevice>>>,
+}
+
+struct SimDevice {
+    running: String,
+    /// Running config before the last applied push, for rollbacks without a snapshot.
+    previous: Option<String>,
+    rng: Rng,
+}
+
+impl SimulatedDriver {
+    pub fn new(device_type: DeviceType, config: Arc<SimulationConfig>) -> Self {
+        Self {
+            device_type,
+            config,
+            devices: Arc::default(),
+        }
+    }
+
+    /// The device's current running config.
+    pub fn running_config(&self, device: &Device) -> String {
+        self.with_device(device, |sim| sim.running.clone())
+    }
+
+    fn with_device<T>(&self, device: &Device, f: impl FnOnce(&mut SimDevice) -> T) -> T {
+        let mut devices = self.devices.lock().unwrap();
+        let sim = devices.entry(device.id.clone()).or_insert_with(|| {
+            let profile = self.config.profile(device);
+            let running = match profile.running_config.as_str() {
+                "" => format!("hostname {}\n", device.name),
+                text => text.replace("{name}", &device.name),
+            };
+            SimDevice {
+                running,
+                previous: None,
+                rng: Rng::new(self.config.seed ^ fnv1a(device.id.as_bytes())),
+            }
+        });
+        f(sim)
+    }
+
+    async fn command_delay(&self, device: &Device, profile: &SimProfile) {
+        let delay = self.with_device(device, |sim| profile.command.sample(&mut sim.rng));
+        tokio::time::sleep(delay).await;
+    }
+
+    async fn push(
+        &self,
+        device: &Device,
+        profile: &SimProfile,
+        snippet: &str,
+        mode: ConfigMode,
+        ctx: &JobContext,
+        res: &mut DriverExecutionResult,
+    ) {
+        self.command_delay(device, profile).await;
+        let before = self.running_config(device);
+        let after = match mode {
+            ConfigMode::Merge => preview::merge(&before, snippet),
+            ConfigMode::Replace => ConfigTree::parse_indented(snippet).render(),
+        };
+        res.diff = Some(config_tree::render_diff(&before, &after));
+        res.pre_snapshot = Some(before.clone());
+        if ctx.dry_run {
+            res.logs
+                .push(format!("[sim] {} dry run, config unchanged", device.name));
+            return;
+        }
+        self.with_device(device, |sim| {
+            sim.previous = Some(before);
+            sim.running = after.clone();
+        });
+        res.post_snapshot = Some(after);
+        res.logs
+            .push(format!("[sim] {} applied {:?} push", device.name, mode));
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for SimulatedDriver {
+    fn device_type(&self) -> DeviceType {
+        self.device_type.clone()
+    }
+
+    fn name(&self) -> &'static str {
+        "Simulated Driver"
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        CapabilitySet {
+            supports_commit: true,
+            supports_rollback: true,
+            supports_diff: true,
+            supports_dry_run: true,
+            supports_replace: true,
+        }
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        let profile = self.config.profile(device);
+        let (delay, failed) = self.with_device(device, |sim| {
+            let delay = profile.connect.sample(&mut sim.rng);
+            (delay, sim.rng.next_f64() < profile.failure_rate)
+        });
+        tokio::time::sleep(delay).await;
+        if failed {
+            bail!("simulated session failure on {}", device.name);
+        }
+
+        let mut res = DriverExecutionResult::default();
+        match action.job_kind() {
+            JobKind::CommandBatch { commands } => {
+                for command in commands {
+                    self.command_delay(device, profile).await;
+                    let raw = match profile.outputs.get(command) {
+                        Some(output) => output.replace("{name}", &device.name),
+                        None if command == "show running-config" => self.running_config(device),
+                        None => format!("{command}: simulated output\n"),
+                    };
+                    res.outputs.push(CommandOutput {
+                        command: command.clone(),
+                        raw,
+                        parsed: None,
+                    });
+                }
+            }
+            JobKind::ConfigPush { snippet, mode } => {
+                self.push(device, profile, snippet, *mode, ctx, &mut res)
+                    .await;
+            }
+            JobKind::ConfigBackup {
+                include_startup, ..
+            } => {
+                self.command_delay(device, profile).await;
+                let running = self.running_config(device);
+                res.backup = Some(ConfigSnapshot {
+                    startup: include_startup.then(|| running.clone()),
+                    running,
+                });
+            }
+            JobKind::Getters { which } => {
+                self.command_delay(device, profile).await;
+                let mut getters = GetterResults::default();
+                if which.contains(&Getter::Facts) {
+                    let running = self.running_config(device);
+                    let hostname = running
+                        .lines()
+                        .find_map(|line| line.strip_prefix("hostname "))
+                        .unwrap_or(&device.name);
+                    getters.facts = Some(DeviceFacts {
+                        hostname: hostname.trim().to_string(),
+                        vendor: "simulated".into(),
+                        os_version: profile.os_version.clone(),
+                        ..Default::default()
+                    });
+                }
+                res.getters = Some(getters);
+            }
+            JobKind::Preflight => {
+                res.preflight = Some(self.preflight(device).await?);
+            }
+            other => bail!("{} does not simulate {:?}", self.name(), other),
+        }
+        res.logs
+            .push(format!("[sim] device={} action={:?}", device.name, action));
+        Ok(res)
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        let profile = self.config.profile(device);
+        self.command_delay(device, profile).await;
+        self.with_device(device, |sim| {
+            match snapshot.or_else(|| sim.previous.take()) {
+                Some(config) => sim.running = config,
+                None => bail!("no snapshot to roll {} back to", device.name),
+            }
+            Ok(())
+        })
+    }
+
+    /// Every check passes after the connect delay.
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let profile = self.config.profile(device);
+        let delay = self.with_device(device, |sim| profile.connect.sample(&mut sim.rng));
+        tokio::time::sleep(delay).await;
+        let check = |step| PreflightCheck {
+            step,
+            status: CheckStatus::Passed,
+            detail: String::new(),
+        };
+        Ok(PreflightReport {
+            endpoint: device.mgmt_address.clone(),
+            checks: vec![
+                check(PreflightStep::Dns),
+                check(PreflightStep::Tcp),
+                check(PreflightStep::HostKey),
+                check(PreflightStep::Auth),
+            ],
+        })
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// SplitMix64; plenty for jitter and failure draws.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Box-Muller.
+    fn standard_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str) -> Device {
+        Device {
+            id: id.into(),
+            name: id.into(),
+            device_type: DeviceType::CiscoIos,
+            mgmt_address: "sim".into(),
+            credential: nauto_model::CredentialRef { name: "sim".into() },
+            tags: Vec::new(),
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    async fn show_run(driver: &SimulatedDriver, device: &Device) -> String {
+        let job = JobKind::CommandBatch {
+            commands: vec!["show running-config".into()],
+        };
+        let res = driver
+            .execute(device, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .unwrap();
+        res.outputs[0].raw.clone()
+    }
+
+    #[tokio::test]
+    async fn pushes_and_rollbacks_change_the_running_config() {
+        let driver = SimulatedDriver::new(DeviceType::CiscoIos, Arc::default());
+        let r1 = device("r1");
+        let push = JobKind::ConfigPush {
+            snippet: "interface Gi1\n description uplink\nno hostname r1\nhostname core\n".into(),
+            mode: ConfigMode::Merge,
+        };
+        let dry = JobContext {
+            dry_run: true,
+            ..Default::default()
+        };
+        let planned = driver
+            .execute(&r1, DriverAction::Job(&push), &dry)
+            .await
+            .unwrap();
+        assert!(planned.diff.unwrap().contains("+ description uplink"));
+        assert_eq!(show_run(&driver, &r1).await, "hostname r1\n");
+
+        driver
+            .execute(&r1, DriverAction::Job(&push), &JobContext::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            show_run(&driver, &r1).await,
+            "interface Gi1\n description uplink\nhostname core\n"
+        );
+        driver.rollback(&r1, None).await.unwrap();
+        assert_eq!(show_run(&driver, &r1).await, "hostname r1\n");
+    }
+
+    #[tokio::test]
+    async fn profiles_pick_outputs_and_failures_per_device() {
+        let config: SimulationConfig = serde_yaml::from_str(
+            r#"
+seed: 7
+default:
+  command: { distribution: uniform, min_ms: 0, max_ms: 1 }
+  outputs:
+    show version: "{name} runs 17.9\n"
+profiles:
+  flaky:
+    failure_rate: 1.0
+"#,
+        )
+        .unwrap();
+        let driver = SimulatedDriver::new(DeviceType::CiscoIos, Arc::new(config));
+        let job = JobKind::CommandBatch {
+            commands: vec!["show version".into(), "show clock".into()],
+        };
+        let res = driver
+            .execute(
+                &device("r1"),
+                DriverAction::Job(&job),
+                &JobContext::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.outputs[0].raw, "r1 runs 17.9\n");
+        assert_eq!(res.outputs[1].raw, "show clock: simulated output\n");
+
+        let mut flaky = device("r2");
+        flaky.tags.push("sim:flaky".into());
+        assert!(driver
+            .execute(&flaky, DriverAction::Job(&job), &JobContext::default())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn latency_draws_follow_the_distribution() {
+        let mut rng = Rng::new(42);
+        let latency = Latency::LogNormal {
+            median_ms: 50.0,
+            sigma: 0.5,
+        };
+        let mut draws: Vec<Duration> = (0..2001).map(|_| latency.sample(&mut rng)).collect();
+        draws.sort();
+        let median = draws[1000].as_secs_f64() * 1000.0;
+        assert!((45.0..55.0).contains(&median), "median {median}ms");
+        assert!(draws[1980] > draws[1000] * 2, "no long tail");
+    }
+}
@@ -0,0 +1,281 @@
+//! Arista EOS getters from the JSON that `| json` and eAPI return.
+
+use super::{as_text, as_u64, normalize_mac, CommandRunner};
+use anyhow::Result;
+use nauto_model::{
+    ArpEntry, BgpNeighbor, DeviceFacts, Getter, GetterResults, InterfaceCounters, InterfaceInfo,
+    LldpNeighbor, MacEntry,
+};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub async fn collect(runner: &dyn CommandRunner, which: &[Getter]) -> Result<GetterResults> {
+    let mut results = GetterResults::default();
+    let mut interfaces_json: Option<Value> = None;
+    for getter in which {
+        match getter {
+            Getter::Facts => {
+                results.facts = Some(facts(
+                    &runner.json("show version").await?,
+                    &runner.json("show hostname").await?,
+                ))
+            }
+            Getter::Interfaces | Getter::InterfaceCounters => {
+                if interfaces_json.is_none() {
+                    interfaces_json = Some(runner.json("show interfaces").await?);
+                }
+                let output = interfaces_json.as_ref().unwrap_or(&Value::Null);
+                if *getter == Getter::Interfaces {
+                    results.interfaces = Some(interfaces(output));
+                } else {
+                    results.interface_counters = Some(interface_counters(output));
+                }
+            }
+            Getter::LldpNeighbors => {
+                results.lldp_neighbors = Some(lldp_neighbors(
+                    &runner.json("show lldp neighbors detail").await?,
+                ))
+            }
+            Getter::ArpTable => {
+                results.arp_table = Some(arp_table(&runner.json("show ip arp").await?))
+            }
+            Getter::MacTable => {
+                results.mac_table = Some(mac_table(&runner.json("show mac address-table").await?))
+            }
+            Getter::BgpNeighbors => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs_f64())
+                    .unwrap_or_default();
+                results.bgp_neighbors = Some(bgp_neighbors(
+                    &runner.json("show ip bgp summary vrf all").await?,
+                    now,
+                ))
+            }
+            Getter::Config => results.config = Some(runner.text("show running-config").await?),
+        }
+    }
+    Ok(results)
+}
+
+pub fn facts(version: &Value, hostname: &Value) -> DeviceFacts {
+    DeviceFacts {
+        hostname: as_text(&hostname["hostname"]),
+        vendor: "Arista".into(),
+        model: as_text(&version["modelName"]),
+        os_version: as_text(&version["version"]),
+        serial_number: as_text(&version["serialNumber"]),
+        uptime_seconds: as_u64(&version["uptime"]),
+    }
+}
+
+pub fn interfaces(output: &Value) -> Vec<InterfaceInfo> {
+    entries(&output["interfaces"])
+        .map(|(name, interface)| {
+            let ipv4_addresses = interface["interfaceAddress"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|address| {
+                    let primary = &address["primaryIp"];
+                    let ip = as_text(&primary["address"]);
+                    (!ip.is_empty() && ip != "0.0.0.0")
+                        .then(|| format!("{}/{}", ip, as_text(&primary["maskLen"])))
+                })
+                .collect();
+            InterfaceInfo {
+                name: name.to_string(),
+                description: as_text(&interface["description"]),
+                enabled: as_text(&interface["interfaceStatus"]) != "disabled",
+                is_up: as_text(&interface["lineProtocolStatus"]) == "up",
+                mtu: as_u64(&interface["mtu"]).map(|mtu| mtu as u32),
+                speed_mbps: as_u64(&interface["bandwidth"])
+                    .filter(|bps| *bps > 0)
+                    .map(|bps| bps / 1_000_000),
+                mac_address: normalize_mac(&as_text(&interface["physicalAddress"])),
+                ipv4_addresses,
+            }
+        })
+        .collect()
+}
+
+pub fn interface_counters(output: &Value) -> Vec<InterfaceCounters> {
+    entries(&output["interfaces"])
+        .filter(|(_, interface)| interface.get("interfaceCounters").is_some())
+        .map(|(name, interface)| {
+            let counters = &interface["interfaceCounters"];
+            let packets = |kinds: [&str; 3]| -> Option<u64> {
+                kinds
+                    .iter()
+                    .map(|kind| as_u64(&counters[*kind]))
+                    .sum::<Option<u64>>()
+            };
+            InterfaceCounters {
+                name: name.to_string(),
+                rx_octets: as_u64(&counters["inOctets"]),
+                tx_octets: as_u64(&counters["outOctets"]),
+                rx_packets: packets(["inUcastPkts", "inMulticastPkts", "inBroadcastPkts"]),
+                tx_packets: packets(["outUcastPkts", "outMulticastPkts", "outBroadcastPkts"]),
+                rx_errors: as_u64(&counters["totalInErrors"]),
+                tx_errors: as_u64(&counters["totalOutErrors"]),
+                rx_discards: as_u64(&counters["inDiscards"]),
+                tx_discards: as_u64(&counters["outDiscards"]),
+            }
+        })
+        .collect()
+}
+
+pub fn lldp_neighbors(output: &Value) -> Vec<LldpNeighbor> {
+    entries(&output["lldpNeighbors"])
+        .flat_map(|(port, entry)| {
+            entry["lldpNeighborInfo"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(move |neighbor| {
+                    let remote = &neighbor["neighborInterfaceInfo"];
+                    let remote_port = remote
+                        .get("interfaceId_v2")
+                        .or_else(|| remote.get("interfaceId"))
+                        .map(as_text)
+                        .unwrap_or_default();
+                    LldpNeighbor {
+                        local_interface: port.to_string(),
+                        remote_system_name: as_text(&neighbor["systemName"]),
+                        remote_port: remote_port.trim_matches('"').to_string(),
+                        remote_chassis_id: as_text(&neighbor["chassisId"]),
+                    }
+                })
+        })
+        .collect()
+}
+
+pub fn arp_table(output: &Value) -> Vec<ArpEntry> {
+    output["ipV4Neighbors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            Some(ArpEntry {
+                ip_address: as_text(&entry["address"]),
+                mac_address: normalize_mac(&as_text(&entry["hwAddress"]))?,
+                interface: as_text(&entry["interface"]),
+                age_seconds: entry["age"].as_f64(),
+            })
+        })
+        .collect()
+}
+
+pub fn mac_table(output: &Value) -> Vec<MacEntry> {
+    output["unicastTable"]["tableEntries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            Some(MacEntry {
+                mac_address: normalize_mac(&as_text(&entry["macAddress"]))?,
+                vlan: as_u64(&entry["vlanId"]).map(|vlan| vlan as u16),
+                interface: as_text(&entry["interface"]),
+                is_static: as_text(&entry["entryType"]).eq_ignore_ascii_case("static"),
+            })
+        })
+        .collect()
+}
+
+/// `upDownTime` is the epoch time of the last state change, so uptime is relative to `now`.
+pub fn bgp_neighbors(output: &Value, now: f64) -> Vec<BgpNeighbor> {
+    entries(&output["vrfs"])
+        .flat_map(|(vrf, table)| {
+            entries(&table["peers"]).map(move |(address, peer)| {
+                let state = as_text(&peer["peerState"]);
+                let is_up = state == "Established";
+                BgpNeighbor {
+                    peer_address: address.to_string(),
+                    remote_as: as_u64(&peer["asn"]).unwrap_or_default() as u32,
+                    vrf: vrf.to_string(),
+                    state,
+                    is_up,
+                    uptime_seconds: peer["upDownTime"]
+                        .as_f64()
+                        .filter(|changed| is_up && *changed > 0.0 && *changed <= now)
+                        .map(|changed| (now - changed) as u64),
+                    received_prefixes: as_u64(&peer["prefixReceived"]),
+                }
+            })
+        })
+        .collect()
+}
+
+fn entries(value: &Value) -> impl Iterator<Item = (&String, &Value)> {
+    value.as_object().into_iter().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn maps_eapi_interfaces_and_bgp_peers() {
+        let output = json!({
+            "interfaces": {
+                "Ethernet1": {
+                    "description": "to-spine1",
+                    "interfaceStatus": "connected",
+                    "lineProtocolStatus": "up",
+                    "mtu": 9214,
+                    "bandwidth": 10_000_000_000u64,
+                    "physicalAddress": "5254.0012.aaaa",
+                    "interfaceAddress": [{"primaryIp": {"address": "10.1.0.1", "maskLen": 31}}],
+                    "interfaceCounters": {
+                        "inOctets": 1000,
+                        "inUcastPkts": 5,
+                        "inMulticastPkts": 2,
+                        "inBroadcastPkts": 1,
+                        "totalInErrors": 0
+                    }
+                },
+                "Ethernet2": {
+                    "interfaceStatus": "disabled",
+                    "lineProtocolStatus": "down",
+                    "bandwidth": 0,
+                    "interfaceAddress": []
+                }
+            }
+        });
+        let parsed = interfaces(&output);
+        assert_eq!(parsed[0].speed_mbps, Some(10_000));
+        assert_eq!(parsed[0].ipv4_addresses, vec!["10.1.0.1/31".to_string()]);
+        assert_eq!(parsed[0].mac_address.as_deref(), Some("52:54:00:12:aa:aa"));
+        assert!(!parsed[1].enabled && parsed[1].speed_mbps.is_none());
+        let counters = interface_counters(&output);
+        assert_eq!(counters.len(), 1);
+        assert_eq!(counters[0].rx_packets, Some(8));
+        assert_eq!(counters[0].tx_packets, None);
+
+        let bgp = bgp_neighbors(
+            &json!({
+                "vrfs": {
+                    "default": {"peers": {"10.1.0.0": {
+                        "asn": "65100",
+                        "peerState": "Established",
+                        "upDownTime": 1_000.0,
+                        "prefixReceived": 12
+                    }}},
+                    "MGMT": {"peers": {"192.0.2.9": {
+                        "asn": 65200,
+                        "peerState": "Active",
+                        "upDownTime": 900.0
+                    }}}
+                }
+            }),
+            1_060.0,
+        );
+        assert_eq!(bgp.len(), 2);
+        assert_eq!(bgp[0].remote_as, 65100);
+        assert_eq!(bgp[0].uptime_seconds, Some(60));
+        assert_eq!(bgp[0].received_prefixes, Some(12));
+        assert_eq!(bgp[1].vrf, "MGMT");
+        assert!(!bgp[1].is_up && bgp[1].uptime_seconds.is_none());
+    }
+}
@@ -0,0 +1,256 @@
+//! Cisco IOS getters: text output parsed with the built-in TextFSM templates.
+
+use super::{normalize_mac, parse_uptime, CommandRunner};
+use crate::parsing::TemplateLibrary;
+use anyhow::{anyhow, Result};
+use nauto_model::{
+    ArpEntry, BgpNeighbor, DeviceFacts, DeviceType, Getter, GetterResults, InterfaceCounters,
+    InterfaceInfo, LldpNeighbor, MacEntry,
+};
+use serde_json::{Map, Value};
+
+const SHOW_VERSION: &str = "show version";
+const SHOW_INTERFACES: &str = "show interfaces";
+const SHOW_LLDP: &str = "show lldp neighbors detail";
+const SHOW_ARP: &str = "show ip arp";
+const SHOW_MAC: &str = "show mac address-table";
+const SHOW_BGP: &str = "show ip bgp summary";
+
+pub async fn collect(runner: &dyn CommandRunner, which: &[Getter]) -> Result<GetterResults> {
+    let mut results = GetterResults::default();
+    // `show interfaces` feeds both interface getters; run it once.
+    let mut interfaces_raw: Option<String> = None;
+    for getter in which {
+        match getter {
+            Getter::Facts => results.facts = Some(facts(&runner.text(SHOW_VERSION).await?)?),
+            Getter::Interfaces | Getter::InterfaceCounters => {
+                if interfaces_raw.is_none() {
+                    interfaces_raw = Some(runner.text(SHOW_INTERFACES).await?);
+                }
+                let raw = interfaces_raw.as_deref().unwrap_or_default();
+                if *getter == Getter::Interfaces {
+                    results.interfaces = Some(interfaces(raw)?);
+                } else {
+                    results.interface_counters = Some(interface_counters(raw)?);
+                }
+            }
+            Getter::LldpNeighbors => {
+                results.lldp_neighbors = Some(lldp_neighbors(&runner.text(SHOW_LLDP).await?)?)
+            }
+            Getter::ArpTable => results.arp_table = Some(arp_table(&runner.text(SHOW_ARP).await?)?),
+            Getter::MacTable => results.mac_table = Some(mac_table(&runner.text(SHOW_MAC).await?)?),
+            Getter::BgpNeighbors => {
+                results.bgp_neighbors = Some(bgp_neighbors(&runner.text(SHOW_BGP).await?)?)
+            }
+            Getter::Config => results.config = Some(runner.text("show running-config").await?),
+        }
+    }
+    Ok(results)
+}
+
+pub fn facts(raw: &str) -> Result<DeviceFacts> {
+    let records = records(SHOW_VERSION, raw)?;
+    let record = records
+        .first()
+        .ok_or_else(|| anyhow!("no data in '{}' output", SHOW_VERSION))?;
+    let serial = match record.get("serial") {
+        Some(Value::Array(serials)) => serials.first().map(super::as_text),
+        _ => None,
+    };
+    Ok(DeviceFacts {
+        hostname: field(record, "hostname"),
+        vendor: "Cisco".into(),
+        model: field(record, "hardware"),
+        os_version: field(record, "version"),
+        serial_number: serial.unwrap_or_default(),
+        uptime_seconds: parse_uptime(&field(record, "uptime")),
+    })
+}
+
+pub fn interfaces(raw: &str) -> Result<Vec<InterfaceInfo>> {
+    Ok(records(SHOW_INTERFACES, raw)?
+        .iter()
+        .map(|record| {
+            let address = field(record, "ip_address");
+            InterfaceInfo {
+                name: field(record, "interface"),
+                description: field(record, "description"),
+                enabled: field(record, "link_status") != "administratively down",
+                is_up: field(record, "protocol_status").starts_with("up"),
+                mtu: number(record, "mtu").map(|mtu| mtu as u32),
+                speed_mbps: number(record, "bandwidth").map(|kbit| kbit / 1000),
+                mac_address: normalize_mac(&field(record, "mac_address")),
+                ipv4_addresses: if address.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![address]
+                },
+            }
+        })
+        .collect())
+}
+
+pub fn interface_counters(raw: &str) -> Result<Vec<InterfaceCounters>> {
+    Ok(records(SHOW_INTERFACES, raw)?
+        .iter()
+        .map(|record| InterfaceCounters {
+            name: field(record, "interface"),
+            rx_octets: number(record, "input_bytes"),
+            tx_octets: number(record, "output_bytes"),
+            rx_packets: number(record, "input_packets"),
+            tx_packets: number(record, "output_packets"),
+            rx_errors: number(record, "input_errors"),
+            tx_errors: number(record, "output_errors"),
+            rx_discards: number(record, "input_drops"),
+            tx_discards: number(record, "output_drops"),
+        })
+        .collect())
+}
+
+pub fn lldp_neighbors(raw: &str) -> Result<Vec<LldpNeighbor>> {
+    Ok(records(SHOW_LLDP, raw)?
+        .iter()
+        .map(|record| LldpNeighbor {
+            local_interface: field(record, "local_interface"),
+            remote_system_name: field(record, "neighbor"),
+            remote_port: field(record, "neighbor_port_id"),
+            remote_chassis_id: field(record, "chassis_id"),
+        })
+        .collect())
+}
+
+pub fn arp_table(raw: &str) -> Result<Vec<ArpEntry>> {
+    Ok(records(SHOW_ARP, raw)?
+        .iter()
+        .filter_map(|record| {
+            Some(ArpEntry {
+                ip_address: field(record, "address"),
+                mac_address: normalize_mac(&field(record, "mac_address"))?,
+                interface: field(record, "interface"),
+                age_seconds: number(record, "age").map(|minutes| (minutes * 60) as f64),
+            })
+        })
+        .collect())
+}
+
+pub fn mac_table(raw: &str) -> Result<Vec<MacEntry>> {
+    Ok(records(SHOW_MAC, raw)?
+        .iter()
+        .filter_map(|record| {
+            Some(MacEntry {
+                mac_address: normalize_mac(&field(record, "mac_address"))?,
+                vlan: field(record, "vlan").parse().ok(),
+                interface: field(record, "ports"),
+                is_static: field(record, "type").eq_ignore_ascii_case("static"),
+            })
+        })
+        .collect())
+}
+
+pub fn bgp_neighbors(raw: &str) -> Result<Vec<BgpNeighbor>> {
+    Ok(records(SHOW_BGP, raw)?
+        .iter()
+        .map(|record| {
+            // The last column is the prefix count once the session is up, else the state.
+            let state_or_prefixes = field(record, "state_pfxrcd");
+            let received_prefixes = state_or_prefixes.parse::<u64>().ok();
+            let is_up = received_prefixes.is_some();
+            BgpNeighbor {
+                peer_address: field(record, "bgp_neigh"),
+                remote_as: field(record, "neigh_as").parse().unwrap_or_default(),
+                vrf: "default".into(),
+                state: if is_up {
+                    "Established".into()
+                } else {
+                    state_or_prefixes
+                },
+                is_up,
+                uptime_seconds: parse_uptime(&field(record, "up_down")),
+                received_prefixes,
+            }
+        })
+        .collect())
+}
+
+fn records(command: &str, raw: &str) -> Result<Vec<Map<String, Value>>> {
+    let parsed = TemplateLibrary::global()
+        .parse(&DeviceType::CiscoIos, command, raw)
+        .ok_or_else(|| anyhow!("no template for '{}'", command))??;
+    Ok(match parsed {
+        Value::Array(rows) => rows
+            .into_iter()
+            .filter_map(|row| match row {
+                Value::Object(record) => Some(record),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+fn field(record: &Map<String, Value>, key: &str) -> String {
+    record.get(key).map(super::as_text).unwrap_or_default()
+}
+
+fn number(record: &Map<String, Value>, key: &str) -> Option<u64> {
+    record.get(key).and_then(super::as_u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOW_INTERFACES_OUTPUT: &str = "\
+GigabitEthernet0/0 is up, line protocol is up
+  Hardware is iGbE, address is 5254.0012.3456 (bia 5254.0012.3456)
+  Description: uplink to core
+  Internet address is 10.0.0.1/24
+  MTU 1500 bytes, BW 1000000 Kbit/sec, DLY 10 usec,
+  Input queue: 0/75/3/0 (size/max/drops/flushes); Total output drops: 7
+     1234 packets input, 567890 bytes, 0 no buffer
+     0 input errors, 0 CRC, 0 frame, 0 overrun, 0 ignored
+     4321 packets output, 98765 bytes, 0 underruns
+     2 output errors, 0 collisions, 1 interface resets
+GigabitEthernet0/1 is administratively down, line protocol is down
+  Hardware is iGbE, address is 5254.0012.3457 (bia 5254.0012.3457)
+  MTU 1500 bytes, BW 1000000 Kbit/sec, DLY 10 usec,
+";
+
+    #[test]
+    fn parses_interfaces_counters_and_bgp_summary() {
+        let parsed = interfaces(SHOW_INTERFACES_OUTPUT).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].description, "uplink to core");
+        assert!(parsed[0].enabled && parsed[0].is_up);
+        assert_eq!(parsed[0].speed_mbps, Some(1000));
+        assert_eq!(parsed[0].mac_address.as_deref(), Some("52:54:00:12:34:56"));
+        assert_eq!(parsed[0].ipv4_addresses, vec!["10.0.0.1/24".to_string()]);
+        assert!(!parsed[1].enabled && !parsed[1].is_up);
+
+        let counters = interface_counters(SHOW_INTERFACES_OUTPUT).unwrap();
+        assert_eq!(counters[0].rx_octets, Some(567_890));
+        assert_eq!(counters[0].tx_errors, Some(2));
+        assert_eq!(counters[0].rx_discards, Some(3));
+        assert_eq!(counters[0].tx_discards, Some(7));
+        assert_eq!(counters[1].rx_octets, None);
+
+        let bgp = bgp_neighbors(
+            "\
+BGP router identifier 192.0.2.1, local AS number 65000
+BGP table version is 5, main routing table version 5
+
+Neighbor        V           AS MsgRcvd MsgSent   TblVer  InQ OutQ Up/Down  State/PfxRcd
+10.0.0.2        4        65001     120     118        5    0    0 01:45:10        3
+10.0.0.3        4        65002       0       0        1    0    0 never    Idle
+",
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 2);
+        assert_eq!(bgp[0].remote_as, 65001);
+        assert!(bgp[0].is_up);
+        assert_eq!(bgp[0].received_prefixes, Some(3));
+        assert_eq!(bgp[0].uptime_seconds, Some(6310));
+        assert_eq!(bgp[1].state, "Idle");
+        assert!(!bgp[1].is_up);
+    }
+}
@@ -0,0 +1,326 @@
+//! Juniper Junos getters from `| display json` output. Junos wraps every element in a
+//! one-element array and puts text under `data`, which the helpers below unwrap.
+
+use super::{as_u64, normalize_mac, parse_speed_mbps, CommandRunner};
+use anyhow::Result;
+use nauto_model::{
+    ArpEntry, BgpNeighbor, DeviceFacts, Getter, GetterResults, InterfaceCounters, InterfaceInfo,
+    LldpNeighbor, MacEntry,
+};
+use serde_json::Value;
+
+pub async fn collect(runner: &dyn CommandRunner, which: &[Getter]) -> Result<GetterResults> {
+    let mut results = GetterResults::default();
+    for getter in which {
+        match getter {
+            Getter::Facts => {
+                results.facts = Some(facts(
+                    &runner.json("show version").await?,
+                    &runner.json("show chassis hardware").await?,
+                    &runner.json("show system uptime").await?,
+                ))
+            }
+            Getter::Interfaces => {
+                results.interfaces = Some(interfaces(&runner.json("show interfaces").await?))
+            }
+            Getter::InterfaceCounters => {
+                results.interface_counters = Some(interface_counters(
+                    &runner.json("show interfaces extensive").await?,
+                ))
+            }
+            Getter::LldpNeighbors => {
+                results.lldp_neighbors =
+                    Some(lldp_neighbors(&runner.json("show lldp neighbors").await?))
+            }
+            Getter::ArpTable => {
+                results.arp_table = Some(arp_table(&runner.json("show arp no-resolve").await?))
+            }
+            Getter::MacTable => {
+                results.mac_table = Some(mac_table(
+                    &runner.json("show ethernet-switching table").await?,
+                ))
+            }
+            Getter::BgpNeighbors => {
+                results.bgp_neighbors = Some(bgp_neighbors(&runner.json("show bgp summary").await?))
+            }
+            Getter::Config => results.config = Some(runner.text("show configuration").await?),
+        }
+    }
+    Ok(results)
+}
+
+pub fn facts(version: &Value, hardware: &Value, uptime: &Value) -> DeviceFacts {
+    let software = first(version, "software-information");
+    let chassis = first(first(hardware, "chassis-inventory"), "chassis");
+    let booted = first(
+        first(
+            first(uptime, "system-uptime-information"),
+            "uptime-information",
+        ),
+        "up-time",
+    );
+    DeviceFacts {
+        hostname: data(software, "host-name"),
+        vendor: "Juniper".into(),
+        model: data(software, "product-model"),
+        os_version: data(software, "junos-version"),
+        serial_number: data(chassis, "serial-number"),
+        uptime_seconds: seconds(booted),
+    }
+}
+
+pub fn interfaces(output: &Value) -> Vec<InterfaceInfo> {
+    physical_interfaces(output)
+        .map(|interface| {
+            let ipv4_addresses = all(interface, "logical-interface")
+                .flat_map(|logical| all(logical, "address-family"))
+                .filter(|family| data(family, "address-family-name") == "inet")
+                .flat_map(|family| all(family, "interface-address"))
+                .filter_map(|address| {
+                    let local = data(address, "ifa-local");
+                    if local.is_empty() {
+                        return None;
+                    }
+                    let destination = data(address, "ifa-destination");
+                    Some(match destination.split_once('/') {
+                        Some((_, len)) => format!("{local}/{len}"),
+                        None => local,
+                    })
+                })
+                .collect();
+            InterfaceInfo {
+                name: data(interface, "name"),
+                description: data(interface, "description"),
+                enabled: data(interface, "admin-status") == "up",
+                is_up: data(interface, "oper-status") == "up",
+                mtu: data(interface, "mtu").parse().ok(),
+                speed_mbps: parse_speed_mbps(&data(interface, "speed")),
+                mac_address: normalize_mac(&data(interface, "current-physical-address")),
+                ipv4_addresses,
+            }
+        })
+        .collect()
+}
+
+pub fn interface_counters(output: &Value) -> Vec<InterfaceCounters> {
+    physical_interfaces(output)
+        .map(|interface| {
+            let traffic = first(interface, "traffic-statistics");
+            let input = first(interface, "input-error-list");
+            let output = first(interface, "output-error-list");
+            InterfaceCounters {
+                name: data(interface, "name"),
+                rx_octets: number(traffic, "input-bytes"),
+                tx_octets: number(traffic, "output-bytes"),
+                rx_packets: number(traffic, "input-packets"),
+                tx_packets: number(traffic, "output-packets"),
+                rx_errors: number(input, "input-errors"),
+                tx_errors: number(output, "output-errors"),
+                rx_discards: number(input, "input-drops"),
+                tx_discards: number(output, "output-drops"),
+            }
+        })
+        .collect()
+}
+
+pub fn lldp_neighbors(output: &Value) -> Vec<LldpNeighbor> {
+    all(
+        first(output, "lldp-neighbors-information"),
+        "lldp-neighbor-information",
+    )
+    .map(|neighbor| LldpNeighbor {
+        local_interface: or_else(
+            data(neighbor, "lldp-local-port-id"),
+            data(neighbor, "lldp-local-interface"),
+        ),
+        remote_system_name: data(neighbor, "lldp-remote-system-name"),
+        remote_port: or_else(
+            data(neighbor, "lldp-remote-port-id"),
+            data(neighbor, "lldp-remote-port-description"),
+        ),
+        remote_chassis_id: data(neighbor, "lldp-remote-chassis-id"),
+    })
+    .collect()
+}
+
+pub fn arp_table(output: &Value) -> Vec<ArpEntry> {
+    all(first(output, "arp-table-information"), "arp-table-entry")
+        .filter_map(|entry| {
+            Some(ArpEntry {
+                ip_address: data(entry, "ip-address"),
+                mac_address: normalize_mac(&data(entry, "mac-address"))?,
+                interface: data(entry, "interface-name"),
+                age_seconds: None,
+            })
+        })
+        .collect()
+}
+
+pub fn mac_table(output: &Value) -> Vec<MacEntry> {
+    all(
+        first(output, "l2ng-l2ald-rtb-macdb"),
+        "l2ng-l2ald-mac-entry-vlan",
+    )
+    .flat_map(|vlan| all(vlan, "l2ng-mac-entry"))
+    .filter_map(|entry| {
+        let vlan_id = data(entry, "l2ng-l2-vlan-id");
+        Some(MacEntry {
+            mac_address: normalize_mac(&data(entry, "l2ng-l2-mac-address"))?,
+            vlan: vlan_id
+                .parse()
+                .ok()
+                .or_else(|| data(entry, "l2ng-l2-mac-vlan-name").parse().ok()),
+            interface: data(entry, "l2ng-l2-mac-logical-interface"),
+            is_static: data(entry, "l2ng-l2-mac-flags").contains('S'),
+        })
+    })
+    .collect()
+}
+
+pub fn bgp_neighbors(output: &Value) -> Vec<BgpNeighbor> {
+    all(first(output, "bgp-information"), "bgp-peer")
+        .map(|peer| {
+            let state = data(peer, "peer-state");
+            // `inet.0` is the master instance; `BLUE.inet.0` belongs to routing instance BLUE.
+            let rib = data(first(peer, "bgp-rib"), "name");
+            let vrf = match rib.rsplit_once(".inet") {
+                Some((instance, _)) if !instance.is_empty() => instance.to_string(),
+                _ => "default".to_string(),
+            };
+            let address = data(peer, "peer-address");
+            BgpNeighbor {
+                peer_address: address.split('+').next().unwrap_or_default().to_string(),
+                remote_as: data(peer, "peer-as").parse().unwrap_or_default(),
+                vrf,
+                is_up: state == "Established",
+                state,
+                uptime_seconds: seconds(first(peer, "elapsed-time")),
+                received_prefixes: peer.get("bgp-rib").and_then(Value::as_array).map(|ribs| {
+                    ribs.iter()
+                        .filter_map(|rib| number(rib, "received-prefix-count"))
+                        .sum()
+                }),
+            }
+        })
+        .collect()
+}
+
+fn physical_interfaces(output: &Value) -> impl Iterator<Item = &Value> {
+    all(first(output, "interface-information"), "physical-interface")
+}
+
+/// `value[key][0]`, or `null` when any step is missing.
+fn first<'a>(value: &'a Value, key: &str) -> &'a Value {
+    value
+        .get(key)
+        .and_then(|items| items.get(0))
+        .unwrap_or(&Value::Null)
+}
+
+fn all<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// Text of `value[key][0].data`, trimmed of the newlines Junos pads it with.
+fn data(value: &Value, key: &str) -> String {
+    first(value, key)
+        .get("data")
+        .map(super::as_text)
+        .unwrap_or_default()
+}
+
+fn number(value: &Value, key: &str) -> Option<u64> {
+    first(value, key).get("data").and_then(as_u64)
+}
+
+/// Durations carry their value in seconds as a `junos:seconds` attribute.
+fn seconds(value: &Value) -> Option<u64> {
+    value
+        .get("attributes")
+        .and_then(|attributes| attributes.get("junos:seconds"))
+        .and_then(as_u64)
+}
+
+fn or_else(preferred: String, fallback: String) -> String {
+    if preferred.is_empty() {
+        fallback
+    } else {
+        preferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn unwraps_display_json_for_interfaces_and_bgp() {
+        let output = json!({
+            "interface-information": [{
+                "physical-interface": [{
+                    "name": [{"data": "ge-0/0/0"}],
+                    "admin-status": [{"data": "up"}],
+                    "oper-status": [{"data": "down"}],
+                    "mtu": [{"data": "1514"}],
+                    "speed": [{"data": "1000mbps"}],
+                    "current-physical-address": [{"data": "2c:6b:f5:00:11:22"}],
+                    "traffic-statistics": [{
+                        "input-bytes": [{"data": "1200"}],
+                        "output-packets": [{"data": "9"}]
+                    }],
+                    "logical-interface": [{
+                        "address-family": [
+                            {
+                                "address-family-name": [{"data": "inet"}],
+                                "interface-address": [{
+                                    "ifa-destination": [{"data": "10.0.0.0/30"}],
+                                    "ifa-local": [{"data": "10.0.0.1"}]
+                                }]
+                            },
+                            {
+                                "address-family-name": [{"data": "inet6"}],
+                                "interface-address": [{"ifa-local": [{"data": "fe80::1"}]}]
+                            }
+                        ]
+                    }]
+                }]
+            }]
+        });
+        let parsed = interfaces(&output);
+        assert_eq!(parsed.len(), 1);
+        assert!(parsed[0].enabled && !parsed[0].is_up);
+        assert_eq!(parsed[0].mtu, Some(1514));
+        assert_eq!(parsed[0].speed_mbps, Some(1000));
+        assert_eq!(parsed[0].ipv4_addresses, vec!["10.0.0.1/30".to_string()]);
+        let counters = interface_counters(&output);
+        assert_eq!(counters[0].rx_octets, Some(1200));
+        assert_eq!(counters[0].tx_packets, Some(9));
+        assert_eq!(counters[0].rx_errors, None);
+
+        let bgp = bgp_neighbors(&json!({
+            "bgp-information": [{
+                "bgp-peer": [{
+                    "peer-address": [{"data": "10.0.0.2+179"}],
+                    "peer-as": [{"data": "65001"}],
+                    "peer-state": [{"data": "Established"}],
+                    "elapsed-time": [{"data": "1:02", "attributes": {"junos:seconds": "62"}}],
+                    "bgp-rib": [{
+                        "name": [{"data": "BLUE.inet.0"}],
+                        "received-prefix-count": [{"data": "4"}]
+                    }]
+                }]
+            }]
+        }));
+        assert_eq!(bgp[0].peer_address, "10.0.0.2");
+        assert_eq!(bgp[0].remote_as, 65001);
+        assert_eq!(bgp[0].vrf, "BLUE");
+        assert!(bgp[0].is_up);
+        assert_eq!(bgp[0].uptime_seconds, Some(62));
+        assert_eq!(bgp[0].received_prefixes, Some(4));
+    }
+}
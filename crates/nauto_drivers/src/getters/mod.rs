@@ -0,0 +1,209 @@
+//! Normalized getters. Each platform module turns its native show output (TextFSM records,
+//! `| display json`, eAPI or NX-API JSON) into the `nauto_model` getter types, and drivers
+//! hand it a [`CommandRunner`] over the transport they already hold.
+
+pub mod eos;
+pub mod ios;
+pub mod junos;
+pub mod nxos;
+
+use crate::ssh;
+use crate::ssh::Client;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{Device, Getter};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    /// Plain text output of an exec-mode command.
+    async fn text(&self, command: &str) -> Result<String>;
+    /// Structured output of a command; platforms without JSON output return an error.
+    async fn json(&self, command: &str) -> Result<Value>;
+}
+
+/// Runs getter commands over an open SSH client. `json_suffix` is the pipe that makes the
+/// CLI print JSON (`| json` on EOS, `| display json` on Junos).
+pub struct SshRunner<'a> {
+    client: &'a Client,
+    device: &'a Device,
+    json_suffix: Option<&'static str>,
+}
+
+impl<'a> SshRunner<'a> {
+    pub fn new(client: &'a Client, device: &'a Device) -> Self {
+        Self {
+            client,
+            device,
+            json_suffix: None,
+        }
+    }
+
+    pub fn with_json_suffix(mut self, suffix: &'static str) -> Self {
+        self.json_suffix = Some(suffix);
+        self
+    }
+}
+
+#[async_trait]
+impl CommandRunner for SshRunner<'_> {
+    async fn text(&self, command: &str) -> Result<String> {
+        let exec = tokio::time::timeout(ssh::command_timeout(), self.client.execute(command))
+            .await
+            .with_context(|| format!("ssh exec timeout {} {}", self.device.name, command))?
+            .with_context(|| format!("ssh exec {} {}", self.device.name, command))?;
+        if exec.exit_status != 0 {
+            bail!(
+                "command '{}' failed on {} (status {}) stderr: {}",
+                command,
+                self.device.name,
+                exec.exit_status,
+                exec.stderr.trim()
+            );
+        }
+        Ok(exec.stdout)
+    }
+
+    async fn json(&self, command: &str) -> Result<Value> {
+        let Some(suffix) = self.json_suffix else {
+            bail!("{} has no JSON output for '{}'", self.device.name, command);
+        };
+        let output = self.text(&format!("{command} {suffix}")).await?;
+        serde_json::from_str(&output)
+            .with_context(|| format!("parse JSON from '{}' on {}", command, self.device.name))
+    }
+}
+
+/// Names of the getters in a request, for job logs.
+pub fn describe(which: &[Getter]) -> String {
+    which
+        .iter()
+        .map(Getter::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `5254.0012.abcd`, `52-54-00-12-AB-CD` or `52:54:00:12:ab:cd` as `52:54:00:12:ab:cd`.
+pub fn normalize_mac(raw: &str) -> Option<String> {
+    let hex: Vec<char> = raw
+        .chars()
+        .filter(|ch| !matches!(ch, '.' | ':' | '-'))
+        .collect();
+    if hex.len() != 12 || !hex.iter().all(char::is_ascii_hexdigit) {
+        return None;
+    }
+    let pairs: Vec<String> = hex
+        .chunks(2)
+        .map(|pair| pair.iter().collect::<String>().to_lowercase())
+        .collect();
+    Some(pairs.join(":"))
+}
+
+static DURATION_PART: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\d+)\s*([a-z]+)").expect("duration pattern"));
+
+/// Seconds in an uptime as devices print it: `01:02:03`, `1d02h`, `2w3d` or
+/// `2 weeks, 3 days, 4 hours, 5 minutes`. `never` and unknown formats give `None`.
+pub fn parse_uptime(raw: &str) -> Option<u64> {
+    let raw = raw.trim().to_lowercase();
+    if raw.is_empty() || raw == "never" {
+        return None;
+    }
+    if raw.contains(':') {
+        let mut seconds = 0u64;
+        for part in raw.split(':') {
+            seconds = seconds * 60 + part.trim().parse::<u64>().ok()?;
+        }
+        return Some(seconds);
+    }
+    let mut seconds = 0u64;
+    let mut matched = false;
+    for part in DURATION_PART.captures_iter(&raw) {
+        let value: u64 = part[1].parse().ok()?;
+        let unit = &part[2];
+        let scale = if unit.starts_with('y') {
+            365 * 86_400
+        } else if unit.starts_with('w') {
+            7 * 86_400
+        } else if unit.starts_with('d') {
+            86_400
+        } else if unit.starts_with('h') {
+            3_600
+        } else if unit.starts_with('m') {
+            60
+        } else if unit.starts_with('s') {
+            1
+        } else {
+            return None;
+        };
+        seconds += value * scale;
+        matched = true;
+    }
+    matched.then_some(seconds)
+}
+
+/// Link speed such as `1000mbps`, `10Gbps` or `100G` in Mbit/s.
+pub fn parse_speed_mbps(raw: &str) -> Option<u64> {
+    let raw = raw.trim().to_lowercase();
+    let digits: String = raw.chars().take_while(char::is_ascii_digit).collect();
+    let value: u64 = digits.parse().ok()?;
+    match raw[digits.len()..].trim_start().chars().next() {
+        Some('g') => Some(value * 1000),
+        Some('m') | None => Some(value),
+        Some('k') => Some(value / 1000),
+        _ => None,
+    }
+}
+
+/// Counters and numbers arrive as JSON numbers or as strings depending on the platform.
+pub fn as_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .or_else(|| number.as_f64().map(|float| float as u64)),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// A JSON string field, or an empty string when it is missing.
+pub fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_macs_and_durations() {
+        assert_eq!(
+            normalize_mac("5254.00AB.cdef").as_deref(),
+            Some("52:54:00:ab:cd:ef")
+        );
+        assert_eq!(
+            normalize_mac("52-54-00-ab-cd-ef").as_deref(),
+            Some("52:54:00:ab:cd:ef")
+        );
+        assert_eq!(normalize_mac("Incomplete"), None);
+
+        assert_eq!(parse_uptime("01:45:10"), Some(6310));
+        assert_eq!(parse_uptime("1d02h"), Some(93_600));
+        assert_eq!(parse_uptime("2w3d"), Some(17 * 86_400));
+        assert_eq!(
+            parse_uptime("1 year, 2 weeks, 3 days, 4 hours, 5 minutes"),
+            Some(365 * 86_400 + 17 * 86_400 + 4 * 3600 + 300)
+        );
+        assert_eq!(parse_uptime("never"), None);
+
+        assert_eq!(parse_speed_mbps("1000mbps"), Some(1000));
+        assert_eq!(parse_speed_mbps("10Gbps"), Some(10_000));
+        assert_eq!(parse_speed_mbps("Unlimited"), None);
+    }
+}
@@ -0,0 +1,265 @@
+//! Cisco NX-OS getters from NX-API `cli_show` JSON, where tables appear as
+//! `TABLE_x.ROW_x` holding either one row object or an array of rows.
+
+use super::{as_text, as_u64, normalize_mac, parse_uptime, CommandRunner};
+use anyhow::Result;
+use nauto_model::{
+    ArpEntry, BgpNeighbor, DeviceFacts, Getter, GetterResults, InterfaceCounters, InterfaceInfo,
+    LldpNeighbor, MacEntry,
+};
+use serde_json::Value;
+
+pub async fn collect(runner: &dyn CommandRunner, which: &[Getter]) -> Result<GetterResults> {
+    let mut results = GetterResults::default();
+    let mut interfaces_json: Option<Value> = None;
+    for getter in which {
+        match getter {
+            Getter::Facts => results.facts = Some(facts(&runner.json("show version").await?)),
+            Getter::Interfaces | Getter::InterfaceCounters => {
+                if interfaces_json.is_none() {
+                    interfaces_json = Some(runner.json("show interface").await?);
+                }
+                let output = interfaces_json.as_ref().unwrap_or(&Value::Null);
+                if *getter == Getter::Interfaces {
+                    results.interfaces = Some(interfaces(output));
+                } else {
+                    results.interface_counters = Some(interface_counters(output));
+                }
+            }
+            Getter::LldpNeighbors => {
+                results.lldp_neighbors = Some(lldp_neighbors(
+                    &runner.json("show lldp neighbors detail").await?,
+                ))
+            }
+            Getter::ArpTable => {
+                results.arp_table = Some(arp_table(&runner.json("show ip arp vrf all").await?))
+            }
+            Getter::MacTable => {
+                results.mac_table = Some(mac_table(&runner.json("show mac address-table").await?))
+            }
+            Getter::BgpNeighbors => {
+                results.bgp_neighbors = Some(bgp_neighbors(
+                    &runner.json("show ip bgp summary vrf all").await?,
+                ))
+            }
+            Getter::Config => results.config = Some(runner.text("show running-config").await?),
+        }
+    }
+    Ok(results)
+}
+
+pub fn facts(version: &Value) -> DeviceFacts {
+    let uptime = [
+        ("kern_uptm_days", 86_400),
+        ("kern_uptm_hrs", 3_600),
+        ("kern_uptm_mins", 60),
+        ("kern_uptm_secs", 1),
+    ]
+    .iter()
+    .map(|(key, scale)| as_u64(&version[*key]).map(|value| value * scale))
+    .sum::<Option<u64>>();
+    let os_version = ["nxos_ver_str", "sys_ver_str", "kickstart_ver_str"]
+        .iter()
+        .map(|key| as_text(&version[*key]))
+        .find(|text| !text.is_empty())
+        .unwrap_or_default();
+    DeviceFacts {
+        hostname: as_text(&version["host_name"]),
+        vendor: "Cisco".into(),
+        model: as_text(&version["chassis_id"]),
+        os_version,
+        serial_number: as_text(&version["proc_board_id"]),
+        uptime_seconds: uptime,
+    }
+}
+
+pub fn interfaces(output: &Value) -> Vec<InterfaceInfo> {
+    rows(output, "interface")
+        .into_iter()
+        .map(|row| {
+            // Routed ports report `eth_*` fields, SVIs the same data as `svi_*`.
+            let pick = |eth: &str, svi: &str| {
+                row.get(eth)
+                    .or_else(|| row.get(svi))
+                    .unwrap_or(&Value::Null)
+            };
+            let address = as_text(pick("eth_ip_addr", "svi_ip_addr"));
+            let admin = as_text(pick("admin_state", "svi_admin_state"));
+            InterfaceInfo {
+                name: as_text(&row["interface"]),
+                description: as_text(pick("desc", "svi_desc")),
+                enabled: admin.is_empty() || admin == "up",
+                is_up: as_text(pick("state", "svi_line_proto")) == "up",
+                mtu: as_u64(pick("eth_mtu", "svi_mtu")).map(|mtu| mtu as u32),
+                speed_mbps: as_u64(pick("eth_bw", "svi_bw")).map(|kbit| kbit / 1000),
+                mac_address: normalize_mac(&as_text(pick("eth_hw_addr", "svi_mac"))),
+                ipv4_addresses: if address.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![format!(
+                        "{}/{}",
+                        address,
+                        as_text(pick("eth_ip_mask", "svi_ip_mask"))
+                    )]
+                },
+            }
+        })
+        .collect()
+}
+
+pub fn interface_counters(output: &Value) -> Vec<InterfaceCounters> {
+    rows(output, "interface")
+        .into_iter()
+        .filter(|row| row.get("eth_inbytes").is_some())
+        .map(|row| InterfaceCounters {
+            name: as_text(&row["interface"]),
+            rx_octets: as_u64(&row["eth_inbytes"]),
+            tx_octets: as_u64(&row["eth_outbytes"]),
+            rx_packets: as_u64(&row["eth_inpkts"]),
+            tx_packets: as_u64(&row["eth_outpkts"]),
+            rx_errors: as_u64(&row["eth_inerr"]),
+            tx_errors: as_u64(&row["eth_outerr"]),
+            rx_discards: as_u64(&row["eth_indiscard"]),
+            tx_discards: as_u64(&row["eth_outdiscard"]),
+        })
+        .collect()
+}
+
+pub fn lldp_neighbors(output: &Value) -> Vec<LldpNeighbor> {
+    rows(output, "nbor_detail")
+        .into_iter()
+        .map(|row| LldpNeighbor {
+            local_interface: as_text(&row["l_port_id"]),
+            remote_system_name: as_text(&row["sys_name"]),
+            remote_port: as_text(&row["port_id"]),
+            remote_chassis_id: as_text(&row["chassis_id"]),
+        })
+        .collect()
+}
+
+pub fn arp_table(output: &Value) -> Vec<ArpEntry> {
+    rows(output, "vrf")
+        .into_iter()
+        .flat_map(|vrf| rows(vrf, "adj"))
+        .filter_map(|row| {
+            Some(ArpEntry {
+                ip_address: as_text(&row["ip-addr-out"]),
+                mac_address: normalize_mac(&as_text(&row["mac"]))?,
+                interface: as_text(&row["intf-out"]),
+                age_seconds: parse_uptime(&as_text(&row["time-stamp"])).map(|secs| secs as f64),
+            })
+        })
+        .collect()
+}
+
+pub fn mac_table(output: &Value) -> Vec<MacEntry> {
+    rows(output, "mac_address")
+        .into_iter()
+        .filter_map(|row| {
+            Some(MacEntry {
+                mac_address: normalize_mac(&as_text(&row["disp_mac_addr"]))?,
+                vlan: as_u64(&row["disp_vlan"]).map(|vlan| vlan as u16),
+                interface: as_text(&row["disp_port"]),
+                is_static: matches!(
+                    as_text(&row["disp_is_static"]).as_str(),
+                    "enabled" | "true" | "1"
+                ),
+            })
+        })
+        .collect()
+}
+
+pub fn bgp_neighbors(output: &Value) -> Vec<BgpNeighbor> {
+    let mut neighbors = Vec::new();
+    for vrf in rows(output, "vrf") {
+        let vrf_name = as_text(&vrf["vrf-name-out"]);
+        for neighbor in rows(vrf, "af")
+            .into_iter()
+            .flat_map(|af| rows(af, "saf"))
+            .flat_map(|saf| rows(saf, "neighbor"))
+        {
+            let state = as_text(&neighbor["state"]);
+            let is_up = state == "Established";
+            neighbors.push(BgpNeighbor {
+                peer_address: as_text(&neighbor["neighborid"]),
+                remote_as: as_u64(&neighbor["neighboras"]).unwrap_or_default() as u32,
+                vrf: vrf_name.clone(),
+                state,
+                is_up,
+                uptime_seconds: parse_uptime(&as_text(&neighbor["time"])),
+                received_prefixes: as_u64(&neighbor["prefixreceived"]),
+            });
+        }
+    }
+    neighbors
+}
+
+/// Rows of `TABLE_<name>.ROW_<name>`, whether NX-API sent one row or several.
+fn rows<'a>(value: &'a Value, name: &str) -> Vec<&'a Value> {
+    match &value[format!("TABLE_{name}").as_str()][format!("ROW_{name}").as_str()] {
+        Value::Array(items) => items.iter().collect(),
+        Value::Null => Vec::new(),
+        row => vec![row],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_single_and_multi_row_tables() {
+        let version = json!({
+            "host_name": "nx1",
+            "nxos_ver_str": "10.2(3)",
+            "chassis_id": "Nexus9000 C9300v Chassis",
+            "proc_board_id": "9ABCDEF",
+            "kern_uptm_days": 1,
+            "kern_uptm_hrs": 2,
+            "kern_uptm_mins": 0,
+            "kern_uptm_secs": 5
+        });
+        let facts = facts(&version);
+        assert_eq!(facts.hostname, "nx1");
+        assert_eq!(facts.uptime_seconds, Some(93_605));
+
+        let output = json!({"TABLE_interface": {"ROW_interface": {
+            "interface": "Ethernet1/1",
+            "state": "up",
+            "admin_state": "up",
+            "eth_mtu": "9216",
+            "eth_bw": 10_000_000,
+            "eth_hw_addr": "5254.0099.0001",
+            "eth_ip_addr": "10.2.0.1",
+            "eth_ip_mask": 30,
+            "eth_inbytes": "400",
+            "eth_outdiscard": "1"
+        }}});
+        let parsed = interfaces(&output);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].mtu, Some(9216));
+        assert_eq!(parsed[0].speed_mbps, Some(10_000));
+        assert_eq!(parsed[0].ipv4_addresses, vec!["10.2.0.1/30".to_string()]);
+        let counters = interface_counters(&output);
+        assert_eq!(counters[0].rx_octets, Some(400));
+        assert_eq!(counters[0].tx_discards, Some(1));
+
+        let bgp = bgp_neighbors(&json!({"TABLE_vrf": {"ROW_vrf": [
+            {"vrf-name-out": "default", "TABLE_af": {"ROW_af": {"TABLE_saf": {"ROW_saf": {
+                "TABLE_neighbor": {"ROW_neighbor": [
+                    {"neighborid": "10.2.0.2", "neighboras": "65010", "state": "Established",
+                     "time": "1d02h", "prefixreceived": "7"},
+                    {"neighborid": "10.2.0.6", "neighboras": "65011", "state": "Idle",
+                     "time": "never"}
+                ]}
+            }}}}},
+            {"vrf-name-out": "tenant-a"}
+        ]}}));
+        assert_eq!(bgp.len(), 2);
+        assert_eq!(bgp[0].uptime_seconds, Some(93_600));
+        assert_eq!(bgp[0].received_prefixes, Some(7));
+        assert_eq!(bgp[1].state, "Idle");
+        assert!(!bgp[1].is_up);
+    }
+}
@@ -1,11 +1,32 @@
+pub mod backup;
+pub mod cli;
 pub mod config;
+pub mod config_tree;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
 pub mod drivers;
+pub mod getters;
+pub mod netconf;
+pub mod parsing;
+pub mod preflight;
+pub mod preview;
+pub mod registry;
+pub mod remediation;
+pub mod replay;
 pub mod ssh;
+pub mod transfer;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use nauto_model::{CapabilitySet, Device, DeviceType, JobKind};
+use nauto_model::{
+    CapabilitySet, CommandOutput, ConfigSnapshot, Device, DeviceType, Getter, GetterResults,
+    JobKind, PreflightReport,
+};
+use nauto_security::CredentialStore;
 use std::sync::Arc;
+use uuid::Uuid;
+
+pub use registry::{DriverRegistry, Transport, VersionReq};
 
 #[derive(Debug, Clone)]
 pub enum DriverAction<'a> {
@@ -20,12 +41,28 @@ impl<'a> DriverAction<'a> {
     }
 }
 
+/// Job-level details the engine passes alongside each action.
+#[derive(Debug, Clone, Default)]
+pub struct JobContext {
+    pub job_id: Option<Uuid>,
+    pub job_name: String,
+    pub dry_run: bool,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct DriverExecutionResult {
     pub logs: Vec<String>,
     pub pre_snapshot: Option<String>,
     pub post_snapshot: Option<String>,
     pub diff: Option<String>,
+    /// Full output of every command run for a CommandBatch, with parsed records if any.
+    pub outputs: Vec<CommandOutput>,
+    /// Normalized data collected for a Getters job.
+    pub getters: Option<GetterResults>,
+    /// Configuration fetched by a ConfigBackup job.
+    pub backup: Option<ConfigSnapshot>,
+    /// Reachability and login checks from a Preflight job.
+    pub preflight: Option<PreflightReport>,
 }
 
 #[async_trait]
@@ -33,33 +70,58 @@ pub trait DeviceDriver: Send + Sync {
     fn device_type(&self) -> DeviceType;
     fn name(&self) -> &'static str;
     fn capabilities(&self) -> CapabilitySet;
+
+    /// Transports this driver reaches devices over; the registry matches them against the
+    /// device's `transport:` tags.
+    fn transports(&self) -> Vec<Transport> {
+        vec![Transport::Ssh]
+    }
+
+    /// Platform versions this driver is limited to. A limited driver takes precedence over
+    /// unlimited ones for devices whose `os_version:` tag matches.
+    fn versions(&self) -> Option<VersionReq> {
+        None
+    }
+
+    /// A copy of this driver that resolves credentials from `store`. Drivers that never
+    /// log in (mock, simulated, plugins) return `None` and are used unchanged.
+    fn with_credentials(&self, _store: Arc<dyn CredentialStore>) -> Option<DynDeviceDriver> {
+        None
+    }
+
     async fn execute(
         &self,
         device: &Device,
         action: DriverAction<'_>,
+        ctx: &JobContext,
     ) -> Result<DriverExecutionResult>;
     async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()>;
-}
-
-pub type DynDeviceDriver = Arc<dyn DeviceDriver>;
 
-pub struct DriverRegistry {
-    drivers: Vec<DynDeviceDriver>,
-}
-
-impl DriverRegistry {
-    pub fn new(drivers: Vec<DynDeviceDriver>) -> Self {
-        Self { drivers }
+    /// Checks that the device resolves, accepts a connection on its management port and
+    /// lets the credential log in, without running anything. Backs Preflight jobs.
+    async fn preflight(&self, _device: &Device) -> Result<PreflightReport> {
+        Err(anyhow!(
+            "{} does not support pre-flight checks",
+            self.name()
+        ))
     }
 
-    pub fn find(&self, device_type: &DeviceType) -> Option<DynDeviceDriver> {
-        self.drivers
-            .iter()
-            .find(|driver| &driver.device_type() == device_type)
-            .cloned()
+    /// Runs the requested getters outside of a job and returns the normalized results.
+    async fn get(&self, device: &Device, which: &[Getter]) -> Result<GetterResults> {
+        let kind = JobKind::Getters {
+            which: which.to_vec(),
+        };
+        let result = self
+            .execute(device, DriverAction::Job(&kind), &JobContext::default())
+            .await?;
+        result
+            .getters
+            .ok_or_else(|| anyhow!("{} returned no getter results", self.name()))
     }
 }
 
+pub type DynDeviceDriver = Arc<dyn DeviceDriver>;
+
 #[cfg(test)]
 mod tests {
     use super::drivers::{
@@ -101,6 +163,6 @@ mod tests {
         let meraki = registry
             .find(&nauto_model::DeviceType::MerakiCloud)
             .unwrap();
-        assert!(!meraki.capabilities().supports_rollback);
+        assert!(meraki.capabilities().supports_rollback);
     }
 }
@@ -0,0 +1,980 @@
+use crate::replay::{self, Exchange, NetconfExchange, NetconfHello, Tape};
+use crate::ssh::{self, Client};
+use anyhow::{anyhow, bail, Context, Result};
+use nauto_model::Device;
+use nauto_security::CredentialStore;
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use serde_json::Value;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+pub const BASE_1_0: &str = "urn:ietf:params:netconf:base:1.0";
+pub const BASE_1_1: &str = "urn:ietf:params:netconf:base:1.1";
+pub const CAP_CANDIDATE: &str = "urn:ietf:params:netconf:capability:candidate:1.0";
+pub const CAP_CONFIRMED_COMMIT: &str = "urn:ietf:params:netconf:capability:confirmed-commit:1.1";
+pub const CAP_VALIDATE: &str = "urn:ietf:params:netconf:capability:validate:1.1";
+pub const CAP_WRITABLE_RUNNING: &str = "urn:ietf:params:netconf:capability:writable-running:1.0";
+pub const CAP_ROLLBACK_ON_ERROR: &str = "urn:ietf:params:netconf:capability:rollback-on-error:1.0";
+pub const CAP_STARTUP: &str = "urn:ietf:params:netconf:capability:startup:1.0";
+pub const CAP_XPATH: &str = "urn:ietf:params:netconf:capability:xpath:1.0";
+
+const NETCONF_NS: &str = "urn:ietf:params:xml:ns:netconf:base:1.0";
+const NETCONF_EOM: &str = "]]>]]>";
+const READ_CHUNK: usize = 4096;
+const MAX_CHUNK_SIZE: u64 = 4_294_967_295;
+
+trait NetconfIo: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T> NetconfIo for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
+
+/// Message framing negotiated during the hello exchange (RFC 6242).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// base:1.0 `]]>]]>` delimited messages.
+    EndOfMessage,
+    /// base:1.1 chunked framing.
+    Chunked,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Datastore {
+    Running,
+    Candidate,
+    Startup,
+}
+
+impl Datastore {
+    fn as_xml(&self) -> &'static str {
+        match self {
+            Datastore::Running => "<running/>",
+            Datastore::Candidate => "<candidate/>",
+            Datastore::Startup => "<startup/>",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Filter {
+    Subtree(String),
+    XPath(String),
+}
+
+impl Filter {
+    fn to_xml(&self) -> String {
+        match self {
+            Filter::Subtree(body) => format!(r#"<filter type="subtree">{body}</filter>"#),
+            Filter::XPath(select) => {
+                format!(r#"<filter type="xpath" select="{}"/>"#, escape_attr(select))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefaultOperation {
+    Merge,
+    Replace,
+    None,
+}
+
+impl DefaultOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DefaultOperation::Merge => "merge",
+            DefaultOperation::Replace => "replace",
+            DefaultOperation::None => "none",
+        }
+    }
+}
+
+/// Payload of an `<edit-config>` request.
+#[derive(Clone, Debug)]
+pub enum EditContent {
+    /// XML element tree placed inside `<config>`.
+    Xml(String),
+    /// Vendor text configuration placed inside `<config-text>` (Junos).
+    Text(String),
+}
+
+impl EditContent {
+    fn to_xml(&self) -> String {
+        match self {
+            EditContent::Xml(body) => format!("<config>{body}</config>"),
+            EditContent::Text(text) => format!(
+                "<config-text><configuration-text>{}</configuration-text></config-text>",
+                cdata(text)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorSeverity {
+    #[default]
+    Error,
+    Warning,
+}
+
+/// A single `<rpc-error>` element from an `<rpc-reply>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RpcError {
+    pub error_type: String,
+    pub tag: String,
+    pub severity: ErrorSeverity,
+    pub app_tag: Option<String>,
+    pub path: Option<String>,
+    pub message: Option<String>,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.error_type, self.tag)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " (at {path})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned (inside `anyhow::Error`) when a reply carries error-severity `<rpc-error>`s.
+#[derive(Debug, Error)]
+#[error("netconf rpc-error: {}", join_errors(.errors))]
+pub struct NetconfRpcError {
+    pub errors: Vec<RpcError>,
+}
+
+fn join_errors(errors: &[RpcError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RpcReply {
+    pub message_id: Option<String>,
+    pub ok: bool,
+    /// Raw inner XML of the `<data>` element, when present.
+    pub data: Option<String>,
+    pub errors: Vec<RpcError>,
+    pub raw: String,
+}
+
+impl RpcReply {
+    pub fn warnings(&self) -> impl Iterator<Item = &RpcError> {
+        self.errors
+            .iter()
+            .filter(|err| err.severity == ErrorSeverity::Warning)
+    }
+
+    fn into_result(self) -> Result<RpcReply> {
+        let errors: Vec<RpcError> = self
+            .errors
+            .iter()
+            .filter(|err| err.severity == ErrorSeverity::Error)
+            .cloned()
+            .collect();
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(NetconfRpcError { errors }.into())
+        }
+    }
+}
+
+pub struct NetconfSession {
+    #[allow(dead_code)]
+    client: Option<Client>,
+    stream: Pin<Box<dyn NetconfIo>>,
+    buffer: Vec<u8>,
+    framing: Framing,
+    capabilities: Vec<String>,
+    session_id: Option<u32>,
+    next_id: u32,
+    /// Records or replays the hello and every RPC (see [`crate::replay`]).
+    tape: Option<Arc<Tape>>,
+}
+
+impl NetconfSession {
+    pub async fn connect(
+        device: &Device,
+        store: &dyn CredentialStore,
+        port: u16,
+    ) -> Result<NetconfSession> {
+        let tape = replay::tape(device)?;
+        if tape.as_ref().is_some_and(|tape| tape.is_replay()) {
+            // Nothing is read from or written to the stream while replaying.
+            let (stream, _) = tokio::io::duplex(1);
+            return NetconfSession::open(stream, tape).await;
+        }
+        // Unencrypted NETCONF for the conformance stand-ins; never compiled into production builds.
+        #[cfg(any(test, feature = "conformance"))]
+        if let Some(addr) = device.mgmt_address.strip_prefix("tcp://") {
+            let stream = tokio::net::TcpStream::connect(addr)
+                .await
+                .with_context(|| format!("netconf tcp connect {} ({addr})", device.name))?;
+            return NetconfSession::open(stream, tape).await;
+        }
+        if device.mgmt_address.starts_with("tcp://") {
+            bail!(
+                "{}: tcp:// management addresses are only supported by the conformance stand-ins",
+                device.name
+            );
+        }
+        let client = ssh::connect(device, store, port).await?;
+        let channel = client
+            .get_channel()
+            .await
+            .with_context(|| format!("netconf channel {}", device.name))?;
+        channel
+            .request_subsystem(true, "netconf")
+            .await
+            .context("netconf subsystem denied")?;
+        let mut session = NetconfSession::open(channel.into_stream(), tape).await?;
+        session.client = Some(client);
+        debug!(
+            target: "drivers::netconf",
+            "{} session {:?} framing {:?}",
+            device.name,
+            session.session_id,
+            session.framing
+        );
+        Ok(session)
+    }
+
+    /// Runs the hello exchange over an already established transport.
+    pub async fn from_stream<S>(stream: S) -> Result<NetconfSession>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        NetconfSession::open(stream, None).await
+    }
+
+    async fn open<S>(stream: S, tape: Option<Arc<Tape>>) -> Result<NetconfSession>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut session = NetconfSession {
+            client: None,
+            stream: Box::pin(stream),
+            buffer: Vec::new(),
+            framing: Framing::EndOfMessage,
+            capabilities: Vec::new(),
+            session_id: None,
+            next_id: 1,
+            tape,
+        };
+        session.hello().await?;
+        Ok(session)
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    pub fn session_id(&self) -> Option<u32> {
+        self.session_id
+    }
+
+    pub fn server_capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    /// Checks for a capability URI, ignoring any `?module=...` parameters.
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|cap| cap.split('?').next() == Some(capability))
+    }
+
+    pub async fn rpc(&mut self, inner: &str) -> Result<RpcReply> {
+        let message_id = self.next_id;
+        self.next_id += 1;
+        let payload = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><rpc message-id="{message_id}" xmlns="{NETCONF_NS}">{inner}</rpc>"#
+        );
+        let replayed = self.tape.as_ref().is_some_and(|tape| tape.is_replay());
+        let raw = match self.tape.clone() {
+            Some(tape) if replayed => match tape.take("netconf", inner)? {
+                Exchange::Netconf(exchange) => exchange.reply,
+                _ => unreachable!("take filters by transport"),
+            },
+            tape => {
+                self.write_message(&payload)
+                    .await
+                    .context("write netconf rpc")?;
+                let raw = self.read_message().await?;
+                if let Some(tape) = tape {
+                    tape.record(Exchange::Netconf(NetconfExchange {
+                        rpc: inner.to_string(),
+                        reply: raw.clone(),
+                    }))?;
+                }
+                raw
+            }
+        };
+        let reply = parse_reply(&raw)?;
+        // Replayed replies keep the message-id they were recorded with.
+        if let Some(id) = reply.message_id.as_deref().filter(|_| !replayed) {
+            if id != message_id.to_string() {
+                bail!("netconf reply message-id {id} does not match request {message_id}");
+            }
+        }
+        reply.into_result()
+    }
+
+    pub async fn get(&mut self, filter: Option<&Filter>) -> Result<String> {
+        let filter = filter.map(Filter::to_xml).unwrap_or_default();
+        let reply = self.rpc(&format!("<get>{filter}</get>")).await?;
+        Ok(reply.data.unwrap_or_default())
+    }
+
+    pub async fn get_config(
+        &mut self,
+        source: Datastore,
+        filter: Option<&Filter>,
+    ) -> Result<String> {
+        let filter = filter.map(Filter::to_xml).unwrap_or_default();
+        let reply = self
+            .rpc(&format!(
+                "<get-config><source>{}</source>{filter}</get-config>",
+                source.as_xml()
+            ))
+            .await?;
+        Ok(reply.data.unwrap_or_default())
+    }
+
+    pub async fn edit_config(
+        &mut self,
+        target: Datastore,
+        content: &EditContent,
+        default_operation: Option<DefaultOperation>,
+    ) -> Result<RpcReply> {
+        let default_operation = default_operation
+            .map(|op| format!("<default-operation>{}</default-operation>", op.as_str()))
+            .unwrap_or_default();
+        self.rpc(&format!(
+            "<edit-config><target>{}</target>{default_operation}{}</edit-config>",
+            target.as_xml(),
+            content.to_xml()
+        ))
+        .await
+    }
+
+    pub async fn lock(&mut self, target: Datastore) -> Result<RpcReply> {
+        self.rpc(&format!(
+            "<lock><target>{}</target></lock>",
+            target.as_xml()
+        ))
+        .await
+    }
+
+    pub async fn unlock(&mut self, target: Datastore) -> Result<RpcReply> {
+        self.rpc(&format!(
+            "<unlock><target>{}</target></unlock>",
+            target.as_xml()
+        ))
+        .await
+    }
+
+    pub async fn validate(&mut self, source: Datastore) -> Result<RpcReply> {
+        self.rpc(&format!(
+            "<validate><source>{}</source></validate>",
+            source.as_xml()
+        ))
+        .await
+    }
+
+    pub async fn commit(&mut self) -> Result<RpcReply> {
+        self.rpc("<commit/>").await
+    }
+
+    /// Starts a confirmed commit; the server reverts unless a plain commit follows within `timeout`.
+    pub async fn commit_confirmed(&mut self, timeout: Duration) -> Result<RpcReply> {
+        self.rpc(&format!(
+            "<commit><confirmed/><confirm-timeout>{}</confirm-timeout></commit>",
+            timeout.as_secs().max(1)
+        ))
+        .await
+    }
+
+    pub async fn cancel_commit(&mut self) -> Result<RpcReply> {
+        self.rpc("<cancel-commit/>").await
+    }
+
+    pub async fn discard_changes(&mut self) -> Result<RpcReply> {
+        self.rpc("<discard-changes/>").await
+    }
+
+    /// Best-effort cleanup after a failed or dry-run candidate edit.
+    pub async fn discard_and_unlock(&mut self) -> Result<()> {
+        let discarded = self.discard_changes().await;
+        let unlocked = self.unlock(Datastore::Candidate).await;
+        discarded?;
+        unlocked?;
+        Ok(())
+    }
+
+    pub async fn close_session(&mut self) -> Result<RpcReply> {
+        self.rpc("<close-session/>").await
+    }
+
+    async fn hello(&mut self) -> Result<()> {
+        let hello = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><hello xmlns="{NETCONF_NS}"><capabilities><capability>{BASE_1_0}</capability><capability>{BASE_1_1}</capability></capabilities></hello>"#
+        );
+        let raw = match self.tape.clone() {
+            Some(tape) if tape.is_replay() => match tape.take("netconf hello", "")? {
+                Exchange::NetconfHello(recorded) => recorded.hello,
+                _ => unreachable!("take filters by transport"),
+            },
+            tape => {
+                self.write_message(&hello)
+                    .await
+                    .context("write netconf hello")?;
+                let raw = self.read_message().await.context("read netconf hello")?;
+                if let Some(tape) = tape {
+                    tape.record(Exchange::NetconfHello(NetconfHello { hello: raw.clone() }))?;
+                }
+                raw
+            }
+        };
+        let (capabilities, session_id) = parse_hello(&raw)?;
+        if capabilities.iter().any(|cap| cap == BASE_1_1) {
+            self.framing = Framing::Chunked;
+        } else if !capabilities.iter().any(|cap| cap == BASE_1_0) {
+            bail!("netconf server advertised neither base:1.0 nor base:1.1");
+        }
+        self.capabilities = capabilities;
+        self.session_id = session_id;
+        Ok(())
+    }
+
+    async fn write_message(&mut self, message: &str) -> Result<()> {
+        let framed = match self.framing {
+            Framing::EndOfMessage => format!("{message}{NETCONF_EOM}"),
+            Framing::Chunked => format!("\n#{}\n{message}\n##\n", message.len()),
+        };
+        self.stream.as_mut().write_all(framed.as_bytes()).await?;
+        self.stream.as_mut().flush().await?;
+        Ok(())
+    }
+
+    /// Next framed message, bounded by `NAUTO_SSH_TIMEOUT_SECS` so a server that stops
+    /// answering fails the RPC instead of hanging it.
+    async fn read_message(&mut self) -> Result<String> {
+        let limit = ssh::command_timeout();
+        tokio::time::timeout(limit, self.read_frame())
+            .await
+            .map_err(|_| anyhow!("netconf reply timed out after {}s", limit.as_secs()))?
+    }
+
+    async fn read_frame(&mut self) -> Result<String> {
+        loop {
+            let decoded = match self.framing {
+                Framing::EndOfMessage => decode_eom(&self.buffer),
+                Framing::Chunked => decode_chunked(&self.buffer)?,
+            };
+            if let Some((message, consumed)) = decoded {
+                self.buffer.drain(..consumed);
+                return String::from_utf8(message).context("netconf not utf8");
+            }
+            let mut chunk = [0u8; READ_CHUNK];
+            let read = self
+                .stream
+                .as_mut()
+                .read(&mut chunk)
+                .await
+                .context("read netconf frame")?;
+            if read == 0 {
+                bail!("netconf stream closed");
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+pub(crate) fn decode_eom(buf: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let marker = NETCONF_EOM.as_bytes();
+    buf.windows(marker.len())
+        .position(|window| window == marker)
+        .map(|pos| (buf[..pos].to_vec(), pos + marker.len()))
+}
+
+/// Decodes one chunked-framing message, returning `None` until the end-of-chunks marker arrives.
+pub(crate) fn decode_chunked(buf: &[u8]) -> Result<Option<(Vec<u8>, usize)>> {
+    let mut message = Vec::new();
+    let mut pos = 0;
+    loop {
+        // Tolerate stray whitespace between messages, but a chunk header is always "\n#".
+        while pos + 1 < buf.len() && buf[pos].is_ascii_whitespace() && buf[pos + 1] != b'#' {
+            pos += 1;
+        }
+        if buf.len() < pos + 3 {
+            return Ok(None);
+        }
+        if &buf[pos..pos + 2] != b"\n#" {
+            bail!("invalid netconf chunk header");
+        }
+        pos += 2;
+        if buf[pos] == b'#' {
+            if buf.len() < pos + 2 {
+                return Ok(None);
+            }
+            if buf[pos + 1] != b'\n' {
+                bail!("invalid netconf end-of-chunks marker");
+            }
+            return Ok(Some((message, pos + 2)));
+        }
+        let Some(len_end) = buf[pos..].iter().position(|b| *b == b'\n') else {
+            if buf.len() - pos > 10 {
+                bail!("netconf chunk size too long");
+            }
+            return Ok(None);
+        };
+        let size: u64 = std::str::from_utf8(&buf[pos..pos + len_end])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .filter(|size| (1..=MAX_CHUNK_SIZE).contains(size))
+            .context("invalid netconf chunk size")?;
+        pos += len_end + 1;
+        let size = size as usize;
+        if buf.len() < pos + size {
+            return Ok(None);
+        }
+        message.extend_from_slice(&buf[pos..pos + size]);
+        pos += size;
+    }
+}
+
+fn parse_hello(raw: &str) -> Result<(Vec<String>, Option<u32>)> {
+    let mut reader = Reader::from_str(raw);
+    reader.config_mut().trim_text(true);
+    let mut capabilities = Vec::new();
+    let mut session_id = None;
+    let mut saw_hello = false;
+    loop {
+        match reader.read_event().context("parse netconf hello")? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"hello" => saw_hello = true,
+                b"capability" => {
+                    let text = reader.read_text(e.name())?;
+                    capabilities.push(text.trim().to_string());
+                }
+                b"session-id" => {
+                    let text = reader.read_text(e.name())?;
+                    session_id = text.trim().parse().ok();
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !saw_hello {
+        bail!("expected netconf <hello>, got: {raw}");
+    }
+    Ok((capabilities, session_id))
+}
+
+/// Parses an `<rpc-reply>` into its `<ok/>`, `<data>` and `<rpc-error>` parts.
+pub fn parse_reply(raw: &str) -> Result<RpcReply> {
+    let mut reader = Reader::from_str(raw);
+    reader.config_mut().trim_text(true);
+    let mut reply = RpcReply {
+        raw: raw.to_string(),
+        ..Default::default()
+    };
+    let mut saw_reply = false;
+    let mut current_error: Option<RpcError> = None;
+    loop {
+        match reader.read_event().context("parse netconf rpc-reply")? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"rpc-reply" => {
+                    saw_reply = true;
+                    reply.message_id = e
+                        .try_get_attribute("message-id")?
+                        .map(|attr| attr.unescape_value().map(|v| v.into_owned()))
+                        .transpose()?;
+                }
+                b"data" if current_error.is_none() => {
+                    reply.data = Some(reader.read_text(e.name())?.trim().to_string());
+                }
+                b"rpc-error" => current_error = Some(RpcError::default()),
+                field if current_error.is_some() => {
+                    let field = field.to_vec();
+                    let text = reader.read_text(e.name())?.trim().to_string();
+                    if let Some(err) = current_error.as_mut() {
+                        match field.as_slice() {
+                            b"error-type" => err.error_type = text,
+                            b"error-tag" => err.tag = text,
+                            b"error-severity" => {
+                                err.severity = if text == "warning" {
+                                    ErrorSeverity::Warning
+                                } else {
+                                    ErrorSeverity::Error
+                                }
+                            }
+                            b"error-app-tag" => err.app_tag = Some(text),
+                            b"error-path" => err.path = Some(text),
+                            b"error-message" => err.message = Some(text),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"ok" if current_error.is_none() => reply.ok = true,
+                b"data" if current_error.is_none() => reply.data = Some(String::new()),
+                _ => {}
+            },
+            Event::End(e) if e.local_name().as_ref() == b"rpc-error" => {
+                if let Some(err) = current_error.take() {
+                    reply.errors.push(err);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !saw_reply {
+        bail!("expected netconf <rpc-reply>, got: {raw}");
+    }
+    Ok(reply)
+}
+
+/// Returns the unescaped text of the first element with the given local name.
+pub fn element_text(xml: &str, local_name: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) if e.local_name().as_ref() == local_name.as_bytes() => {
+                let mut text = String::new();
+                loop {
+                    match reader.read_event().ok()? {
+                        Event::Text(t) => text.push_str(&t.unescape().ok()?),
+                        Event::CData(c) => text.push_str(std::str::from_utf8(&c).ok()?),
+                        Event::End(end) if end.local_name() == e.local_name() => break,
+                        Event::Eof => return None,
+                        _ => {}
+                    }
+                }
+                return Some(text);
+            }
+            Event::Empty(e) if e.local_name().as_ref() == local_name.as_bytes() => {
+                return Some(String::new())
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Returns the raw inner XML of every element with the given local name.
+pub fn elements(xml: &str, local_name: &str) -> Vec<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut found = Vec::new();
+    while let Ok(event) = reader.read_event() {
+        match event {
+            Event::Start(e) if e.local_name().as_ref() == local_name.as_bytes() => {
+                match reader.read_text(e.name()) {
+                    Ok(inner) => found.push(inner.into_owned()),
+                    Err(_) => break,
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Every element that holds text, as its `/`-joined path of local names and its trimmed
+/// text. Subtrees carrying an `operation="delete"` or `operation="remove"` attribute are
+/// skipped, since an edit-config with them takes content away rather than adding it.
+pub fn leaf_values(xml: &str) -> Vec<(String, String)> {
+    let mut reader = Reader::from_str(xml);
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut removed_depth: Option<usize> = None;
+    let mut leaves = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                path.push(String::from_utf8_lossy(e.local_name().as_ref()).into_owned());
+                if removed_depth.is_none() && removes(&e) {
+                    removed_depth = Some(path.len());
+                }
+                text.clear();
+            }
+            Ok(Event::Text(t)) => text.push_str(&t.unescape().unwrap_or_default()),
+            Ok(Event::CData(c)) => text.push_str(&String::from_utf8_lossy(&c)),
+            Ok(Event::End(_)) => {
+                if removed_depth.is_none() && !text.trim().is_empty() {
+                    leaves.push((path.join("/"), text.trim().to_string()));
+                }
+                if removed_depth == Some(path.len()) {
+                    removed_depth = None;
+                }
+                path.pop();
+                text.clear();
+            }
+            Ok(Event::Empty(_)) => text.clear(),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    leaves
+}
+
+fn removes(element: &quick_xml::events::BytesStart) -> bool {
+    element.attributes().flatten().any(|attr| {
+        attr.key.local_name().as_ref() == b"operation"
+            && matches!(attr.value.as_ref(), b"delete" | b"remove")
+    })
+}
+
+/// Re-indents an XML document one element per line so line diffs stay readable.
+pub fn pretty_xml(raw: &str) -> String {
+    let mut reader = Reader::from_str(raw);
+    reader.config_mut().trim_text(true);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Eof) => break,
+            Ok(event) => {
+                if writer.write_event(event).is_err() {
+                    return raw.to_string();
+                }
+            }
+            Err(_) => return raw.to_string(),
+        }
+    }
+    String::from_utf8(writer.into_inner()).unwrap_or_else(|_| raw.to_string())
+}
+
+/// Maps a JSON document onto XML without a YANG schema: object keys become elements,
+/// arrays repeat their parent element, and `@`-prefixed keys become attributes
+/// (e.g. `"@xmlns"` or `"@operation"`).
+pub fn json_to_xml(value: &Value) -> Result<String> {
+    let Value::Object(map) = value else {
+        bail!("netconf JSON payload must be an object at the top level");
+    };
+    let mut out = String::new();
+    for (name, child) in map {
+        if name.starts_with('@') {
+            bail!("attribute '{name}' is not allowed at the top level");
+        }
+        write_json_element(&mut out, name, child)?;
+    }
+    Ok(out)
+}
+
+fn write_json_element(out: &mut String, name: &str, value: &Value) -> Result<()> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '<' || c == '>') {
+        bail!("invalid XML element name '{name}' in netconf JSON payload");
+    }
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                write_json_element(out, name, item)?;
+            }
+        }
+        Value::Object(map) => {
+            out.push('<');
+            out.push_str(name);
+            for (key, attr) in map.iter().filter(|(key, _)| key.starts_with('@')) {
+                let attr = match attr {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                out.push_str(&format!(r#" {}="{}""#, &key[1..], escape_attr(&attr)));
+            }
+            let children: Vec<_> = map
+                .iter()
+                .filter(|(key, _)| !key.starts_with('@'))
+                .collect();
+            if children.is_empty() {
+                out.push_str("/>");
+                return Ok(());
+            }
+            out.push('>');
+            for (key, child) in children {
+                if key == "#text" {
+                    out.push_str(&escape_text(&scalar_text(child)));
+                } else {
+                    write_json_element(out, key, child)?;
+                }
+            }
+            out.push_str(&format!("</{name}>"));
+        }
+        Value::Null => out.push_str(&format!("<{name}/>")),
+        scalar => out.push_str(&format!(
+            "<{name}>{}</{name}>",
+            escape_text(&scalar_text(scalar))
+        )),
+    }
+    Ok(())
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn escape_text(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, DuplexStream};
+
+    const SERVER_HELLO_11: &str = r#"<hello xmlns="urn:ietf:params:xml:ns:netconf:base:1.0"><capabilities><capability>urn:ietf:params:netconf:base:1.0</capability><capability>urn:ietf:params:netconf:base:1.1</capability><capability>urn:ietf:params:netconf:capability:candidate:1.0</capability><capability>urn:ietf:params:netconf:capability:validate:1.1?module=x</capability></capabilities><session-id>42</session-id></hello>]]>]]>"#;
+
+    async fn read_until(stream: &mut DuplexStream, marker: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut byte = [0u8; 1];
+        while !buf.ends_with(marker) {
+            stream.read_exact(&mut byte).await.expect("read client");
+            buf.push(byte[0]);
+        }
+        buf
+    }
+
+    #[test]
+    fn decodes_multi_chunk_messages() {
+        let framed = b"\n#4\n<rpc\n#17\n message-id=\"1\"/>\n##\n\n#2\nxx";
+        let (message, consumed) = decode_chunked(framed).unwrap().expect("complete frame");
+        assert_eq!(message, b"<rpc message-id=\"1\"/>");
+        assert_eq!(&framed[consumed..], b"\n#2\nxx");
+        assert!(decode_chunked(&framed[consumed..]).unwrap().is_none());
+        assert!(decode_chunked(b"\n#0\n").is_err());
+    }
+
+    #[test]
+    fn maps_json_payload_to_xml() {
+        let payload = serde_json::json!({
+            "interfaces": {
+                "@xmlns": "urn:ietf:params:xml:ns:yang:ietf-interfaces",
+                "interface": [
+                    {"name": "Gi0/0", "description": "uplink & core", "enabled": true},
+                    {"@operation": "delete", "name": "Loopback9"}
+                ]
+            }
+        });
+        assert_eq!(
+            json_to_xml(&payload).unwrap(),
+            r#"<interfaces xmlns="urn:ietf:params:xml:ns:yang:ietf-interfaces"><interface><name>Gi0/0</name><description>uplink &amp; core</description><enabled>true</enabled></interface><interface operation="delete"><name>Loopback9</name></interface></interfaces>"#
+        );
+        assert!(json_to_xml(&serde_json::json!(["x"])).is_err());
+    }
+
+    #[test]
+    fn leaf_values_skip_deleted_subtrees() {
+        let payload = r#"<interfaces xmlns:nc="urn:ietf:params:xml:ns:netconf:base:1.0"><interface><name>Gi0/0</name><description>uplink &amp; core</description><shutdown/></interface><interface nc:operation="delete"><name>Loopback9</name></interface></interfaces>"#;
+        assert_eq!(
+            leaf_values(payload),
+            [
+                ("interfaces/interface/name".to_string(), "Gi0/0".to_string()),
+                (
+                    "interfaces/interface/description".to_string(),
+                    "uplink & core".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_rpc_errors_with_severity() {
+        let reply = parse_reply(
+            r#"<nc:rpc-reply xmlns:nc="urn:ietf:params:xml:ns:netconf:base:1.0" message-id="7">
+                 <nc:rpc-error>
+                   <nc:error-type>application</nc:error-type>
+                   <nc:error-tag>invalid-value</nc:error-tag>
+                   <nc:error-severity>error</nc:error-severity>
+                   <nc:error-path>/configuration/system</nc:error-path>
+                   <nc:error-message>syntax error</nc:error-message>
+                 </nc:rpc-error>
+                 <nc:rpc-error>
+                   <nc:error-type>protocol</nc:error-type>
+                   <nc:error-tag>operation-failed</nc:error-tag>
+                   <nc:error-severity>warning</nc:error-severity>
+                 </nc:rpc-error>
+               </nc:rpc-reply>"#,
+        )
+        .unwrap();
+        assert_eq!(reply.message_id.as_deref(), Some("7"));
+        assert_eq!(reply.errors.len(), 2);
+        assert_eq!(reply.warnings().count(), 1);
+        let err = reply.into_result().unwrap_err();
+        let rpc_err = err.downcast_ref::<NetconfRpcError>().expect("typed error");
+        assert_eq!(rpc_err.errors.len(), 1);
+        assert_eq!(rpc_err.errors[0].tag, "invalid-value");
+        assert_eq!(rpc_err.errors[0].message.as_deref(), Some("syntax error"));
+    }
+
+    #[tokio::test]
+    async fn negotiates_chunked_framing_and_reads_data() {
+        let (client_io, mut server_io) = duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let hello = read_until(&mut server_io, NETCONF_EOM.as_bytes()).await;
+            assert!(String::from_utf8_lossy(&hello).contains(BASE_1_1));
+            server_io
+                .write_all(SERVER_HELLO_11.as_bytes())
+                .await
+                .unwrap();
+
+            let request = read_until(&mut server_io, b"\n##\n").await;
+            let request = String::from_utf8(request).unwrap();
+            assert!(request.starts_with("\n#"));
+            assert!(request.contains("<get-config><source><running/></source>"));
+            let reply = r#"<rpc-reply message-id="1" xmlns="urn:ietf:params:xml:ns:netconf:base:1.0"><data><configuration><system><host-name>r1</host-name></system></configuration></data></rpc-reply>"#;
+            let (head, tail) = reply.split_at(20);
+            let framed = format!("\n#{}\n{head}\n#{}\n{tail}\n##\n", head.len(), tail.len());
+            server_io.write_all(framed.as_bytes()).await.unwrap();
+        });
+
+        let mut session = NetconfSession::from_stream(client_io).await.unwrap();
+        assert_eq!(session.framing(), Framing::Chunked);
+        assert_eq!(session.session_id(), Some(42));
+        assert!(session.supports(CAP_CANDIDATE));
+        assert!(session.supports(CAP_VALIDATE));
+        assert!(!session.supports(CAP_CONFIRMED_COMMIT));
+
+        let data = session.get_config(Datastore::Running, None).await.unwrap();
+        assert_eq!(
+            data,
+            "<configuration><system><host-name>r1</host-name></system></configuration>"
+        );
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+//! Structured parsing of CLI output. Templates use TextFSM syntax and are selected by
+//! (device type, command) through an ntc-templates style index, so existing template
+//! libraries can be dropped into `NAUTO_TEMPLATE_DIR` unchanged.
+
+pub mod textfsm;
+
+use crate::config;
+use anyhow::{anyhow, bail, Context, Result};
+use nauto_model::{CommandOutput, DeviceType};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use std::path::Path;
+use std::str::FromStr;
+use textfsm::Template;
+use tracing::{debug, warn};
+
+const BUILTIN_INDEX: &str = include_str!("../../templates/index");
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "cisco_ios_show_version.textfsm",
+        include_str!("../../templates/cisco_ios_show_version.textfsm"),
+    ),
+    (
+        "cisco_ios_show_ip_interface_brief.textfsm",
+        include_str!("../../templates/cisco_ios_show_ip_interface_brief.textfsm"),
+    ),
+    (
+        "cisco_ios_show_interfaces.textfsm",
+        include_str!("../../templates/cisco_ios_show_interfaces.textfsm"),
+    ),
+    (
+        "cisco_ios_show_lldp_neighbors_detail.textfsm",
+        include_str!("../../templates/cisco_ios_show_lldp_neighbors_detail.textfsm"),
+    ),
+    (
+        "cisco_ios_show_ip_arp.textfsm",
+        include_str!("../../templates/cisco_ios_show_ip_arp.textfsm"),
+    ),
+    (
+        "cisco_ios_show_mac_address_table.textfsm",
+        include_str!("../../templates/cisco_ios_show_mac_address_table.textfsm"),
+    ),
+    (
+        "cisco_ios_show_ip_bgp_summary.textfsm",
+        include_str!("../../templates/cisco_ios_show_ip_bgp_summary.textfsm"),
+    ),
+    (
+        "cisco_iosxr_show_ip_interface_brief.textfsm",
+        include_str!("../../templates/cisco_iosxr_show_ip_interface_brief.textfsm"),
+    ),
+    (
+        "juniper_junos_show_interfaces_terse.textfsm",
+        include_str!("../../templates/juniper_junos_show_interfaces_terse.textfsm"),
+    ),
+    (
+        "arista_eos_show_version.textfsm",
+        include_str!("../../templates/arista_eos_show_version.textfsm"),
+    ),
+    (
+        "linux_df.textfsm",
+        include_str!("../../templates/linux_df.textfsm"),
+    ),
+];
+
+static LIBRARY: Lazy<TemplateLibrary> = Lazy::new(|| {
+    let mut library = TemplateLibrary::builtin();
+    if let Some(dir) = config::template_dir() {
+        if let Err(err) = library.load_dir(dir) {
+            warn!(
+                target: "drivers::parsing",
+                "ignoring templates in {}: {:#}",
+                dir.display(),
+                err
+            );
+        }
+    }
+    library
+});
+
+struct TemplateEntry {
+    name: String,
+    device_type: DeviceType,
+    command: Regex,
+    template: Template,
+}
+
+/// Templates keyed by device type and command pattern; the first matching entry wins.
+#[derive(Default)]
+pub struct TemplateLibrary {
+    entries: Vec<TemplateEntry>,
+}
+
+impl TemplateLibrary {
+    /// Built-in templates plus any found under `NAUTO_TEMPLATE_DIR`.
+    pub fn global() -> &'static TemplateLibrary {
+        &LIBRARY
+    }
+
+    pub fn builtin() -> TemplateLibrary {
+        let mut library = TemplateLibrary::default();
+        library
+            .add_index(BUILTIN_INDEX, |name| {
+                BUILTIN_TEMPLATES
+                    .iter()
+                    .find(|(file, _)| *file == name)
+                    .map(|(_, text)| text.to_string())
+                    .ok_or_else(|| anyhow!("missing built-in template {}", name))
+            })
+            .expect("built-in templates are valid");
+        library
+    }
+
+    /// Loads `<dir>/index` and the templates it names. These entries are checked before
+    /// anything already in the library.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let index = std::fs::read_to_string(dir.join("index"))
+            .with_context(|| format!("reading {}", dir.join("index").display()))?;
+        let mut loaded = TemplateLibrary::default();
+        loaded.add_index(&index, |name| {
+            std::fs::read_to_string(dir.join(name))
+                .with_context(|| format!("reading template {}", name))
+        })?;
+        loaded.entries.append(&mut self.entries);
+        self.entries = loaded.entries;
+        Ok(())
+    }
+
+    /// Adds one template for a device type. `command` uses the index syntax, where
+    /// `sh[[ow]]` matches `sh`, `sho` and `show`.
+    pub fn insert(&mut self, device_type: DeviceType, command: &str, template: &str) -> Result<()> {
+        self.entries.push(TemplateEntry {
+            name: command.to_string(),
+            device_type,
+            command: command_regex(command)?,
+            template: Template::parse(template)?,
+        });
+        Ok(())
+    }
+
+    pub fn find(&self, device_type: &DeviceType, command: &str) -> Option<&Template> {
+        self.entry(device_type, command)
+            .map(|entry| &entry.template)
+    }
+
+    /// Parses `raw` with the matching template, if there is one.
+    pub fn parse(
+        &self,
+        device_type: &DeviceType,
+        command: &str,
+        raw: &str,
+    ) -> Option<Result<Value>> {
+        let entry = self.entry(device_type, command)?;
+        Some(
+            entry
+                .template
+                .parse_text(raw)
+                .map(|rows| Value::Array(rows.into_iter().map(Value::Object).collect()))
+                .with_context(|| format!("template {}", entry.name)),
+        )
+    }
+
+    fn entry(&self, device_type: &DeviceType, command: &str) -> Option<&TemplateEntry> {
+        let command = command.trim();
+        self.entries
+            .iter()
+            .find(|entry| &entry.device_type == device_type && entry.command.is_match(command))
+    }
+
+    fn add_index<F>(&mut self, index: &str, mut load: F) -> Result<()>
+    where
+        F: FnMut(&str) -> Result<String>,
+    {
+        let mut header_seen = false;
+        for (idx, line) in index.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if !header_seen {
+                header_seen = true;
+                if line.starts_with("Template") {
+                    continue;
+                }
+            }
+            let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+            let (file, platform, command) = match columns.as_slice() {
+                [file, _hostname, platform, command] => (*file, *platform, *command),
+                [file, platform, command] => (*file, *platform, *command),
+                _ => bail!(
+                    "index line {}: expected Template, Hostname, Platform, Command",
+                    idx + 1
+                ),
+            };
+            let Ok(device_type) = DeviceType::from_str(platform) else {
+                debug!(
+                    target: "drivers::parsing",
+                    "skipping template {} for unsupported platform {}",
+                    file,
+                    platform
+                );
+                continue;
+            };
+            // ntc-templates chains templates with `:`; only the first one is used.
+            let file = file.split(':').next().unwrap_or(file);
+            let text = load(file)?;
+            let template = Template::parse(&text).with_context(|| format!("template {}", file))?;
+            self.entries.push(TemplateEntry {
+                name: file.to_string(),
+                device_type,
+                command: command_regex(command)
+                    .with_context(|| format!("index line {}", idx + 1))?,
+                template,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Wraps raw command output for the job result, parsed with the global library.
+pub fn command_output(device_type: &DeviceType, command: &str, raw: String) -> CommandOutput {
+    let parsed = match TemplateLibrary::global().parse(device_type, command, &raw) {
+        Some(Ok(records)) => Some(records),
+        Some(Err(err)) => {
+            warn!(
+                target: "drivers::parsing",
+                "template for '{}' failed: {:#}",
+                command,
+                err
+            );
+            None
+        }
+        None => None,
+    };
+    CommandOutput {
+        command: command.to_string(),
+        raw,
+        parsed,
+    }
+}
+
+/// Output from APIs that already return JSON (eAPI, NX-API, REST); no template needed.
+pub fn json_output(command: &str, value: Value) -> CommandOutput {
+    CommandOutput {
+        command: command.to_string(),
+        raw: serde_json::to_string_pretty(&value).unwrap_or_default(),
+        parsed: Some(value),
+    }
+}
+
+/// Turns an index command such as `sh[[ow]] ip int[[erface]] br[[ief]]` into an anchored
+/// regex; `[[...]]` marks an optional completion and words may be separated by any
+/// whitespace.
+fn command_regex(command: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    for (idx, word) in command.split_whitespace().enumerate() {
+        if idx > 0 {
+            pattern.push_str(r"\s+");
+        }
+        let mut rest = word;
+        while let Some(start) = rest.find("[[") {
+            pattern.push_str(&regex::escape(&rest[..start]));
+            let end = rest[start..]
+                .find("]]")
+                .map(|end| start + end)
+                .ok_or_else(|| anyhow!("unterminated [[ in {}", command))?;
+            let optional: Vec<char> = rest[start + 2..end].chars().collect();
+            for ch in &optional {
+                pattern.push('(');
+                pattern.push_str(&regex::escape(&ch.to_string()));
+            }
+            pattern.push_str(&")?".repeat(optional.len()));
+            rest = &rest[end + 2..];
+        }
+        pattern.push_str(&regex::escape(rest));
+    }
+    pattern.push_str(r"\s*$");
+    Regex::new(&pattern).with_context(|| format!("command pattern {}", command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOW_IP_INT_BRIEF: &str = "\
+Interface              IP-Address      OK? Method Status                Protocol
+GigabitEthernet0/0     10.0.0.1        YES NVRAM  up                    up
+GigabitEthernet0/1     unassigned      YES NVRAM  administratively down down
+Loopback0              192.0.2.1       YES manual up                    up
+";
+
+    #[test]
+    fn builtin_index_parses_abbreviated_commands() {
+        let library = TemplateLibrary::builtin();
+        assert!(library
+            .find(&DeviceType::CiscoIos, "sh ip int br")
+            .is_some());
+        assert!(library
+            .find(&DeviceType::CiscoIos, "show ip interface brief | i up")
+            .is_none());
+        assert!(library
+            .find(&DeviceType::JuniperJunos, "show ip int br")
+            .is_none());
+
+        let parsed = library
+            .parse(
+                &DeviceType::CiscoIos,
+                "show ip interface brief",
+                SHOW_IP_INT_BRIEF,
+            )
+            .unwrap()
+            .unwrap();
+        let rows = parsed.as_array().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1]["interface"], "GigabitEthernet0/1");
+        assert_eq!(rows[1]["status"], "administratively down");
+        assert_eq!(rows[2]["ip_address"], "192.0.2.1");
+    }
+
+    #[test]
+    fn junos_terse_collects_addresses_per_interface() {
+        let raw = "\
+Interface               Admin Link Proto    Local                 Remote
+ge-0/0/0                up    up
+ge-0/0/0.0              up    up   inet     10.0.0.1/24
+                                   inet6    fe80::1/64
+lo0.0                   up    up   inet     192.0.2.1           --> 0/0
+";
+        let parsed = TemplateLibrary::builtin()
+            .parse(&DeviceType::JuniperJunos, "show interfaces terse", raw)
+            .unwrap()
+            .unwrap();
+        let rows = parsed.as_array().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["interface"], "ge-0/0/0");
+        assert_eq!(
+            rows[1]["local"],
+            serde_json::json!(["10.0.0.1/24", "fe80::1/64"])
+        );
+        assert_eq!(rows[2]["proto"], "inet");
+    }
+}
@@ -0,0 +1,468 @@
+//! TextFSM template engine. Supports the syntax used by ntc-templates: `Value` lines with
+//! `Filldown`, `Fillup`, `Key`, `Required` and `List` options, named states with `^regex`
+//! rules, and `Next`/`Continue` line actions combined with `Record`/`NoRecord`/`Clear`/
+//! `Clearall`, state transitions and `Error`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    values: Vec<ValueDef>,
+    states: Vec<State>,
+    explicit_eof: bool,
+}
+
+#[derive(Debug, Clone)]
+struct ValueDef {
+    name: String,
+    pattern: String,
+    filldown: bool,
+    fillup: bool,
+    required: bool,
+    list: bool,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    name: String,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    regex: Regex,
+    line: LineOp,
+    record: RecordOp,
+    next_state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineOp {
+    Next,
+    Continue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordOp {
+    NoRecord,
+    Record,
+    Clear,
+    Clearall,
+}
+
+#[derive(Debug, Clone)]
+enum Slot {
+    Single(Option<String>),
+    List(Vec<String>),
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template> {
+        let mut lines = text.lines().enumerate();
+        let mut values = Vec::new();
+
+        // Value definitions run until the first blank line.
+        for (idx, line) in lines.by_ref() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                break;
+            }
+            if trimmed.starts_with('#') {
+                continue;
+            }
+            let def = trimmed
+                .strip_prefix("Value ")
+                .ok_or_else(|| anyhow!("line {}: expected a Value definition", idx + 1))?;
+            values.push(parse_value(def).with_context(|| format!("line {}", idx + 1))?);
+        }
+        if values.is_empty() {
+            bail!("template defines no values");
+        }
+
+        let mut states: Vec<State> = Vec::new();
+        let mut current: Option<State> = None;
+        for (idx, line) in lines {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') {
+                continue;
+            }
+            if trimmed.is_empty() {
+                if let Some(state) = current.take() {
+                    states.push(state);
+                }
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                if let Some(state) = current.take() {
+                    states.push(state);
+                }
+                if states.iter().any(|state| state.name == trimmed) {
+                    bail!("line {}: duplicate state {}", idx + 1, trimmed);
+                }
+                current = Some(State {
+                    name: trimmed.to_string(),
+                    rules: Vec::new(),
+                });
+                continue;
+            }
+            let state = current
+                .as_mut()
+                .ok_or_else(|| anyhow!("line {}: rule outside of a state", idx + 1))?;
+            state
+                .rules
+                .push(parse_rule(trimmed, &values).with_context(|| format!("line {}", idx + 1))?);
+        }
+        if let Some(state) = current.take() {
+            states.push(state);
+        }
+
+        if !states.iter().any(|state| state.name == "Start") {
+            bail!("template has no Start state");
+        }
+        for state in &states {
+            for rule in &state.rules {
+                if let Some(target) = &rule.next_state {
+                    let known = matches!(target.as_str(), "End" | "EOF")
+                        || states.iter().any(|state| &state.name == target);
+                    if !known {
+                        bail!(
+                            "state {} transitions to unknown state {}",
+                            state.name,
+                            target
+                        );
+                    }
+                }
+            }
+        }
+        let explicit_eof = states.iter().any(|state| state.name == "EOF");
+        Ok(Template {
+            values,
+            states,
+            explicit_eof,
+        })
+    }
+
+    /// Runs the state machine over `input` and returns one JSON object per record, keyed by
+    /// lowercased value name (as ntc-templates does). `List` values become arrays.
+    pub fn parse_text(&self, input: &str) -> Result<Vec<Map<String, Value>>> {
+        let mut run = Run {
+            template: self,
+            slots: self.values.iter().map(Slot::empty).collect(),
+            records: Vec::new(),
+        };
+        let mut state = self.state("Start").expect("validated Start state");
+        let mut ended = false;
+
+        'lines: for line in input.lines() {
+            let line = line.trim_end_matches('\r');
+            for rule in &state.rules {
+                let Some(captures) = rule.regex.captures(line) else {
+                    continue;
+                };
+                for (idx, value) in self.values.iter().enumerate() {
+                    if let Some(found) = captures.name(&value.name) {
+                        run.assign(idx, found.as_str());
+                    }
+                }
+                if let Some(message) = &rule.error {
+                    bail!("template error on line '{}': {}", line, message);
+                }
+                match rule.record {
+                    RecordOp::NoRecord => {}
+                    RecordOp::Record => run.record(),
+                    RecordOp::Clear => run.clear(false),
+                    RecordOp::Clearall => run.clear(true),
+                }
+                if let Some(target) = &rule.next_state {
+                    match target.as_str() {
+                        // `End` stops without the implicit EOF record; `EOF` keeps it.
+                        "End" => {
+                            ended = true;
+                            break 'lines;
+                        }
+                        "EOF" => break 'lines,
+                        name => state = self.state(name).expect("validated state"),
+                    }
+                }
+                if rule.line == LineOp::Next {
+                    continue 'lines;
+                }
+            }
+        }
+
+        if !ended && !self.explicit_eof {
+            run.record();
+        }
+        Ok(run.records)
+    }
+
+    fn state(&self, name: &str) -> Option<&State> {
+        self.states.iter().find(|state| state.name == name)
+    }
+}
+
+struct Run<'a> {
+    template: &'a Template,
+    slots: Vec<Slot>,
+    records: Vec<Map<String, Value>>,
+}
+
+impl Run<'_> {
+    fn assign(&mut self, idx: usize, found: &str) {
+        let def = &self.template.values[idx];
+        match &mut self.slots[idx] {
+            Slot::List(items) => items.push(found.to_string()),
+            Slot::Single(slot) => *slot = Some(found.to_string()),
+        }
+        if def.fillup {
+            let key = def.name.to_lowercase();
+            for record in self.records.iter_mut().rev() {
+                match record.get(&key) {
+                    Some(Value::String(existing)) if existing.is_empty() => {
+                        record.insert(key.clone(), Value::String(found.to_string()));
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    fn record(&mut self) {
+        let values = &self.template.values;
+        let empty = self.slots.iter().all(Slot::is_empty);
+        let missing_required = values
+            .iter()
+            .zip(&self.slots)
+            .any(|(def, slot)| def.required && slot.is_empty());
+        if !empty && !missing_required {
+            let row = values
+                .iter()
+                .zip(&self.slots)
+                .map(|(def, slot)| (def.name.to_lowercase(), slot.to_json()))
+                .collect();
+            self.records.push(row);
+        }
+        self.clear(false);
+    }
+
+    fn clear(&mut self, all: bool) {
+        for (def, slot) in self.template.values.iter().zip(self.slots.iter_mut()) {
+            if all || !def.filldown {
+                *slot = Slot::empty(def);
+            }
+        }
+    }
+}
+
+impl Slot {
+    fn empty(def: &ValueDef) -> Slot {
+        if def.list {
+            Slot::List(Vec::new())
+        } else {
+            Slot::Single(None)
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Slot::Single(value) => value.as_deref().unwrap_or_default().is_empty(),
+            Slot::List(items) => items.is_empty(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Slot::Single(value) => Value::String(value.clone().unwrap_or_default()),
+            Slot::List(items) => Value::Array(items.iter().cloned().map(Value::String).collect()),
+        }
+    }
+}
+
+fn parse_value(def: &str) -> Result<ValueDef> {
+    let (first, rest) = def
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("incomplete Value definition"))?;
+    let (options, name, pattern) = if rest.trim_start().starts_with('(') {
+        (None, first, rest.trim())
+    } else {
+        let (name, pattern) = rest
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("Value {} has no regex", rest))?;
+        (Some(first), name, pattern.trim())
+    };
+    if !(pattern.starts_with('(') && pattern.ends_with(')')) {
+        bail!("Value {} regex must be wrapped in parentheses", name);
+    }
+    let mut value = ValueDef {
+        name: name.to_string(),
+        pattern: pattern.to_string(),
+        filldown: false,
+        fillup: false,
+        required: false,
+        list: false,
+    };
+    for option in options.into_iter().flat_map(|opts| opts.split(',')) {
+        match option {
+            "Filldown" => value.filldown = true,
+            "Fillup" => value.fillup = true,
+            "Required" => value.required = true,
+            "List" => value.list = true,
+            "Key" => {}
+            other => bail!("unknown Value option {}", other),
+        }
+    }
+    Regex::new(&value.pattern).with_context(|| format!("Value {} regex", value.name))?;
+    Ok(value)
+}
+
+fn parse_rule(line: &str, values: &[ValueDef]) -> Result<Rule> {
+    if !line.starts_with('^') {
+        bail!("rule must start with ^: {}", line);
+    }
+    let (pattern, action) = match line.rsplit_once(" -> ") {
+        Some((pattern, action)) => (pattern.trim_end(), Some(action.trim())),
+        None => (line, None),
+    };
+    let regex = Regex::new(&expand_values(pattern, values)?)
+        .with_context(|| format!("rule regex {}", pattern))?;
+    let mut rule = Rule {
+        regex,
+        line: LineOp::Next,
+        record: RecordOp::NoRecord,
+        next_state: None,
+        error: None,
+    };
+    let Some(action) = action else {
+        return Ok(rule);
+    };
+
+    let mut parts = action.splitn(2, char::is_whitespace);
+    let ops = parts.next().unwrap_or_default();
+    let target = parts.next().map(str::trim).filter(|rest| !rest.is_empty());
+    if ops == "Error" {
+        rule.error = Some(
+            target
+                .map(|msg| msg.trim_matches('"').to_string())
+                .unwrap_or_else(|| "state error".into()),
+        );
+        return Ok(rule);
+    }
+
+    let mut matched_op = false;
+    for op in ops.split('.') {
+        match op {
+            "Next" => rule.line = LineOp::Next,
+            "Continue" => rule.line = LineOp::Continue,
+            "NoRecord" => rule.record = RecordOp::NoRecord,
+            "Record" => rule.record = RecordOp::Record,
+            "Clear" => rule.record = RecordOp::Clear,
+            "Clearall" => rule.record = RecordOp::Clearall,
+            _ => continue,
+        }
+        matched_op = true;
+    }
+    rule.next_state = match (matched_op, target) {
+        (true, target) => target.map(str::to_string),
+        // A bare word after `->` is a state transition.
+        (false, None) => Some(ops.to_string()),
+        (false, Some(extra)) => bail!("unexpected action {} {}", ops, extra),
+    };
+    if rule.line == LineOp::Continue && rule.next_state.is_some() {
+        bail!("Continue cannot change state");
+    }
+    Ok(rule)
+}
+
+/// Replaces `${Name}`/`$Name` with the value's regex as a named group; `$$` is a literal `$`.
+fn expand_values(pattern: &str, values: &[ValueDef]) -> Result<String> {
+    let mut out = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(tail) = after.strip_prefix('$') {
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+        let (name, tail) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| anyhow!("unterminated ${{ in {}", pattern))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            // A trailing `$` is the regex end anchor.
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+        let value = values
+            .iter()
+            .find(|value| value.name == name)
+            .ok_or_else(|| anyhow!("rule references unknown value {}", name))?;
+        let inner = &value.pattern[1..value.pattern.len() - 1];
+        out.push_str(&format!("(?P<{}>{})", value.name, inner));
+        rest = tail;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERFACES: &str = r#"Value Filldown CHASSIS (\S+)
+Value Required INTERFACE (\S+)
+Value STATUS (up|down|administratively down)
+Value List ADDRESSES (\d+\.\d+\.\d+\.\d+)
+
+Start
+  ^Chassis ${CHASSIS}
+  ^Interface -> Continue.Record
+  ^Interface ${INTERFACE} is ${STATUS}
+  ^\s+inet ${ADDRESSES}
+  ^! -> Record End
+"#;
+
+    #[test]
+    fn records_with_filldown_and_lists() {
+        let template = Template::parse(INTERFACES).unwrap();
+        let input = "Chassis sw1\n\
+                     Interface Gi1 is up\n  inet 10.0.0.1\n  inet 10.0.1.1\n\
+                     Interface Gi2 is administratively down\n\
+                     !\nInterface Gi3 is up\n";
+        let rows = template.parse_text(input).unwrap();
+        // `Continue.Record` closes the previous interface; `End` drops everything after `!`.
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["chassis"], "sw1");
+        assert_eq!(rows[0]["interface"], "Gi1");
+        assert_eq!(
+            rows[0]["addresses"],
+            serde_json::json!(["10.0.0.1", "10.0.1.1"])
+        );
+        assert_eq!(rows[1]["interface"], "Gi2");
+        assert_eq!(rows[1]["status"], "administratively down");
+        assert_eq!(rows[1]["chassis"], "sw1");
+    }
+
+    #[test]
+    fn rejects_unknown_values_and_states() {
+        assert!(Template::parse("Value A (\\S+)\n\nStart\n  ^${B}\n").is_err());
+        assert!(Template::parse("Value A (\\S+)\n\nStart\n  ^${A} -> Nowhere\n").is_err());
+        let err = Template::parse("Value A (\\S+)\n\nStart\n  ^bad -> Error \"unexpected\"\n")
+            .unwrap()
+            .parse_text("bad")
+            .unwrap_err();
+        assert!(err.to_string().contains("unexpected"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Pre-flight checks for the Preflight job: does the management address resolve, does the
+//! port accept TCP, is the SSH host key already trusted and does the resolved credential log
+//! in. Each step gets `NAUTO_PREFLIGHT_TIMEOUT_SECS`; once one fails the rest are skipped.
+
+use crate::{config, ssh};
+use anyhow::{anyhow, Context, Result};
+use nauto_model::{CheckStatus, Device, PreflightCheck, PreflightReport, PreflightStep};
+use nauto_security::CredentialStore;
+use reqwest::Url;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+
+/// Checks an SSH (or NETCONF over SSH) endpoint. Credentials are only sent once the host key
+/// matched `known_hosts`.
+pub async fn ssh(device: &Device, store: &dyn CredentialStore, port: u16) -> PreflightReport {
+    let (host, port) = match device.mgmt_address.parse::<SocketAddr>() {
+        Ok(addr) => (addr.ip().to_string(), addr.port()),
+        Err(_) => (device.mgmt_address.clone(), port),
+    };
+    let mut checks = Checks::new(&host, port);
+    if !checks.reach(&host, port).await {
+        checks.skip(&[PreflightStep::HostKey, PreflightStep::Auth]);
+        return checks.report;
+    }
+    match timeout(
+        config::preflight_timeout(),
+        ssh::connect(device, store, port),
+    )
+    .await
+    {
+        Ok(Ok(_client)) => {
+            checks.pass(PreflightStep::HostKey, "");
+            checks.pass(PreflightStep::Auth, "");
+        }
+        Ok(Err(err)) => match rejected_step(&err) {
+            Some(PreflightStep::HostKey) => {
+                checks.fail(
+                    PreflightStep::HostKey,
+                    "host key missing from known_hosts or changed",
+                );
+                checks.skip(&[PreflightStep::Auth]);
+            }
+            Some(PreflightStep::Auth) => {
+                checks.pass(PreflightStep::HostKey, "");
+                checks.fail(PreflightStep::Auth, &format!("{err:#}"));
+            }
+            _ => {
+                checks.skip(&[PreflightStep::HostKey]);
+                checks.fail(PreflightStep::Auth, &format!("{err:#}"));
+            }
+        },
+        Err(_) => {
+            checks.skip(&[PreflightStep::HostKey]);
+            checks.fail(PreflightStep::Auth, "login timed out");
+        }
+    }
+    checks.report
+}
+
+/// Checks an HTTPS API at `address` (a base URL or bare `host[:port]`); `login` is the
+/// cheapest authenticated request the driver can make.
+pub async fn http<F>(address: &str, login: F) -> PreflightReport
+where
+    F: Future<Output = Result<()>>,
+{
+    let (host, port) = match http_endpoint(address) {
+        Ok(endpoint) => endpoint,
+        Err(err) => {
+            let mut checks = Checks::new(address, 0);
+            checks.fail(PreflightStep::Dns, &format!("{err:#}"));
+            checks.skip(&[PreflightStep::Tcp, PreflightStep::Auth]);
+            return checks.report;
+        }
+    };
+    let mut checks = Checks::new(&host, port);
+    if checks.reach(&host, port).await {
+        match timeout(config::preflight_timeout(), login).await {
+            Ok(Ok(())) => checks.pass(PreflightStep::Auth, ""),
+            Ok(Err(err)) => checks.fail(PreflightStep::Auth, &format!("{err:#}")),
+            Err(_) => checks.fail(PreflightStep::Auth, "login timed out"),
+        }
+    } else {
+        checks.skip(&[PreflightStep::Auth]);
+    }
+    checks.report
+}
+
+/// One job log line per check.
+pub fn describe(device: &Device, report: &PreflightReport) -> Vec<String> {
+    report
+        .checks
+        .iter()
+        .map(|check| {
+            let step = match check.step {
+                PreflightStep::Dns => "dns",
+                PreflightStep::Tcp => "tcp",
+                PreflightStep::HostKey => "host key",
+                PreflightStep::Auth => "auth",
+            };
+            let status = match check.status {
+                CheckStatus::Passed => "ok",
+                CheckStatus::Failed => "FAILED",
+                CheckStatus::Skipped => "skipped",
+            };
+            let mut line = format!("[{}] {} {step}: {status}", device.name, report.endpoint);
+            if !check.detail.is_empty() {
+                line.push_str(&format!(" ({})", check.detail));
+            }
+            line
+        })
+        .collect()
+}
+
+struct Checks {
+    report: PreflightReport,
+}
+
+impl Checks {
+    fn new(host: &str, port: u16) -> Self {
+        let endpoint = if host.contains(':') {
+            format!("[{host}]:{port}")
+        } else {
+            format!("{host}:{port}")
+        };
+        Self {
+            report: PreflightReport {
+                endpoint,
+                checks: Vec::new(),
+            },
+        }
+    }
+
+    /// DNS then TCP; true when both passed.
+    async fn reach(&mut self, host: &str, port: u16) -> bool {
+        let addrs: Vec<SocketAddr> = if let Ok(ip) = host.parse::<IpAddr>() {
+            self.pass(PreflightStep::Dns, "literal address");
+            vec![SocketAddr::new(ip, port)]
+        } else {
+            match timeout(config::preflight_timeout(), lookup_host((host, port))).await {
+                Ok(Ok(addrs)) => {
+                    let addrs: Vec<SocketAddr> = addrs.collect();
+                    let resolved = addrs
+                        .iter()
+                        .map(|addr| addr.ip().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    self.pass(PreflightStep::Dns, &resolved);
+                    addrs
+                }
+                Ok(Err(err)) => {
+                    self.fail(PreflightStep::Dns, &err.to_string());
+                    Vec::new()
+                }
+                Err(_) => {
+                    self.fail(PreflightStep::Dns, "lookup timed out");
+                    Vec::new()
+                }
+            }
+        };
+        if addrs.is_empty() {
+            self.skip(&[PreflightStep::Tcp]);
+            return false;
+        }
+        match timeout(config::preflight_timeout(), TcpStream::connect(&addrs[..])).await {
+            Ok(Ok(_stream)) => {
+                self.pass(PreflightStep::Tcp, "");
+                true
+            }
+            Ok(Err(err)) => {
+                self.fail(PreflightStep::Tcp, &err.to_string());
+                false
+            }
+            Err(_) => {
+                self.fail(PreflightStep::Tcp, "connect timed out");
+                false
+            }
+        }
+    }
+
+    fn pass(&mut self, step: PreflightStep, detail: &str) {
+        self.push(step, CheckStatus::Passed, detail);
+    }
+
+    fn fail(&mut self, step: PreflightStep, detail: &str) {
+        self.push(step, CheckStatus::Failed, detail);
+    }
+
+    fn skip(&mut self, steps: &[PreflightStep]) {
+        for step in steps {
+            self.push(*step, CheckStatus::Skipped, "");
+        }
+    }
+
+    fn push(&mut self, step: PreflightStep, status: CheckStatus, detail: &str) {
+        self.report.checks.push(PreflightCheck {
+            step,
+            status,
+            detail: detail.to_string(),
+        });
+    }
+}
+
+fn http_endpoint(address: &str) -> Result<(String, u16)> {
+    let url = if address.starts_with("http://") || address.starts_with("https://") {
+        Url::parse(address)
+    } else {
+        Url::parse(&format!("https://{address}"))
+    }
+    .with_context(|| format!("invalid management address {address}"))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("no host in {address}"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    Ok((host, url.port_or_known_default().unwrap_or(443)))
+}
+
+/// Which step an SSH connect error belongs to, when the server got that far.
+fn rejected_step(err: &anyhow::Error) -> Option<PreflightStep> {
+    use async_ssh2_tokio::Error;
+    match err.downcast_ref::<Error>()? {
+        Error::ServerCheckFailed | Error::SshError(russh::Error::UnknownKey) => {
+            Some(PreflightStep::HostKey)
+        }
+        Error::PasswordWrong
+        | Error::KeyAuthFailed
+        | Error::KeyboardInteractiveAuthFailed
+        | Error::KeyInvalid(_) => Some(PreflightStep::Auth),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn skips_login_when_port_is_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}/api", listener.local_addr().unwrap());
+        let report = http(&address, async { Err(anyhow!("401 Unauthorized")) }).await;
+        assert!(report.reachable());
+        assert!(!report.passed());
+        assert_eq!(
+            report.status(PreflightStep::Auth),
+            Some(CheckStatus::Failed)
+        );
+
+        drop(listener);
+        let report = http(&address, async { Ok(()) }).await;
+        assert_eq!(report.status(PreflightStep::Dns), Some(CheckStatus::Passed));
+        assert_eq!(report.status(PreflightStep::Tcp), Some(CheckStatus::Failed));
+        assert_eq!(
+            report.status(PreflightStep::Auth),
+            Some(CheckStatus::Skipped)
+        );
+        assert!(!report.reachable());
+    }
+}
@@ -0,0 +1,100 @@
+//! Predicted dry runs for CLI platforms that cannot stage a change on the device (IOS merge
+//! pushes, generic SSH). The snippet is merged into the running config offline and the
+//! result diffed against it, so the preview shows what the push is expected to change
+//! without the device having parsed or validated anything. Platforms with a native
+//! preview (EOS sessions, Junos `commit check` with `show | compare`) use that instead.
+
+use crate::config_tree::{self, ConfigTree, Node};
+use crate::remediation::{overwritten, NEGATE_OVERWRITES};
+use crate::DriverExecutionResult;
+use nauto_model::Device;
+
+/// First line of every predicted diff, so it is never mistaken for one the device reported.
+pub const PREDICTED_LABEL: &str =
+    "# predicted: snippet merged into running-config offline, not validated by the device";
+
+/// Merges an indented snippet into `running`: new lines go under their parents, a
+/// single-value line (`hostname`, `description`, ...) replaces the previous value, and
+/// `no <line>` removes `<line>` at the same level.
+pub fn merge(running: &str, snippet: &str) -> String {
+    let mut tree = ConfigTree::parse_indented(running);
+    merge_nodes(&mut tree.roots, ConfigTree::parse_indented(snippet).roots);
+    tree.render()
+}
+
+fn merge_nodes(existing: &mut Vec<Node>, incoming: Vec<Node>) {
+    for node in incoming {
+        if let Some(negated) = node.text.strip_prefix("no ") {
+            existing.retain(|old| {
+                old.text != negated && overwritten(&old.text, NEGATE_OVERWRITES) != Some(negated)
+            });
+            continue;
+        }
+        let slot = existing
+            .iter()
+            .position(|old| old.text == node.text)
+            .or_else(|| {
+                let key = overwritten(&node.text, NEGATE_OVERWRITES)?;
+                existing
+                    .iter()
+                    .position(|old| overwritten(&old.text, NEGATE_OVERWRITES) == Some(key))
+            });
+        match slot {
+            Some(index) => {
+                let old = &mut existing[index];
+                old.text = node.text;
+                merge_nodes(&mut old.children, node.children);
+            }
+            None => existing.push(node),
+        }
+    }
+}
+
+/// Fills in a dry-run result from the running config: the pre-change snapshot, the
+/// predicted diff (headed by [`PREDICTED_LABEL`]) and a log line saying it was predicted.
+pub fn predict(device: &Device, running: String, snippet: &str, res: &mut DriverExecutionResult) {
+    let diff = config_tree::render_diff(&running, &merge(&running, snippet));
+    if diff.is_empty() {
+        res.logs.push(format!(
+            "[{}] dry run: no change predicted (computed offline, nothing sent)",
+            device.name
+        ));
+        res.diff = Some(String::new());
+    } else {
+        res.logs.push(format!(
+            "[{}] dry run: predicted diff of {} lines (computed offline, nothing sent)",
+            device.name,
+            diff.lines().count()
+        ));
+        res.diff = Some(format!("{PREDICTED_LABEL}\n{diff}"));
+    }
+    res.pre_snapshot = Some(running);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_overwrites_and_negates_under_parents() {
+        let running = "hostname r1\n!\ninterface Gi1\n description uplink\n shutdown\n!\nntp server 10.0.0.1\n";
+        let snippet = "hostname r2\ninterface Gi1\n description core uplink\n no shutdown\nntp server 10.0.0.2\n";
+        assert_eq!(
+            merge(running, snippet),
+            "hostname r2\ninterface Gi1\n description core uplink\nntp server 10.0.0.1\nntp server 10.0.0.2\n"
+        );
+    }
+
+    #[test]
+    fn predicts_reordered_acl_entries() {
+        let running =
+            "ip access-list extended EDGE\n permit tcp any any eq 443\n deny ip any any\n";
+        let snippet =
+            "no ip access-list extended EDGE\nip access-list extended EDGE\n deny ip any any\n\
+                       \x20permit tcp any any eq 443\n";
+        assert_eq!(
+            config_tree::render_diff(running, &merge(running, snippet)),
+            " ip access-list extended EDGE\n- permit tcp any any eq 443\n+ permit tcp any any eq 443\n"
+        );
+    }
+}
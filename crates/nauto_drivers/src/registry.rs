@@ -0,0 +1,559 @@
+//! Driver lookup by device type, transport preference and platform version.
+//!
+//! Inventory expresses the preference with tags: `transport:<name>` tags in order of
+//! preference (`transport:eapi`, then `transport:ssh` falls back to SSH when eAPI cannot be
+//! reached) and `os_version:<version>` for drivers that only serve some releases.
+
+use crate::{DeviceDriver, DriverAction, DriverExecutionResult, DynDeviceDriver, JobContext};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use nauto_model::{CapabilitySet, Device, DeviceType, Getter, GetterResults, PreflightReport};
+use nauto_security::CredentialStore;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+/// How a driver reaches the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Ssh,
+    Netconf,
+    /// Vendor HTTP APIs: eAPI, NX-API, REST.
+    Http,
+}
+
+impl FromStr for Transport {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "ssh" | "cli" => Ok(Transport::Ssh),
+            "netconf" => Ok(Transport::Netconf),
+            "http" | "https" | "api" | "eapi" | "nxapi" => Ok(Transport::Http),
+            other => bail!("unknown transport '{other}'"),
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transport::Ssh => "ssh",
+            Transport::Netconf => "netconf",
+            Transport::Http => "http",
+        })
+    }
+}
+
+/// Transports the device's `transport:` tags ask for, most preferred first. A device with
+/// no such tag but an `http(s)://` management address prefers HTTP.
+pub fn preferred_transports(device: &Device) -> Vec<Transport> {
+    let mut preferred = Vec::new();
+    for tag in &device.tags {
+        let Some(name) = tag_value(tag, "transport:") else {
+            continue;
+        };
+        match name.parse() {
+            Ok(transport) if !preferred.contains(&transport) => preferred.push(transport),
+            Ok(_) => {}
+            Err(err) => warn!(target: "drivers::registry", "{}: {err}", device.name),
+        }
+    }
+    if preferred.is_empty()
+        && (device.mgmt_address.starts_with("http://")
+            || device.mgmt_address.starts_with("https://"))
+    {
+        preferred.push(Transport::Http);
+    }
+    preferred
+}
+
+/// The platform version from the device's `os_version:` tag.
+pub fn device_version(device: &Device) -> Option<&str> {
+    device
+        .tags
+        .iter()
+        .find_map(|tag| tag_value(tag, "os_version:"))
+}
+
+fn tag_value<'a>(tag: &'a str, prefix: &str) -> Option<&'a str> {
+    tag.get(..prefix.len())
+        .filter(|head| head.eq_ignore_ascii_case(prefix))
+        .map(|_| tag[prefix.len()..].trim())
+        .filter(|value| !value.is_empty())
+}
+
+/// Comma-separated version constraints such as `>=4.28, <4.32`, all of which must hold.
+/// Versions compare by their numeric and alphabetic runs, so `4.28.1F` and `17.9(4)a` work.
+/// A version is only compared as far as the constraint spells it out: `<=4.30` includes
+/// 4.30.2F and `=17.9` matches every 17.9 release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    clauses: Vec<(Op, Vec<Segment>)>,
+    raw: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    // Declared first so that `4.28.1` sorts above a `4.28.F` style letter segment.
+    Alpha(String),
+    Num(u64),
+}
+
+impl FromStr for VersionReq {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut clauses = Vec::new();
+        for clause in value.split(',').map(str::trim) {
+            let (op, version) = [
+                (">=", Op::Ge),
+                ("<=", Op::Le),
+                (">", Op::Gt),
+                ("<", Op::Lt),
+                ("=", Op::Eq),
+            ]
+            .into_iter()
+            .find_map(|(prefix, op)| clause.strip_prefix(prefix).map(|rest| (op, rest)))
+            .unwrap_or((Op::Eq, clause));
+            let segments = segments(version);
+            if segments.is_empty() {
+                bail!("invalid version constraint '{value}'");
+            }
+            clauses.push((op, segments));
+        }
+        Ok(VersionReq {
+            clauses,
+            raw: value.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &str) -> bool {
+        let version = segments(version);
+        !version.is_empty()
+            && self.clauses.iter().all(|(op, wanted)| {
+                let seen = &version[..version.len().min(wanted.len())];
+                let ordering = seen.cmp(wanted.as_slice());
+                match op {
+                    Op::Eq => ordering == Ordering::Equal,
+                    Op::Lt => ordering == Ordering::Less,
+                    Op::Le => ordering != Ordering::Greater,
+                    Op::Gt => ordering == Ordering::Greater,
+                    Op::Ge => ordering != Ordering::Less,
+                }
+            })
+    }
+}
+
+fn segments(version: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut chars = version.trim().chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        if ch.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            segments.push(Segment::Num(digits.parse().unwrap_or(u64::MAX)));
+        } else if ch.is_ascii_alphabetic() {
+            let mut letters = String::new();
+            while let Some(letter) = chars.next_if(char::is_ascii_alphabetic) {
+                letters.push(letter.to_ascii_lowercase());
+            }
+            segments.push(Segment::Alpha(letters));
+        } else {
+            chars.next();
+        }
+    }
+    segments
+}
+
+/// The connection itself failed (refused, unreachable, name not resolved), so nothing was
+/// sent to the device and another transport can be tried safely.
+pub fn is_connect_failure(err: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return err.is_connect();
+        }
+        if let Some(async_ssh2_tokio::Error::AddressInvalid(_)) = cause.downcast_ref() {
+            return true;
+        }
+        cause.downcast_ref::<std::io::Error>().is_some_and(|err| {
+            matches!(
+                err.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::HostUnreachable
+                    | ErrorKind::NetworkUnreachable
+                    | ErrorKind::AddrNotAvailable
+            )
+        })
+    })
+}
+
+pub struct DriverRegistry {
+    drivers: Vec<DynDeviceDriver>,
+}
+
+impl DriverRegistry {
+    pub fn new(drivers: Vec<DynDeviceDriver>) -> Self {
+        Self { drivers }
+    }
+
+    /// Points every driver that logs in at `store`, so all of them share one credential
+    /// backend (and its cache) instead of each using [`crate::ssh::default_credential_store`].
+    pub fn with_credential_store(self, store: Arc<dyn CredentialStore>) -> Self {
+        let drivers = self
+            .drivers
+            .into_iter()
+            .map(|driver| driver.with_credentials(store.clone()).unwrap_or(driver))
+            .collect();
+        Self { drivers }
+    }
+
+    /// First registered driver for the type, ignoring transport and version.
+    pub fn find(&self, device_type: &DeviceType) -> Option<DynDeviceDriver> {
+        self.drivers
+            .iter()
+            .find(|driver| &driver.device_type() == device_type)
+            .cloned()
+    }
+
+    /// Drivers to try for the device, in order. Drivers restricted to versions that include
+    /// the device's `os_version:` come ahead of unrestricted ones, so they override them;
+    /// restricted drivers never serve a device without a known version. With transport
+    /// tags, the best driver for each listed transport is returned in tag order; without
+    /// them, or when no driver offers a listed transport, just the best driver overall.
+    pub fn candidates(&self, device: &Device) -> Vec<DynDeviceDriver> {
+        let version = device_version(device);
+        let mut eligible: Vec<&DynDeviceDriver> = self
+            .drivers
+            .iter()
+            .filter(|driver| driver.device_type() == device.device_type)
+            .filter(|driver| match driver.versions() {
+                Some(req) => version.is_some_and(|version| req.matches(version)),
+                None => true,
+            })
+            .collect();
+        eligible.sort_by_key(|driver| driver.versions().is_none());
+
+        let mut candidates: Vec<DynDeviceDriver> = Vec::new();
+        for transport in preferred_transports(device) {
+            let Some(driver) = eligible
+                .iter()
+                .find(|driver| driver.transports().contains(&transport))
+            else {
+                continue;
+            };
+            if !candidates
+                .iter()
+                .any(|seen| std::ptr::addr_eq(seen.as_ref(), driver.as_ref()))
+            {
+                candidates.push(Arc::clone(driver));
+            }
+        }
+        if candidates.is_empty() {
+            candidates.extend(eligible.first().map(|driver| Arc::clone(driver)));
+        }
+        candidates
+    }
+
+    /// The driver the engine runs the device with. Several candidates come back as one
+    /// driver that moves on to the next when a connection attempt fails.
+    pub fn select(&self, device: &Device) -> Option<DynDeviceDriver> {
+        let mut candidates = self.candidates(device);
+        match candidates.len() {
+            0 => None,
+            1 => candidates.pop(),
+            _ => Some(Arc::new(Fallback { candidates })),
+        }
+    }
+}
+
+/// Tries each candidate in turn while the failure is a connection failure.
+struct Fallback {
+    candidates: Vec<DynDeviceDriver>,
+}
+
+impl Fallback {
+    fn primary(&self) -> &DynDeviceDriver {
+        &self.candidates[0]
+    }
+
+    fn next_after(&self, index: usize, device: &Device, err: &anyhow::Error) -> bool {
+        let Some(next) = self.candidates.get(index + 1) else {
+            return false;
+        };
+        if !is_connect_failure(err) {
+            return false;
+        }
+        warn!(
+            target: "drivers::registry",
+            "{}: {} could not connect ({:#}), falling back to {}",
+            device.name,
+            self.candidates[index].name(),
+            err,
+            next.name()
+        );
+        true
+    }
+}
+
+#[async_trait]
+impl DeviceDriver for Fallback {
+    fn device_type(&self) -> DeviceType {
+        self.primary().device_type()
+    }
+
+    fn name(&self) -> &'static str {
+        self.primary().name()
+    }
+
+    fn capabilities(&self) -> CapabilitySet {
+        self.primary().capabilities()
+    }
+
+    fn transports(&self) -> Vec<Transport> {
+        self.candidates
+            .iter()
+            .flat_map(|driver| driver.transports())
+            .collect()
+    }
+
+    async fn execute(
+        &self,
+        device: &Device,
+        action: DriverAction<'_>,
+        ctx: &JobContext,
+    ) -> Result<DriverExecutionResult> {
+        for (index, driver) in self.candidates.iter().enumerate() {
+            match driver.execute(device, action.clone(), ctx).await {
+                Err(err) if self.next_after(index, device, &err) => continue,
+                result => return result,
+            }
+        }
+        Err(anyhow!("no driver candidates for {}", device.name))
+    }
+
+    async fn rollback(&self, device: &Device, snapshot: Option<String>) -> Result<()> {
+        for (index, driver) in self.candidates.iter().enumerate() {
+            match driver.rollback(device, snapshot.clone()).await {
+                Err(err) if self.next_after(index, device, &err) => continue,
+                result => return result,
+            }
+        }
+        Err(anyhow!("no driver candidates for {}", device.name))
+    }
+
+    /// Reports from the first candidate that reached the device, else from the primary.
+    async fn preflight(&self, device: &Device) -> Result<PreflightReport> {
+        let mut first = None;
+        for driver in &self.candidates {
+            let report = driver.preflight(device).await?;
+            if report.reachable() {
+                return Ok(report);
+            }
+            first.get_or_insert(report);
+        }
+        first.ok_or_else(|| anyhow!("no driver candidates for {}", device.name))
+    }
+
+    async fn get(&self, device: &Device, which: &[Getter]) -> Result<GetterResults> {
+        for (index, driver) in self.candidates.iter().enumerate() {
+            match driver.get(device, which).await {
+                Err(err) if self.next_after(index, device, &err) => continue,
+                result => return result,
+            }
+        }
+        Err(anyhow!("no driver candidates for {}", device.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nauto_model::{CredentialRef, JobKind};
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    struct Stub {
+        name: &'static str,
+        transports: Vec<Transport>,
+        versions: Option<VersionReq>,
+        refuse: bool,
+        calls: AtomicUsize,
+    }
+
+    fn stub(name: &'static str, transport: Transport) -> Stub {
+        Stub {
+            name,
+            transports: vec![transport],
+            versions: None,
+            refuse: false,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    #[async_trait]
+    impl DeviceDriver for Stub {
+        fn device_type(&self) -> DeviceType {
+            DeviceType::AristaEos
+        }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn capabilities(&self) -> CapabilitySet {
+            CapabilitySet::default()
+        }
+
+        fn transports(&self) -> Vec<Transport> {
+            self.transports.clone()
+        }
+
+        fn versions(&self) -> Option<VersionReq> {
+            self.versions.clone()
+        }
+
+        async fn execute(
+            &self,
+            _device: &Device,
+            _action: DriverAction<'_>,
+            _ctx: &JobContext,
+        ) -> Result<DriverExecutionResult> {
+            self.calls.fetch_add(1, AtomicOrdering::SeqCst);
+            if self.refuse {
+                let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+                return Err(anyhow::Error::new(refused).context("connect"));
+            }
+            Ok(DriverExecutionResult {
+                logs: vec![self.name.into()],
+                ..Default::default()
+            })
+        }
+
+        async fn rollback(&self, _device: &Device, _snapshot: Option<String>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn device(tags: &[&str]) -> Device {
+        Device {
+            id: "eos1".into(),
+            name: "eos1".into(),
+            device_type: DeviceType::AristaEos,
+            mgmt_address: "10.0.0.1".into(),
+            credential: CredentialRef { name: "lab".into() },
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            capabilities: CapabilitySet::default(),
+        }
+    }
+
+    fn names(registry: &DriverRegistry, device: &Device) -> Vec<&'static str> {
+        registry
+            .candidates(device)
+            .iter()
+            .map(|driver| driver.name())
+            .collect()
+    }
+
+    #[test]
+    fn version_constraints_compare_vendor_versions() {
+        let req: VersionReq = ">=4.28, <4.32".parse().unwrap();
+        assert!(req.matches("4.28.1F"));
+        assert!(req.matches("4.31.0F"));
+        assert!(!req.matches("4.32.0F"));
+        assert!(!req.matches("4.9.3M"));
+
+        let req: VersionReq = "<=17.9".parse().unwrap();
+        assert!(req.matches("17.9(4)a"));
+        assert!(!req.matches("17.10.1"));
+        assert!("=9.3".parse::<VersionReq>().unwrap().matches("9.3(8)"));
+        assert!(">=".parse::<VersionReq>().is_err());
+    }
+
+    #[test]
+    fn candidates_follow_transport_tags_and_versions() {
+        let mut newer = stub("override", Transport::Ssh);
+        newer.versions = Some(">=4.30".parse().unwrap());
+        let registry = DriverRegistry::new(vec![
+            Arc::new(stub("ssh", Transport::Ssh)),
+            Arc::new(stub("eapi", Transport::Http)),
+            Arc::new(newer),
+        ]);
+
+        assert_eq!(names(&registry, &device(&[])), ["ssh"]);
+        assert_eq!(
+            names(&registry, &device(&["transport:eapi", "transport:ssh"])),
+            ["eapi", "ssh"]
+        );
+        assert_eq!(
+            names(&registry, &device(&["transport:netconf"])),
+            ["ssh"],
+            "an unavailable transport falls back to the default driver"
+        );
+        assert_eq!(
+            names(&registry, &device(&["os_version:4.31.2F"])),
+            ["override"]
+        );
+        assert_eq!(names(&registry, &device(&["os_version:4.29.0F"])), ["ssh"]);
+        assert_eq!(
+            names(
+                &registry,
+                &device(&["os_version:4.31.2F", "transport:eapi", "transport:ssh"])
+            ),
+            ["eapi", "override"]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_only_on_connect_failure() {
+        let mut eapi = stub("eapi", Transport::Http);
+        eapi.refuse = true;
+        let eapi = Arc::new(eapi);
+        let ssh = Arc::new(stub("ssh", Transport::Ssh));
+        let registry = DriverRegistry::new(vec![ssh.clone(), eapi.clone()]);
+        let device = device(&["transport:eapi", "transport:ssh"]);
+        let kind = JobKind::CommandBatch {
+            commands: vec!["show version".into()],
+        };
+
+        let driver = registry.select(&device).unwrap();
+        let res = driver
+            .execute(&device, DriverAction::Job(&kind), &JobContext::default())
+            .await
+            .unwrap();
+        assert_eq!(res.logs, ["ssh"]);
+        assert_eq!(eapi.calls.load(AtomicOrdering::SeqCst), 1);
+
+        // Only eAPI listed: the refusal is reported rather than trying SSH.
+        let device = super::tests::device(&["transport:eapi"]);
+        let err = registry
+            .select(&device)
+            .unwrap()
+            .execute(&device, DriverAction::Job(&kind), &JobContext::default())
+            .await
+            .unwrap_err();
+        assert!(is_connect_failure(&err));
+        assert_eq!(ssh.calls.load(AtomicOrdering::SeqCst), 1);
+    }
+}
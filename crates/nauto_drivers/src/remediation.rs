@@ -0,0 +1,335 @@
+//! Remediation: the configuration to merge into a device so its running config converges on
+//! a desired config. Built on the [`config_tree`](crate::config_tree) diff, so only blocks that
+//! are missing or stale under a parent produce commands, and those commands are placed under
+//! their parent lines.
+//!
+//! IOS-style platforms get `no <line>` for stale lines; Junos gets `delete: <statement>;`
+//! inside the brace hierarchy, which `load merge` understands. Lines that set a single value
+//! (hostname, description, interface address, ...) are simply overwritten, not negated first.
+//!
+//! An entry that moved within an ordered list (ACL, route-map, prefix list, Junos firewall
+//! terms) cannot be fixed in place, since a merge only appends. The whole list is rebuilt from
+//! the desired config instead: removed and re-entered on IOS-style platforms, `replace:` on
+//! Junos. On IOS-style platforms the list is briefly absent while the snippet applies.
+
+use crate::config_tree::{self, Change, ChangeKind, ConfigTree, Node};
+use anyhow::{bail, Result};
+use nauto_model::DeviceType;
+
+/// Single-value commands on IOS, IOS-XR, EOS and NX-OS; a new value replaces the old one.
+pub(crate) const NEGATE_OVERWRITES: &[&str] = &[
+    "hostname",
+    "description",
+    "ip address",
+    "ipv4 address",
+    "ip mtu",
+    "mtu",
+    "bandwidth",
+    "speed",
+    "duplex",
+    "switchport mode",
+    "switchport access vlan",
+    "switchport trunk native vlan",
+    "ip domain name",
+    "ip domain-name",
+    "logging buffered",
+    "snmp-server location",
+    "snmp-server contact",
+    "clock timezone",
+    "exec-timeout",
+];
+
+/// Single-value Junos statements.
+const JUNOS_OVERWRITES: &[&str] = &[
+    "host-name",
+    "domain-name",
+    "time-zone",
+    "description",
+    "mtu",
+    "speed",
+    "location",
+    "contact",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Negate,
+    JunosDelete,
+}
+
+/// Config snippet that brings `running` to `desired` when pushed with `ConfigPush` in merge
+/// mode. Empty when the two already match.
+pub fn remediate(device_type: &DeviceType, running: &str, desired: &str) -> Result<String> {
+    let style = match device_type {
+        DeviceType::CiscoIos
+        | DeviceType::CiscoIosXr
+        | DeviceType::AristaEos
+        | DeviceType::CiscoNxosApi => Style::Negate,
+        DeviceType::JuniperJunos => Style::JunosDelete,
+        other => bail!("remediation is not supported for {other:?}"),
+    };
+    let (running, desired) = match style {
+        Style::Negate => (
+            ConfigTree::parse_indented(running),
+            ConfigTree::parse_indented(desired),
+        ),
+        Style::JunosDelete => (
+            ConfigTree::parse_braces(running),
+            ConfigTree::parse_braces(desired),
+        ),
+    };
+    let diff = running.diff(&desired);
+    let overwrites = match style {
+        Style::Negate => NEGATE_OVERWRITES,
+        Style::JunosDelete => JUNOS_OVERWRITES,
+    };
+
+    let mut roots: Vec<Node> = Vec::new();
+    let mut rebuilt: Vec<(&[Node], Option<String>)> = Vec::new();
+    for change in &diff.changes {
+        if rebuilt
+            .iter()
+            .any(|(parents, key)| in_list(change, parents, key))
+        {
+            continue;
+        }
+        if moved(change, &diff.changes) {
+            let key = config_tree::order_group(&[], &change.node);
+            rebuild(style, &desired, &change.parents, key.as_deref(), &mut roots);
+            rebuilt.push((&change.parents, key));
+            continue;
+        }
+        let line = match change.kind {
+            ChangeKind::Added => change.node.clone(),
+            ChangeKind::Removed => {
+                let replaced = overwritten(&change.node.text, overwrites).is_some_and(|key| {
+                    diff.changes.iter().any(|other| {
+                        other.kind == ChangeKind::Added
+                            && same_parents(&other.parents, &change.parents)
+                            && overwritten(&other.node.text, overwrites) == Some(key)
+                    })
+                });
+                if replaced {
+                    continue;
+                }
+                removal(style, &change.node)
+            }
+        };
+        let siblings = branch(&mut roots, &change.parents);
+        if !siblings.iter().any(|node| node.text == line.text) {
+            siblings.push(line);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for node in &roots {
+        push_lines(node, &mut lines);
+    }
+    let mut snippet = lines.join("\n");
+    if !snippet.is_empty() {
+        snippet.push('\n');
+    }
+    Ok(snippet)
+}
+
+/// Whether `change` is the old position of an entry that moved within its ordered list.
+fn moved(change: &Change, changes: &[Change]) -> bool {
+    change.kind == ChangeKind::Removed
+        && config_tree::order_group(&change.parents, &change.node).is_some()
+        && changes.iter().any(|other| {
+            other.kind == ChangeKind::Added
+                && other.node.text == change.node.text
+                && same_parents(&other.parents, &change.parents)
+        })
+}
+
+/// Whether `change` falls inside the list rebuilt for `parents`: everything under the block
+/// owning the list, or for a top-level list, its lines.
+fn in_list(change: &Change, parents: &[Node], key: &Option<String>) -> bool {
+    if parents.is_empty() {
+        change.parents.is_empty() && config_tree::order_group(&[], &change.node) == *key
+    } else {
+        change.parents.len() >= parents.len()
+            && same_parents(&change.parents[..parents.len()], parents)
+    }
+}
+
+/// Re-enters the ordered list under `parents` (or the top-level list `key`) as `desired` has it.
+fn rebuild(
+    style: Style,
+    desired: &ConfigTree,
+    parents: &[Node],
+    key: Option<&str>,
+    roots: &mut Vec<Node>,
+) {
+    let Some((owner, outer)) = parents.split_last() else {
+        let Some(key) = key else { return };
+        let list = Node {
+            indent: String::new(),
+            text: key.to_string(),
+            children: Vec::new(),
+        };
+        roots.push(removal(style, &list));
+        roots.extend(
+            desired
+                .roots
+                .iter()
+                .filter(|node| config_tree::order_group(&[], node).as_deref() == Some(key))
+                .cloned(),
+        );
+        return;
+    };
+    let Some(wanted) = find(&desired.roots, parents) else {
+        return;
+    };
+    let siblings = branch(roots, outer);
+    match style {
+        Style::Negate => {
+            siblings.push(removal(style, owner));
+            siblings.push(wanted.clone());
+        }
+        Style::JunosDelete => siblings.push(Node {
+            text: format!("replace: {}", wanted.text),
+            ..wanted.clone()
+        }),
+    }
+}
+
+/// The node at the end of `path` in `siblings`.
+fn find<'a>(siblings: &'a [Node], path: &[Node]) -> Option<&'a Node> {
+    let (first, rest) = path.split_first()?;
+    let node = siblings.iter().find(|node| node.text == first.text)?;
+    if rest.is_empty() {
+        Some(node)
+    } else {
+        find(&node.children, rest)
+    }
+}
+
+/// The line that removes `node` (and everything under it).
+fn removal(style: Style, node: &Node) -> Node {
+    let text = match style {
+        Style::Negate => match node.text.strip_prefix("no ") {
+            Some(positive) => positive.to_string(),
+            None => format!("no {}", node.text),
+        },
+        Style::JunosDelete => {
+            let statement = node
+                .text
+                .trim_end_matches('{')
+                .trim_end_matches(';')
+                .trim_end();
+            format!("delete: {statement};")
+        }
+    };
+    Node {
+        indent: node.indent.clone(),
+        text,
+        children: Vec::new(),
+    }
+}
+
+/// The overwrite key `text` starts with, if any.
+pub(crate) fn overwritten(text: &str, overwrites: &[&'static str]) -> Option<&'static str> {
+    if text.ends_with(" secondary") {
+        return None;
+    }
+    overwrites.iter().copied().find(|key| {
+        text.strip_prefix(key)
+            .is_some_and(|rest| rest.starts_with(' ') || rest == ";")
+    })
+}
+
+fn same_parents(a: &[Node], b: &[Node]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.text == b.text)
+}
+
+/// The children list under `parents`, creating the parent lines that are not there yet.
+fn branch<'a>(siblings: &'a mut Vec<Node>, parents: &[Node]) -> &'a mut Vec<Node> {
+    let Some((parent, rest)) = parents.split_first() else {
+        return siblings;
+    };
+    let index = match siblings.iter().position(|node| node.text == parent.text) {
+        Some(index) => index,
+        None => {
+            siblings.push(Node {
+                indent: parent.indent.clone(),
+                text: parent.text.clone(),
+                children: Vec::new(),
+            });
+            siblings.len() - 1
+        }
+    };
+    branch(&mut siblings[index].children, rest)
+}
+
+fn push_lines(node: &Node, lines: &mut Vec<String>) {
+    lines.push(format!("{}{}", node.indent, node.text));
+    for child in &node.children {
+        push_lines(child, lines);
+    }
+    if node.text.ends_with('{') {
+        lines.push(format!("{}}}", node.indent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negates_stale_lines_under_their_parents() {
+        let running = "hostname r1\n!\ninterface Gi1\n description uplink\n ip address 10.0.0.1 255.255.255.0\n\
+                       \x20shutdown\n!\nip access-list extended OLD\n permit ip any any\n!\nntp server 10.0.0.50\n\
+                       ntp server 10.0.0.51\nend\n";
+        let desired = "hostname r2\n!\ninterface Gi1\n description core uplink\n ip address 10.0.0.1 255.255.255.0\n\
+                       \x20no shutdown\n!\nntp server 10.0.0.50\nntp server 10.0.0.52\n";
+        assert_eq!(
+            remediate(&DeviceType::CiscoIos, running, desired).unwrap(),
+            "no ip access-list extended OLD\nno ntp server 10.0.0.51\ninterface Gi1\n no shutdown\n\
+             \x20description core uplink\nhostname r2\nntp server 10.0.0.52\n"
+        );
+        assert_eq!(
+            remediate(&DeviceType::CiscoIos, running, running).unwrap(),
+            ""
+        );
+
+        let junos_running =
+            "system {\n    host-name r1;\n    ntp {\n        server 10.0.0.1;\n    }\n}\n";
+        let junos_desired =
+            "system {\n    host-name r2;\n    ntp {\n        server 10.0.0.2;\n    }\n}\n";
+        assert_eq!(
+            remediate(&DeviceType::JuniperJunos, junos_running, junos_desired).unwrap(),
+            "system {\n    ntp {\n        delete: server 10.0.0.1;\n        server 10.0.0.2;\n    }\n    host-name r2;\n}\n"
+        );
+        assert!(remediate(&DeviceType::LinuxHost, "", "").is_err());
+    }
+
+    #[test]
+    fn rebuilds_ordered_lists_whose_entries_moved() {
+        let running =
+            "ip access-list extended EDGE\n permit tcp any any eq 443\n deny ip any any\n!\n\
+                       access-list 10 permit 10.0.0.0 0.255.255.255\naccess-list 10 deny any\n\
+                       ntp server 10.0.0.50\n";
+        let desired =
+            "ip access-list extended EDGE\n deny ip any any\n permit tcp any any eq 443\n!\n\
+                       access-list 10 deny any\naccess-list 10 permit 10.0.0.0 0.255.255.255\n\
+                       ntp server 10.0.0.51\n";
+        assert_eq!(
+            remediate(&DeviceType::CiscoIos, running, desired).unwrap(),
+            "no access-list 10\naccess-list 10 deny any\naccess-list 10 permit 10.0.0.0 0.255.255.255\n\
+             no ntp server 10.0.0.50\nno ip access-list extended EDGE\nip access-list extended EDGE\n\
+             \x20deny ip any any\n permit tcp any any eq 443\nntp server 10.0.0.51\n"
+        );
+
+        let junos_running = "firewall {\n    filter EDGE {\n        term web {\n            then accept;\n        }\n\
+                             \x20       term block {\n            then discard;\n        }\n    }\n}\n";
+        let junos_desired = "firewall {\n    filter EDGE {\n        term block {\n            then discard;\n        }\n\
+                             \x20       term web {\n            then accept;\n        }\n    }\n}\n";
+        assert_eq!(
+            remediate(&DeviceType::JuniperJunos, junos_running, junos_desired).unwrap(),
+            "firewall {\n    replace: filter EDGE {\n        term block {\n            then discard;\n        }\n\
+             \x20       term web {\n            then accept;\n        }\n    }\n}\n"
+        );
+    }
+}
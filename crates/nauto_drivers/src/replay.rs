@@ -0,0 +1,533 @@
+//! Record and replay of device sessions. While recording, every SSH exec, NETCONF RPC and
+//! HTTP request a driver makes is written with its response to `<dir>/<device id>.yaml`
+//! (a cassette). While replaying, the same calls are answered from the cassette and no
+//! device is contacted, so a session captured in production can be run again through the
+//! driver without lab access.
+//!
+//! Secrets of the device's credential are replaced with `<redacted>` before anything is
+//! written, and so are keys a driver derives from them (see [`redact`]). HTTP headers other
+//! than a few the drivers read are not kept. On replay a recorded `<redacted>` matches any
+//! text, and EOS session and NX-OS checkpoint names, which come from the clock or a hash,
+//! match any name of the same shape.
+
+use crate::config;
+use anyhow::{anyhow, bail, Context, Result};
+use nauto_model::{Credential, Device};
+use nauto_security::CredentialStore;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::info;
+
+pub const REDACTED: &str = "<redacted>";
+
+/// Response headers kept in recordings; the drivers read nothing else.
+const KEPT_HEADERS: &[&str] = &["content-type", "link", "location", "retry-after"];
+
+/// Names derived from the clock, a job id or a config hash.
+static GENERATED_NAMES: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"netrust-(rollback-)?[0-9a-f]{10,}").expect("generated names"));
+
+static MODE: Lazy<RwLock<Option<Mode>>> = Lazy::new(|| {
+    let mode = match (config::replay_dir(), config::record_dir()) {
+        (Some(dir), _) => Some(Mode::Replay(dir.to_path_buf())),
+        (None, Some(dir)) => Some(Mode::Record(dir.to_path_buf())),
+        (None, None) => None,
+    };
+    RwLock::new(mode)
+});
+
+static TAPES: Lazy<Mutex<HashMap<String, Arc<Tape>>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// Overrides `NAUTO_RECORD_DIR` / `NAUTO_REPLAY_DIR` (replay wins when both are set).
+/// Cassettes opened under the previous mode are dropped.
+pub fn set_mode(mode: Option<Mode>) {
+    *MODE.write().unwrap() = mode;
+    TAPES.lock().unwrap().clear();
+}
+
+pub fn mode() -> Option<Mode> {
+    MODE.read().unwrap().clone()
+}
+
+pub fn replaying() -> bool {
+    matches!(mode(), Some(Mode::Replay(_)))
+}
+
+/// Everything recorded for one device.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub device: String,
+    /// The credential with its secrets redacted, so replays resolve the same kind.
+    #[serde(default)]
+    pub credential: Option<Credential>,
+    #[serde(default)]
+    pub exchanges: Vec<Exchange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Exchange {
+    Ssh(SshExchange),
+    NetconfHello(NetconfHello),
+    Netconf(NetconfExchange),
+    Http(HttpExchange),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshExchange {
+    pub command: String,
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    #[serde(default)]
+    pub exit_status: u32,
+}
+
+/// The server hello, as received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetconfHello {
+    pub hello: String,
+}
+
+/// One RPC: the operation inside `<rpc>` and the raw `<rpc-reply>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetconfExchange {
+    pub rpc: String,
+    pub reply: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpExchange {
+    pub method: String,
+    /// Path and query, without scheme and host, so recordings survive address changes.
+    pub path: String,
+    #[serde(default)]
+    pub body: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub response: String,
+}
+
+impl Exchange {
+    fn transport(&self) -> &'static str {
+        match self {
+            Exchange::Ssh(_) => "ssh",
+            Exchange::NetconfHello(_) => "netconf hello",
+            Exchange::Netconf(_) => "netconf",
+            Exchange::Http(_) => "http",
+        }
+    }
+
+    fn request(&self) -> String {
+        match self {
+            Exchange::Ssh(ssh) => ssh.command.clone(),
+            Exchange::NetconfHello(_) => String::new(),
+            Exchange::Netconf(netconf) => netconf.rpc.clone(),
+            Exchange::Http(http) => http_request(&http.method, &http.path, &http.body),
+        }
+    }
+
+    fn redact(&mut self, tape: &TapeState) {
+        match self {
+            Exchange::Ssh(ssh) => {
+                ssh.command = tape.redact(&ssh.command);
+                ssh.stdout = tape.redact(&ssh.stdout);
+                ssh.stderr = tape.redact(&ssh.stderr);
+            }
+            Exchange::NetconfHello(_) => {}
+            Exchange::Netconf(netconf) => {
+                netconf.rpc = tape.redact(&netconf.rpc);
+                netconf.reply = tape.redact(&netconf.reply);
+            }
+            Exchange::Http(http) => {
+                http.path = tape.redact(&http.path);
+                http.body = tape.redact(&http.body);
+                http.response = tape.redact(&http.response);
+            }
+        }
+    }
+}
+
+/// The open cassette of one device.
+pub struct Tape {
+    device: String,
+    path: PathBuf,
+    replay: bool,
+    state: Mutex<TapeState>,
+}
+
+#[derive(Default)]
+struct TapeState {
+    cassette: Cassette,
+    used: Vec<bool>,
+    /// Secrets in every textual form they are sent in.
+    secrets: Vec<String>,
+}
+
+/// The cassette for `device` under the current mode, or `None` when neither recording nor
+/// replaying.
+pub fn tape(device: &Device) -> Result<Option<Arc<Tape>>> {
+    let Some(mode) = mode() else {
+        return Ok(None);
+    };
+    let mut tapes = TAPES.lock().unwrap();
+    if let Some(tape) = tapes.get(&device.id) {
+        return Ok(Some(tape.clone()));
+    }
+    let tape = match mode {
+        Mode::Record(dir) => Tape::create(&dir, device)?,
+        Mode::Replay(dir) => Tape::open(&dir, device)?,
+    };
+    let tape = Arc::new(tape);
+    tapes.insert(device.id.clone(), tape.clone());
+    Ok(Some(tape))
+}
+
+/// Resolves the device credential. Recordings keep a redacted copy, and replays return it
+/// without touching the credential store.
+pub async fn resolve_credential(
+    store: &dyn CredentialStore,
+    device: &Device,
+) -> Result<Credential> {
+    let Some(tape) = tape(device)? else {
+        return store.resolve(&device.credential).await;
+    };
+    if tape.replay {
+        return tape
+            .state
+            .lock()
+            .unwrap()
+            .cassette
+            .credential
+            .clone()
+            .ok_or_else(|| {
+                anyhow!(
+                    "recording for {} has no credential {}",
+                    tape.device,
+                    device.credential.name
+                )
+            });
+    }
+    let credential = store.resolve(&device.credential).await?;
+    let mut state = tape.state.lock().unwrap();
+    let redacted = match &credential {
+        Credential::UserPassword { username, password } => {
+            state.add_secret(password);
+            Credential::UserPassword {
+                username: username.clone(),
+                password: REDACTED.into(),
+            }
+        }
+        Credential::SshKey {
+            username,
+            key_path,
+            passphrase,
+        } => {
+            if let Some(passphrase) = passphrase {
+                state.add_secret(passphrase);
+            }
+            Credential::SshKey {
+                username: username.clone(),
+                key_path: key_path.clone(),
+                passphrase: passphrase.as_ref().map(|_| REDACTED.into()),
+            }
+        }
+        Credential::Token { token } => {
+            state.add_secret(token);
+            Credential::Token {
+                token: REDACTED.into(),
+            }
+        }
+    };
+    state.cassette.credential = Some(redacted);
+    tape.save(&state)?;
+    Ok(credential)
+}
+
+/// Marks a secret the driver obtained from the device (e.g. a session key) for redaction.
+pub fn redact(device: &Device, secret: &str) -> Result<()> {
+    if let Some(tape) = tape(device)? {
+        tape.state.lock().unwrap().add_secret(secret);
+    }
+    Ok(())
+}
+
+/// Sends an HTTP request, recording or replaying it when a cassette is open for `device`.
+pub async fn send(device: &Device, request: RequestBuilder) -> Result<Response> {
+    let Some(tape) = tape(device)? else {
+        return Ok(request.send().await?);
+    };
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().to_string();
+    let url = request.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        .unwrap_or_default();
+
+    if tape.replay {
+        let http = match tape.take("http", &http_request(&method, &path, &body))? {
+            Exchange::Http(http) => http,
+            _ => unreachable!("take filters by transport"),
+        };
+        return rebuild(http.status, &http.headers, http.response.into_bytes());
+    }
+
+    let response = client.execute(request).await?;
+    let status = response.status().as_u16();
+    let headers: BTreeMap<String, String> = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| KEPT_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let bytes = response.bytes().await?.to_vec();
+    tape.record(Exchange::Http(HttpExchange {
+        method,
+        path,
+        body,
+        status,
+        headers: headers.clone(),
+        response: String::from_utf8_lossy(&bytes).into_owned(),
+    }))?;
+    rebuild(status, &headers, bytes)
+}
+
+fn rebuild(status: u16, headers: &BTreeMap<String, String>, body: Vec<u8>) -> Result<Response> {
+    let mut builder = http::Response::builder().status(status);
+    for (name, value) in headers {
+        builder = builder.header(name, value);
+    }
+    Ok(Response::from(builder.body(body)?))
+}
+
+fn http_request(method: &str, path: &str, body: &str) -> String {
+    if body.is_empty() {
+        format!("{method} {path}")
+    } else {
+        format!("{method} {path}\n{body}")
+    }
+}
+
+impl Tape {
+    fn create(dir: &Path, device: &Device) -> Result<Tape> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("create recording directory {}", dir.display()))?;
+        let tape = Tape {
+            device: device.id.clone(),
+            path: cassette_path(dir, device),
+            replay: false,
+            state: Mutex::new(TapeState {
+                cassette: Cassette {
+                    device: device.id.clone(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        };
+        tape.save(&tape.state.lock().unwrap())?;
+        info!(
+            target: "drivers::replay",
+            "recording {} to {}",
+            device.name,
+            tape.path.display()
+        );
+        Ok(tape)
+    }
+
+    fn open(dir: &Path, device: &Device) -> Result<Tape> {
+        let path = cassette_path(dir, device);
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("no recording for {} at {}", device.id, path.display()))?;
+        let cassette: Cassette = serde_yaml::from_str(&text)
+            .with_context(|| format!("parse recording {}", path.display()))?;
+        info!(
+            target: "drivers::replay",
+            "replaying {} exchanges for {} from {}",
+            cassette.exchanges.len(),
+            device.name,
+            path.display()
+        );
+        Ok(Tape {
+            device: device.id.clone(),
+            path,
+            replay: true,
+            state: Mutex::new(TapeState {
+                used: vec![false; cassette.exchanges.len()],
+                cassette,
+                secrets: Vec::new(),
+            }),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.replay
+    }
+
+    /// Appends an exchange (redacted) and rewrites the cassette.
+    pub fn record(&self, mut exchange: Exchange) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        exchange.redact(&state);
+        state.cassette.exchanges.push(exchange);
+        self.save(&state)
+    }
+
+    /// The first unplayed exchange on `transport` whose request matches.
+    pub fn take(&self, transport: &str, request: &str) -> Result<Exchange> {
+        let mut state = self.state.lock().unwrap();
+        let TapeState { cassette, used, .. } = &mut *state;
+        let index = cassette
+            .exchanges
+            .iter()
+            .enumerate()
+            .position(|(index, exchange)| {
+                !used[index]
+                    && exchange.transport() == transport
+                    && matches_recorded(&exchange.request(), request)
+            });
+        let Some(index) = index else {
+            bail!(
+                "replay: no recorded {} exchange for {:?} on {} in {}",
+                transport,
+                request,
+                self.device,
+                self.path.display()
+            );
+        };
+        used[index] = true;
+        Ok(cassette.exchanges[index].clone())
+    }
+
+    fn save(&self, state: &TapeState) -> Result<()> {
+        let yaml = serde_yaml::to_string(&state.cassette)?;
+        std::fs::write(&self.path, yaml)
+            .with_context(|| format!("write recording {}", self.path.display()))
+    }
+}
+
+impl TapeState {
+    fn add_secret(&mut self, secret: &str) {
+        if secret.is_empty() || secret == REDACTED {
+            return;
+        }
+        let json = serde_json::to_string(secret).unwrap_or_default();
+        let mut form = reqwest::Url::parse("http://recording/").expect("static url");
+        form.query_pairs_mut().append_pair("s", secret);
+        let forms = [
+            secret.to_string(),
+            json.trim_matches('"').to_string(),
+            form.query()
+                .unwrap_or_default()
+                .trim_start_matches("s=")
+                .to_string(),
+        ];
+        for form in forms {
+            if !self.secrets.contains(&form) {
+                self.secrets.push(form);
+            }
+        }
+        // Longest first, so a secret containing another is replaced whole.
+        self.secrets
+            .sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+    }
+}
+
+fn cassette_path(dir: &Path, device: &Device) -> PathBuf {
+    let name: String = device
+        .id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("{name}.yaml"))
+}
+
+/// Whether a live request is the one recorded, treating `<redacted>` as any text and
+/// generated session/checkpoint names as equal.
+fn matches_recorded(recorded: &str, live: &str) -> bool {
+    let recorded = GENERATED_NAMES.replace_all(recorded, "netrust-${1}*");
+    let live = GENERATED_NAMES.replace_all(live, "netrust-${1}*");
+    let mut parts = recorded.split(REDACTED);
+    let Some(rest) = live.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    let mut rest = rest;
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_secrets_and_generated_names_match_on_replay() {
+        let mut state = TapeState::default();
+        state.add_secret("s3cr+t&pw");
+        assert_eq!(
+            state.redact("type=keygen&user=admin&password=s3cr%2Bt%26pw"),
+            "type=keygen&user=admin&password=<redacted>"
+        );
+        assert_eq!(
+            state.redact(r#"{"password": "s3cr+t&pw"}"#),
+            r#"{"password": "<redacted>"}"#
+        );
+
+        assert!(matches_recorded(
+            "POST /api/?type=keygen&password=<redacted>",
+            "POST /api/?type=keygen&password=%3Credacted%3E"
+        ));
+        assert!(!matches_recorded(
+            "POST /api/?type=keygen&password=<redacted>",
+            "GET /api/?type=keygen&password=x"
+        ));
+        assert!(matches_recorded(
+            "configure session netrust-1718000000123",
+            "configure session netrust-1718000999999"
+        ));
+        assert!(matches_recorded(
+            "configure session netrust-rollback-1718000000123\nrollback clean-config",
+            "configure session netrust-rollback-1720000000000\nrollback clean-config"
+        ));
+        assert!(!matches_recorded("show version", "show version | json"));
+    }
+}
@@ -1,28 +1,144 @@
 use crate::config;
-use anyhow::{bail, Context, Result};
-use async_ssh2_tokio::{AuthMethod, Client, ServerCheckMethod};
-use nauto_model::{Credential, Device};
-use nauto_security::{CredentialStore, KeyringStore};
+use crate::replay::{self, Exchange, SshExchange, Tape};
+use anyhow::{anyhow, bail, Context, Result};
+use async_ssh2_tokio::client::CommandExecutedResult;
+use async_ssh2_tokio::{AuthMethod, ServerCheckMethod};
+use async_trait::async_trait;
+use nauto_model::{Credential, CredentialRef, Device};
+use nauto_security::{CredentialStore, KeyringStore, VaultConfig, VaultStore};
+use once_cell::sync::Lazy;
+use russh::client::Msg;
+use russh::Channel;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
+use tracing::debug;
 
 pub const KEYRING_SERVICE: &str = "netrust";
 pub const DEFAULT_SSH_PORT: u16 = 22;
 pub const DEFAULT_NETCONF_PORT: u16 = 830;
+/// Pause between reconnect attempts while a device reloads.
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(15);
 
-pub fn default_credential_store() -> KeyringStore {
-    KeyringStore::new(KEYRING_SERVICE)
+static CREDENTIAL_STORE: Lazy<Arc<dyn CredentialStore>> =
+    Lazy::new(|| match config::credential_backend() {
+        None | Some("keyring") => Arc::new(KeyringStore::new(KEYRING_SERVICE)),
+        Some("vault") => match VaultConfig::from_env() {
+            Some(vault) => match VaultStore::new(vault) {
+                Ok(store) => Arc::new(store),
+                Err(err) => Arc::new(Unavailable(format!("Vault credential store: {err:#}"))),
+            },
+            None => Arc::new(Unavailable(
+                "NAUTO_CREDENTIAL_STORE=vault needs VAULT_ADDR".into(),
+            )),
+        },
+        Some(other) => Arc::new(Unavailable(format!(
+            "unknown NAUTO_CREDENTIAL_STORE '{other}' (expected keyring or vault)"
+        ))),
+    });
+
+/// The store drivers resolve credentials from unless they are given one: the OS keyring, or
+/// Vault with `NAUTO_CREDENTIAL_STORE=vault` (see `nauto_security::VaultConfig::from_env`).
+/// One instance per process, so Vault logins and cached secrets are shared by every driver.
+///
+/// A `credentials:` chain in the netrust config file replaces this store: the CLI builds the
+/// chain and passes it to every driver through [`crate::DriverRegistry::with_credential_store`],
+/// and `NAUTO_CREDENTIAL_STORE` is ignored.
+pub fn default_credential_store() -> Arc<dyn CredentialStore> {
+    CREDENTIAL_STORE.clone()
+}
+
+/// A store that could not be set up. Every lookup fails with the reason, rather than
+/// quietly falling back to a different backend.
+struct Unavailable(String);
+
+#[async_trait]
+impl CredentialStore for Unavailable {
+    async fn store(&self, _reference: &CredentialRef, _credential: &Credential) -> Result<()> {
+        Err(anyhow!(self.0.clone()))
+    }
+
+    async fn resolve(&self, _reference: &CredentialRef) -> Result<Credential> {
+        Err(anyhow!(self.0.clone()))
+    }
 }
 
 pub fn command_timeout() -> std::time::Duration {
     config::ssh_command_timeout()
 }
 
-pub async fn connect(device: &Device, store: &KeyringStore, port: u16) -> Result<Client> {
-    let credential = store
-        .resolve(&device.credential)
+/// An SSH connection, or the recording standing in for one while replaying (see
+/// [`crate::replay`]). Exec requests are recorded and replayed; channels and SFTP uploads
+/// are not, so interactive shells, NETCONF over SSH and file transfers need a live device
+/// (NETCONF records its RPCs itself).
+pub struct Client {
+    inner: Inner,
+}
+
+enum Inner {
+    Live {
+        client: async_ssh2_tokio::Client,
+        tape: Option<Arc<Tape>>,
+    },
+    Replay(Arc<Tape>),
+}
+
+impl Client {
+    pub async fn execute(&self, command: &str) -> Result<CommandExecutedResult> {
+        match &self.inner {
+            Inner::Live { client, tape } => {
+                let result = client.execute(command).await?;
+                if let Some(tape) = tape {
+                    tape.record(Exchange::Ssh(SshExchange {
+                        command: command.to_string(),
+                        stdout: result.stdout.clone(),
+                        stderr: result.stderr.clone(),
+                        exit_status: result.exit_status,
+                    }))?;
+                }
+                Ok(result)
+            }
+            Inner::Replay(tape) => match tape.take("ssh", command)? {
+                Exchange::Ssh(exec) => Ok(CommandExecutedResult {
+                    stdout: exec.stdout,
+                    stderr: exec.stderr,
+                    exit_status: exec.exit_status,
+                }),
+                _ => unreachable!("take filters by transport"),
+            },
+        }
+    }
+
+    pub async fn get_channel(&self) -> Result<Channel<Msg>> {
+        match &self.inner {
+            Inner::Live { client, .. } => Ok(client.get_channel().await?),
+            Inner::Replay(_) => bail!("ssh channels are not replayed"),
+        }
+    }
+
+    pub async fn upload_file<T: AsRef<Path>, U: Into<String>>(
+        &self,
+        source: T,
+        destination: U,
+    ) -> Result<()> {
+        match &self.inner {
+            Inner::Live { client, .. } => Ok(client.upload_file(source, destination).await?),
+            Inner::Replay(_) => bail!("sftp uploads are not replayed"),
+        }
+    }
+}
+
+pub async fn connect(device: &Device, store: &dyn CredentialStore, port: u16) -> Result<Client> {
+    let tape = replay::tape(device)?;
+    if let Some(tape) = tape.as_ref().filter(|tape| tape.is_replay()) {
+        return Ok(Client {
+            inner: Inner::Replay(tape.clone()),
+        });
+    }
+    let credential = replay::resolve_credential(store, device)
         .await
         .with_context(|| format!("loading credential {}", device.credential.name))?;
     let (username, auth) = credential_to_auth(&credential).await?;
@@ -31,15 +147,55 @@ pub async fn connect(device: &Device, store: &KeyringStore, port: u16) -> Result
         .map(TargetAddr::Socket)
         .unwrap_or_else(|_| TargetAddr::HostPort(device.mgmt_address.clone(), port));
 
-    let server_check = ServerCheckMethod::DefaultKnownHostsFile;
+    let server_check = match config::ssh_known_hosts() {
+        Some(path) => ServerCheckMethod::KnownHostsFile(path.to_string_lossy().into_owned()),
+        None => ServerCheckMethod::DefaultKnownHostsFile,
+    };
 
-    match target {
-        TargetAddr::Socket(addr) => Client::connect(addr, &username, auth, server_check).await,
+    let client = match target {
+        TargetAddr::Socket(addr) => {
+            async_ssh2_tokio::Client::connect(addr, &username, auth, server_check).await
+        }
         TargetAddr::HostPort(host, port) => {
-            Client::connect((host.as_str(), port), &username, auth, server_check).await
+            async_ssh2_tokio::Client::connect((host.as_str(), port), &username, auth, server_check)
+                .await
+        }
+    }
+    .with_context(|| format!("ssh connect {} ({})", device.name, device.mgmt_address))?;
+    Ok(Client {
+        inner: Inner::Live { client, tape },
+    })
+}
+
+/// Polls until the device accepts SSH again after a reload, giving up after `timeout`.
+/// The first attempt waits one poll interval so a device still shutting down is not
+/// mistaken for one that is back.
+pub async fn wait_until_reachable(
+    device: &Device,
+    store: &dyn CredentialStore,
+    port: u16,
+    timeout: Duration,
+) -> Result<Client> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if !replay::replaying() {
+            tokio::time::sleep(RELOAD_POLL_INTERVAL).await;
+        }
+        match tokio::time::timeout(command_timeout(), connect(device, store, port)).await {
+            Ok(Ok(client)) => return Ok(client),
+            Ok(Err(err)) => {
+                debug!(target: "drivers::ssh", "{} not back yet: {:#}", device.name, err)
+            }
+            Err(_) => debug!(target: "drivers::ssh", "{} connect timed out", device.name),
+        }
+        if tokio::time::Instant::now() >= deadline {
+            bail!(
+                "{} did not come back within {}s",
+                device.name,
+                timeout.as_secs()
+            );
         }
     }
-    .with_context(|| format!("ssh connect {} ({})", device.name, device.mgmt_address))
 }
 
 enum TargetAddr {
@@ -0,0 +1,573 @@
+//! File copies onto devices over SCP or SFTP. The local digest is computed while the file
+//! is read, so a copy can be checked afterwards with the device's own `verify` command.
+
+use crate::getters::CommandRunner;
+use crate::ssh::Client;
+use anyhow::{anyhow, bail, Context, Result};
+use nauto_model::{Device, TransferProtocol};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha512};
+use std::fmt;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::info;
+
+const CHUNK: usize = 64 * 1024;
+const DEFAULT_FILESYSTEM: &str = "flash:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Md5,
+    Sha512,
+}
+
+impl Algorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+
+    fn hex_len(self) -> usize {
+        match self {
+            Algorithm::Md5 => 32,
+            Algorithm::Sha512 => 128,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub hex: String,
+}
+
+impl Checksum {
+    /// Parses `md5:<hex>` or `sha512:<hex>`; a bare digest is told apart by its length.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (algorithm, hex) = match text.split_once(':') {
+            Some((name, hex)) => match name.to_ascii_lowercase().as_str() {
+                "md5" => (Algorithm::Md5, hex),
+                "sha512" => (Algorithm::Sha512, hex),
+                other => bail!("unsupported checksum algorithm '{}'", other),
+            },
+            None if text.len() == Algorithm::Md5.hex_len() => (Algorithm::Md5, text),
+            None if text.len() == Algorithm::Sha512.hex_len() => (Algorithm::Sha512, text),
+            None => bail!(
+                "cannot tell the algorithm of checksum '{}'; prefix it with md5: or sha512:",
+                text
+            ),
+        };
+        if hex.len() != algorithm.hex_len() || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            bail!("'{}' is not a {} digest", hex, algorithm.name());
+        }
+        Ok(Self {
+            algorithm,
+            hex: hex.to_ascii_lowercase(),
+        })
+    }
+
+    /// Exec command that prints the digest of `path`; IOS and EOS share the syntax.
+    pub fn verify_command(&self, path: &str) -> String {
+        format!("verify /{} {}", self.algorithm.name(), path)
+    }
+
+    /// Whether the output of [`Checksum::verify_command`] reports this digest.
+    pub fn found_in(&self, output: &str) -> bool {
+        output.to_ascii_lowercase().contains(&self.hex)
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm.name(), self.hex)
+    }
+}
+
+/// A local file about to be copied.
+#[derive(Debug, Clone)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+    pub checksum: Checksum,
+}
+
+impl LocalFile {
+    /// Reads and hashes `source`. An `expected` digest picks the algorithm and must match
+    /// before anything is sent; without one the file gets an MD5.
+    pub async fn inspect(source: &str, expected: Option<&str>) -> Result<Self> {
+        let expected = expected.map(Checksum::parse).transpose()?;
+        let algorithm = expected
+            .as_ref()
+            .map_or(Algorithm::Md5, |checksum| checksum.algorithm);
+        let path = PathBuf::from(source);
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("{} has no file name", source))?
+            .to_string();
+        let mut file = tokio::fs::File::open(&path)
+            .await
+            .with_context(|| format!("opening {}", source))?;
+        let mut hasher = Hasher::new(algorithm);
+        let mut buf = vec![0u8; CHUNK];
+        let mut size = 0u64;
+        loop {
+            let read = file
+                .read(&mut buf)
+                .await
+                .with_context(|| format!("reading {}", source))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            size += read as u64;
+        }
+        let checksum = Checksum {
+            algorithm,
+            hex: hasher.finish(),
+        };
+        if let Some(expected) = expected {
+            if expected != checksum {
+                bail!(
+                    "{} has {} but the job expects {}",
+                    source,
+                    checksum,
+                    expected
+                );
+            }
+        }
+        Ok(Self {
+            path,
+            name,
+            size,
+            checksum,
+        })
+    }
+}
+
+enum Hasher {
+    Md5(md5::Context),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(md5::Context::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(context) => context.consume(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Hasher::Md5(context) => format!("{:x}", context.compute()),
+            Hasher::Sha512(hasher) => hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+}
+
+/// A checked Upgrade job: the local image and the version the device should report once
+/// it has booted from it.
+#[derive(Debug, Clone)]
+pub struct UpgradePlan {
+    pub image: LocalFile,
+    pub target_version: String,
+    pub protocol: TransferProtocol,
+}
+
+impl UpgradePlan {
+    pub async fn prepare(
+        image: &str,
+        target_version: &str,
+        protocol: TransferProtocol,
+        checksum: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self {
+            image: LocalFile::inspect(image, checksum).await?,
+            target_version: target_version.to_string(),
+            protocol,
+        })
+    }
+
+    /// CLI path the image is staged at and booted from; IOS and EOS both boot from `flash:`.
+    pub fn boot_path(&self) -> String {
+        format!("{DEFAULT_FILESYSTEM}{}", self.image.name)
+    }
+}
+
+/// Where a copy lands: the path the CLI uses for `dir` and `verify` (`flash:x.bin`) and the
+/// one the SCP or SFTP server expects, which differ on some platforms.
+#[derive(Debug, Clone)]
+pub struct Destination {
+    pub cli_path: String,
+    pub remote_path: String,
+}
+
+impl Destination {
+    pub fn new(cli_path: impl Into<String>, remote_path: impl Into<String>) -> Self {
+        Self {
+            cli_path: cli_path.into(),
+            remote_path: remote_path.into(),
+        }
+    }
+
+    /// Filesystem of the CLI path (`bootflash:` for `bootflash:/a.bin`), `flash:` if none.
+    pub fn filesystem(&self) -> &str {
+        match self.cli_path.find(':') {
+            Some(idx) => &self.cli_path[..=idx],
+            None => DEFAULT_FILESYSTEM,
+        }
+    }
+}
+
+/// Free-space check, copy and on-device verification of `file`. A copy that is already in
+/// place with the right digest is left alone, so a retried upgrade skips the transfer.
+pub async fn stage(
+    runner: &dyn CommandRunner,
+    client: &Client,
+    device: &Device,
+    file: &LocalFile,
+    destination: &Destination,
+    protocol: TransferProtocol,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    let verify = file.checksum.verify_command(&destination.cli_path);
+    if let Ok(output) = runner.text(&verify).await {
+        if file.checksum.found_in(&output) {
+            logs.push(format!(
+                "[{}] {} already present with {}; transfer skipped",
+                device.name, destination.cli_path, file.checksum
+            ));
+            return Ok(());
+        }
+    }
+    check_free_space(runner, device, file, destination, logs).await?;
+    upload(
+        client,
+        device,
+        file,
+        &destination.remote_path,
+        protocol,
+        logs,
+    )
+    .await?;
+    let output = runner.text(&verify).await?;
+    if !file.checksum.found_in(&output) {
+        bail!(
+            "{} on {} failed verification, expected {}: {}",
+            destination.cli_path,
+            device.name,
+            file.checksum,
+            output.trim()
+        );
+    }
+    logs.push(format!(
+        "[{}] verified {} ({})",
+        device.name, destination.cli_path, file.checksum
+    ));
+    Ok(())
+}
+
+/// Fails unless the destination filesystem has room for `file`.
+pub async fn check_free_space(
+    runner: &dyn CommandRunner,
+    device: &Device,
+    file: &LocalFile,
+    destination: &Destination,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    let filesystem = destination.filesystem();
+    let listing = runner.text(&format!("dir {filesystem}")).await?;
+    let free = bytes_free(&listing)
+        .ok_or_else(|| anyhow!("no free space figure in 'dir {}' output", filesystem))?;
+    if free < file.size {
+        bail!(
+            "{} needs {} bytes on {} {} but only {} are free",
+            file.name,
+            file.size,
+            device.name,
+            filesystem,
+            free
+        );
+    }
+    logs.push(format!(
+        "[{}] {} has {} bytes free for {} ({} bytes)",
+        device.name, filesystem, free, file.name, file.size
+    ));
+    Ok(())
+}
+
+/// `(N bytes free)` from the `dir` summary line on IOS and EOS.
+pub fn bytes_free(listing: &str) -> Option<u64> {
+    static FREE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\((\d+) bytes free\)").expect("bytes free regex"));
+    FREE.captures(listing)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+/// Copies `file` to `remote_path` on the device.
+pub async fn upload(
+    client: &Client,
+    device: &Device,
+    file: &LocalFile,
+    remote_path: &str,
+    protocol: TransferProtocol,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    info!(
+        target: "drivers::transfer",
+        "{} copying {} ({} bytes) to {} over {:?}",
+        device.name,
+        file.name,
+        file.size,
+        remote_path,
+        protocol
+    );
+    let mut progress = Progress::new(&device.name, file.size, logs);
+    match protocol {
+        TransferProtocol::Scp => {
+            let channel = client
+                .get_channel()
+                .await
+                .with_context(|| format!("scp channel {}", device.name))?;
+            channel
+                .exec(true, format!("scp -t {remote_path}"))
+                .await
+                .context("scp exec denied")?;
+            let mut stream = channel.into_stream();
+            let mut source = tokio::fs::File::open(&file.path)
+                .await
+                .with_context(|| format!("opening {}", file.path.display()))?;
+            scp_send(
+                &mut stream,
+                &mut source,
+                &file.name,
+                file.size,
+                &mut progress,
+            )
+            .await
+            .with_context(|| format!("scp {} to {}", file.name, device.name))?;
+        }
+        TransferProtocol::Sftp => {
+            client
+                .upload_file(&file.path, remote_path.to_string())
+                .await
+                .with_context(|| format!("sftp {} to {}", file.name, device.name))?;
+            progress.advance(file.size);
+        }
+    }
+    Ok(())
+}
+
+/// Source side of SCP for a single file: wait for the sink to be ready, then send the `C`
+/// header, the content and a closing NUL, checking the acknowledgement after each.
+pub async fn scp_send<S, R>(
+    stream: &mut S,
+    source: &mut R,
+    name: &str,
+    size: u64,
+    progress: &mut Progress<'_>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    read_ack(stream).await?;
+    stream
+        .write_all(format!("C0644 {size} {name}\n").as_bytes())
+        .await?;
+    read_ack(stream).await?;
+    let mut buf = vec![0u8; CHUNK];
+    let mut sent = 0u64;
+    while sent < size {
+        let want = buf.len().min((size - sent) as usize);
+        let read = source.read(&mut buf[..want]).await?;
+        if read == 0 {
+            bail!("{} ended after {} of {} bytes", name, sent, size);
+        }
+        stream.write_all(&buf[..read]).await?;
+        sent += read as u64;
+        progress.advance(sent);
+    }
+    stream.write_all(&[0]).await?;
+    stream.flush().await?;
+    read_ack(stream).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// One status byte from the sink: 0 is OK, 1 and 2 carry a message up to the newline.
+async fn read_ack<S: AsyncRead + Unpin>(stream: &mut S) -> Result<()> {
+    let mut byte = [0u8; 1];
+    stream
+        .read_exact(&mut byte)
+        .await
+        .context("scp sink closed the channel")?;
+    if byte[0] == 0 {
+        return Ok(());
+    }
+    let mut message = Vec::new();
+    loop {
+        let mut next = [0u8; 1];
+        if stream.read(&mut next).await? == 0 || next[0] == b'\n' {
+            break;
+        }
+        message.push(next[0]);
+    }
+    bail!(
+        "scp sink {}: {}",
+        if byte[0] == 1 { "warning" } else { "error" },
+        String::from_utf8_lossy(&message).trim()
+    )
+}
+
+/// Turns byte counts into a job log line at every quarter of a transfer.
+pub struct Progress<'a> {
+    device: &'a str,
+    total: u64,
+    next_percent: u64,
+    logs: &'a mut Vec<String>,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(device: &'a str, total: u64, logs: &'a mut Vec<String>) -> Self {
+        Self {
+            device,
+            total,
+            next_percent: 25,
+            logs,
+        }
+    }
+
+    pub fn advance(&mut self, sent: u64) {
+        let percent = sent
+            .saturating_mul(100)
+            .checked_div(self.total)
+            .unwrap_or(100);
+        while self.next_percent <= percent.min(100) {
+            self.logs.push(format!(
+                "[{}] transferred {}% ({} of {} bytes)",
+                self.device,
+                self.next_percent,
+                sent.min(self.total),
+                self.total
+            ));
+            self.next_percent += 25;
+        }
+    }
+}
+
+/// Whether a reported OS version is the upgrade target. Platforms append build details
+/// (`4.31.2F-35233.4312F`), so a prefix match is enough.
+pub fn version_matches(running: &str, target: &str) -> bool {
+    let target = target.trim();
+    !target.is_empty()
+        && running
+            .trim()
+            .to_ascii_lowercase()
+            .starts_with(&target.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scp_send_follows_sink_protocol_and_reports_progress() {
+        let dir = std::env::temp_dir().join(format!("netrust-transfer-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("image.bin");
+        tokio::fs::write(&path, b"hello").await.unwrap();
+        let source = path.to_str().unwrap();
+        let file = LocalFile::inspect(source, Some("MD5:5D41402ABC4B2A76B9719D911017C592"))
+            .await
+            .unwrap();
+        assert_eq!(file.name, "image.bin");
+        assert_eq!(file.size, 5);
+        assert!(LocalFile::inspect(source, Some(&"0".repeat(32)))
+            .await
+            .is_err());
+        assert!(file
+            .checksum
+            .found_in("verify /md5 (flash:image.bin) = 5d41402abc4b2a76b9719d911017c592"));
+
+        let (mut local, mut remote) = tokio::io::duplex(1024);
+        let sink = tokio::spawn(async move {
+            let mut received = Vec::new();
+            remote.write_all(&[0]).await.unwrap();
+            let mut byte = [0u8; 1];
+            loop {
+                remote.read_exact(&mut byte).await.unwrap();
+                received.push(byte[0]);
+                if byte[0] == b'\n' {
+                    break;
+                }
+            }
+            remote.write_all(&[0]).await.unwrap();
+            let mut body = [0u8; 6];
+            remote.read_exact(&mut body).await.unwrap();
+            received.extend_from_slice(&body);
+            remote.write_all(&[0]).await.unwrap();
+            received
+        });
+        let mut logs = Vec::new();
+        let mut progress = Progress::new("ios1", file.size, &mut logs);
+        let mut content = tokio::fs::File::open(&path).await.unwrap();
+        scp_send(
+            &mut local,
+            &mut content,
+            &file.name,
+            file.size,
+            &mut progress,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sink.await.unwrap(), b"C0644 5 image.bin\nhello\0");
+        assert_eq!(logs.len(), 4);
+        assert_eq!(logs[3], "[ios1] transferred 100% (5 of 5 bytes)");
+
+        let (mut local, mut remote) = tokio::io::duplex(64);
+        remote
+            .write_all(b"\x02scp: flash:image.bin: No space left\n")
+            .await
+            .unwrap();
+        let mut logs = Vec::new();
+        let mut progress = Progress::new("ios1", 5, &mut logs);
+        let err = scp_send(
+            &mut local,
+            &mut &b"hello"[..],
+            "image.bin",
+            5,
+            &mut progress,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("No space left"));
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        let listing = "Directory of flash:/\n\n1621966848 bytes total (1546358784 bytes free)\n";
+        assert_eq!(bytes_free(listing), Some(1_546_358_784));
+        assert_eq!(
+            Destination::new("bootflash:/a.bin", "bootflash:/a.bin").filesystem(),
+            "bootflash:"
+        );
+        assert!(version_matches("4.31.2F-35233.4312F", "4.31.2f"));
+        assert!(!version_matches("17.6.1", "17.9.4a"));
+    }
+}
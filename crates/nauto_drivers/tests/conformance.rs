@@ -0,0 +1,235 @@
+//! Runs the conformance suite for each built-in driver against its local stand-in. Lives in
+//! its own test binary so the short transport timeouts are in place before any driver reads
+//! them. `MockDriver` and `SimulatedDriver` are not run: they keep device state in-process
+//! and have no transport for a stand-in to play.
+
+use nauto_drivers::conformance::{
+    assert_conformant, cli::CliStandIn, fortios::FortiosStandIn, linux::LinuxStandIn,
+    meraki::MerakiStandIn, netconf::NetconfStandIn, panos::PanosStandIn, use_fallback_credentials,
+    use_short_timeouts, Scenario,
+};
+use nauto_drivers::drivers::{
+    AristaEosDriver, CiscoIosDriver, CiscoIosXrDriver, CiscoNxosApiDriver, FortinetFortiosDriver,
+    GenericNetconfDriver, GenericSshDriver, JuniperJunosDriver, LinuxHostDriver, MerakiCloudDriver,
+    PaloAltoPanosDriver,
+};
+use nauto_drivers::netconf::{CAP_CANDIDATE, CAP_VALIDATE};
+use nauto_model::{CapabilitySet, CredentialRef, Device, DeviceType};
+use serde_json::json;
+
+const RUNNING: &[&str] = &[
+    "hostname lab1",
+    "interface Ethernet1",
+    "   description uplink",
+];
+
+fn setup() {
+    use_short_timeouts();
+    use_fallback_credentials();
+}
+
+fn device(device_type: DeviceType, address: &str, credential: &str, tags: &[&str]) -> Device {
+    Device {
+        id: "dut".into(),
+        name: "conformance-dut".into(),
+        device_type,
+        mgmt_address: address.into(),
+        credential: CredentialRef {
+            name: credential.into(),
+        },
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        capabilities: CapabilitySet::default(),
+    }
+}
+
+fn cli_scenario(device: Device) -> Scenario {
+    Scenario {
+        device,
+        commands: vec!["show version".into(), "show ip interface brief".into()],
+        snippet: "ntp server 10.0.0.9\n".into(),
+        expect: "ntp server 10.0.0.9".into(),
+    }
+}
+
+#[tokio::test]
+async fn cisco_ios_over_ssh() {
+    setup();
+    let standin = CliStandIn::ssh(RUNNING).await;
+    let device = device(DeviceType::CiscoIos, standin.address(), "lab", &[]);
+    assert_conformant(&CiscoIosDriver::default(), &standin, &cli_scenario(device)).await;
+}
+
+#[tokio::test]
+async fn cisco_iosxr_over_interactive_ssh() {
+    setup();
+    let standin = CliStandIn::iosxr(&["hostname xr1"]).await;
+    let device = device(DeviceType::CiscoIosXr, standin.address(), "lab", &[]);
+    assert_conformant(
+        &CiscoIosXrDriver::default(),
+        &standin,
+        &cli_scenario(device),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn generic_ssh() {
+    setup();
+    let standin = CliStandIn::ssh(RUNNING).await;
+    let device = device(DeviceType::GenericSsh, standin.address(), "lab", &[]);
+    assert_conformant(
+        &GenericSshDriver::default(),
+        &standin,
+        &cli_scenario(device),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn arista_eos_over_ssh() {
+    setup();
+    let standin = CliStandIn::ssh(RUNNING).await;
+    let device = device(DeviceType::AristaEos, standin.address(), "lab", &[]);
+    assert_conformant(&AristaEosDriver::default(), &standin, &cli_scenario(device)).await;
+}
+
+#[tokio::test]
+async fn arista_eos_over_eapi() {
+    setup();
+    let standin = CliStandIn::eapi(RUNNING).await;
+    let device = device(DeviceType::AristaEos, standin.address(), "lab", &[]);
+    assert_conformant(&AristaEosDriver::default(), &standin, &cli_scenario(device)).await;
+}
+
+#[tokio::test]
+async fn cisco_nxos_over_nxapi() {
+    setup();
+    let standin = CliStandIn::nxapi(RUNNING).await;
+    let device = device(DeviceType::CiscoNxosApi, standin.address(), "nxos-lab", &[]);
+    assert_conformant(
+        &CiscoNxosApiDriver::default(),
+        &standin,
+        &cli_scenario(device),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn generic_netconf() {
+    setup();
+    let standin =
+        NetconfStandIn::spawn(&[CAP_CANDIDATE, CAP_VALIDATE], "<hostname>lab1</hostname>").await;
+    let device = device(DeviceType::Netconf, standin.address(), "lab", &[]);
+    let scenario = Scenario {
+        device,
+        commands: vec!["<get-config><source><running/></source></get-config>".into()],
+        snippet: "<ntp><server>10.0.0.9</server></ntp>".into(),
+        expect: "<server>10.0.0.9</server>".into(),
+    };
+    assert_conformant(&GenericNetconfDriver::default(), &standin, &scenario).await;
+}
+
+#[tokio::test]
+async fn juniper_junos_over_netconf() {
+    setup();
+    let standin = NetconfStandIn::junos(
+        "## Last commit: 2024-05-01 10:00:00 UTC by admin\nsystem {\n    host-name lab1;\n}\n",
+    )
+    .await;
+    let device = device(DeviceType::JuniperJunos, standin.address(), "lab", &[]);
+    let scenario = Scenario {
+        device,
+        commands: vec!["show version".into(), "show interfaces terse".into()],
+        snippet: "system {\n    ntp {\n        server 10.0.0.9;\n    }\n}\n".into(),
+        expect: "server 10.0.0.9;".into(),
+    };
+    assert_conformant(&JuniperJunosDriver::default(), &standin, &scenario).await;
+}
+
+#[tokio::test]
+async fn paloalto_panos_over_xml_api() {
+    setup();
+    let standin = PanosStandIn::spawn("<config><address/></config>").await;
+    let device = device(
+        DeviceType::PaloAltoPanos,
+        standin.address(),
+        "panos-lab",
+        &[],
+    );
+    let scenario = Scenario {
+        device,
+        commands: vec!["show system info".into(), "show jobs all".into()],
+        snippet: "- action: set\n  xpath: /config/address\n  element: <entry name=\"web01\"><ip-netmask>10.0.0.10/32</ip-netmask></entry>\n".into(),
+        expect: "10.0.0.10/32".into(),
+    };
+    assert_conformant(&PaloAltoPanosDriver::default(), &standin, &scenario).await;
+}
+
+#[tokio::test]
+async fn fortinet_fortios_over_rest() {
+    setup();
+    let standin = FortiosStandIn::spawn(&[("web01", "10.0.0.10 255.255.255.255")]).await;
+    let device = device(
+        DeviceType::FortinetFortios,
+        standin.address(),
+        "fortios-lab",
+        &["vdom:root"],
+    );
+    let scenario = Scenario {
+        device,
+        commands: vec![
+            "monitor/system/status".into(),
+            "cmdb/firewall/address".into(),
+        ],
+        snippet: "- method: PUT\n  path: firewall/address/web01\n  body: {subnet: 10.0.0.11 255.255.255.255}\n".into(),
+        expect: "set subnet 10.0.0.11 255.255.255.255".into(),
+    };
+    assert_conformant(&FortinetFortiosDriver::default(), &standin, &scenario).await;
+}
+
+#[tokio::test]
+async fn linux_host_over_ssh_and_sftp() {
+    setup();
+    let standin = LinuxStandIn::spawn(&[("/etc/nginx/nginx.conf", "worker_processes 1;\n")]).await;
+    let device = device(DeviceType::LinuxHost, standin.address(), "lab", &[]);
+    let scenario = Scenario {
+        device,
+        commands: vec!["uname -a".into(), "ip -br addr".into()],
+        snippet: "files:\n  - path: /etc/nginx/conf.d/netrust.conf\n    content: \"server { listen 8080; }\\n\"\n    mode: \"0644\"\nvalidate: [nginx -t]\nreload: [systemctl reload nginx]\n".into(),
+        expect: "==> /etc/nginx/conf.d/netrust.conf <==\nserver { listen 8080; }".into(),
+    };
+    assert_conformant(&LinuxHostDriver::default(), &standin, &scenario).await;
+}
+
+#[tokio::test]
+async fn meraki_dashboard() {
+    setup();
+    let standin = MerakiStandIn::spawn(&[
+        (
+            "/networks/N_1",
+            json!({ "id": "N_1", "name": "branch", "organizationId": "O_1" }),
+        ),
+        (
+            "/networks/N_1/wireless/ssids/0",
+            json!({ "number": 0, "name": "corp", "enabled": true }),
+        ),
+    ])
+    .await;
+    let driver = MerakiCloudDriver::default().with_api_base(standin.api_base());
+    let device = device(
+        DeviceType::MerakiCloud,
+        "N_1",
+        "meraki-lab",
+        &["serial:Q2AA-0001"],
+    );
+    let scenario = Scenario {
+        device,
+        commands: vec![
+            "network".into(),
+            "GET /networks/{network}/wireless/ssids/0".into(),
+        ],
+        snippet: "- type: ssid\n  number: 0\n  settings: { name: guest }\n".into(),
+        expect: "\"name\": \"guest\"".into(),
+    };
+    assert_conformant(&driver, &standin, &scenario).await;
+}
@@ -0,0 +1,150 @@
+//! Records sessions against the conformance stand-ins and replays them with the stand-ins
+//! unreachable. The record/replay mode is process-wide, so this lives in its own test binary
+//! and runs as a single test.
+
+use nauto_drivers::conformance::{
+    cli::CliStandIn, netconf::NetconfStandIn, use_fallback_credentials, use_short_timeouts,
+};
+use nauto_drivers::drivers::{AristaEosDriver, CiscoIosDriver, GenericNetconfDriver};
+use nauto_drivers::netconf::{CAP_CANDIDATE, CAP_VALIDATE};
+use nauto_drivers::replay::{self, Cassette, Mode, REDACTED};
+use nauto_drivers::{DeviceDriver, DriverAction, DriverExecutionResult, JobContext};
+use nauto_model::{
+    CapabilitySet, ConfigMode, Credential, CredentialRef, Device, DeviceType, JobKind,
+};
+
+const RUNNING: &[&str] = &[
+    "hostname lab1",
+    "interface Ethernet1",
+    "   description uplink",
+];
+
+fn device(id: &str, device_type: DeviceType, address: &str) -> Device {
+    Device {
+        id: id.into(),
+        name: id.into(),
+        device_type,
+        mgmt_address: address.into(),
+        credential: CredentialRef { name: "lab".into() },
+        tags: Vec::new(),
+        capabilities: CapabilitySet::default(),
+    }
+}
+
+fn jobs(command: &str, snippet: &str) -> Vec<JobKind> {
+    vec![
+        JobKind::CommandBatch {
+            commands: vec![command.into()],
+        },
+        JobKind::ConfigPush {
+            snippet: snippet.into(),
+            mode: ConfigMode::Merge,
+        },
+    ]
+}
+
+/// Outputs, diffs and snapshots of each job, which a replay must reproduce.
+async fn run(driver: &dyn DeviceDriver, device: &Device, jobs: &[JobKind]) -> Vec<String> {
+    let mut seen = Vec::new();
+    for job in jobs {
+        let res: DriverExecutionResult = driver
+            .execute(device, DriverAction::Job(job), &JobContext::default())
+            .await
+            .unwrap_or_else(|err| panic!("{} {:?}: {err:#}", device.id, job));
+        seen.extend(res.outputs.into_iter().map(|output| output.raw));
+        seen.extend(res.diff);
+        seen.extend(res.post_snapshot);
+    }
+    seen
+}
+
+#[tokio::test]
+async fn recorded_sessions_replay_without_the_devices() {
+    use_short_timeouts();
+    use_fallback_credentials();
+    let dir = tempfile::tempdir().unwrap();
+    let ios = CiscoIosDriver::default();
+    let eos = AristaEosDriver::default();
+    let netconf = GenericNetconfDriver::default();
+    let cli_jobs = jobs("show version", "ntp server 10.0.0.9\n");
+    let netconf_jobs = jobs(
+        "<get-config><source><running/></source></get-config>",
+        "<ntp><server>10.0.0.9</server></ntp>",
+    );
+
+    replay::set_mode(Some(Mode::Record(dir.path().to_path_buf())));
+    let ssh = CliStandIn::ssh(RUNNING).await;
+    let eapi = CliStandIn::eapi(RUNNING).await;
+    let server =
+        NetconfStandIn::spawn(&[CAP_CANDIDATE, CAP_VALIDATE], "<hostname>lab1</hostname>").await;
+    let recorded = [
+        run(
+            &ios,
+            &device("ios", DeviceType::CiscoIos, ssh.address()),
+            &cli_jobs,
+        )
+        .await,
+        run(
+            &eos,
+            &device("eos", DeviceType::AristaEos, eapi.address()),
+            &cli_jobs,
+        )
+        .await,
+        run(
+            &netconf,
+            &device("netconf", DeviceType::Netconf, server.address()),
+            &netconf_jobs,
+        )
+        .await,
+    ];
+
+    let cassette: Cassette =
+        serde_yaml::from_str(&std::fs::read_to_string(dir.path().join("ios.yaml")).unwrap())
+            .unwrap();
+    assert!(!cassette.exchanges.is_empty());
+    match cassette.credential {
+        Some(Credential::UserPassword { password, .. }) => assert_eq!(password, REDACTED),
+        other => panic!("unexpected recorded credential {other:?}"),
+    }
+
+    // Nothing listens on the discard port, so any attempt to reach a device fails.
+    replay::set_mode(Some(Mode::Replay(dir.path().to_path_buf())));
+    let replayed = [
+        run(
+            &ios,
+            &device("ios", DeviceType::CiscoIos, "127.0.0.1:9"),
+            &cli_jobs,
+        )
+        .await,
+        run(
+            &eos,
+            &device("eos", DeviceType::AristaEos, "http://127.0.0.1:9"),
+            &cli_jobs,
+        )
+        .await,
+        run(
+            &netconf,
+            &device("netconf", DeviceType::Netconf, "tcp://127.0.0.1:9"),
+            &netconf_jobs,
+        )
+        .await,
+    ];
+    assert_eq!(recorded, replayed);
+
+    let unrecorded = JobKind::CommandBatch {
+        commands: vec!["show clock".into()],
+    };
+    let err = ios
+        .execute(
+            &device("ios", DeviceType::CiscoIos, "127.0.0.1:9"),
+            DriverAction::Job(&unrecorded),
+            &JobContext::default(),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("no recorded ssh exchange"),
+        "{err:#}"
+    );
+    replay::set_mode(None);
+}
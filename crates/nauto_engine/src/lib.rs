@@ -7,8 +7,11 @@ use crate::store::{JobStore, NoOpJobStore};
 
 use anyhow::{Context, Result};
 use nauto_compliance::{ComplianceEngine, DeviceConfigs};
-use nauto_drivers::{DeviceDriver, DriverAction, DriverExecutionResult, DriverRegistry};
+use nauto_drivers::{
+    DeviceDriver, DriverAction, DriverExecutionResult, DriverRegistry, JobContext,
+};
 use nauto_model::{ComplianceRule, Device, Job, JobResult, TaskStatus, TaskSummary};
+use nauto_security::{CredentialStore, Redactor};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
@@ -23,11 +26,15 @@ pub enum JobEngineError {
     MissingDriver,
 }
 
+/// Pre-flight checks mostly wait on the network, so they run wider than other jobs.
+const PREFLIGHT_PARALLEL: usize = 256;
+
 pub struct JobEngine<I: DeviceInventory> {
     inventory: I,
     drivers: DriverRegistry,
     default_parallel: usize,
     store: Arc<dyn JobStore>,
+    redactor: Arc<Redactor>,
 }
 
 impl<I: DeviceInventory> JobEngine<I> {
@@ -37,6 +44,7 @@ impl<I: DeviceInventory> JobEngine<I> {
             drivers,
             default_parallel: 32,
             store: Arc::new(NoOpJobStore),
+            redactor: Arc::new(Redactor::default()),
         }
     }
 
@@ -50,6 +58,18 @@ impl<I: DeviceInventory> JobEngine<I> {
         self
     }
 
+    /// Replaces the built-in redaction rules applied to every task summary.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Arc::new(redactor);
+        self
+    }
+
+    /// Has every driver resolve credentials from `store`, shared across the job's devices.
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.drivers = self.drivers.with_credential_store(store);
+        self
+    }
+
     #[instrument(skip(self))]
     pub async fn execute(&self, job: Job) -> Result<JobResult> {
         self.store.create_job(&job).await?;
@@ -66,17 +86,25 @@ impl<I: DeviceInventory> JobEngine<I> {
             .await;
         }
         let started_at = chrono::Utc::now();
-        let semaphore = Arc::new(Semaphore::new(
-            job.max_parallel.unwrap_or(self.default_parallel),
-        ));
+        let default_parallel = match &job.kind {
+            nauto_model::JobKind::Preflight => self.default_parallel.max(PREFLIGHT_PARALLEL),
+            _ => self.default_parallel,
+        };
+        let semaphore = Arc::new(Semaphore::new(job.max_parallel.unwrap_or(default_parallel)));
         let mut join_set = tokio::task::JoinSet::new();
+        let task_timeout = task_timeout(&job.kind);
 
         for device in devices {
             let sem = semaphore.clone();
-            let driver = self.drivers.find(&device.device_type);
+            let driver = self.drivers.select(&device);
             let job_kind = job.kind.clone();
-            let dry_run = job.dry_run;
+            let ctx = JobContext {
+                job_id: Some(job.id),
+                job_name: job.name.clone(),
+                dry_run: job.dry_run,
+            };
             let device_id = device.id.clone();
+            let redactor = self.redactor.clone();
 
             join_set.spawn(async move {
                 let permit = match sem.acquire_owned().await {
@@ -89,14 +117,18 @@ impl<I: DeviceInventory> JobEngine<I> {
                             finished_at: Some(chrono::Utc::now()),
                             logs: vec!["Semaphore closed".into()],
                             diff: None,
+                            outputs: Vec::new(),
+                            getters: None,
+                            backup: None,
+                            preflight: None,
                         }
                     }
                 };
 
                 let device_id = device.id.clone();
                 match tokio::time::timeout(
-                    std::time::Duration::from_secs(300),
-                    run_device(device, driver, job_kind, dry_run, permit),
+                    task_timeout,
+                    run_device(device, driver, job_kind, ctx, permit, redactor),
                 )
                 .await
                 {
@@ -108,6 +140,10 @@ impl<I: DeviceInventory> JobEngine<I> {
                         finished_at: Some(chrono::Utc::now()),
                         logs: vec!["Job execution timed out".into()],
                         diff: None,
+                        outputs: Vec::new(),
+                        getters: None,
+                        backup: None,
+                        preflight: None,
                     },
                 }
             });
@@ -181,6 +217,10 @@ async fn execute_compliance_job(
                     finished_at: Some(chrono::Utc::now()),
                     logs,
                     diff: None,
+                    outputs: Vec::new(),
+                    getters: None,
+                    backup: None,
+                    preflight: None,
                 }
             } else {
                 TaskSummary {
@@ -190,6 +230,10 @@ async fn execute_compliance_job(
                     finished_at: Some(chrono::Utc::now()),
                     logs: vec!["no config provided for compliance evaluation".into()],
                     diff: None,
+                    outputs: Vec::new(),
+                    getters: None,
+                    backup: None,
+                    preflight: None,
                 }
             }
         });
@@ -285,8 +329,9 @@ async fn run_device(
     device: nauto_model::Device,
     driver: Option<Arc<dyn DeviceDriver>>,
     job_kind: nauto_model::JobKind,
-    dry_run: bool,
+    ctx: JobContext,
     permit: tokio::sync::OwnedSemaphorePermit,
+    redactor: Arc<Redactor>,
 ) -> TaskSummary {
     let span = info_span!(
         "device_task",
@@ -296,21 +341,30 @@ async fn run_device(
     let _enter = span.enter();
     let start = chrono::Utc::now();
 
-    let summary = match driver {
-        Some(driver) => match execute_with_driver(&device, driver, job_kind, dry_run).await {
+    let mut summary = match driver {
+        Some(driver) => match execute_with_driver(&device, driver, job_kind, &ctx).await {
             Ok(result) => TaskSummary {
                 device_id: device.id.clone(),
-                status: TaskStatus::Success,
+                // A Preflight run that completed still fails the device if any check failed.
+                status: match &result.preflight {
+                    Some(report) if !report.passed() => TaskStatus::Failed,
+                    _ => TaskStatus::Success,
+                },
                 started_at: Some(start),
                 finished_at: Some(chrono::Utc::now()),
                 logs: result.logs,
                 diff: result.diff,
+                outputs: result.outputs,
+                getters: result.getters,
+                backup: result.backup,
+                preflight: result.preflight,
             },
             Err(err) => {
                 error!(
                     target: "engine::device",
-                    "device={} failed: {err:?}",
-                    device.name
+                    "device={} failed: {}",
+                    device.name,
+                    redactor.redact(&device.device_type, &format!("{err:?}"))
                 );
                 TaskSummary {
                     device_id: device.id.clone(),
@@ -319,6 +373,10 @@ async fn run_device(
                     finished_at: Some(chrono::Utc::now()),
                     logs: vec![format!("error: {err}")],
                     diff: None,
+                    outputs: Vec::new(),
+                    getters: None,
+                    backup: None,
+                    preflight: None,
                 }
             }
         },
@@ -329,20 +387,69 @@ async fn run_device(
             finished_at: Some(chrono::Utc::now()),
             logs: vec!["No driver available".into()],
             diff: None,
+            outputs: Vec::new(),
+            getters: None,
+            backup: None,
+            preflight: None,
         },
     };
 
     drop(permit);
+    redact_summary(&redactor, &device.device_type, &mut summary);
     summary
 }
 
+/// Masks secrets in everything a summary carries to the store, the audit log and the
+/// console. The driver's own pre-change snapshot never reaches the summary, so rollback
+/// still works from the cleartext copy.
+fn redact_summary(
+    redactor: &Redactor,
+    device_type: &nauto_model::DeviceType,
+    summary: &mut TaskSummary,
+) {
+    for line in &mut summary.logs {
+        redactor.redact_in_place(device_type, line);
+    }
+    if let Some(diff) = &mut summary.diff {
+        redactor.redact_in_place(device_type, diff);
+    }
+    for output in &mut summary.outputs {
+        redactor.redact_in_place(device_type, &mut output.raw);
+        if let Some(parsed) = &mut output.parsed {
+            redactor.redact_value(device_type, parsed);
+        }
+    }
+    if let Some(backup) = &mut summary.backup {
+        redactor.redact_in_place(device_type, &mut backup.running);
+        if let Some(startup) = &mut backup.startup {
+            redactor.redact_in_place(device_type, startup);
+        }
+    }
+}
+
 async fn execute_with_driver(
     device: &nauto_model::Device,
     driver: Arc<dyn DeviceDriver>,
     job_kind: nauto_model::JobKind,
-    dry_run: bool,
+    ctx: &JobContext,
 ) -> Result<DriverExecutionResult> {
-    if dry_run && !driver.capabilities().supports_dry_run {
+    if let nauto_model::JobKind::ConfigPush {
+        mode: nauto_model::ConfigMode::Replace,
+        ..
+    } = &job_kind
+    {
+        return replace_with_preview(device, driver, &job_kind, ctx).await;
+    }
+    if let nauto_model::JobKind::Preflight = &job_kind {
+        // Read-only, so it runs the same way in dry-run jobs.
+        let report = driver.preflight(device).await?;
+        return Ok(DriverExecutionResult {
+            logs: nauto_drivers::preflight::describe(device, &report),
+            preflight: Some(report),
+            ..Default::default()
+        });
+    }
+    if ctx.dry_run && !driver.capabilities().supports_dry_run {
         info!(
             target: "engine::device",
             "device={} dry-run requested but unsupported, skipping apply",
@@ -354,7 +461,71 @@ async fn execute_with_driver(
         });
     }
 
-    driver.execute(device, DriverAction::Job(&job_kind)).await
+    driver
+        .execute(device, DriverAction::Job(&job_kind), ctx)
+        .await
+}
+
+/// A full-config replace always runs as a dry run first so the removal diff is known before
+/// anything changes. The real replace only follows when the preview produced a non-empty
+/// diff; the preview logs and diff are kept in the result.
+async fn replace_with_preview(
+    device: &nauto_model::Device,
+    driver: Arc<dyn DeviceDriver>,
+    job_kind: &nauto_model::JobKind,
+    ctx: &JobContext,
+) -> Result<DriverExecutionResult> {
+    if !driver.capabilities().supports_replace {
+        anyhow::bail!("{} does not support replace mode", driver.name());
+    }
+    let preview_ctx = JobContext {
+        dry_run: true,
+        ..ctx.clone()
+    };
+    let mut preview = driver
+        .execute(device, DriverAction::Job(job_kind), &preview_ctx)
+        .await
+        .context("replace preview")?;
+    let Some(diff) = preview.diff.clone() else {
+        anyhow::bail!("{} returned no diff for the replace preview", driver.name());
+    };
+    if ctx.dry_run {
+        return Ok(preview);
+    }
+    if diff.trim().is_empty() {
+        preview
+            .logs
+            .push("running config already matches; replace skipped".into());
+        return Ok(preview);
+    }
+    info!(
+        target: "engine::device",
+        "device={} replace preview {} diff lines",
+        device.name,
+        diff.lines().count()
+    );
+
+    let mut result = driver
+        .execute(device, DriverAction::Job(job_kind), ctx)
+        .await?;
+    preview.logs.append(&mut result.logs);
+    result.logs = preview.logs;
+    result.diff = result.diff.or(Some(diff));
+    Ok(result)
+}
+
+/// Per-device budget. Transfers and upgrades copy images and sit through a reload, so they
+/// get the reload timeout on top of the usual five minutes.
+fn task_timeout(kind: &nauto_model::JobKind) -> std::time::Duration {
+    let base = std::time::Duration::from_secs(300);
+    match kind {
+        nauto_model::JobKind::FileTransfer { .. } | nauto_model::JobKind::Upgrade { .. } => {
+            base + nauto_drivers::config::reload_timeout()
+        }
+        // Resolve, connect and log in, each bounded by the pre-flight timeout.
+        nauto_model::JobKind::Preflight => nauto_drivers::config::preflight_timeout() * 4,
+        _ => base,
+    }
 }
 
 fn job_kind_label(kind: &nauto_model::JobKind) -> &'static str {
@@ -362,6 +533,11 @@ fn job_kind_label(kind: &nauto_model::JobKind) -> &'static str {
         nauto_model::JobKind::CommandBatch { .. } => "command_batch",
         nauto_model::JobKind::ConfigPush { .. } => "config_push",
         nauto_model::JobKind::ComplianceCheck { .. } => "compliance_check",
+        nauto_model::JobKind::Getters { .. } => "getters",
+        nauto_model::JobKind::FileTransfer { .. } => "file_transfer",
+        nauto_model::JobKind::Upgrade { .. } => "upgrade",
+        nauto_model::JobKind::ConfigBackup { .. } => "config_backup",
+        nauto_model::JobKind::Preflight => "preflight",
     }
 }
 
@@ -433,5 +609,162 @@ mod tests {
         let result = engine.execute(job).await.expect("job execution");
         assert_eq!(result.device_results.len(), 2);
         assert_eq!(result.success_count(), 2);
+        for summary in &result.device_results {
+            assert_eq!(summary.outputs.len(), 1);
+            assert_eq!(summary.outputs[0].command, "show version");
+        }
+    }
+
+    #[tokio::test]
+    async fn summaries_are_redacted() {
+        let inventory = InMemoryInventory::new(mock_devices());
+        let redactor = Redactor::default()
+            .with_patterns(&[r"community (\S+)"])
+            .unwrap();
+        let engine = JobEngine::new(inventory, registry()).with_redactor(redactor);
+
+        let job = Job {
+            id: Uuid::new_v4(),
+            name: "Show community".into(),
+            kind: nauto_model::JobKind::CommandBatch {
+                commands: vec!["show community S3cret".into()],
+            },
+            targets: TargetSelector::All,
+            parameters: Default::default(),
+            max_parallel: None,
+            dry_run: false,
+            approval_id: None,
+        };
+
+        let result = engine.execute(job).await.expect("job execution");
+        for summary in &result.device_results {
+            let shown = format!("{:?} {:?}", summary.logs, summary.outputs[0].raw);
+            assert!(!shown.contains("S3cret"), "{shown}");
+            assert!(shown.contains("community <redacted>"), "{shown}");
+        }
+    }
+
+    /// Reports the pushed snippet as added lines, the way the CLI drivers render a diff.
+    struct SnippetDiffDriver;
+
+    #[async_trait::async_trait]
+    impl DeviceDriver for SnippetDiffDriver {
+        fn device_type(&self) -> DeviceType {
+            DeviceType::CiscoIos
+        }
+
+        fn name(&self) -> &'static str {
+            "Snippet Diff Driver"
+        }
+
+        fn capabilities(&self) -> CapabilitySet {
+            CapabilitySet::default()
+        }
+
+        async fn execute(
+            &self,
+            _device: &Device,
+            action: DriverAction<'_>,
+            _ctx: &JobContext,
+        ) -> Result<DriverExecutionResult> {
+            let nauto_model::JobKind::ConfigPush { snippet, .. } = action.job_kind() else {
+                anyhow::bail!("only config pushes are supported");
+            };
+            Ok(DriverExecutionResult {
+                diff: Some(nauto_drivers::config_tree::render_diff("", snippet)),
+                ..Default::default()
+            })
+        }
+
+        async fn rollback(&self, _device: &Device, _snapshot: Option<String>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn config_push_diffs_are_redacted() {
+        let inventory = InMemoryInventory::new(mock_devices()[..1].to_vec());
+        let drivers: Vec<Arc<dyn DeviceDriver>> = vec![Arc::new(SnippetDiffDriver)];
+        let engine = JobEngine::new(inventory, DriverRegistry::new(drivers));
+
+        let job = Job {
+            id: Uuid::new_v4(),
+            name: "Push secrets".into(),
+            kind: nauto_model::JobKind::ConfigPush {
+                snippet: "enable secret 5 $1$new\nsnmp-server community S3cr3t RO\n\
+                          username bob secret 0 plainpw\ntacacs-server host 10.0.0.5 key 7 0822\n\
+                          router bgp 65000\n neighbor 10.0.0.1 password 0 bgppw\n"
+                    .into(),
+                mode: Default::default(),
+            },
+            targets: TargetSelector::All,
+            parameters: Default::default(),
+            max_parallel: None,
+            dry_run: false,
+            approval_id: None,
+        };
+
+        let result = engine.execute(job).await.expect("job execution");
+        assert_eq!(result.success_count(), 1);
+        let diff = result.device_results[0].diff.as_deref().expect("diff");
+        for secret in ["$1$new", "S3cr3t", "plainpw", "0822", "bgppw"] {
+            assert!(!diff.contains(secret), "{diff}");
+        }
+        assert!(diff.contains("+enable secret 5 <redacted>"), "{diff}");
+        assert!(diff.contains("+ neighbor 10.0.0.1 password 0 <redacted>"), "{diff}");
+    }
+
+    #[tokio::test]
+    async fn replace_runs_preview_before_applying() {
+        let inventory = InMemoryInventory::new(mock_devices());
+        let engine = JobEngine::new(inventory, registry());
+
+        let job = Job {
+            id: Uuid::new_v4(),
+            name: "Replace config".into(),
+            kind: nauto_model::JobKind::ConfigPush {
+                snippet: "hostname core-r1".into(),
+                mode: nauto_model::ConfigMode::Replace,
+            },
+            targets: TargetSelector::All,
+            parameters: Default::default(),
+            max_parallel: None,
+            dry_run: false,
+            approval_id: None,
+        };
+
+        let result = engine.execute(job).await.expect("job execution");
+        assert_eq!(result.success_count(), 2);
+        for summary in &result.device_results {
+            // One driver call for the preview, one for the replace.
+            assert_eq!(summary.logs.len(), 2);
+            assert_eq!(summary.diff.as_deref(), Some("mock diff"));
+        }
+    }
+
+    #[tokio::test]
+    async fn preflight_fails_unreachable_devices_in_dry_run() {
+        let mut devices = mock_devices();
+        devices[1].tags.push("mock:unreachable".into());
+        let engine = JobEngine::new(InMemoryInventory::new(devices), registry());
+
+        let job = Job {
+            id: Uuid::new_v4(),
+            name: "Preflight".into(),
+            kind: nauto_model::JobKind::Preflight,
+            targets: TargetSelector::All,
+            parameters: Default::default(),
+            max_parallel: None,
+            dry_run: true,
+            approval_id: None,
+        };
+
+        let result = engine.execute(job).await.expect("job execution");
+        assert_eq!(result.success_count(), 1);
+        for summary in &result.device_results {
+            let report = summary.preflight.as_ref().expect("preflight report");
+            assert_eq!(report.reachable(), summary.device_id == "r1");
+            assert_eq!(summary.logs.len(), report.checks.len());
+        }
     }
 }
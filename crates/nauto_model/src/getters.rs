@@ -0,0 +1,159 @@
+//! Vendor-neutral shapes for the data drivers collect through `JobKind::Getters`.
+//! MAC addresses are lowercase and colon separated, interface names are as the device
+//! prints them, and counters or timers a platform does not report are left empty.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Getter {
+    Facts,
+    Interfaces,
+    InterfaceCounters,
+    LldpNeighbors,
+    ArpTable,
+    MacTable,
+    BgpNeighbors,
+    Config,
+}
+
+impl Getter {
+    pub const ALL: [Getter; 8] = [
+        Getter::Facts,
+        Getter::Interfaces,
+        Getter::InterfaceCounters,
+        Getter::LldpNeighbors,
+        Getter::ArpTable,
+        Getter::MacTable,
+        Getter::BgpNeighbors,
+        Getter::Config,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Getter::Facts => "facts",
+            Getter::Interfaces => "interfaces",
+            Getter::InterfaceCounters => "interface_counters",
+            Getter::LldpNeighbors => "lldp_neighbors",
+            Getter::ArpTable => "arp_table",
+            Getter::MacTable => "mac_table",
+            Getter::BgpNeighbors => "bgp_neighbors",
+            Getter::Config => "config",
+        }
+    }
+}
+
+impl fmt::Display for Getter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Getter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = s.trim().to_lowercase().replace('-', "_");
+        Getter::ALL
+            .into_iter()
+            .find(|getter| getter.as_str() == wanted)
+            .ok_or_else(|| format!("unknown getter '{}'", s))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct DeviceFacts {
+    pub hostname: String,
+    pub vendor: String,
+    pub model: String,
+    pub os_version: String,
+    pub serial_number: String,
+    pub uptime_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub description: String,
+    /// Administratively enabled.
+    pub enabled: bool,
+    /// Operationally up.
+    pub is_up: bool,
+    pub mtu: Option<u32>,
+    pub speed_mbps: Option<u64>,
+    pub mac_address: Option<String>,
+    /// Addresses in CIDR notation, e.g. `10.0.0.1/24`.
+    pub ipv4_addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct InterfaceCounters {
+    pub name: String,
+    pub rx_octets: Option<u64>,
+    pub tx_octets: Option<u64>,
+    pub rx_packets: Option<u64>,
+    pub tx_packets: Option<u64>,
+    pub rx_errors: Option<u64>,
+    pub tx_errors: Option<u64>,
+    pub rx_discards: Option<u64>,
+    pub tx_discards: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct LldpNeighbor {
+    pub local_interface: String,
+    pub remote_system_name: String,
+    pub remote_port: String,
+    pub remote_chassis_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ArpEntry {
+    pub ip_address: String,
+    pub mac_address: String,
+    pub interface: String,
+    pub age_seconds: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct MacEntry {
+    pub mac_address: String,
+    pub vlan: Option<u16>,
+    pub interface: String,
+    pub is_static: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct BgpNeighbor {
+    pub peer_address: String,
+    pub remote_as: u32,
+    pub vrf: String,
+    /// Session state as reported (`Established`, `Idle`, `Active`, ...).
+    pub state: String,
+    pub is_up: bool,
+    pub uptime_seconds: Option<u64>,
+    pub received_prefixes: Option<u64>,
+}
+
+/// Results of one getter run; only the requested getters are filled in.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct GetterResults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facts: Option<DeviceFacts>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interfaces: Option<Vec<InterfaceInfo>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface_counters: Option<Vec<InterfaceCounters>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lldp_neighbors: Option<Vec<LldpNeighbor>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arp_table: Option<Vec<ArpEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac_table: Option<Vec<MacEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bgp_neighbors: Option<Vec<BgpNeighbor>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+}
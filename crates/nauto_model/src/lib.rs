@@ -5,17 +5,29 @@ use std::fmt;
 use std::str::FromStr;
 use uuid::Uuid;
 
+mod getters;
+
+pub use getters::{
+    ArpEntry, BgpNeighbor, DeviceFacts, Getter, GetterResults, InterfaceCounters, InterfaceInfo,
+    LldpNeighbor, MacEntry,
+};
+
 pub type DeviceId = String;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum DeviceType {
     CiscoIos,
+    CiscoIosXr,
     JuniperJunos,
     GenericSsh,
     AristaEos,
     CiscoNxosApi,
     MerakiCloud,
+    Netconf,
+    PaloAltoPanos,
+    FortinetFortios,
+    LinuxHost,
 }
 
 impl FromStr for DeviceType {
@@ -24,11 +36,22 @@ impl FromStr for DeviceType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.trim().to_lowercase().as_str() {
             "ciscoios" | "cisco_ios" => Ok(DeviceType::CiscoIos),
+            "ciscoiosxr" | "cisco_ios_xr" | "cisco_iosxr" | "cisco_xr" | "iosxr" => {
+                Ok(DeviceType::CiscoIosXr)
+            }
             "juniperjunos" | "juniper_junos" => Ok(DeviceType::JuniperJunos),
             "genericssh" | "generic_ssh" => Ok(DeviceType::GenericSsh),
             "aristaeos" | "arista_eos" => Ok(DeviceType::AristaEos),
-            "cisconxosapi" | "cisco_nxos_api" | "nxos" => Ok(DeviceType::CiscoNxosApi),
+            "cisconxosapi" | "cisco_nxos_api" | "cisco_nxos" | "nxos" => {
+                Ok(DeviceType::CiscoNxosApi)
+            }
             "merakicloud" | "meraki_cloud" => Ok(DeviceType::MerakiCloud),
+            "netconf" | "generic_netconf" => Ok(DeviceType::Netconf),
+            "paloaltopanos" | "palo_alto_panos" | "panos" => Ok(DeviceType::PaloAltoPanos),
+            "fortinetfortios" | "fortinet_fortios" | "fortios" | "fortigate" => {
+                Ok(DeviceType::FortinetFortios)
+            }
+            "linuxhost" | "linux_host" | "linux" | "sonic" | "cumulus" => Ok(DeviceType::LinuxHost),
             other => Err(format!("unknown device type '{}'", other)),
         }
     }
@@ -64,9 +87,7 @@ impl fmt::Debug for Credential {
                 .field("password", &"******")
                 .finish(),
             Credential::SshKey {
-                username,
-                key_path,
-                ..
+                username, key_path, ..
             } => f
                 .debug_struct("SshKey")
                 .field("username", username)
@@ -96,6 +117,8 @@ pub struct CapabilitySet {
     pub supports_rollback: bool,
     pub supports_diff: bool,
     pub supports_dry_run: bool,
+    /// Can replace the whole running configuration (`ConfigMode::Replace`).
+    pub supports_replace: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,9 +136,52 @@ pub struct Job {
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum JobKind {
-    CommandBatch { commands: Vec<String> },
-    ConfigPush { snippet: String },
-    ComplianceCheck { rules: Vec<ComplianceRule> },
+    CommandBatch {
+        commands: Vec<String>,
+    },
+    ConfigPush {
+        snippet: String,
+        #[serde(default)]
+        mode: ConfigMode,
+    },
+    ComplianceCheck {
+        rules: Vec<ComplianceRule>,
+    },
+    Getters {
+        which: Vec<Getter>,
+    },
+    /// Copies a local file to `destination` on the device (e.g. `flash:image.bin`) and
+    /// verifies its checksum there.
+    FileTransfer {
+        source: String,
+        destination: String,
+        #[serde(default)]
+        protocol: TransferProtocol,
+        /// Expected digest as `md5:<hex>` or `sha512:<hex>`; computed from `source` if absent.
+        #[serde(default)]
+        checksum: Option<String>,
+    },
+    /// Stages the OS image at `image`, boots the device from it and checks that it comes
+    /// back running `target_version`.
+    Upgrade {
+        image: String,
+        target_version: String,
+        #[serde(default)]
+        protocol: TransferProtocol,
+        #[serde(default)]
+        checksum: Option<String>,
+    },
+    /// Fetches each device's configuration, strips volatile lines and stores it as a new
+    /// version when it differs from the previous backup.
+    ConfigBackup {
+        #[serde(default)]
+        include_startup: bool,
+        #[serde(default)]
+        storage: BackupStorage,
+    },
+    /// Checks that each device resolves, accepts TCP on its management port and lets the
+    /// resolved credential log in, without running anything on it.
+    Preflight,
 }
 
 impl fmt::Debug for JobKind {
@@ -125,18 +191,152 @@ impl fmt::Debug for JobKind {
                 .debug_struct("CommandBatch")
                 .field("commands", commands)
                 .finish(),
-            JobKind::ConfigPush { snippet: _ } => f
+            JobKind::ConfigPush { snippet: _, mode } => f
                 .debug_struct("ConfigPush")
                 .field("snippet", &"***redacted***")
+                .field("mode", mode)
                 .finish(),
             JobKind::ComplianceCheck { rules } => f
                 .debug_struct("ComplianceCheck")
                 .field("rules", rules)
                 .finish(),
+            JobKind::Getters { which } => f.debug_struct("Getters").field("which", which).finish(),
+            JobKind::FileTransfer {
+                source,
+                destination,
+                protocol,
+                checksum,
+            } => f
+                .debug_struct("FileTransfer")
+                .field("source", source)
+                .field("destination", destination)
+                .field("protocol", protocol)
+                .field("checksum", checksum)
+                .finish(),
+            JobKind::Upgrade {
+                image,
+                target_version,
+                protocol,
+                checksum,
+            } => f
+                .debug_struct("Upgrade")
+                .field("image", image)
+                .field("target_version", target_version)
+                .field("protocol", protocol)
+                .field("checksum", checksum)
+                .finish(),
+            JobKind::ConfigBackup {
+                include_startup,
+                storage,
+            } => f
+                .debug_struct("ConfigBackup")
+                .field("include_startup", include_startup)
+                .field("storage", storage)
+                .finish(),
+            JobKind::Preflight => f.write_str("Preflight"),
         }
     }
 }
 
+/// How a ConfigPush snippet is applied. `Replace` treats the snippet as the complete
+/// intended configuration, so lines missing from it are removed from the device.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigMode {
+    #[default]
+    Merge,
+    Replace,
+}
+
+/// How files reach the device. Network OSes usually run an SCP server; SFTP needs the
+/// subsystem enabled.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferProtocol {
+    #[default]
+    Scp,
+    Sftp,
+}
+
+/// Where ConfigBackup keeps its versions: a directory with a `history/` copy of every
+/// change, or a git repository that gets one commit per backup run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackupStorage {
+    Directory { path: String },
+    Git { path: String },
+}
+
+impl Default for BackupStorage {
+    fn default() -> Self {
+        BackupStorage::Directory {
+            path: "backups".into(),
+        }
+    }
+}
+
+/// Configuration text collected by a ConfigBackup job, already normalized.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct ConfigSnapshot {
+    pub running: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub startup: Option<String>,
+}
+
+/// Result of a Preflight job on one device: the endpoint probed and each check in order.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct PreflightReport {
+    pub endpoint: String,
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    pub fn status(&self, step: PreflightStep) -> Option<CheckStatus> {
+        self.checks
+            .iter()
+            .find(|check| check.step == step)
+            .map(|check| check.status)
+    }
+
+    /// The name resolved and the management port accepted a connection.
+    pub fn reachable(&self) -> bool {
+        self.status(PreflightStep::Dns) == Some(CheckStatus::Passed)
+            && self.status(PreflightStep::Tcp) == Some(CheckStatus::Passed)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PreflightCheck {
+    pub step: PreflightStep,
+    pub status: CheckStatus,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightStep {
+    Dns,
+    Tcp,
+    Auth,
+    HostKey,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Not applicable to the transport, or not attempted because an earlier check failed.
+    Skipped,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComplianceRule {
     pub name: String,
@@ -160,6 +360,24 @@ pub struct TaskSummary {
     pub finished_at: Option<DateTime<Utc>>,
     pub logs: Vec<String>,
     pub diff: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<CommandOutput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub getters: Option<GetterResults>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<ConfigSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preflight: Option<PreflightReport>,
+}
+
+/// Untruncated output of one command, plus structured records when the driver got JSON
+/// from the device or a parser template matched the command.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct CommandOutput {
+    pub command: String,
+    pub raw: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -11,6 +11,7 @@ fn job_round_trip() {
         name: "Config Push".into(),
         kind: JobKind::ConfigPush {
             snippet: "set system ntp server 1.2.3.4".into(),
+            mode: ConfigMode::Replace,
         },
         targets: TargetSelector::ByTags {
             all_of: vec!["site:oslo".into()],
@@ -26,6 +27,23 @@ fn job_round_trip() {
     assert_eq!(restored.name, "Config Push");
     assert!(restored.dry_run);
     assert_eq!(restored.max_parallel, Some(25));
+    assert!(matches!(
+        restored.kind,
+        JobKind::ConfigPush {
+            mode: ConfigMode::Replace,
+            ..
+        }
+    ));
+
+    let merged: JobKind =
+        serde_yaml::from_str("type: config_push\nsnippet: hostname r1\n").expect("default mode");
+    assert!(matches!(
+        merged,
+        JobKind::ConfigPush {
+            mode: ConfigMode::Merge,
+            ..
+        }
+    ));
 }
 
 #[test]
@@ -44,6 +62,7 @@ fn device_capabilities_default() {
             supports_rollback: true,
             supports_diff: true,
             supports_dry_run: true,
+            supports_replace: false,
         },
     };
 
@@ -52,3 +71,90 @@ fn device_capabilities_default() {
     assert!(loaded.capabilities.supports_commit);
     assert!(loaded.capabilities.supports_diff);
 }
+
+#[test]
+fn getters_job_kind_from_yaml() {
+    let kind: JobKind = serde_yaml::from_str("type: getters\nwhich: [facts, bgp_neighbors]\n")
+        .expect("deserialize getters");
+    match kind {
+        JobKind::Getters { which } => {
+            assert_eq!(which, vec![Getter::Facts, Getter::BgpNeighbors])
+        }
+        other => panic!("unexpected kind {other:?}"),
+    }
+    assert_eq!(
+        "lldp-neighbors".parse::<Getter>(),
+        Ok(Getter::LldpNeighbors)
+    );
+
+    let results = GetterResults {
+        config: Some("hostname r1".into()),
+        ..Default::default()
+    };
+    assert_eq!(
+        serde_json::to_value(&results).unwrap(),
+        json!({ "config": "hostname r1" })
+    );
+}
+
+#[test]
+fn upgrade_job_kind_defaults_to_scp() {
+    let kind: JobKind = serde_yaml::from_str(
+        "type: upgrade\nimage: images/c8000v-17.09.04a.bin\ntarget_version: 17.9.4a\n",
+    )
+    .expect("deserialize upgrade");
+    match kind {
+        JobKind::Upgrade {
+            protocol, checksum, ..
+        } => {
+            assert_eq!(protocol, TransferProtocol::Scp);
+            assert!(checksum.is_none());
+        }
+        other => panic!("unexpected kind {other:?}"),
+    }
+
+    let transfer: JobKind = serde_json::from_value(json!({
+        "type": "file_transfer",
+        "source": "EOS-4.31.2F.swi",
+        "destination": "flash:EOS-4.31.2F.swi",
+        "protocol": "sftp",
+        "checksum": "md5:0123"
+    }))
+    .expect("deserialize file transfer");
+    assert!(matches!(
+        transfer,
+        JobKind::FileTransfer {
+            protocol: TransferProtocol::Sftp,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn config_backup_defaults_to_directory_storage() {
+    let kind: JobKind = serde_yaml::from_str("type: config_backup\n").expect("deserialize backup");
+    match kind {
+        JobKind::ConfigBackup {
+            include_startup,
+            storage,
+        } => {
+            assert!(!include_startup);
+            assert_eq!(
+                storage,
+                BackupStorage::Directory {
+                    path: "backups".into()
+                }
+            );
+        }
+        other => panic!("unexpected kind {other:?}"),
+    }
+
+    let snapshot = ConfigSnapshot {
+        running: "hostname r1\n".into(),
+        startup: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&snapshot).unwrap(),
+        json!({"running": "hostname r1\n"})
+    );
+}
@@ -7,6 +7,7 @@ bitflags! {
         const ROLLBACK = 1 << 1;
         const DIFF = 1 << 2;
         const DRY_RUN = 1 << 3;
+        const REPLACE = 1 << 4;
     }
 }
 
@@ -27,6 +28,9 @@ impl CapabilityMask {
 pub struct PluginMetadata {
     pub vendor: &'static str,
     pub device_type: &'static str,
+    /// Platform versions the driver covers, e.g. `">=4.32"`. Empty means every version;
+    /// a non-empty constraint lets the plugin override a built-in driver for those versions.
+    pub versions: &'static str,
     pub capabilities: CapabilityMask,
 }
 
@@ -59,5 +63,15 @@ macro_rules! export_plugin {
         pub extern "C" fn plugin_device_type_len() -> usize {
             _PLUGIN_META.device_type.len()
         }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_versions_ptr() -> *const u8 {
+            _PLUGIN_META.versions.as_ptr()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_versions_len() -> usize {
+            _PLUGIN_META.versions.len()
+        }
     };
 }
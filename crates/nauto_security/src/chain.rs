@@ -0,0 +1,593 @@
+//! Credential lookup across several stores.
+//!
+//! [`ChainStore`] tries its stores in order and returns the first credential found. A
+//! reference can also be routed to one store: by a prefix naming it (`vault:core`,
+//! `env:ci-lab`; the prefix is stripped before the lookup) or by a route pattern from the
+//! config file (`prod-*` → `vault`). [`CredentialsConfig`] is the `credentials:` section
+//! of the netrust config file and builds the chain.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{Credential, CredentialRef};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::{CredentialStore, FileStore, KeyringStore, VaultConfig, VaultStore};
+
+const DEFAULT_ENV_PREFIX: &str = "NAUTO_CRED_";
+const DEFAULT_KEYRING_SERVICE: &str = "netrust";
+
+/// Credentials from environment variables, for CI runners. Reference `lab-core` with the
+/// default prefix reads `NAUTO_CRED_LAB_CORE_USERNAME` and `..._PASSWORD`,
+/// `..._USERNAME` and `..._KEY_PATH` (optional `..._PASSPHRASE`), or `..._TOKEN`.
+#[derive(Clone)]
+pub struct EnvStore {
+    prefix: String,
+    lookup: Arc<VarLookup>,
+}
+
+type VarLookup = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+impl Default for EnvStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_ENV_PREFIX)
+    }
+}
+
+impl EnvStore {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            lookup: Arc::new(|name| std::env::var(name).ok()),
+        }
+    }
+
+    /// Reads variables through `lookup` instead of the process environment, so tests do
+    /// not have to set process-wide variables while other tests read them.
+    pub fn with_lookup(
+        mut self,
+        lookup: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.lookup = Arc::new(lookup);
+        self
+    }
+
+    fn var_name(&self, reference: &CredentialRef, field: &str) -> String {
+        let name: String = reference
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}{name}_{field}", self.prefix)
+    }
+
+    fn var(&self, reference: &CredentialRef, field: &str) -> Option<String> {
+        (self.lookup)(&self.var_name(reference, field)).filter(|value| !value.is_empty())
+    }
+}
+
+#[async_trait]
+impl CredentialStore for EnvStore {
+    async fn store(&self, reference: &CredentialRef, _credential: &Credential) -> Result<()> {
+        bail!(
+            "environment credentials are read-only (set {} instead)",
+            self.var_name(reference, "*")
+        )
+    }
+
+    async fn resolve(&self, reference: &CredentialRef) -> Result<Credential> {
+        if let Some(token) = self.var(reference, "TOKEN") {
+            return Ok(Credential::Token { token });
+        }
+        let username = self
+            .var(reference, "USERNAME")
+            .ok_or_else(|| anyhow!("{} is not set", self.var_name(reference, "USERNAME")))?;
+        if let Some(password) = self.var(reference, "PASSWORD") {
+            return Ok(Credential::UserPassword { username, password });
+        }
+        match self.var(reference, "KEY_PATH") {
+            Some(key_path) => Ok(Credential::SshKey {
+                username,
+                key_path,
+                passphrase: self.var(reference, "PASSPHRASE"),
+            }),
+            None => bail!(
+                "{} needs {} or {}",
+                self.var_name(reference, "USERNAME"),
+                self.var_name(reference, "PASSWORD"),
+                self.var_name(reference, "KEY_PATH")
+            ),
+        }
+    }
+}
+
+/// Sends references matching `pattern` (`*` matches any run of characters) to `store`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    pub pattern: String,
+    pub store: String,
+}
+
+struct Member {
+    name: String,
+    store: Arc<dyn CredentialStore>,
+    /// Tried for unrouted references; otherwise only reachable by routing.
+    chained: bool,
+}
+
+#[derive(Default)]
+pub struct ChainStore {
+    members: Vec<Member>,
+    routes: Vec<Route>,
+}
+
+impl ChainStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a store to the lookup order, reachable by routing as `name`.
+    pub fn with_store(mut self, name: impl Into<String>, store: Arc<dyn CredentialStore>) -> Self {
+        self.members.push(Member {
+            name: name.into(),
+            store,
+            chained: true,
+        });
+        self
+    }
+
+    /// Adds a store that is only used for references routed to `name`.
+    pub fn with_routed_store(
+        mut self,
+        name: impl Into<String>,
+        store: Arc<dyn CredentialStore>,
+    ) -> Self {
+        self.members.push(Member {
+            name: name.into(),
+            store,
+            chained: false,
+        });
+        self
+    }
+
+    pub fn with_route(mut self, pattern: impl Into<String>, store: impl Into<String>) -> Self {
+        self.routes.push(Route {
+            pattern: pattern.into(),
+            store: store.into(),
+        });
+        self
+    }
+
+    fn member(&self, name: &str) -> Result<&Member> {
+        self.members
+            .iter()
+            .find(|member| member.name == name)
+            .ok_or_else(|| anyhow!("credential store '{name}' is not configured"))
+    }
+
+    /// The store a reference is routed to and the reference to look up there, or `None`
+    /// to try the whole chain. A prefix only routes when it names a store kind, so names
+    /// that merely contain a colon are looked up as they are.
+    fn route(&self, reference: &CredentialRef) -> Result<Option<(&Member, CredentialRef)>> {
+        if let Some((prefix, rest)) = reference.name.split_once(':') {
+            if StoreKind::ALL.iter().any(|kind| kind.name() == prefix)
+                || self.members.iter().any(|member| member.name == prefix)
+            {
+                let member = self
+                    .member(prefix)
+                    .with_context(|| format!("credential '{}'", reference.name))?;
+                return Ok(Some((member, CredentialRef { name: rest.into() })));
+            }
+        }
+        match self
+            .routes
+            .iter()
+            .find(|route| glob_match(&route.pattern, &reference.name))
+        {
+            Some(route) => {
+                let member = self.member(&route.store).with_context(|| {
+                    format!("route '{}' for '{}'", route.pattern, reference.name)
+                })?;
+                Ok(Some((member, reference.clone())))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialStore for ChainStore {
+    async fn store(&self, reference: &CredentialRef, credential: &Credential) -> Result<()> {
+        if let Some((member, routed)) = self.route(reference)? {
+            return member.store.store(&routed, credential).await;
+        }
+        let mut failures = Vec::new();
+        for member in self.members.iter().filter(|member| member.chained) {
+            match member.store.store(reference, credential).await {
+                Ok(()) => {
+                    info!(
+                        target: "security::audit",
+                        "stored credential '{}' in {}",
+                        reference.name,
+                        member.name
+                    );
+                    return Ok(());
+                }
+                Err(err) => failures.push(format!("{}: {err:#}", member.name)),
+            }
+        }
+        bail!(
+            "no credential store accepted '{}' ({})",
+            reference.name,
+            failures.join("; ")
+        )
+    }
+
+    async fn resolve(&self, reference: &CredentialRef) -> Result<Credential> {
+        if let Some((member, routed)) = self.route(reference)? {
+            return member
+                .store
+                .resolve(&routed)
+                .await
+                .with_context(|| format!("credential '{}' from {}", reference.name, member.name));
+        }
+        let mut failures = Vec::new();
+        for member in self.members.iter().filter(|member| member.chained) {
+            match member.store.resolve(reference).await {
+                Ok(credential) => {
+                    debug!(
+                        target: "security::chain",
+                        "credential '{}' found in {}",
+                        reference.name,
+                        member.name
+                    );
+                    return Ok(credential);
+                }
+                Err(err) => failures.push(format!("{}: {err:#}", member.name)),
+            }
+        }
+        if failures.is_empty() {
+            bail!("no credential stores configured");
+        }
+        bail!(
+            "credential '{}' not found ({})",
+            reference.name,
+            failures.join("; ")
+        )
+    }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = name.strip_prefix(parts.next().unwrap_or("")) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    Env,
+    File,
+    Keyring,
+    Vault,
+}
+
+impl StoreKind {
+    const ALL: [StoreKind; 4] = [
+        StoreKind::Env,
+        StoreKind::File,
+        StoreKind::Keyring,
+        StoreKind::Vault,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StoreKind::Env => "env",
+            StoreKind::File => "file",
+            StoreKind::Keyring => "keyring",
+            StoreKind::Vault => "vault",
+        }
+    }
+}
+
+impl fmt::Display for StoreKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvSettings {
+    /// Variable prefix, `NAUTO_CRED_` by default.
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSettings {
+    /// Credentials file, age-encrypted with `NAUTO_ENCRYPTION_KEY`.
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyringSettings {
+    /// Keyring service name, `netrust` by default.
+    pub service: Option<String>,
+}
+
+/// Non-secret Vault settings. Tokens and AppRole secret IDs come from the environment
+/// (`VAULT_TOKEN`, `VAULT_SECRET_ID`), which also overrides anything set here.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VaultSettings {
+    pub address: Option<String>,
+    pub namespace: Option<String>,
+    pub mount: Option<String>,
+    pub path: Option<String>,
+    pub approle_mount: Option<String>,
+    pub cache_ttl_secs: Option<u64>,
+    pub ca_cert: Option<String>,
+}
+
+/// The `credentials:` section of the netrust config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CredentialsConfig {
+    /// Stores tried in order for references that are not routed. Stores configured below
+    /// but left out of the chain are still reachable by prefix or route.
+    pub chain: Vec<StoreKind>,
+    pub routes: Vec<Route>,
+    pub env: EnvSettings,
+    pub file: Option<FileSettings>,
+    pub keyring: KeyringSettings,
+    pub vault: Option<VaultSettings>,
+}
+
+impl Default for CredentialsConfig {
+    fn default() -> Self {
+        Self {
+            chain: vec![StoreKind::Keyring],
+            routes: Vec::new(),
+            env: EnvSettings::default(),
+            file: None,
+            keyring: KeyringSettings::default(),
+            vault: None,
+        }
+    }
+}
+
+impl CredentialsConfig {
+    pub fn build(&self) -> Result<ChainStore> {
+        let mut chain = ChainStore::new();
+        for kind in StoreKind::ALL {
+            let configured = match kind {
+                StoreKind::File => self.file.is_some(),
+                StoreKind::Vault => self.vault.is_some(),
+                StoreKind::Env | StoreKind::Keyring => true,
+            };
+            if configured && !self.chain.contains(&kind) {
+                chain = chain.with_routed_store(kind.name(), self.open(kind)?);
+            }
+        }
+        // Chained stores go in `chain` order; routed-only ones were added above.
+        for kind in &self.chain {
+            chain = chain.with_store(kind.name(), self.open(*kind)?);
+        }
+        for route in &self.routes {
+            chain.member(&route.store).with_context(|| {
+                format!(
+                    "credential route '{}' names an unknown store",
+                    route.pattern
+                )
+            })?;
+        }
+        chain.routes = self.routes.clone();
+        Ok(chain)
+    }
+
+    fn open(&self, kind: StoreKind) -> Result<Arc<dyn CredentialStore>> {
+        Ok(match kind {
+            StoreKind::Env => Arc::new(match &self.env.prefix {
+                Some(prefix) => EnvStore::new(prefix.clone()),
+                None => EnvStore::default(),
+            }),
+            StoreKind::File => {
+                let file = self
+                    .file
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("credential store 'file' needs a `file.path`"))?;
+                Arc::new(FileStore::new(file.path.clone()))
+            }
+            StoreKind::Keyring => Arc::new(KeyringStore::new(
+                self.keyring
+                    .service
+                    .as_deref()
+                    .unwrap_or(DEFAULT_KEYRING_SERVICE),
+            )),
+            StoreKind::Vault => {
+                let settings = self.vault.clone().unwrap_or_default();
+                Arc::new(VaultStore::new(settings.config()?)?)
+            }
+        })
+    }
+}
+
+impl VaultSettings {
+    fn config(&self) -> Result<VaultConfig> {
+        let address = std::env::var("VAULT_ADDR")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .or_else(|| self.address.clone())
+            .ok_or_else(|| {
+                anyhow!("credential store 'vault' needs `vault.address` or VAULT_ADDR")
+            })?;
+        let mut config = VaultConfig::new(address);
+        config.namespace = self.namespace.clone();
+        config.ca_cert = self.ca_cert.clone();
+        if let Some(mount) = &self.mount {
+            config.mount = mount.clone();
+        }
+        if let Some(path) = &self.path {
+            config.path_template = path.clone();
+        }
+        if let Some(ttl) = self.cache_ttl_secs {
+            config.cache_ttl = Duration::from_secs(ttl);
+        }
+        Ok(config.with_env_overrides(self.approle_mount.as_deref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Memory(Mutex<HashMap<String, Credential>>);
+
+    #[async_trait]
+    impl CredentialStore for Memory {
+        async fn store(&self, reference: &CredentialRef, credential: &Credential) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(reference.name.clone(), credential.clone());
+            Ok(())
+        }
+
+        async fn resolve(&self, reference: &CredentialRef) -> Result<Credential> {
+            self.0
+                .lock()
+                .unwrap()
+                .get(&reference.name)
+                .cloned()
+                .ok_or_else(|| anyhow!("missing"))
+        }
+    }
+
+    fn token(value: &str) -> Credential {
+        Credential::Token {
+            token: value.into(),
+        }
+    }
+
+    fn reference(name: &str) -> CredentialRef {
+        CredentialRef { name: name.into() }
+    }
+
+    fn token_of(credential: Credential) -> String {
+        match credential {
+            Credential::Token { token } => token,
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn chain_falls_through_and_routes_by_prefix_and_pattern() {
+        let env = EnvStore::new("NAUTO_TEST_CHAIN_").with_lookup(|name| {
+            (name == "NAUTO_TEST_CHAIN_CI_LAB_TOKEN").then(|| "from-env".to_string())
+        });
+        let first = Arc::new(Memory::default());
+        let second = Arc::new(Memory::default());
+        let vault = Arc::new(Memory::default());
+        first.store(&reference("a"), &token("first")).await.unwrap();
+        second
+            .store(&reference("a"), &token("second"))
+            .await
+            .unwrap();
+        second
+            .store(&reference("b"), &token("second"))
+            .await
+            .unwrap();
+        vault
+            .store(&reference("core"), &token("vault"))
+            .await
+            .unwrap();
+        vault
+            .store(&reference("prod-r1"), &token("vault"))
+            .await
+            .unwrap();
+        let chain = ChainStore::new()
+            .with_store("env", Arc::new(env))
+            .with_store("file", first)
+            .with_store("keyring", second)
+            .with_routed_store("vault", vault)
+            .with_route("prod-*", "vault");
+
+        let resolve = |name: &'static str| {
+            let chain = &chain;
+            async move { chain.resolve(&reference(name)).await }
+        };
+        assert_eq!(token_of(resolve("a").await.unwrap()), "first");
+        assert_eq!(token_of(resolve("b").await.unwrap()), "second");
+        assert_eq!(token_of(resolve("ci-lab").await.unwrap()), "from-env");
+        assert_eq!(token_of(resolve("env:ci-lab").await.unwrap()), "from-env");
+        assert_eq!(token_of(resolve("vault:core").await.unwrap()), "vault");
+        assert_eq!(token_of(resolve("prod-r1").await.unwrap()), "vault");
+        // Routed-only stores are not part of the fallback order.
+        let err = resolve("core").await.unwrap_err().to_string();
+        assert!(err.contains("not found") && !err.contains("vault"), "{err}");
+        // A prefix naming an unconfigured store kind is an error, other colons are names.
+        assert!(ChainStore::new()
+            .resolve(&reference("file:x"))
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("'file:x'"));
+        assert!(resolve("site:a").await.is_err());
+
+        assert!(glob_match("prod-*", "prod-"));
+        assert!(glob_match("*-core-*", "oslo-core-1"));
+        assert!(!glob_match("a*b*b", "ab"));
+        assert!(glob_match("exact", "exact") && !glob_match("exact", "exact2"));
+    }
+
+    #[test]
+    fn config_builds_chain_in_order_and_rejects_unknown_routes() {
+        let config: CredentialsConfig = serde_json::from_value(serde_json::json!({
+            "chain": ["env", "keyring"],
+            "routes": [{"pattern": "prod-*", "store": "vault"}],
+            "vault": {"address": "http://127.0.0.1:8200"}
+        }))
+        .unwrap();
+        let chain = config.build().unwrap();
+        let names: Vec<(&str, bool)> = chain
+            .members
+            .iter()
+            .map(|member| (member.name.as_str(), member.chained))
+            .collect();
+        assert_eq!(names, [("vault", false), ("env", true), ("keyring", true)]);
+
+        let config: CredentialsConfig = serde_json::from_value(serde_json::json!({
+            "routes": [{"pattern": "prod-*", "store": "vault"}]
+        }))
+        .unwrap();
+        assert!(config.build().is_err());
+    }
+}
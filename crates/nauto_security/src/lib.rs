@@ -1,14 +1,23 @@
+pub mod chain;
+pub mod redaction;
+pub mod vault;
+
+pub use chain::{ChainStore, CredentialsConfig, EnvStore};
+pub use redaction::Redactor;
+pub use vault::{VaultAuth, VaultConfig, VaultStore};
+
 use age::{
     secrecy::SecretString,
     Encryptor,
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use keyring::Entry;
 use nauto_model::{Credential, CredentialRef};
 use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::task;
 use tracing::{info, instrument};
 
@@ -18,6 +27,18 @@ pub trait CredentialStore: Send + Sync {
     async fn resolve(&self, reference: &CredentialRef) -> Result<Credential>;
 }
 
+/// Lets a shared store be passed wherever a `&dyn CredentialStore` is expected.
+#[async_trait]
+impl<T: CredentialStore + ?Sized> CredentialStore for Arc<T> {
+    async fn store(&self, reference: &CredentialRef, credential: &Credential) -> Result<()> {
+        (**self).store(reference, credential).await
+    }
+
+    async fn resolve(&self, reference: &CredentialRef) -> Result<Credential> {
+        (**self).resolve(reference).await
+    }
+}
+
 #[derive(Clone)]
 pub struct KeyringStore {
     service: String,
@@ -95,6 +116,52 @@ impl CredentialStore for KeyringStore {
     }
 }
 
+/// Credentials in a JSON file keyed by reference name, age-encrypted with the
+/// `NAUTO_ENCRYPTION_KEY` passphrase; the same format as the keyring's fallback file.
+#[derive(Clone)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl CredentialStore for FileStore {
+    #[instrument(skip(self, credential))]
+    async fn store(&self, reference: &CredentialRef, credential: &Credential) -> Result<()> {
+        info!(
+            target: "security::audit",
+            "storing credential '{}' in {}",
+            reference.name,
+            self.path.display()
+        );
+        write_fallback_secret(&self.path, reference, credential).await
+    }
+
+    #[instrument(skip(self))]
+    async fn resolve(&self, reference: &CredentialRef) -> Result<Credential> {
+        info!(
+            target: "security::audit",
+            "resolving credential '{}' from {}",
+            reference.name,
+            self.path.display()
+        );
+        read_fallback_secret(&self.path, reference)
+            .await?
+            .ok_or_else(|| {
+                anyhow!(
+                    "no credential '{}' in {}",
+                    reference.name,
+                    self.path.display()
+                )
+            })
+    }
+}
+
 async fn write_fallback_secret(
     path: &PathBuf,
     reference: &CredentialRef,
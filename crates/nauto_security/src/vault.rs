@@ -0,0 +1,580 @@
+//! HashiCorp Vault credential store (KV version 2).
+//!
+//! A `CredentialRef` name is substituted into a path template (`netrust/{name}` by default)
+//! under a KV v2 mount, and the secret's fields map onto a [`Credential`]:
+//! `username` + `password`, `username` + `key_path` (+ `passphrase`), or `token`.
+//! Authentication is a static token or an AppRole login; AppRole tokens are renewed by
+//! logging in again shortly before their lease runs out. Secrets are cached for their
+//! lease duration, or `cache_ttl` for KV secrets, which carry no lease.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use nauto_model::{Credential, CredentialRef};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, instrument};
+
+use crate::CredentialStore;
+
+const DEFAULT_MOUNT: &str = "secret";
+const DEFAULT_PATH_TEMPLATE: &str = "netrust/{name}";
+const DEFAULT_APPROLE_MOUNT: &str = "approle";
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Log in again once a token has less than this left, so it never expires mid-request.
+const RENEW_MARGIN: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub enum VaultAuth {
+    Token(String),
+    AppRole {
+        role_id: String,
+        secret_id: String,
+        /// Auth mount the AppRole backend is enabled at (`approle` by default).
+        mount: String,
+    },
+}
+
+#[derive(Clone)]
+pub struct VaultConfig {
+    /// Base URL, e.g. `https://vault.example.net:8200`.
+    pub address: String,
+    pub namespace: Option<String>,
+    /// KV v2 secrets engine mount.
+    pub mount: String,
+    /// Secret path below the mount; `{name}` is replaced by the credential reference name.
+    pub path_template: String,
+    pub auth: Option<VaultAuth>,
+    /// How long secrets without a lease (all KV secrets) are cached. Zero disables caching.
+    pub cache_ttl: Duration,
+    /// PEM file with the CA that signed Vault's certificate, for private PKI.
+    pub ca_cert: Option<String>,
+}
+
+impl VaultConfig {
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            namespace: None,
+            mount: DEFAULT_MOUNT.into(),
+            path_template: DEFAULT_PATH_TEMPLATE.into(),
+            auth: None,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            ca_cert: None,
+        }
+    }
+
+    pub fn with_auth(mut self, auth: VaultAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    pub fn with_mount(mut self, mount: impl Into<String>) -> Self {
+        self.mount = mount.into();
+        self
+    }
+
+    pub fn with_path_template(mut self, template: impl Into<String>) -> Self {
+        self.path_template = template.into();
+        self
+    }
+
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Settings from the standard Vault variables (`VAULT_ADDR`, `VAULT_TOKEN`,
+    /// `VAULT_NAMESPACE`, `VAULT_CACERT`) plus `VAULT_ROLE_ID`/`VAULT_SECRET_ID` for AppRole
+    /// and `NAUTO_VAULT_MOUNT`, `NAUTO_VAULT_PATH`, `NAUTO_VAULT_APPROLE_MOUNT`,
+    /// `NAUTO_VAULT_CACHE_TTL_SECS`. `None` when `VAULT_ADDR` is not set.
+    pub fn from_env() -> Option<Self> {
+        let address = env_var("VAULT_ADDR")?;
+        Some(Self::new(address).with_env_overrides(None))
+    }
+
+    /// Applies the variables read by [`VaultConfig::from_env`] other than `VAULT_ADDR` on
+    /// top of these settings. Authentication only ever comes from the environment;
+    /// `approle_mount` is used when `NAUTO_VAULT_APPROLE_MOUNT` is not set.
+    pub fn with_env_overrides(mut self, approle_mount: Option<&str>) -> Self {
+        if let Some(namespace) = env_var("VAULT_NAMESPACE") {
+            self.namespace = Some(namespace);
+        }
+        if let Some(ca_cert) = env_var("VAULT_CACERT") {
+            self.ca_cert = Some(ca_cert);
+        }
+        if let Some(mount) = env_var("NAUTO_VAULT_MOUNT") {
+            self.mount = mount;
+        }
+        if let Some(template) = env_var("NAUTO_VAULT_PATH") {
+            self.path_template = template;
+        }
+        if let Some(ttl) = env_var("NAUTO_VAULT_CACHE_TTL_SECS").and_then(|v| v.parse().ok()) {
+            self.cache_ttl = Duration::from_secs(ttl);
+        }
+        self.auth = match (env_var("VAULT_ROLE_ID"), env_var("VAULT_SECRET_ID")) {
+            (Some(role_id), Some(secret_id)) => Some(VaultAuth::AppRole {
+                role_id,
+                secret_id,
+                mount: env_var("NAUTO_VAULT_APPROLE_MOUNT")
+                    .or_else(|| approle_mount.map(str::to_string))
+                    .unwrap_or_else(|| DEFAULT_APPROLE_MOUNT.into()),
+            }),
+            _ => env_var("VAULT_TOKEN").map(VaultAuth::Token),
+        };
+        self
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+struct Lease<T> {
+    value: T,
+    /// `None` never expires (static tokens).
+    expires: Option<Instant>,
+}
+
+impl<T> Lease<T> {
+    fn new(value: T, lease: Duration) -> Self {
+        Self {
+            value,
+            expires: Some(Instant::now() + lease),
+        }
+    }
+
+    fn valid(&self, margin: Duration) -> bool {
+        self.expires
+            .is_none_or(|expires| Instant::now() + margin < expires)
+    }
+}
+
+pub struct VaultStore {
+    config: VaultConfig,
+    client: reqwest::Client,
+    token: Mutex<Option<Lease<String>>>,
+    cache: Mutex<HashMap<String, Lease<Credential>>>,
+}
+
+impl VaultStore {
+    pub fn new(config: VaultConfig) -> Result<Self> {
+        let mut builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+        if let Some(path) = &config.ca_cert {
+            let pem =
+                std::fs::read(path).with_context(|| format!("reading VAULT_CACERT {path}"))?;
+            builder = builder.add_root_certificate(
+                reqwest::Certificate::from_pem(&pem)
+                    .with_context(|| format!("parsing VAULT_CACERT {path}"))?,
+            );
+        }
+        let token = match &config.auth {
+            Some(VaultAuth::Token(token)) => Some(Lease {
+                value: token.clone(),
+                expires: None,
+            }),
+            _ => None,
+        };
+        Ok(Self {
+            client: builder.build()?,
+            config,
+            token: Mutex::new(token),
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// KV v2 API path (`<mount>/data/<path>`) for a reference.
+    fn secret_path(&self, reference: &CredentialRef) -> Result<String> {
+        let name = &reference.name;
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+            && !name
+                .split('/')
+                .any(|segment| segment.is_empty() || segment == "..");
+        if !valid {
+            bail!("credential name '{name}' cannot be used as a Vault path");
+        }
+        let path = self.config.path_template.replace("{name}", name);
+        Ok(format!(
+            "{}/data/{}",
+            self.config.mount.trim_matches('/'),
+            path.trim_matches('/')
+        ))
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/v1/{path}", self.config.address.trim_end_matches('/'));
+        let request = self.client.request(method, url);
+        match &self.config.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    async fn token(&self) -> Result<String> {
+        if let Some(lease) = self.token.lock().unwrap().as_ref() {
+            if lease.valid(RENEW_MARGIN) {
+                return Ok(lease.value.clone());
+            }
+        }
+        let Some(VaultAuth::AppRole {
+            role_id,
+            secret_id,
+            mount,
+        }) = &self.config.auth
+        else {
+            bail!("no Vault credentials: set VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID");
+        };
+        debug!(target: "security::vault", "logging in with AppRole");
+        let body = send(
+            self.request(reqwest::Method::POST, &format!("auth/{mount}/login"))
+                .json(&json!({ "role_id": role_id, "secret_id": secret_id })),
+        )
+        .await
+        .context("Vault AppRole login")?;
+        let token = body["auth"]["client_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Vault AppRole login returned no client_token"))?
+            .to_string();
+        let lease = Duration::from_secs(body["auth"]["lease_duration"].as_u64().unwrap_or(0));
+        *self.token.lock().unwrap() = Some(if lease.is_zero() {
+            Lease {
+                value: token.clone(),
+                expires: None,
+            }
+        } else {
+            Lease::new(token.clone(), lease)
+        });
+        Ok(token)
+    }
+
+    /// Sends a request with the current token, logging in again once if an AppRole token
+    /// was revoked before its lease ran out.
+    async fn authorized(&self, build: impl Fn(&str) -> reqwest::RequestBuilder) -> Result<Value> {
+        let token = self.token().await?;
+        match send(build(&token)).await {
+            Err(err)
+                if matches!(self.config.auth, Some(VaultAuth::AppRole { .. }))
+                    && err.downcast_ref::<Forbidden>().is_some() =>
+            {
+                self.token.lock().unwrap().take();
+                let token = self.token().await?;
+                send(build(&token)).await
+            }
+            result => result,
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialStore for VaultStore {
+    #[instrument(skip(self, credential))]
+    async fn store(&self, reference: &CredentialRef, credential: &Credential) -> Result<()> {
+        info!(
+            target: "security::audit",
+            "storing credential '{}' in Vault",
+            reference.name
+        );
+        let path = self.secret_path(reference)?;
+        let data = credential_to_data(credential);
+        self.authorized(|token| {
+            self.request(reqwest::Method::POST, &path)
+                .header("X-Vault-Token", token)
+                .json(&json!({ "data": data }))
+        })
+        .await
+        .with_context(|| format!("writing Vault secret {path}"))?;
+        self.cache.lock().unwrap().remove(&reference.name);
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn resolve(&self, reference: &CredentialRef) -> Result<Credential> {
+        if let Some(lease) = self.cache.lock().unwrap().get(&reference.name) {
+            if lease.valid(Duration::ZERO) {
+                return Ok(lease.value.clone());
+            }
+        }
+        info!(
+            target: "security::audit",
+            "resolving credential '{}' from Vault",
+            reference.name
+        );
+        let path = self.secret_path(reference)?;
+        let body = self
+            .authorized(|token| {
+                self.request(reqwest::Method::GET, &path)
+                    .header("X-Vault-Token", token)
+            })
+            .await
+            .with_context(|| format!("reading Vault secret {path}"))?;
+        let data = body["data"]["data"]
+            .as_object()
+            .ok_or_else(|| anyhow!("Vault secret {path} has no KV v2 data"))?;
+        let credential =
+            credential_from_data(data).with_context(|| format!("Vault secret {path}"))?;
+        let lease = match body["lease_duration"].as_u64().unwrap_or(0) {
+            0 => self.config.cache_ttl,
+            secs => Duration::from_secs(secs),
+        };
+        if !lease.is_zero() {
+            self.cache.lock().unwrap().insert(
+                reference.name.clone(),
+                Lease::new(credential.clone(), lease),
+            );
+        }
+        Ok(credential)
+    }
+}
+
+/// A 403 from Vault: the token is invalid, expired or lacks the policy.
+#[derive(Debug)]
+struct Forbidden(String);
+
+impl std::fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "permission denied: {}", self.0)
+    }
+}
+
+impl std::error::Error for Forbidden {}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<Value> {
+    let response = request.send().await?;
+    let status = response.status();
+    let text = response.text().await?;
+    if status.is_success() {
+        return Ok(if text.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&text)?
+        });
+    }
+    let errors = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|body| {
+            body["errors"].as_array().map(|errors| {
+                errors
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })
+        })
+        .unwrap_or_default();
+    match status.as_u16() {
+        403 => Err(Forbidden(errors).into()),
+        404 => bail!("no such secret"),
+        _ => bail!("Vault returned {status}: {errors}"),
+    }
+}
+
+fn credential_from_data(data: &Map<String, Value>) -> Result<Credential> {
+    let field = |name: &str| data.get(name).and_then(Value::as_str).map(str::to_string);
+    if let Some(token) = field("token") {
+        return Ok(Credential::Token { token });
+    }
+    let username = field("username")
+        .ok_or_else(|| anyhow!("expected `username` with `password` or `key_path`, or `token`"))?;
+    if let Some(password) = field("password") {
+        return Ok(Credential::UserPassword { username, password });
+    }
+    match field("key_path") {
+        Some(key_path) => Ok(Credential::SshKey {
+            username,
+            key_path,
+            passphrase: field("passphrase"),
+        }),
+        None => bail!("`username` needs `password` or `key_path`"),
+    }
+}
+
+fn credential_to_data(credential: &Credential) -> Value {
+    match credential {
+        Credential::UserPassword { username, password } => {
+            json!({ "username": username, "password": password })
+        }
+        Credential::SshKey {
+            username,
+            key_path,
+            passphrase,
+        } => {
+            let mut data = json!({ "username": username, "key_path": key_path });
+            if let Some(passphrase) = passphrase {
+                data["passphrase"] = json!(passphrase);
+            }
+            data
+        }
+        Credential::Token { token } => json!({ "token": token }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    type Seen = Arc<Mutex<Vec<(String, String, Option<String>)>>>;
+
+    /// Minimal Vault stand-in: records (method, path, token) and answers with `handler`.
+    async fn stand_in(
+        handler: impl Fn(&str, &str, &str) -> (u16, Value) + Send + Sync + 'static,
+    ) -> (String, Seen) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let seen: Seen = Arc::default();
+        let log = seen.clone();
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let (head, body) = loop {
+                    let read = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..read]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break (head.to_string(), body.to_string());
+                        }
+                    }
+                };
+                let mut request_line = head.lines().next().unwrap().split_whitespace();
+                let method = request_line.next().unwrap().to_string();
+                let path = request_line.next().unwrap().to_string();
+                let token = head.lines().find_map(|line| {
+                    line.to_ascii_lowercase()
+                        .starts_with("x-vault-token:")
+                        .then(|| line[14..].trim().to_string())
+                });
+                log.lock()
+                    .unwrap()
+                    .push((method.clone(), path.clone(), token));
+                let (status, reply) = handler(&method, &path, &body);
+                let reply = reply.to_string();
+                let response = format!(
+                    "HTTP/1.1 {status} Stand-in\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (format!("http://{addr}"), seen)
+    }
+
+    fn reference(name: &str) -> CredentialRef {
+        CredentialRef { name: name.into() }
+    }
+
+    #[tokio::test]
+    async fn approle_login_reads_kv_v2_through_the_path_template_and_caches() {
+        let (address, seen) = stand_in(|method, path, body| match (method, path) {
+            ("POST", "/v1/auth/approle/login") => {
+                assert_eq!(
+                    serde_json::from_str::<Value>(body).unwrap(),
+                    json!({"role_id": "role", "secret_id": "secret"})
+                );
+                (200, json!({"auth": {"client_token": "s.abc", "lease_duration": 3600}}))
+            }
+            ("GET", "/v1/kv/data/network/core") => (
+                200,
+                json!({"lease_duration": 0, "data": {"data": {"username": "admin", "password": "hunter2"}}}),
+            ),
+            _ => (404, json!({"errors": []})),
+        })
+        .await;
+        let store = VaultStore::new(
+            VaultConfig::new(address)
+                .with_mount("kv")
+                .with_path_template("network/{name}")
+                .with_auth(VaultAuth::AppRole {
+                    role_id: "role".into(),
+                    secret_id: "secret".into(),
+                    mount: "approle".into(),
+                }),
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            match store.resolve(&reference("core")).await.unwrap() {
+                Credential::UserPassword { username, password } => {
+                    assert_eq!((username.as_str(), password.as_str()), ("admin", "hunter2"))
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2, "one login and one read: {seen:?}");
+        assert_eq!(seen[1].2.as_deref(), Some("s.abc"));
+
+        let err = store.resolve(&reference("../sys")).await.unwrap_err();
+        assert!(err.to_string().contains("cannot be used as a Vault path"));
+    }
+
+    #[tokio::test]
+    async fn revoked_approle_token_logs_in_again_and_token_auth_writes_back() {
+        let logins = Arc::new(Mutex::new(0));
+        let counter = logins.clone();
+        let (address, _) = stand_in(move |method, path, _| match (method, path) {
+            ("POST", "/v1/auth/approle/login") => {
+                let mut logins = counter.lock().unwrap();
+                *logins += 1;
+                let token = format!("s.{logins}");
+                (
+                    200,
+                    json!({"auth": {"client_token": token, "lease_duration": 3600}}),
+                )
+            }
+            _ => (403, json!({"errors": ["permission denied"]})),
+        })
+        .await;
+        let store = VaultStore::new(VaultConfig::new(address).with_auth(VaultAuth::AppRole {
+            role_id: "role".into(),
+            secret_id: "secret".into(),
+            mount: "approle".into(),
+        }))
+        .unwrap();
+        let err = store.resolve(&reference("core")).await.unwrap_err();
+        assert!(format!("{err:#}").contains("permission denied"));
+        assert_eq!(*logins.lock().unwrap(), 2);
+
+        let written = Arc::new(Mutex::new(Value::Null));
+        let sink = written.clone();
+        let (address, seen) = stand_in(move |method, path, body| match (method, path) {
+            ("POST", "/v1/secret/data/netrust/api") => {
+                *sink.lock().unwrap() = serde_json::from_str(body).unwrap();
+                (200, json!({"data": {"version": 1}}))
+            }
+            _ => (404, json!({"errors": []})),
+        })
+        .await;
+        let store =
+            VaultStore::new(VaultConfig::new(address).with_auth(VaultAuth::Token("root".into())))
+                .unwrap();
+        store
+            .store(
+                &reference("api"),
+                &Credential::Token {
+                    token: "t0k".into(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(*written.lock().unwrap(), json!({"data": {"token": "t0k"}}));
+        assert_eq!(seen.lock().unwrap()[0].2.as_deref(), Some("root"));
+    }
+}